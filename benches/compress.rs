@@ -0,0 +1,56 @@
+//! Time writing 300,000 records as BGZF, compressing on one thread and on
+//! several.
+//!
+//! Run with `cargo bench --bench compress --features bgzf`.
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use paf::{Compression, PafRecord, Reader, Result, WriterBuilder};
+
+const RECORDS: usize = 300_000;
+
+fn records() -> Vec<PafRecord> {
+    let paf: String = (0..RECORDS)
+        .map(|i| {
+            format!(
+                "read{}\t{}\t0\t100\t+\tchr{}\t1000000\t{}\t{}\t95\t100\t60\tNM:i:{}\tcg:Z:40M2I30M1D28M\n",
+                i,
+                100 + i % 50,
+                i % 20,
+                i,
+                i + 100,
+                i % 7
+            )
+        })
+        .collect();
+    Reader::from_reader(paf.as_bytes())
+        .records()
+        .collect::<Result<_>>()
+        .unwrap()
+}
+
+fn time_writes(records: &[PafRecord], threads: usize) -> Result<Duration> {
+    let start = Instant::now();
+    let mut writer = WriterBuilder::new()
+        .compression(Compression::Bgzf)
+        .compression_threads(threads)
+        .from_writer(io::sink());
+    writer.write_records(records)?;
+    writer.into_inner().map_err(|(_, err)| err)?;
+    Ok(start.elapsed())
+}
+
+fn main() -> Result<()> {
+    let records = records();
+    let threads = std::thread::available_parallelism().map_or(4, |n| n.get().max(2));
+    let single = time_writes(&records, 1)?;
+    let threaded = time_writes(&records, threads)?;
+    println!("{} records, 1 thread: {:?}", RECORDS, single);
+    println!("{} records, {} threads: {:?}", RECORDS, threads, threaded);
+    println!(
+        "speedup: {:.1}x",
+        single.as_secs_f64() / threaded.as_secs_f64()
+    );
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! Time writing 100,000 records to a file, with and without the writer's
+//! buffer.
+//!
+//! Run with `cargo bench --bench write`.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use paf::{AlignmentType, PafRecord, Result, Tag, Type, WriterBuilder};
+
+const RECORDS: usize = 100_000;
+
+fn record() -> PafRecord {
+    let mut tags = HashMap::new();
+    tags.insert("tp".to_string(), Tag::tp(AlignmentType::Primary));
+    tags.insert("NM".to_string(), Tag::NM(Type::Int(12)));
+    tags.insert("cm".to_string(), Tag::cm(Type::Int(180)));
+    tags.insert("s1".to_string(), Tag::s1(Type::Int(1800)));
+    tags.insert("de".to_string(), Tag::de(Type::float(0.0012)));
+    tags.insert(
+        "cg".to_string(),
+        Tag::cg(Type::String("4000M2I3000M1D3000M".to_string())),
+    );
+    PafRecord::new(
+        "query".to_owned(),
+        12000,
+        1000,
+        11002,
+        '+',
+        "target".to_owned(),
+        5000000,
+        200000,
+        210001,
+        9988,
+        10003,
+        60,
+        tags,
+    )
+}
+
+fn time_writes(capacity: usize) -> Result<Duration> {
+    let path = std::env::temp_dir().join(format!("paf-bench-{}.paf", std::process::id()));
+    let record = record();
+    let start = Instant::now();
+    let mut writer = WriterBuilder::new()
+        .buffer_capacity(capacity)
+        .from_path(&path)?;
+    for _ in 0..RECORDS {
+        writer.write_record(&record)?;
+    }
+    writer.flush()?;
+    drop(writer);
+    let elapsed = start.elapsed();
+    std::fs::remove_file(&path)?;
+    Ok(elapsed)
+}
+
+fn main() -> Result<()> {
+    let unbuffered = time_writes(0)?;
+    let buffered = time_writes(8 * 1024)?;
+    println!("{} records, unbuffered: {:?}", RECORDS, unbuffered);
+    println!("{} records, 8 KiB buffer: {:?}", RECORDS, buffered);
+    println!(
+        "speedup: {:.1}x",
+        unbuffered.as_secs_f64() / buffered.as_secs_f64()
+    );
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use paf::{Reader, Result, Writer};
+use std::env;
+
+// Print the records of a PAF file with at least a given mapping quality,
+// e.g. `cargo run --example filter_paf ./data/5_GD_domestica.paf 60 | head`.
+fn run(filename: &str, min_mapq: u8) -> Result<()> {
+    let mut reader = Reader::from_path(filename)?;
+    let mut writer = Writer::to_stdout();
+    // errors are kept, so that they stop the writing
+    let records = reader.records().filter(|record| match record {
+        Ok(record) => record.mapping_quality() >= min_mapq,
+        Err(_) => true,
+    });
+    writer.try_write_records(records)?;
+    writer.flush()
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: filter_paf <filename> <min_mapq>");
+        std::process::exit(1);
+    }
+    let min_mapq = args[2].parse().unwrap_or_else(|_| {
+        eprintln!("min_mapq must be a number from 0 to 255");
+        std::process::exit(1);
+    });
+
+    match run(&args[1], min_mapq) {
+        // the output was closed early, e.g. by `head`
+        Err(e) if e.is_broken_pipe() => {}
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        Ok(()) => {}
+    }
+}
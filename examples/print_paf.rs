@@ -2,7 +2,7 @@ use paf::Reader;
 use std::env;
 
 fn main() {
-    let data = env::args().skip(1).next();
+    let data = env::args().nth(1);
 
     if let Some(filename) = data {
         let mut reader = Reader::from_path(&filename).unwrap();
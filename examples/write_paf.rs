@@ -1,4 +1,4 @@
-use paf::{PafRecord, Result, Tag, Type, Writer};
+use paf::{AlignmentType, PafRecord, Result, Tag, Type, Writer};
 use std::collections::HashMap;
 
 fn main() -> Result<()> {
@@ -7,7 +7,7 @@ fn main() -> Result<()> {
 
     // Create some fake PAF records
     let mut optional_fields1 = HashMap::new();
-    optional_fields1.insert("tp".to_string(), Tag::tp(Type::Char('P')));
+    optional_fields1.insert("tp".to_string(), Tag::tp(AlignmentType::Primary));
     let record1 = PafRecord::new(
         "query1".to_owned(),
         1000,
@@ -0,0 +1,616 @@
+use std::fmt;
+
+use crate::{CsString, Error, ErrorKind, PafRecord, Result, Tag, Type};
+
+/// A record field the audit can check against the cs and cg tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AuditField {
+    /// Column 10, the number of matching bases.
+    ResidueMatches,
+    /// Column 11, the alignment block length.
+    AlignmentBlockLen,
+    /// The NM tag, mismatches plus inserted and deleted bases.
+    Nm,
+    /// The de tag, the gap-compressed per-base divergence.
+    De,
+}
+
+impl fmt::Display for AuditField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            AuditField::ResidueMatches => "residue_matches",
+            AuditField::AlignmentBlockLen => "alignment_block_len",
+            AuditField::Nm => "NM",
+            AuditField::De => "de",
+        })
+    }
+}
+
+/// A field whose value disagrees with the one recomputed from the tags.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Discrepancy {
+    /// Column 10 disagrees with the matches in the cs or cg tag.
+    ResidueMatches { expected: u64, observed: u64 },
+    /// Column 11 disagrees with the alignment columns, less `nn`.
+    AlignmentBlockLen { expected: u64, observed: u64 },
+    /// The NM tag disagrees with the mismatches and gaps.
+    Nm { expected: u64, observed: u64 },
+    /// The de tag disagrees beyond the four significant figures minimap2
+    /// writes.
+    De { expected: f64, observed: f64 },
+}
+
+impl Discrepancy {
+    /// The field in question.
+    pub fn field(&self) -> AuditField {
+        match self {
+            Discrepancy::ResidueMatches { .. } => AuditField::ResidueMatches,
+            Discrepancy::AlignmentBlockLen { .. } => AuditField::AlignmentBlockLen,
+            Discrepancy::Nm { .. } => AuditField::Nm,
+            Discrepancy::De { .. } => AuditField::De,
+        }
+    }
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Discrepancy::ResidueMatches { expected, observed }
+            | Discrepancy::AlignmentBlockLen { expected, observed }
+            | Discrepancy::Nm { expected, observed } => write!(
+                f,
+                "{}: expected {}, observed {}",
+                self.field(),
+                expected,
+                observed
+            ),
+            Discrepancy::De { expected, observed } => write!(
+                f,
+                "{}: expected {:.4}, observed {}",
+                self.field(),
+                expected,
+                observed
+            ),
+        }
+    }
+}
+
+/// The outcome of [`PafRecord::audit`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditReport {
+    /// The fields that could be checked with the tags present.
+    pub checked: Vec<AuditField>,
+    /// The checked fields that disagree with the tags.
+    pub discrepancies: Vec<Discrepancy>,
+    /// Messages for cs or cg tags that failed to parse, and so were not used.
+    pub tag_errors: Vec<String>,
+}
+
+impl AuditReport {
+    /// Whether every checked field agrees and every tag parsed.
+    pub fn is_consistent(&self) -> bool {
+        self.discrepancies.is_empty() && self.tag_errors.is_empty()
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.checked.is_empty() && self.tag_errors.is_empty() {
+            return write!(f, "nothing to check");
+        }
+        for field in &self.checked {
+            match self.discrepancies.iter().find(|d| d.field() == *field) {
+                Some(discrepancy) => writeln!(f, "{}", discrepancy)?,
+                None => writeln!(f, "{}: ok", field)?,
+            }
+        }
+        for error in &self.tag_errors {
+            writeln!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// A field of [`RecomputedFields`], with its values before and after.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Recomputed<T> {
+    /// The value before, for a tag `None` if it was absent.
+    pub old: T,
+    /// The value recomputed.
+    pub new: T,
+}
+
+impl<T: PartialEq> Recomputed<T> {
+    /// Whether the value recomputed differs from the old one.
+    pub fn changed(&self) -> bool {
+        self.old != self.new
+    }
+}
+
+/// The outcome of [`PafRecord::recompute_stats`]: each field recomputed,
+/// or `None` for a field the tags do not give.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RecomputedFields {
+    /// Column 10, the number of matching bases.
+    pub residue_matches: Option<Recomputed<u32>>,
+    /// Column 11, the alignment block length.
+    pub alignment_block_len: Option<Recomputed<u32>>,
+    /// The NM tag.
+    pub nm: Option<Recomputed<Option<i64>>>,
+    /// The de tag.
+    pub de: Option<Recomputed<Option<f64>>>,
+    /// The dv tag, only recomputed when present.
+    pub dv: Option<Recomputed<Option<f64>>>,
+}
+
+impl RecomputedFields {
+    /// Whether any field recomputed differs from its old value.
+    pub fn changed(&self) -> bool {
+        self.residue_matches.is_some_and(|r| r.changed())
+            || self.alignment_block_len.is_some_and(|r| r.changed())
+            || self.nm.is_some_and(|r| r.changed())
+            || self.de.is_some_and(|r| r.changed())
+            || self.dv.is_some_and(|r| r.changed())
+    }
+}
+
+/// Formats the fields which changed, one to a line, as old and new values.
+impl fmt::Display for RecomputedFields {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn tag<T: fmt::Display>(value: Option<T>) -> String {
+            value.map_or_else(|| "absent".to_string(), |v| v.to_string())
+        }
+        let counts = [
+            (AuditField::ResidueMatches, self.residue_matches),
+            (AuditField::AlignmentBlockLen, self.alignment_block_len),
+        ];
+        for (field, r) in counts.into_iter().filter_map(|(f, r)| Some((f, r?))) {
+            if r.changed() {
+                writeln!(f, "{}: {} -> {}", field, r.old, r.new)?;
+            }
+        }
+        if let Some(r) = self.nm.filter(Recomputed::changed) {
+            writeln!(f, "NM: {} -> {}", tag(r.old), tag(r.new))?;
+        }
+        for (name, r) in [("de", self.de), ("dv", self.dv)] {
+            if let Some(r) = r.filter(Recomputed::changed) {
+                writeln!(f, "{}: {} -> {}", name, tag(r.old), tag(r.new))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Counts recomputed from the cs or cg tag. Matches and mismatches are only
+/// known from cs, or from cg when it uses `=` and `X`.
+struct Tally {
+    matches: Option<u64>,
+    mismatches: Option<u64>,
+    inserted_bases: u64,
+    deleted_bases: u64,
+    gap_events: u64,
+}
+
+/// The fields as the tags give them, where they do.
+struct Expected {
+    residue_matches: Option<u64>,
+    alignment_block_len: Option<u64>,
+    nm: Option<u64>,
+    de: Option<f64>,
+}
+
+impl Tally {
+    fn from_cs(cs: &CsString) -> Tally {
+        let counts = cs.counts();
+        Tally {
+            matches: Some(counts.matches),
+            mismatches: Some(counts.mismatches),
+            inserted_bases: counts.inserted_bases,
+            deleted_bases: counts.deleted_bases,
+            gap_events: counts.insertions + counts.deletions,
+        }
+    }
+
+    fn from_cg(record: &PafRecord) -> Option<Result<Tally>> {
+        Some(record.cigar_stats()?.map(|stats| Tally {
+            matches: stats.exact_matches,
+            mismatches: stats.mismatches,
+            inserted_bases: stats.inserted_bases,
+            deleted_bases: stats.deleted_bases,
+            gap_events: stats.insertions + stats.deletions,
+        }))
+    }
+
+    /// Alignment columns, counting ambiguous bases.
+    fn columns(&self, aligned: u64) -> u64 {
+        aligned + self.inserted_bases + self.deleted_bases
+    }
+
+    /// The fields of `record` as the counts give them. As minimap2 leaves
+    /// ambiguous bases out of the block length and divergence, the `nn` tag
+    /// is taken into account.
+    fn expected(&self, record: &PafRecord) -> Expected {
+        let ambiguous = record.nn().unwrap_or(0).max(0) as u64;
+
+        // aligned bases are matches plus mismatches, or the M runs of the cg
+        // tag when those are unknown
+        let aligned = match (self.matches, self.mismatches) {
+            (Some(matches), Some(mismatches)) => Some(matches + mismatches),
+            _ => record
+                .cigar_stats()
+                .and_then(|s| s.ok())
+                .map(|s| s.aligned_bases),
+        };
+
+        let de = match (self.matches, self.mismatches) {
+            (Some(matches), Some(mismatches)) => {
+                let differences = mismatches.saturating_sub(ambiguous) + self.gap_events;
+                let total = matches + differences;
+                Some(if total == 0 {
+                    0.0
+                } else {
+                    differences as f64 / total as f64
+                })
+            }
+            _ => None,
+        };
+
+        Expected {
+            residue_matches: self.matches,
+            alignment_block_len: aligned
+                .map(|aligned| self.columns(aligned).saturating_sub(ambiguous)),
+            nm: self
+                .mismatches
+                .map(|mismatches| mismatches + self.inserted_bases + self.deleted_bases),
+            de,
+        }
+    }
+}
+
+impl PafRecord {
+    /// Recompute the matches, mismatches and gaps from the cs tag, or the cg
+    /// tag without one, and compare them with the record's fields.
+    ///
+    /// Only what the tags allow is checked: a cg tag of plain `M` operations
+    /// cannot tell matches from mismatches, and NM and de are only checked
+    /// when present. As minimap2 leaves ambiguous bases out of the block
+    /// length and divergence, the `nn` tag is taken into account.
+    pub fn audit(&self) -> AuditReport {
+        let mut report = AuditReport::default();
+        let tally = match self.cs_parsed() {
+            Some(Ok(cs)) => Some(Tally::from_cs(&cs)),
+            other => {
+                if let Some(Err(e)) = other {
+                    report.tag_errors.push(format!("cs: {}", e));
+                }
+                match Tally::from_cg(self) {
+                    Some(Ok(tally)) => Some(tally),
+                    Some(Err(e)) => {
+                        report.tag_errors.push(format!("cg: {}", e));
+                        None
+                    }
+                    None => None,
+                }
+            }
+        };
+        let expected = match tally {
+            Some(tally) => tally.expected(self),
+            None => return report,
+        };
+
+        let mut check = |discrepancy: Discrepancy, agrees: bool| {
+            report.checked.push(discrepancy.field());
+            if !agrees {
+                report.discrepancies.push(discrepancy);
+            }
+        };
+
+        if let Some(expected) = expected.residue_matches {
+            let observed = self.residue_matches() as u64;
+            check(
+                Discrepancy::ResidueMatches { expected, observed },
+                expected == observed,
+            );
+        }
+
+        if let Some(expected) = expected.alignment_block_len {
+            let observed = self.alignment_block_len() as u64;
+            check(
+                Discrepancy::AlignmentBlockLen { expected, observed },
+                expected == observed,
+            );
+        }
+
+        if let (Some(expected), Some(nm)) = (expected.nm, self.nm()) {
+            let observed = nm.max(0) as u64;
+            check(Discrepancy::Nm { expected, observed }, expected == observed);
+        }
+
+        if let (Some(expected), Some(observed)) = (expected.de, self.de()) {
+            // minimap2 writes de to four significant figures
+            let agrees = (expected - observed).abs() <= 5e-4 * expected.abs() + 1e-12;
+            check(Discrepancy::De { expected, observed }, agrees);
+        }
+
+        report
+    }
+
+    /// Recompute residue_matches, alignment_block_len, NM and de from the
+    /// cs tag, or the cg tag without one, and update the record to match,
+    /// returning the old values. Use this after trimming, merging or editing
+    /// records, so that their summary fields agree with their alignments.
+    ///
+    /// The values are those [`PafRecord::audit`] checks against, and only
+    /// what the tags allow is recomputed: a cg tag of plain `M` operations
+    /// gives the block length alone. NM and de are added where absent. A dv
+    /// tag, minimap2's estimate for records without an alignment, is set to
+    /// the gap-compressed divergence when present. The new de and dv are
+    /// written at full precision, unless the writer is set to round floats
+    /// with [`WriterBuilder::float_precision`](crate::WriterBuilder::float_precision).
+    ///
+    /// It is an error for the record to have neither tag, for the tag used
+    /// not to parse, or for a count not to fit in its field.
+    pub fn recompute_stats(&mut self) -> Result<RecomputedFields> {
+        let tally = match self.cs_parsed() {
+            Some(cs) => Tally::from_cs(&cs?),
+            None => Tally::from_cg(self).ok_or_else(|| {
+                Error::new(ErrorKind::InvalidRecord(
+                    "recomputing the stats of a record needs a cs or cg tag".to_string(),
+                ))
+            })??,
+        };
+        let expected = tally.expected(self);
+        let field = |value: u64, what: &str| {
+            u32::try_from(value).map_err(|_| {
+                Error::new(ErrorKind::InvalidRecord(format!(
+                    "recomputed {} of {} does not fit in the field",
+                    what, value
+                )))
+            })
+        };
+        let residue_matches = expected
+            .residue_matches
+            .map(|value| field(value, "residue_matches"))
+            .transpose()?;
+        let alignment_block_len = expected
+            .alignment_block_len
+            .map(|value| field(value, "alignment_block_len"))
+            .transpose()?;
+
+        let mut fields = RecomputedFields::default();
+        if let Some(new) = residue_matches {
+            fields.residue_matches = Some(Recomputed {
+                old: self.residue_matches(),
+                new,
+            });
+        }
+        if let Some(new) = alignment_block_len {
+            fields.alignment_block_len = Some(Recomputed {
+                old: self.alignment_block_len(),
+                new,
+            });
+        }
+        self.set_matches(
+            residue_matches.unwrap_or(self.residue_matches()),
+            alignment_block_len.unwrap_or(self.alignment_block_len()),
+        );
+        if let Some(nm) = expected.nm {
+            let new = nm as i64;
+            fields.nm = Some(Recomputed {
+                old: self.nm(),
+                new: Some(new),
+            });
+            self.set_tag(Tag::NM(Type::Int(new)));
+        }
+        if let Some(de) = expected.de {
+            fields.de = Some(Recomputed {
+                old: self.de(),
+                new: Some(de),
+            });
+            self.set_tag(Tag::de(Type::float(de)));
+            if let Some(old) = self.dv() {
+                fields.dv = Some(Recomputed {
+                    old: Some(old),
+                    new: Some(de),
+                });
+                self.set_tag(Tag::dv(Type::float(de)));
+            }
+        }
+        Ok(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::tests::PAF_RECORD_1;
+    use crate::test_util::record;
+    use crate::Reader;
+
+    /// A consistent record: 18 matches, 2 mismatches, a 2 base insertion
+    /// and a 3 base deletion, so 25 columns, NM 7 and de 4/22.
+    const CONSISTENT: &str = "q\t100\t0\t22\t+\tt\t100\t0\t23\t18\t25\t60\t\
+        NM:i:7\tde:f:0.1818\tcg:Z:10=1X2I5=3D1X3=\tcs:Z::10*ag+ac:5-ttt*ct:3";
+
+    /// Replace the first occurrence of `from` in the consistent record.
+    fn perturbed(from: &str, to: &str) -> PafRecord {
+        assert!(CONSISTENT.contains(from));
+        record(&CONSISTENT.replacen(from, to, 1))
+    }
+
+    #[test]
+    fn test_consistent() {
+        let report = record(CONSISTENT).audit();
+        assert!(report.is_consistent(), "{}", report);
+        assert_eq!(
+            report.checked,
+            vec![
+                AuditField::ResidueMatches,
+                AuditField::AlignmentBlockLen,
+                AuditField::Nm,
+                AuditField::De,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_perturbed_fields() {
+        let report = perturbed("\t18\t25\t", "\t17\t25\t").audit();
+        assert_eq!(
+            report.discrepancies,
+            vec![Discrepancy::ResidueMatches {
+                expected: 18,
+                observed: 17
+            }]
+        );
+
+        let report = perturbed("\t18\t25\t", "\t18\t26\t").audit();
+        assert_eq!(
+            report.discrepancies,
+            vec![Discrepancy::AlignmentBlockLen {
+                expected: 25,
+                observed: 26
+            }]
+        );
+
+        let report = perturbed("NM:i:7", "NM:i:5").audit();
+        assert_eq!(
+            report.discrepancies,
+            vec![Discrepancy::Nm {
+                expected: 7,
+                observed: 5
+            }]
+        );
+
+        let report = perturbed("de:f:0.1818", "de:f:0.2").audit();
+        assert_eq!(report.discrepancies.len(), 1);
+        assert_eq!(report.discrepancies[0].field(), AuditField::De);
+        assert!(report
+            .to_string()
+            .contains("de: expected 0.1818, observed 0.2"));
+    }
+
+    #[test]
+    fn test_partial_tags() {
+        // without cs, the =/X cg gives the same answers
+        let report = record(&CONSISTENT.replace("\tcs:Z::10*ag+ac:5-ttt*ct:3", "")).audit();
+        assert!(report.is_consistent(), "{}", report);
+        assert_eq!(report.checked.len(), 4);
+
+        // a plain M cg only gives the block length
+        let line = "q\t100\t0\t22\t+\tt\t100\t0\t23\t18\t25\t60\tNM:i:7\tcg:Z:11M2I5M3D4M";
+        let report = record(line).audit();
+        assert!(report.is_consistent(), "{}", report);
+        assert_eq!(report.checked, vec![AuditField::AlignmentBlockLen]);
+
+        // no tags, nothing to check
+        let line = "q\t100\t0\t22\t+\tt\t100\t0\t23\t18\t25\t60";
+        let report = record(line).audit();
+        assert!(report.checked.is_empty());
+        assert_eq!(report.to_string(), "nothing to check");
+    }
+
+    #[test]
+    fn test_unparsable_tag() {
+        let report = perturbed("cs:Z::10", "cs:Z:?10").audit();
+        assert!(!report.is_consistent());
+        assert_eq!(report.tag_errors.len(), 1);
+        // falls back to the cg tag
+        assert_eq!(report.checked.len(), 4);
+        assert!(report.discrepancies.is_empty());
+    }
+
+    #[test]
+    fn test_fixture() {
+        let mut reader = Reader::from_reader(PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let report = record.audit();
+        assert!(report.is_consistent(), "{}", report);
+        assert_eq!(report.checked, vec![AuditField::AlignmentBlockLen]);
+    }
+
+    #[test]
+    fn test_recompute_consistent() {
+        // recomputing changes nothing but the precision of de
+        let mut record = record(CONSISTENT);
+        let fields = record.recompute_stats().unwrap();
+        assert_eq!(
+            fields.residue_matches,
+            Some(Recomputed { old: 18, new: 18 })
+        );
+        assert_eq!(
+            fields.alignment_block_len,
+            Some(Recomputed { old: 25, new: 25 })
+        );
+        assert_eq!(
+            fields.nm,
+            Some(Recomputed {
+                old: Some(7),
+                new: Some(7)
+            })
+        );
+        let de = fields.de.unwrap();
+        assert_eq!(de.old, Some(0.1818));
+        assert!((de.new.unwrap() - 4.0 / 22.0).abs() < 1e-12);
+        assert_eq!(fields.dv, None);
+        assert!(record.audit().is_consistent());
+    }
+
+    #[test]
+    fn test_recompute_stale() {
+        // as if hand edited: stale columns, NM and dv, and no de
+        let line = CONSISTENT
+            .replacen("\t18\t25\t", "\t20\t30\t", 1)
+            .replace("NM:i:7", "NM:i:3")
+            .replace("de:f:0.1818", "dv:f:0.01");
+        let mut record = record(&line);
+        let fields = record.recompute_stats().unwrap();
+        assert!(fields.changed());
+        assert_eq!(
+            (record.residue_matches(), record.alignment_block_len()),
+            (18, 25)
+        );
+        assert_eq!(record.nm(), Some(7));
+        assert_eq!(record.de(), record.dv());
+        assert!(record.audit().is_consistent());
+        assert_eq!(
+            fields.to_string(),
+            format!(
+                "residue_matches: 20 -> 18\nalignment_block_len: 30 -> 25\nNM: 3 -> 7\n\
+                 de: absent -> {0}\ndv: 0.01 -> {0}\n",
+                4.0 / 22.0
+            )
+        );
+        // once recomputed, nothing changes
+        assert!(!record.recompute_stats().unwrap().changed());
+    }
+
+    #[test]
+    fn test_recompute_fixture() {
+        // the plain M cg of minimap2's record gives its block length back,
+        // leaving the rest
+        let mut reader = Reader::from_reader(PAF_RECORD_1);
+        let mut record = reader.read_record().unwrap().unwrap();
+        let fields = record.recompute_stats().unwrap();
+        assert_eq!(
+            fields.alignment_block_len,
+            Some(Recomputed {
+                old: 515145,
+                new: 515145
+            })
+        );
+        assert_eq!(fields.residue_matches, None);
+        assert_eq!((fields.nm, fields.de), (None, None));
+        assert_eq!(record.residue_matches(), 495111);
+        assert_eq!((record.nm(), record.de()), (Some(48730), Some(0.0003)));
+    }
+
+    #[test]
+    fn test_recompute_errors() {
+        let line = "q\t100\t0\t22\t+\tt\t100\t0\t23\t18\t25\t60\tNM:i:7";
+        assert!(record(line).recompute_stats().is_err());
+        // a cs tag which fails to parse is an error, rather than falling
+        // back to the cg tag
+        assert!(perturbed("cs:Z::10", "cs:Z:?10").recompute_stats().is_err());
+    }
+}
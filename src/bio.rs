@@ -0,0 +1,187 @@
+/*!
+Use records with [rust-bio](https://docs.rs/bio)'s strand and interval
+types, from the bio-types crate. Needs the `bio` feature.
+
+```
+use bio_types::genome::AbstractInterval;
+use paf::bio::TargetInterval;
+use paf::Reader;
+
+let paf = b"q\t1000\t0\t500\t+\tchr1\t5000\t1000\t1500\t500\t500\t60\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let record = reader.records().next().unwrap().unwrap();
+let interval = TargetInterval(&record);
+assert_eq!(interval.contig(), "chr1");
+assert_eq!(interval.range(), 1000..1500);
+```
+*/
+
+use std::ops::Range;
+
+use bio_types::genome::{AbstractInterval, Position};
+use bio_types::strand::Strand;
+
+use crate::{Error, ErrorKind, PafRecord, Result, TranscriptStrand};
+
+/// The interval a record covers on its target, as an [`AbstractInterval`].
+#[derive(Debug, Clone, Copy)]
+pub struct TargetInterval<'a>(pub &'a PafRecord);
+
+/// The interval a record covers on its query, as an [`AbstractInterval`].
+#[derive(Debug, Clone, Copy)]
+pub struct QueryInterval<'a>(pub &'a PafRecord);
+
+impl TargetInterval<'_> {
+    /// The strand of the query relative to the target.
+    pub fn strand(&self) -> Strand {
+        strand(self.0)
+    }
+}
+
+impl QueryInterval<'_> {
+    /// The strand of the query relative to the target.
+    pub fn strand(&self) -> Strand {
+        strand(self.0)
+    }
+}
+
+impl AbstractInterval for TargetInterval<'_> {
+    fn contig(&self) -> &str {
+        self.0.target_name()
+    }
+
+    fn range(&self) -> Range<Position> {
+        self.0.target_start() as Position..self.0.target_end() as Position
+    }
+}
+
+impl AbstractInterval for QueryInterval<'_> {
+    fn contig(&self) -> &str {
+        self.0.query_name()
+    }
+
+    fn range(&self) -> Range<Position> {
+        self.0.query_start() as Position..self.0.query_end() as Position
+    }
+}
+
+/// The strand of a record, `Unknown` for anything but `+` or `-`.
+fn strand(record: &PafRecord) -> Strand {
+    crate::Strand::try_from(record.strand()).map_or(Strand::Unknown, Strand::from)
+}
+
+impl From<crate::Strand> for Strand {
+    fn from(strand: crate::Strand) -> Self {
+        match strand {
+            crate::Strand::Forward => Strand::Forward,
+            crate::Strand::Reverse => Strand::Reverse,
+        }
+    }
+}
+
+/// An alignment is on one strand or the other, so it is an error for the
+/// strand to be `Unknown`.
+impl TryFrom<Strand> for crate::Strand {
+    type Error = Error;
+
+    fn try_from(strand: Strand) -> Result<Self> {
+        match strand {
+            Strand::Forward => Ok(crate::Strand::Forward),
+            Strand::Reverse => Ok(crate::Strand::Reverse),
+            Strand::Unknown => Err(Error::new(ErrorKind::InvalidRecord(
+                "an alignment strand is + or -, not unknown".to_string(),
+            ))),
+        }
+    }
+}
+
+/// The transcript strand of the ts tag.
+impl From<TranscriptStrand> for Strand {
+    fn from(strand: TranscriptStrand) -> Self {
+        match strand {
+            TranscriptStrand::Forward => Strand::Forward,
+            TranscriptStrand::Reverse => Strand::Reverse,
+            TranscriptStrand::Unknown => Strand::Unknown,
+        }
+    }
+}
+
+impl From<Strand> for TranscriptStrand {
+    fn from(strand: Strand) -> Self {
+        match strand {
+            Strand::Forward => TranscriptStrand::Forward,
+            Strand::Reverse => TranscriptStrand::Reverse,
+            Strand::Unknown => TranscriptStrand::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use ::bio::data_structures::interval_tree::IntervalTree;
+
+    use super::*;
+    use crate::test_util::records;
+
+    #[test]
+    fn test_interval_tree() {
+        let paf = "\
+q1\t1000\t0\t500\t+\tchr1\t5000\t1000\t1500\t500\t500\t60
+q2\t1000\t100\t900\t-\tchr1\t5000\t1400\t2200\t800\t800\t60
+q3\t1000\t0\t300\t+\tchr2\t5000\t1000\t1300\t300\t300\t60
+";
+        let records = records(paf);
+
+        // one tree for each target, holding the records over it
+        let mut trees: HashMap<&str, IntervalTree<Position, &PafRecord>> = HashMap::new();
+        for record in &records {
+            let interval = TargetInterval(record);
+            trees
+                .entry(record.target_name())
+                .or_default()
+                .insert(interval.range(), record);
+        }
+        let found: Vec<_> = trees["chr1"]
+            .find(1450..1460)
+            .map(|entry| entry.data().query_name())
+            .collect();
+        assert_eq!(found.len(), 2);
+        assert!(found.contains(&"q1") && found.contains(&"q2"));
+        let found: Vec<_> = trees["chr1"].find(2000..3000).collect();
+        assert_eq!(found.len(), 1);
+        assert_eq!(TargetInterval(found[0].data()).strand(), Strand::Reverse);
+        assert!(trees["chr2"].find(0..1000).next().is_none());
+
+        let query = QueryInterval(&records[1]);
+        assert_eq!((query.contig(), query.range()), ("q2", 100..900));
+        assert_eq!(query.strand(), Strand::Reverse);
+    }
+
+    #[test]
+    fn test_alignment_strand() {
+        for strand in [crate::Strand::Forward, crate::Strand::Reverse] {
+            let bio = Strand::from(strand);
+            assert_eq!(bio.strand_symbol(), strand.as_char().to_string());
+            assert_eq!(crate::Strand::try_from(bio).unwrap(), strand);
+        }
+        assert!(crate::Strand::try_from(Strand::Unknown).is_err());
+        let strand = crate::Strand::try_from('-').unwrap();
+        assert_eq!(Strand::from(strand), Strand::Reverse);
+        assert!(crate::Strand::try_from('.').is_err());
+    }
+
+    #[test]
+    fn test_strand() {
+        for strand in [
+            TranscriptStrand::Forward,
+            TranscriptStrand::Reverse,
+            TranscriptStrand::Unknown,
+        ] {
+            let bio = Strand::from(strand);
+            assert_eq!(bio.strand_symbol(), strand.as_char().to_string());
+            assert_eq!(TranscriptStrand::from(bio), strand);
+        }
+    }
+}
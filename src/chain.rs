@@ -0,0 +1,378 @@
+/*!
+Chain colinear alignments into synteny blocks.
+
+```
+use paf::chain::{chain_synteny, SyntenyOptions};
+use paf::Reader;
+
+let paf = b"q\t10000\t0\t1000\t+\tt\t10000\t0\t1000\t1000\t1000\t60\n\
+            q\t10000\t1500\t2500\t+\tt\t10000\t1200\t2200\t1000\t1000\t60\n\
+            q\t10000\t9000\t10000\t-\tt\t10000\t5000\t6000\t1000\t1000\t60\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+let blocks = chain_synteny(&records, &SyntenyOptions::default());
+assert_eq!(blocks.len(), 2);
+assert_eq!(blocks[0].members, [0, 1]);
+assert_eq!((blocks[0].query_start, blocks[0].query_end), (0, 2500));
+```
+*/
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+
+use crate::PafRecord;
+
+/// How far apart alignments can be and still chain. See [`chain_synteny`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntenyOptions {
+    /// The longest gap between neighbouring alignments, on the query and on
+    /// the target.
+    pub max_gap: u64,
+    /// The most neighbouring alignments may overlap, on the query and on the
+    /// target.
+    pub max_overlap: u64,
+}
+
+impl Default for SyntenyOptions {
+    /// Gaps up to 100 kb and overlaps up to 1 kb.
+    fn default() -> Self {
+        SyntenyOptions {
+            max_gap: 100_000,
+            max_overlap: 1000,
+        }
+    }
+}
+
+/// Colinear alignments of a query to a target on one strand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntenyBlock {
+    /// The query name.
+    pub query_name: String,
+    /// The target name.
+    pub target_name: String,
+    /// The strand, `+` or `-`.
+    pub strand: char,
+    /// The least query start of the members.
+    pub query_start: u64,
+    /// The greatest query end of the members.
+    pub query_end: u64,
+    /// The least target start of the members.
+    pub target_start: u64,
+    /// The greatest target end of the members.
+    pub target_end: u64,
+    /// The residue matches of the members, summed.
+    pub matches: u64,
+    /// The number of members.
+    pub alignments: usize,
+    /// The indices of the members among the records given, in order along
+    /// the target.
+    pub members: Vec<usize>,
+}
+
+/// An alignment as a chaining anchor.
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+    record: usize,
+    query_start: u64,
+    query_end: u64,
+    target_start: u64,
+    target_end: u64,
+    matches: u64,
+}
+
+/// Whether `next` can follow `prev` in a chain, with both coordinates
+/// moving forward on the plus strand, and the query moving back on the
+/// minus strand.
+fn colinear(prev: &Anchor, next: &Anchor, reverse: bool, options: &SyntenyOptions) -> bool {
+    let within = |before_start: u64, before_end: u64, after_start: u64, after_end: u64| {
+        after_start > before_start
+            && after_end > before_end
+            && after_start + options.max_overlap >= before_end
+            && after_start <= before_end + options.max_gap
+    };
+    within(
+        prev.target_start,
+        prev.target_end,
+        next.target_start,
+        next.target_end,
+    ) && if reverse {
+        within(
+            next.query_start,
+            next.query_end,
+            prev.query_start,
+            prev.query_end,
+        )
+    } else {
+        within(
+            prev.query_start,
+            prev.query_end,
+            next.query_start,
+            next.query_end,
+        )
+    }
+}
+
+/// Chains the alignments of each query, target and strand into blocks
+/// which are colinear: ordered the same way on the query and the target,
+/// or the opposite way on the minus strand.
+///
+/// Neighbours in a block are no further apart than `max_gap` and overlap by
+/// no more than `max_overlap`, on both sequences. Chains are found by
+/// dynamic programming over the alignments sorted along the target, scoring
+/// a chain by its residue matches, and taken best first, so that every
+/// alignment is in one block, bar those whose query or target interval
+/// ends before it starts, which are left out. Blocks are in order of
+/// target name and start, then query name and start.
+pub fn chain_synteny<I>(records: I, options: &SyntenyOptions) -> Vec<SyntenyBlock>
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    let mut groups: HashMap<(String, String, char), Vec<Anchor>> = HashMap::new();
+    for (i, record) in records.into_iter().enumerate() {
+        let record = record.borrow();
+        if record.query_end() < record.query_start() || record.target_end() < record.target_start()
+        {
+            continue;
+        }
+        let strand = if record.strand() == '-' { '-' } else { '+' };
+        let anchor = Anchor {
+            record: i,
+            query_start: record.query_start() as u64,
+            query_end: record.query_end() as u64,
+            target_start: record.target_start() as u64,
+            target_end: record.target_end() as u64,
+            matches: record.residue_matches() as u64,
+        };
+        groups
+            .entry((
+                record.query_name().to_string(),
+                record.target_name().to_string(),
+                strand,
+            ))
+            .or_default()
+            .push(anchor);
+    }
+
+    let mut blocks = Vec::new();
+    for ((query_name, target_name, strand), anchors) in groups {
+        for members in chain(anchors, strand == '-', options) {
+            blocks.push(SyntenyBlock {
+                query_name: query_name.clone(),
+                target_name: target_name.clone(),
+                strand,
+                query_start: members.iter().map(|a| a.query_start).min().unwrap(),
+                query_end: members.iter().map(|a| a.query_end).max().unwrap(),
+                target_start: members.iter().map(|a| a.target_start).min().unwrap(),
+                target_end: members.iter().map(|a| a.target_end).max().unwrap(),
+                matches: members.iter().map(|a| a.matches).sum(),
+                alignments: members.len(),
+                members: members.iter().map(|a| a.record).collect(),
+            });
+        }
+    }
+    blocks.sort_by(|a, b| {
+        (&a.target_name, a.target_start, &a.query_name, a.query_start).cmp(&(
+            &b.target_name,
+            b.target_start,
+            &b.query_name,
+            b.query_start,
+        ))
+    });
+    blocks
+}
+
+/// Splits the anchors of one group into chains, each in order along the
+/// target.
+fn chain(mut anchors: Vec<Anchor>, reverse: bool, options: &SyntenyOptions) -> Vec<Vec<Anchor>> {
+    anchors.sort_unstable_by_key(|a| (a.target_start, a.target_end, a.query_start, a.record));
+    let n = anchors.len();
+    // an anchor this far before another along the target cannot precede it
+    let longest = anchors
+        .iter()
+        .map(|a| a.target_end - a.target_start)
+        .max()
+        .unwrap_or(0);
+    let reach = longest + options.max_gap;
+
+    // the best score of a chain ending at each anchor, and the anchor before
+    let mut scores = vec![0; n];
+    let mut prev = vec![None; n];
+    for i in 0..n {
+        scores[i] = anchors[i].matches;
+        for j in (0..i).rev() {
+            if anchors[j].target_start + reach < anchors[i].target_start {
+                break;
+            }
+            if scores[j] + anchors[i].matches > scores[i]
+                && colinear(&anchors[j], &anchors[i], reverse, options)
+            {
+                scores[i] = scores[j] + anchors[i].matches;
+                prev[i] = Some(j);
+            }
+        }
+    }
+
+    // best chains first, each stopping at an anchor taken by a better one
+    let mut ends: Vec<usize> = (0..n).collect();
+    ends.sort_unstable_by_key(|&i| (std::cmp::Reverse(scores[i]), anchors[i].record));
+    let mut used = vec![false; n];
+    let mut chains = Vec::new();
+    for end in ends {
+        let mut members = Vec::new();
+        let mut i = Some(end);
+        while let Some(at) = i.filter(|&at| !used[at]) {
+            used[at] = true;
+            members.push(anchors[at]);
+            i = prev[at];
+        }
+        if !members.is_empty() {
+            members.reverse();
+            chains.push(members);
+        }
+    }
+    chains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+
+    /// Alignments of query `q` to target `t`, as query start, query end,
+    /// target start, target end and strand, with matches the target span.
+    fn blocks(
+        alignments: &[(u32, u32, u32, u32, char)],
+        options: &SyntenyOptions,
+    ) -> Vec<SyntenyBlock> {
+        let paf: String = alignments
+            .iter()
+            .map(|&(qs, qe, ts, te, strand)| {
+                format!(
+                    "q\t20000\t{}\t{}\t{}\tt\t20000\t{}\t{}\t{}\t{}\t60\n",
+                    qs,
+                    qe,
+                    strand,
+                    ts,
+                    te,
+                    te - ts,
+                    te - ts
+                )
+            })
+            .collect();
+        chain_synteny(records(&paf), options)
+    }
+
+    fn spans(blocks: &[SyntenyBlock]) -> Vec<(u64, u64, u64, u64, char, Vec<usize>)> {
+        blocks
+            .iter()
+            .map(|b| {
+                (
+                    b.query_start,
+                    b.query_end,
+                    b.target_start,
+                    b.target_end,
+                    b.strand,
+                    b.members.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_minus_strand() {
+        // along the target, the query runs back from 9000 to 4000, then
+        // jumps forward to 9500, which breaks the chain; given out of order
+        let alignments = [
+            (4000, 5000, 4000, 5000, '-'),
+            (8000, 9000, 1000, 2000, '-'),
+            (8500, 9500, 6000, 7000, '-'),
+            (6000, 7000, 2500, 3500, '-'),
+        ];
+        let options = SyntenyOptions {
+            max_gap: 1000,
+            max_overlap: 0,
+        };
+        let minus = blocks(&alignments, &options);
+        assert_eq!(
+            spans(&minus),
+            [
+                (4000, 9000, 1000, 5000, '-', vec![1, 3, 0]),
+                (8500, 9500, 6000, 7000, '-', vec![2]),
+            ]
+        );
+        assert_eq!(minus[0].matches, 3000);
+        assert_eq!(minus[0].alignments, 3);
+
+        // the same alignments on the plus strand do not chain at all
+        let plus: Vec<_> = alignments
+            .iter()
+            .map(|&(qs, qe, ts, te, _)| (qs, qe, ts, te, '+'))
+            .collect();
+        let plus = blocks(&plus, &options);
+        assert_eq!(plus.len(), 4);
+        assert!(plus.iter().all(|block| block.alignments == 1));
+    }
+
+    #[test]
+    fn test_gaps_and_overlaps() {
+        let options = SyntenyOptions {
+            max_gap: 1000,
+            max_overlap: 100,
+        };
+        let blocks = blocks(
+            &[
+                (0, 1000, 0, 1000, '+'),
+                // overlaps the first by 50 on the target
+                (1200, 2000, 950, 1750, '+'),
+                // 1500 past the second on the target
+                (2500, 3000, 3250, 3750, '+'),
+                // on the other strand
+                (3000, 3500, 3800, 4300, '-'),
+                // overlaps the third by 200 on the query
+                (2800, 3300, 3800, 4300, '+'),
+            ],
+            &options,
+        );
+        assert_eq!(
+            spans(&blocks),
+            [
+                (0, 2000, 0, 1750, '+', vec![0, 1]),
+                (2500, 3000, 3250, 3750, '+', vec![2]),
+                (2800, 3300, 3800, 4300, '+', vec![4]),
+                (3000, 3500, 3800, 4300, '-', vec![3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_best_chain_first() {
+        // a long chain and a short alignment which could follow either of
+        // its first two; every alignment ends up in exactly one block
+        let blocks = blocks(
+            &[
+                (0, 1000, 0, 1000, '+'),
+                (1000, 2000, 1000, 2000, '+'),
+                (2000, 3000, 2000, 3000, '+'),
+                (1000, 1100, 1000, 1100, '+'),
+            ],
+            &SyntenyOptions::default(),
+        );
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].members, [0, 1, 2]);
+        assert_eq!(blocks[1].members, [3]);
+        assert!(chain_synteny(Vec::<PafRecord>::new(), &SyntenyOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_reversed_coordinates() {
+        // an alignment whose target interval ends before it starts is left
+        // out of the blocks
+        let paf = "\
+q\t20000\t0\t1000\t+\tt\t20000\t0\t1000\t1000\t1000\t60
+q\t20000\t1000\t2000\t+\tt\t20000\t9000\t1000\t1000\t1000\t60
+";
+        let blocks = chain_synteny(records(paf), &SyntenyOptions::default());
+        assert_eq!(spans(&blocks), [(0, 1000, 0, 1000, '+', vec![0])]);
+    }
+}
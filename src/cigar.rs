@@ -0,0 +1,1716 @@
+use std::{fmt, ops::Range};
+
+use crate::{tags, CsOp, CsString, Error, ErrorKind, PafRecord, Result, Tag, Type};
+
+/// A CIGAR operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CigarOp {
+    /// Alignment match, which may be a sequence match or mismatch (`M`).
+    Match,
+    /// Insertion to the target (`I`).
+    Insertion,
+    /// Deletion from the target (`D`).
+    Deletion,
+    /// Skipped region of the target, e.g. an intron (`N`).
+    Skip,
+    /// Soft clipping, where the clipped bases are present in the query (`S`).
+    SoftClip,
+    /// Hard clipping, where the clipped bases are absent from the query (`H`).
+    HardClip,
+    /// Padding, a silent deletion from a padded reference (`P`).
+    Padding,
+    /// Sequence match (`=`).
+    Equal,
+    /// Sequence mismatch (`X`).
+    Diff,
+}
+
+impl CigarOp {
+    /// Look up an operation by its letter.
+    pub fn from_char(c: char) -> Option<CigarOp> {
+        match c {
+            'M' => Some(CigarOp::Match),
+            'I' => Some(CigarOp::Insertion),
+            'D' => Some(CigarOp::Deletion),
+            'N' => Some(CigarOp::Skip),
+            'S' => Some(CigarOp::SoftClip),
+            'H' => Some(CigarOp::HardClip),
+            'P' => Some(CigarOp::Padding),
+            '=' => Some(CigarOp::Equal),
+            'X' => Some(CigarOp::Diff),
+            _ => None,
+        }
+    }
+
+    /// The letter representing this operation.
+    pub fn as_char(&self) -> char {
+        match self {
+            CigarOp::Match => 'M',
+            CigarOp::Insertion => 'I',
+            CigarOp::Deletion => 'D',
+            CigarOp::Skip => 'N',
+            CigarOp::SoftClip => 'S',
+            CigarOp::HardClip => 'H',
+            CigarOp::Padding => 'P',
+            CigarOp::Equal => '=',
+            CigarOp::Diff => 'X',
+        }
+    }
+
+    /// The BAM code of this operation, as used in the packed representation.
+    fn code(&self) -> u8 {
+        match self {
+            CigarOp::Match => 0,
+            CigarOp::Insertion => 1,
+            CigarOp::Deletion => 2,
+            CigarOp::Skip => 3,
+            CigarOp::SoftClip => 4,
+            CigarOp::HardClip => 5,
+            CigarOp::Padding => 6,
+            CigarOp::Equal => 7,
+            CigarOp::Diff => 8,
+        }
+    }
+
+    /// Look up an operation by its BAM code.
+    fn from_code(code: u8) -> CigarOp {
+        match code {
+            0 => CigarOp::Match,
+            1 => CigarOp::Insertion,
+            2 => CigarOp::Deletion,
+            3 => CigarOp::Skip,
+            4 => CigarOp::SoftClip,
+            5 => CigarOp::HardClip,
+            6 => CigarOp::Padding,
+            7 => CigarOp::Equal,
+            8 => CigarOp::Diff,
+            _ => unreachable!("invalid packed CIGAR code {}", code),
+        }
+    }
+
+    /// Whether this operation consumes bases of the query.
+    pub fn consumes_query(&self) -> bool {
+        matches!(
+            self,
+            CigarOp::Match
+                | CigarOp::Insertion
+                | CigarOp::SoftClip
+                | CigarOp::Equal
+                | CigarOp::Diff
+        )
+    }
+
+    /// Whether this operation consumes bases of the target.
+    pub fn consumes_target(&self) -> bool {
+        matches!(
+            self,
+            CigarOp::Match | CigarOp::Deletion | CigarOp::Skip | CigarOp::Equal | CigarOp::Diff
+        )
+    }
+}
+
+/// Summary statistics of a CIGAR.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CigarStats {
+    /// Aligned bases (`M`, `=` and `X`).
+    pub aligned_bases: u64,
+    /// Inserted bases.
+    pub inserted_bases: u64,
+    /// Deleted bases.
+    pub deleted_bases: u64,
+    /// Number of insertion events.
+    pub insertions: u64,
+    /// Number of deletion events.
+    pub deletions: u64,
+    /// Length of the longest insertion.
+    pub longest_insertion: u32,
+    /// Length of the longest deletion.
+    pub longest_deletion: u32,
+    /// Exactly matching bases, if the CIGAR has `=` or `X` operations.
+    pub exact_matches: Option<u64>,
+    /// Mismatching bases, if the CIGAR has `=` or `X` operations.
+    pub mismatches: Option<u64>,
+}
+
+/// Bits of a run's first byte holding the operation code.
+const CODE_MASK: u8 = 0x0f;
+/// Bits of a run's first byte holding the low bits of the length.
+const FIRST_LEN_BITS: u32 = 3;
+/// Bits of each following byte holding more of the length.
+const LEN_BITS: u32 = 7;
+/// The flag set on every byte of a run but the last.
+const CONTINUES: u8 = 0x80;
+
+/// A parsed CIGAR string.
+///
+/// The operations are stored packed, each run as the BAM operation code in
+/// the low 4 bits of a byte, and its length spread over the remaining bits
+/// and as many following bytes as it needs, 7 bits at a time. Runs of up
+/// to 1023 bases take 1 or 2 bytes, well under the space of their text,
+/// while BAM's fixed 4 bytes are more than the text of the short runs which
+/// make up most of a typical cg tag.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Cigar {
+    ops: Vec<u8>,
+}
+
+/// Build a CIGAR parse error at the given byte offset.
+fn cigar_error(offset: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::ReadRecord(format!(
+        "Invalid CIGAR at byte {}: {}",
+        offset, msg
+    )))
+}
+
+impl Cigar {
+    /// Create a CIGAR from a list of `(length, operation)` pairs.
+    pub fn new(ops: Vec<(u32, CigarOp)>) -> Self {
+        let mut cigar = Cigar {
+            ops: Vec::with_capacity(ops.len()),
+        };
+        for (len, op) in ops {
+            cigar.push_run(len, op);
+        }
+        cigar
+    }
+
+    /// Append a run without merging it into the last one.
+    fn push_run(&mut self, len: u32, op: CigarOp) {
+        let mut byte = op.code() | ((len & ((1 << FIRST_LEN_BITS) - 1)) as u8) << 4;
+        let mut rest = len >> FIRST_LEN_BITS;
+        while rest > 0 {
+            self.ops.push(byte | CONTINUES);
+            byte = (rest & ((1 << LEN_BITS) - 1)) as u8;
+            rest >>= LEN_BITS;
+        }
+        self.ops.push(byte);
+    }
+
+    /// Remove and return the last run.
+    fn pop_run(&mut self) -> Option<(u32, CigarOp)> {
+        let (last, rest) = self.ops.split_last()?;
+        debug_assert_eq!(last & CONTINUES, 0);
+        // the run starts after the previous byte without the flag
+        let start = rest
+            .iter()
+            .rposition(|byte| byte & CONTINUES == 0)
+            .map_or(0, |i| i + 1);
+        let (run, _) = decode_run(&self.ops[start..]);
+        self.ops.truncate(start);
+        Some(run)
+    }
+
+    /// The heap memory used by the packed operations, in bytes.
+    pub(crate) fn heap_size(&self) -> usize {
+        self.ops.capacity()
+    }
+
+    /// Parse a CIGAR string, e.g. `10M2I5M`.
+    pub fn parse(cigar: &str) -> Result<Self> {
+        let mut parsed = Cigar::default();
+        let mut len: Option<u32> = None;
+        let mut len_start = 0;
+
+        for (i, c) in cigar.char_indices() {
+            if let Some(d) = c.to_digit(10) {
+                if len.is_none() {
+                    len_start = i;
+                }
+                len = len
+                    .unwrap_or(0)
+                    .checked_mul(10)
+                    .and_then(|l| l.checked_add(d))
+                    .map(Some)
+                    .ok_or_else(|| cigar_error(len_start, "operation length overflows"))?;
+                continue;
+            }
+
+            let op = CigarOp::from_char(c)
+                .ok_or_else(|| cigar_error(i, &format!("unknown operation {:?}", c)))?;
+            match len.take() {
+                None => return Err(cigar_error(i, "operation without a length")),
+                Some(0) => return Err(cigar_error(len_start, "zero length operation")),
+                Some(n) => parsed.push_run(n, op),
+            }
+        }
+
+        if len.is_some() {
+            return Err(cigar_error(len_start, "length without an operation"));
+        }
+
+        parsed.ops.shrink_to_fit();
+        Ok(parsed)
+    }
+
+    /// An iterator over the `(length, operation)` pairs.
+    pub fn ops(&self) -> CigarOps<'_> {
+        CigarOps { bytes: &self.ops }
+    }
+
+    /// The number of operations.
+    pub fn len(&self) -> usize {
+        // every run ends in a byte without the flag
+        self.ops
+            .iter()
+            .filter(|&&byte| byte & CONTINUES == 0)
+            .count()
+    }
+
+    /// Whether there are no operations.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Compute summary statistics of the operations.
+    pub fn stats(&self) -> CigarStats {
+        let mut stats = CigarStats::default();
+        let mut has_eqx = false;
+        let (mut exact, mut mismatches) = (0, 0);
+
+        for (len, op) in self {
+            match op {
+                CigarOp::Match => stats.aligned_bases += len as u64,
+                CigarOp::Equal => {
+                    has_eqx = true;
+                    stats.aligned_bases += len as u64;
+                    exact += len as u64;
+                }
+                CigarOp::Diff => {
+                    has_eqx = true;
+                    stats.aligned_bases += len as u64;
+                    mismatches += len as u64;
+                }
+                CigarOp::Insertion => {
+                    stats.insertions += 1;
+                    stats.inserted_bases += len as u64;
+                    stats.longest_insertion = stats.longest_insertion.max(len);
+                }
+                CigarOp::Deletion => {
+                    stats.deletions += 1;
+                    stats.deleted_bases += len as u64;
+                    stats.longest_deletion = stats.longest_deletion.max(len);
+                }
+                CigarOp::Skip | CigarOp::SoftClip | CigarOp::HardClip | CigarOp::Padding => {}
+            }
+        }
+
+        if has_eqx {
+            stats.exact_matches = Some(exact);
+            stats.mismatches = Some(mismatches);
+        }
+        stats
+    }
+
+    /// The number of query bases consumed.
+    pub fn query_len(&self) -> u64 {
+        self.ops()
+            .filter(|(_, op)| op.consumes_query())
+            .map(|(len, _)| len as u64)
+            .sum()
+    }
+
+    /// The number of target bases consumed.
+    pub fn target_len(&self) -> u64 {
+        self.ops()
+            .filter(|(_, op)| op.consumes_target())
+            .map(|(len, _)| len as u64)
+            .sum()
+    }
+
+    /// The alignment block length: the number of columns in the alignment,
+    /// counting matches, insertions and deletions but not skips or clips.
+    pub fn block_len(&self) -> u64 {
+        self.ops()
+            .filter(|(_, op)| {
+                matches!(
+                    op,
+                    CigarOp::Match
+                        | CigarOp::Insertion
+                        | CigarOp::Deletion
+                        | CigarOp::Equal
+                        | CigarOp::Diff
+                )
+            })
+            .map(|(len, _)| len as u64)
+            .sum()
+    }
+
+    /// The CIGAR with the roles of query and target swapped.
+    ///
+    /// Insertions become deletions and vice versa, while matches are left
+    /// alone. This is what swapping the query and target of a record
+    /// requires; the operation order is unchanged, so on a minus strand
+    /// record it must be combined with [`Cigar::reversed`] to keep reading
+    /// along the new target. Skips, clips and padding have no counterpart
+    /// on the other sequence and are kept as they are.
+    pub fn inverted(&self) -> Cigar {
+        Cigar::new(
+            self.ops()
+                .map(|(len, op)| {
+                    let op = match op {
+                        CigarOp::Insertion => CigarOp::Deletion,
+                        CigarOp::Deletion => CigarOp::Insertion,
+                        op => op,
+                    };
+                    (len, op)
+                })
+                .collect(),
+        )
+    }
+
+    /// The CIGAR with its operations in reverse order.
+    ///
+    /// This describes the same alignment read from the other end, which is
+    /// what flipping the strand of a record requires. It leaves query and
+    /// target roles alone; see [`Cigar::inverted`] for that.
+    pub fn reversed(&self) -> Cigar {
+        let mut ops: Vec<_> = self.ops().collect();
+        ops.reverse();
+        Cigar::new(ops)
+    }
+
+    /// Split the CIGAR after `n` target bases.
+    ///
+    /// An operation straddling the cut is divided in two. Operations that
+    /// consume no target, such as insertions, sitting exactly at the cut go
+    /// to the second half, so the first half never ends in one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is greater than [`Cigar::target_len`].
+    pub fn split_at_target(&self, n: u64) -> (Cigar, Cigar) {
+        assert!(
+            n <= self.target_len(),
+            "split at target base {} of a CIGAR consuming {}",
+            n,
+            self.target_len()
+        );
+        self.split_at(n, CigarOp::consumes_target)
+    }
+
+    /// Split the CIGAR after `n` query bases.
+    ///
+    /// An operation straddling the cut is divided in two. Operations that
+    /// consume no query, such as deletions, sitting exactly at the cut go
+    /// to the second half, so the first half never ends in one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is greater than [`Cigar::query_len`].
+    pub fn split_at_query(&self, n: u64) -> (Cigar, Cigar) {
+        assert!(
+            n <= self.query_len(),
+            "split at query base {} of a CIGAR consuming {}",
+            n,
+            self.query_len()
+        );
+        self.split_at(n, CigarOp::consumes_query)
+    }
+
+    /// The part of the CIGAR covering a range of target offsets, counted
+    /// from the start of the alignment.
+    ///
+    /// Insertions at the start of the range are kept and those at the end
+    /// dropped, as for [`Cigar::split_at_target`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is decreasing or ends past [`Cigar::target_len`].
+    pub fn slice_target(&self, range: Range<u64>) -> Cigar {
+        assert!(range.start <= range.end, "decreasing range {:?}", range);
+        let (head, _) = self.split_at_target(range.end);
+        head.split_at_target(range.start).1
+    }
+
+    /// The CIGAR with sequence matches and mismatches (`=` and `X`) merged
+    /// into alignment matches (`M`).
+    pub fn collapse_eqx(&self) -> Cigar {
+        let mut cigar = Cigar::default();
+        for (len, op) in self {
+            let op = match op {
+                CigarOp::Equal | CigarOp::Diff => CigarOp::Match,
+                op => op,
+            };
+            cigar.push(len as u64, op);
+        }
+        cigar
+    }
+
+    /// The CIGAR with alignment matches (`M`) split into sequence matches
+    /// and mismatches (`=` and `X`) using a cs string of the same alignment.
+    ///
+    /// Existing `=` and `X` runs are redone from the cs string too. It is an
+    /// error for the cs string to align a different number of bases.
+    pub fn expand_to_eqx(&self, cs: &CsString) -> Result<Cigar> {
+        let mismatch = |msg: &str| {
+            Error::new(ErrorKind::InvalidRecord(format!(
+                "cs string does not fit the CIGAR: {}",
+                msg
+            )))
+        };
+        let mut columns = cs.ops().filter_map(|op| match op {
+            CsOp::Identity(_) | CsOp::Match(_) => Some((op.query_len(), CigarOp::Equal)),
+            CsOp::Mismatch { .. } => Some((1, CigarOp::Diff)),
+            _ => None,
+        });
+        let mut current: Option<(u32, CigarOp)> = None;
+        let mut cigar = Cigar::default();
+        for (len, op) in self {
+            if !matches!(op, CigarOp::Match | CigarOp::Equal | CigarOp::Diff) {
+                cigar.push(len as u64, op);
+                continue;
+            }
+            let mut remaining = len;
+            while remaining > 0 {
+                let (available, column) = match current.take().or_else(|| columns.next()) {
+                    Some(run) => run,
+                    None => return Err(mismatch("too few aligned bases")),
+                };
+                let taken = available.min(remaining);
+                cigar.push(taken as u64, column);
+                remaining -= taken;
+                if taken < available {
+                    current = Some((available - taken, column));
+                }
+            }
+        }
+        if current.is_some() || columns.next().is_some() {
+            return Err(mismatch("too many aligned bases"));
+        }
+        Ok(cigar)
+    }
+
+    /// Join another CIGAR onto the end of this one.
+    ///
+    /// Runs of the same operation either side of the seam are merged.
+    pub fn concat(&self, other: &Cigar) -> Cigar {
+        self.concat_with_gap(0, 0, other)
+    }
+
+    /// Join another CIGAR onto the end of this one across an unaligned gap.
+    ///
+    /// The `query_gap` bases between the two alignments become an insertion
+    /// and the `target_gap` bases a deletion, placed in that order between
+    /// them. Runs of the same operation at the seams are merged.
+    pub fn concat_with_gap(&self, query_gap: u64, target_gap: u64, other: &Cigar) -> Cigar {
+        self.join(query_gap, target_gap, CigarOp::Deletion, other)
+    }
+
+    /// Join another CIGAR onto the end of this one across an intron.
+    ///
+    /// As [`Cigar::concat_with_gap`], but the unaligned target bases become
+    /// a skip (`N`) rather than a deletion, as for spliced alignments.
+    pub fn concat_with_skip(&self, query_gap: u64, target_gap: u64, other: &Cigar) -> Cigar {
+        self.join(query_gap, target_gap, CigarOp::Skip, other)
+    }
+
+    /// Join across a gap whose target bases become `target_op` runs.
+    fn join(&self, query_gap: u64, target_gap: u64, target_op: CigarOp, other: &Cigar) -> Cigar {
+        let mut cigar = self.clone();
+        cigar.push(query_gap, CigarOp::Insertion);
+        cigar.push(target_gap, target_op);
+        for (len, op) in other {
+            cigar.push(len as u64, op);
+        }
+        cigar
+    }
+
+    /// Append a run, merging it into the last one when the operations match.
+    fn push(&mut self, mut len: u64, op: CigarOp) {
+        if len == 0 {
+            return;
+        }
+        match self.pop_run() {
+            Some((last, last_op)) if last_op == op => len += last as u64,
+            Some((last, last_op)) => self.push_run(last, last_op),
+            None => {}
+        }
+        while len > 0 {
+            let run = len.min(u32::MAX as u64);
+            self.push_run(run as u32, op);
+            len -= run;
+        }
+    }
+
+    /// Split after `n` bases consumed by operations satisfying `consumes`.
+    fn split_at(&self, n: u64, consumes: fn(&CigarOp) -> bool) -> (Cigar, Cigar) {
+        let mut head = Vec::new();
+        let mut remaining = n;
+        let mut ops = self.ops();
+        for (len, op) in ops.by_ref() {
+            if remaining == 0 {
+                let mut tail = vec![(len, op)];
+                tail.extend(ops);
+                return (Cigar::new(head), Cigar::new(tail));
+            }
+            if !consumes(&op) || len as u64 <= remaining {
+                if consumes(&op) {
+                    remaining -= len as u64;
+                }
+                head.push((len, op));
+            } else {
+                let first = remaining as u32;
+                head.push((first, op));
+                let mut tail = vec![(len - first, op)];
+                tail.extend(ops);
+                return (Cigar::new(head), Cigar::new(tail));
+            }
+        }
+        (Cigar::new(head), Cigar::default())
+    }
+}
+
+/// Decode the packed run at the start of `bytes`, returning it and the
+/// number of bytes it took.
+fn decode_run(bytes: &[u8]) -> ((u32, CigarOp), usize) {
+    let first = bytes[0];
+    let mut len = ((first & !CONTINUES) >> 4) as u32;
+    let mut shift = FIRST_LEN_BITS;
+    let mut used = 1;
+    let mut byte = first;
+    while byte & CONTINUES != 0 {
+        byte = bytes[used];
+        len |= ((byte & !CONTINUES) as u32) << shift;
+        shift += LEN_BITS;
+        used += 1;
+    }
+    ((len, CigarOp::from_code(first & CODE_MASK)), used)
+}
+
+/// An iterator over the `(length, operation)` pairs of a [`Cigar`].
+#[derive(Debug, Clone)]
+pub struct CigarOps<'a> {
+    bytes: &'a [u8],
+}
+
+impl Iterator for CigarOps<'_> {
+    type Item = (u32, CigarOp);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+        let (run, used) = decode_run(self.bytes);
+        self.bytes = &self.bytes[used..];
+        Some(run)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let bytes = self.bytes.len();
+        (bytes.div_ceil(6), Some(bytes))
+    }
+}
+
+impl<'a> IntoIterator for &'a Cigar {
+    type Item = (u32, CigarOp);
+    type IntoIter = CigarOps<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ops()
+    }
+}
+
+/// Shows the operations, as `Cigar { ops: [(10, Match), ...] }`.
+impl fmt::Debug for Cigar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cigar")
+            .field("ops", &self.ops().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl fmt::Display for Cigar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (len, op) in self {
+            write!(f, "{}{}", len, op.as_char())?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts offsets along the alignment into query coordinates on the
+/// original query strand.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct QueryAxis {
+    start: u64,
+    end: u64,
+    reverse: bool,
+}
+
+impl QueryAxis {
+    /// The query axis of a record.
+    pub(crate) fn of(record: &PafRecord) -> Self {
+        QueryAxis {
+            start: record.query_start() as u64,
+            end: record.query_end() as u64,
+            reverse: record.strand() == '-',
+        }
+    }
+
+    /// The query coordinate `offset` query bases into the alignment.
+    ///
+    /// On the minus strand the alignment walks the query backwards from
+    /// `query_end - 1`.
+    pub(crate) fn position(&self, offset: u64) -> u64 {
+        if self.reverse {
+            self.end.saturating_sub(offset + 1)
+        } else {
+            self.start + offset
+        }
+    }
+
+    /// The query interval covered by the alignment offsets `from..to`, as a
+    /// half-open interval on the original strand.
+    pub(crate) fn interval(&self, from: u64, to: u64) -> (u64, u64) {
+        if self.reverse {
+            (self.end - to, self.end - from)
+        } else {
+            (self.start + from, self.start + to)
+        }
+    }
+
+    /// The offset into the alignment of the query coordinate `pos`, if it
+    /// lies within the aligned interval.
+    pub(crate) fn offset(&self, pos: u64) -> Option<u64> {
+        if pos < self.start || pos >= self.end {
+            None
+        } else if self.reverse {
+            Some(self.end - 1 - pos)
+        } else {
+            Some(pos - self.start)
+        }
+    }
+}
+
+/// The result of lifting a coordinate from one sequence of an alignment to
+/// the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiftoverResult {
+    /// The position is aligned to this coordinate on the other sequence.
+    Mapped(u64),
+    /// The position falls in a gap on the other sequence (an insertion when
+    /// lifting from the query, a deletion when lifting from the target).
+    /// `flank` is the coordinate on the other sequence of the base which
+    /// follows the gap in alignment order.
+    Deleted { flank: u64 },
+    /// The position lies outside the aligned interval.
+    OutOfRange,
+}
+
+/// Whether an indel is an insertion or a deletion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndelKind {
+    /// Bases present in the query but not the target.
+    Insertion,
+    /// Bases present in the target but not the query.
+    Deletion,
+}
+
+/// An insertion or deletion, placed on both sequences.
+///
+/// Both intervals are half-open, with the query interval on the original
+/// query strand. An insertion is a point on the target, where
+/// `target_start == target_end`, and a deletion a point on the query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Indel {
+    /// Insertion or deletion.
+    pub kind: IndelKind,
+    /// The number of inserted or deleted bases.
+    pub len: u32,
+    /// Start of the indel on the target.
+    pub target_start: u64,
+    /// End of the indel on the target.
+    pub target_end: u64,
+    /// Start of the indel on the query.
+    pub query_start: u64,
+    /// End of the indel on the query.
+    pub query_end: u64,
+}
+
+/// A run of a single CIGAR operation, placed on the alignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Segment {
+    /// The operation.
+    pub(crate) op: CigarOp,
+    /// The length of the run.
+    pub(crate) len: u32,
+    /// Query bases consumed before this run.
+    pub(crate) query_offset: u64,
+    /// Target coordinate at the start of this run.
+    pub(crate) target_pos: u64,
+}
+
+/// Walk the operations of a CIGAR, tracking the query offset and target
+/// coordinate at the start of each one.
+pub(crate) fn segments(cigar: &Cigar, target_start: u64) -> impl Iterator<Item = Segment> + '_ {
+    let mut query_offset = 0;
+    let mut target_pos = target_start;
+    cigar.ops().map(move |(len, op)| {
+        let segment = Segment {
+            op,
+            len,
+            query_offset,
+            target_pos,
+        };
+        if op.consumes_query() {
+            query_offset += len as u64;
+        }
+        if op.consumes_target() {
+            target_pos += len as u64;
+        }
+        segment
+    })
+}
+
+/// An iterator over the aligned pairs of a record.
+///
+/// Yields `(query_pos, target_pos)` for every column of the alignment, with
+/// `None` on the side which has no base (insertions, deletions, skips and
+/// soft clips). Query positions are on the original query strand, so they
+/// decrease along a minus strand alignment.
+#[derive(Debug, Clone)]
+pub struct AlignedPairs {
+    segments: std::vec::IntoIter<Segment>,
+    current: Option<(Segment, u32)>,
+    axis: QueryAxis,
+}
+
+impl Iterator for AlignedPairs {
+    type Item = (Option<u64>, Option<u64>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.current {
+                Some((segment, i)) if i < segment.len => {
+                    self.current = Some((segment, i + 1));
+                    let query = segment
+                        .op
+                        .consumes_query()
+                        .then(|| self.axis.position(segment.query_offset + i as u64));
+                    let target = segment
+                        .op
+                        .consumes_target()
+                        .then(|| segment.target_pos + i as u64);
+                    if query.is_none() && target.is_none() {
+                        continue;
+                    }
+                    return Some((query, target));
+                }
+                _ => self.current = Some((self.segments.next()?, 0)),
+            }
+        }
+    }
+}
+
+impl PafRecord {
+    /// Parse the cg tag, if present.
+    ///
+    /// A cg tag already parsed by a reader set to
+    /// [`ReaderBuilder::compact_cigar`](crate::ReaderBuilder::compact_cigar)
+    /// is copied rather than parsed again.
+    pub fn cigar(&self) -> Option<Result<Cigar>> {
+        if let Some(Tag::cg(Type::Cigar(cigar))) = self.optional_fields().get(tags::CG) {
+            return Some(Ok(cigar.clone()));
+        }
+        self.cg_checked()
+            .transpose()
+            .map(|cg| cg.and_then(Cigar::parse))
+    }
+
+    /// Parse the cg tag, if present, and compute its summary statistics.
+    pub fn cigar_stats(&self) -> Option<Result<CigarStats>> {
+        self.cigar().map(|cigar| cigar.map(|c| c.stats()))
+    }
+
+    /// An iterator over the aligned `(query_pos, target_pos)` pairs of the
+    /// alignment, walking the cg tag. See [`AlignedPairs`].
+    ///
+    /// Returns an error if the cg tag is absent or invalid.
+    pub fn aligned_pairs(&self) -> Result<AlignedPairs> {
+        let cigar = self.require_cigar("aligned pairs")?;
+        Ok(AlignedPairs {
+            segments: segments(&cigar, self.target_start() as u64)
+                .collect::<Vec<_>>()
+                .into_iter(),
+            current: None,
+            axis: QueryAxis::of(self),
+        })
+    }
+
+    /// Parse the cg tag, returning an error if it is absent.
+    pub(crate) fn require_cigar(&self, what: &str) -> Result<Cigar> {
+        self.cigar().ok_or_else(|| {
+            Error::new(ErrorKind::InvalidRecord(format!("{} needs a cg tag", what)))
+        })?
+    }
+
+    /// Lift a query coordinate to the target through the cg tag.
+    ///
+    /// The query coordinate is on the original query strand, also for minus
+    /// strand records. This walks the CIGAR operations rather than the bases,
+    /// so takes time proportional to the number of operations.
+    pub fn query_to_target(&self, pos: u64) -> Result<LiftoverResult> {
+        let cigar = self.require_cigar("liftover")?;
+        let offset = match QueryAxis::of(self).offset(pos) {
+            Some(offset) => offset,
+            None => return Ok(LiftoverResult::OutOfRange),
+        };
+
+        for segment in segments(&cigar, self.target_start() as u64) {
+            if !segment.op.consumes_query() {
+                continue;
+            }
+            let within = offset.wrapping_sub(segment.query_offset);
+            if offset < segment.query_offset || within >= segment.len as u64 {
+                continue;
+            }
+            return Ok(if segment.op.consumes_target() {
+                LiftoverResult::Mapped(segment.target_pos + within)
+            } else {
+                LiftoverResult::Deleted {
+                    flank: segment.target_pos,
+                }
+            });
+        }
+        // the CIGAR is shorter than the query interval
+        Ok(LiftoverResult::OutOfRange)
+    }
+
+    /// Lift a target coordinate to the query through the cg tag.
+    ///
+    /// The returned query coordinate is on the original query strand, also
+    /// for minus strand records. To lift many positions, use
+    /// [`PafRecord::target_to_query_many`].
+    pub fn target_to_query(&self, pos: u64) -> Result<LiftoverResult> {
+        Ok(self.target_to_query_many(&[pos])?[0])
+    }
+
+    /// Lift many target coordinates to the query through the cg tag.
+    ///
+    /// The positions are sorted and lifted in a single sweep over the CIGAR,
+    /// and the results returned in the order of the input.
+    pub fn target_to_query_many(&self, positions: &[u64]) -> Result<Vec<LiftoverResult>> {
+        let cigar = self.require_cigar("liftover")?;
+        let axis = QueryAxis::of(self);
+        let (start, end) = (self.target_start() as u64, self.target_end() as u64);
+
+        let mut order: Vec<usize> = (0..positions.len()).collect();
+        order.sort_by_key(|&i| positions[i]);
+
+        let mut results = vec![LiftoverResult::OutOfRange; positions.len()];
+        let mut segments = segments(&cigar, start)
+            .filter(|s| s.op.consumes_target())
+            .peekable();
+        for i in order {
+            let pos = positions[i];
+            if pos < start || pos >= end {
+                continue;
+            }
+            while segments
+                .next_if(|s| s.target_pos + s.len as u64 <= pos)
+                .is_some()
+            {}
+            let segment = match segments.peek() {
+                Some(segment) if segment.target_pos <= pos => segment,
+                _ => continue,
+            };
+            results[i] = if segment.op.consumes_query() {
+                LiftoverResult::Mapped(
+                    axis.position(segment.query_offset + pos - segment.target_pos),
+                )
+            } else {
+                LiftoverResult::Deleted {
+                    flank: axis.position(segment.query_offset),
+                }
+            };
+        }
+        Ok(results)
+    }
+
+    /// The insertions and deletions of at least `min_len` bases, in
+    /// alignment order.
+    ///
+    /// These come from the cg tag, or the cs tag when there is no cg tag.
+    /// It is an error for the tag not to span the aligned intervals.
+    pub fn indels(&self, min_len: u32) -> Result<Vec<Indel>> {
+        let cigar = match (self.cigar(), self.cs_parsed()) {
+            (Some(cigar), _) => cigar?,
+            (None, Some(cs)) => cs?.to_cigar(),
+            (None, None) => {
+                return Err(Error::new(ErrorKind::InvalidRecord(
+                    "finding indels needs a cg or cs tag".to_string(),
+                )))
+            }
+        };
+        self.check_cigar_spans(&cigar)?;
+        let axis = QueryAxis::of(self);
+        Ok(segments(&cigar, self.target_start() as u64)
+            .filter(|s| s.len >= min_len)
+            .filter_map(|s| {
+                let len = s.len as u64;
+                let (kind, query_len, target_len) = match s.op {
+                    CigarOp::Insertion => (IndelKind::Insertion, len, 0),
+                    CigarOp::Deletion => (IndelKind::Deletion, 0, len),
+                    _ => return None,
+                };
+                let (query_start, query_end) =
+                    axis.interval(s.query_offset, s.query_offset + query_len);
+                Some(Indel {
+                    kind,
+                    len: s.len,
+                    target_start: s.target_pos,
+                    target_end: s.target_pos + target_len,
+                    query_start,
+                    query_end,
+                })
+            })
+            .collect())
+    }
+
+    /// Check the cg tag agrees with the record's coordinates.
+    ///
+    /// The query and target bases consumed by the CIGAR must equal the
+    /// lengths of the query and target intervals, and its block length
+    /// must equal the alignment block length. As minimap2 leaves ambiguous
+    /// bases out of the alignment block length, the nn tag (if present) is
+    /// added back for the comparison. Records without a cg tag pass.
+    pub fn check_cigar(&self) -> Result<()> {
+        let cigar = match self.cigar() {
+            Some(cigar) => cigar?,
+            None => return Ok(()),
+        };
+        self.check_cigar_spans(&cigar)?;
+        check_length(
+            "CIGAR block length",
+            "alignment_block_len + nn",
+            cigar.block_len(),
+            self.alignment_block_len() as u64 + self.nn().unwrap_or(0).max(0) as u64,
+        )
+    }
+
+    /// Check a CIGAR of the record consumes as many query and target bases
+    /// as the query and target intervals hold, as walking it along the
+    /// record's coordinates needs.
+    pub(crate) fn check_cigar_spans(&self, cigar: &Cigar) -> Result<()> {
+        check_length(
+            "query bases consumed by the CIGAR",
+            "query_end - query_start",
+            cigar.query_len(),
+            (self.query_end() as u64).saturating_sub(self.query_start() as u64),
+        )?;
+        check_length(
+            "target bases consumed by the CIGAR",
+            "target_end - target_start",
+            cigar.target_len(),
+            (self.target_end() as u64).saturating_sub(self.target_start() as u64),
+        )
+    }
+}
+
+fn check_length(what: &str, field: &str, from_cigar: u64, expected: u64) -> Result<()> {
+    if from_cigar != expected {
+        return Err(Error::new(ErrorKind::InvalidRecord(format!(
+            "{} ({}) differs from {} ({}) by {} bases",
+            what,
+            from_cigar,
+            field,
+            expected,
+            from_cigar.abs_diff(expected)
+        ))));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::tests::PAF_RECORD_1;
+    use crate::{Reader, ReaderBuilder};
+
+    #[test]
+    fn test_parse() {
+        let cigar = Cigar::parse("10M2I5M1D3N").unwrap();
+        assert_eq!(
+            cigar.ops().collect::<Vec<_>>(),
+            vec![
+                (10, CigarOp::Match),
+                (2, CigarOp::Insertion),
+                (5, CigarOp::Match),
+                (1, CigarOp::Deletion),
+                (3, CigarOp::Skip),
+            ]
+        );
+        assert_eq!(cigar.len(), 5);
+        assert_eq!(cigar.to_string(), "10M2I5M1D3N");
+        assert!(Cigar::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_extended() {
+        let cigar = Cigar::parse("5S3=1X2P4H").unwrap();
+        assert_eq!(
+            cigar.ops().map(|(_, op)| op).collect::<Vec<_>>(),
+            vec![
+                CigarOp::SoftClip,
+                CigarOp::Equal,
+                CigarOp::Diff,
+                CigarOp::Padding,
+                CigarOp::HardClip,
+            ]
+        );
+        assert_eq!(cigar.to_string(), "5S3=1X2P4H");
+    }
+
+    #[test]
+    fn test_consumes() {
+        // (op, consumes query, consumes target), as in the SAM specification
+        let table = [
+            ('M', true, true),
+            ('I', true, false),
+            ('D', false, true),
+            ('N', false, true),
+            ('S', true, false),
+            ('H', false, false),
+            ('P', false, false),
+            ('=', true, true),
+            ('X', true, true),
+        ];
+        for (c, query, target) in table {
+            let op = CigarOp::from_char(c).unwrap();
+            assert_eq!(op.as_char(), c);
+            assert_eq!(op.consumes_query(), query, "{}", c);
+            assert_eq!(op.consumes_target(), target, "{}", c);
+        }
+        assert_eq!(CigarOp::from_char('Q'), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = Cigar::parse("10M0I5M").unwrap_err();
+        assert!(err.to_string().contains("byte 3"), "{}", err);
+        let err = Cigar::parse("10M5Q").unwrap_err();
+        assert!(err.to_string().contains("byte 4"), "{}", err);
+        let err = Cigar::parse("10M5").unwrap_err();
+        assert!(err.to_string().contains("byte 3"), "{}", err);
+        let err = Cigar::parse("M").unwrap_err();
+        assert!(err.to_string().contains("byte 0"), "{}", err);
+        assert!(Cigar::parse("99999999999M").is_err());
+    }
+
+    fn small_record(cg: &str, query: (u32, u32), target: (u32, u32), block: u32) -> PafRecord {
+        stranded_record(cg, '+', query, target, block)
+    }
+
+    fn stranded_record(
+        cg: &str,
+        strand: char,
+        query: (u32, u32),
+        target: (u32, u32),
+        block: u32,
+    ) -> PafRecord {
+        let line = format!(
+            "q\t100\t{}\t{}\t{}\tt\t200\t{}\t{}\t{}\t{}\t60\tcg:Z:{}\n",
+            query.0, query.1, strand, target.0, target.1, block, block, cg
+        );
+        Reader::from_reader(line.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_check_cigar() {
+        // 10M2I5M1D: 17 query bases, 16 target bases, 18 columns
+        assert!(small_record("10M2I5M1D", (0, 17), (0, 16), 18)
+            .check_cigar()
+            .is_ok());
+
+        let err = small_record("10M2I5M1D", (0, 20), (0, 16), 18)
+            .check_cigar()
+            .unwrap_err();
+        assert!(err.to_string().contains("query"), "{}", err);
+        assert!(err.to_string().contains("by 3 bases"), "{}", err);
+
+        let err = small_record("10M2I5M1D", (0, 17), (10, 20), 18)
+            .check_cigar()
+            .unwrap_err();
+        assert!(err.to_string().contains("target"), "{}", err);
+        assert!(err.to_string().contains("by 6 bases"), "{}", err);
+
+        let err = small_record("10M2I5M1D", (0, 17), (0, 16), 17)
+            .check_cigar()
+            .unwrap_err();
+        assert!(err.to_string().contains("block length"), "{}", err);
+        assert!(err.to_string().contains("by 1 bases"), "{}", err);
+    }
+
+    #[test]
+    fn test_check_cigar_fixture() {
+        let mut reader = Reader::from_reader(PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        record.check_cigar().unwrap();
+        record.validate().unwrap();
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = Cigar::parse("10M2I5M1D3N4I20M7D").unwrap().stats();
+        assert_eq!(
+            stats,
+            CigarStats {
+                aligned_bases: 35,
+                inserted_bases: 6,
+                deleted_bases: 8,
+                insertions: 2,
+                deletions: 2,
+                longest_insertion: 4,
+                longest_deletion: 7,
+                exact_matches: None,
+                mismatches: None,
+            }
+        );
+
+        let stats = Cigar::parse("5=1X4=2I3M").unwrap().stats();
+        assert_eq!(stats.aligned_bases, 13);
+        assert_eq!(stats.exact_matches, Some(9));
+        assert_eq!(stats.mismatches, Some(1));
+    }
+
+    #[test]
+    fn test_stats_fixture() {
+        let mut reader = Reader::from_reader(PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let stats = record.cigar_stats().unwrap().unwrap();
+        // minimap2 leaves the ambiguous bases (nn) out of the block length
+        assert_eq!(
+            stats.aligned_bases + stats.inserted_bases + stats.deleted_bases,
+            record.alignment_block_len() as u64 + record.nn().unwrap() as u64
+        );
+    }
+
+    #[test]
+    fn test_aligned_pairs_plus() {
+        let record = stranded_record("2M1I2M1D1M", '+', (2, 8), (100, 106), 7);
+        let pairs: Vec<_> = record.aligned_pairs().unwrap().collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (Some(2), Some(100)),
+                (Some(3), Some(101)),
+                (Some(4), None),
+                (Some(5), Some(102)),
+                (Some(6), Some(103)),
+                (None, Some(104)),
+                (Some(7), Some(105)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aligned_pairs_minus() {
+        // on the minus strand the alignment starts at the query end
+        let record = stranded_record("2M1I2M1D1M", '-', (2, 8), (100, 106), 7);
+        let pairs: Vec<_> = record.aligned_pairs().unwrap().collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (Some(7), Some(100)),
+                (Some(6), Some(101)),
+                (Some(5), None),
+                (Some(4), Some(102)),
+                (Some(3), Some(103)),
+                (None, Some(104)),
+                (Some(2), Some(105)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aligned_pairs_fixture() {
+        let mut reader = Reader::from_reader(PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let stats = record.cigar_stats().unwrap().unwrap();
+        let n = record.aligned_pairs().unwrap().count() as u64;
+        assert_eq!(
+            n,
+            stats.aligned_bases + stats.inserted_bases + stats.deleted_bases
+        );
+        let last = record.aligned_pairs().unwrap().last().unwrap();
+        assert_eq!(
+            last,
+            (
+                Some(record.query_end() as u64 - 1),
+                Some(record.target_end() as u64 - 1)
+            )
+        );
+    }
+
+    /// Check a liftover agrees with the aligned pairs at every query position.
+    fn assert_query_to_target_matches_pairs(record: &PafRecord) {
+        let pairs: Vec<_> = record.aligned_pairs().unwrap().collect();
+        for (query, target) in pairs.iter().copied() {
+            if let (Some(q), Some(t)) = (query, target) {
+                assert_eq!(
+                    record.query_to_target(q).unwrap(),
+                    LiftoverResult::Mapped(t)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_query_to_target_plus() {
+        let record = stranded_record("2M1I2M1D1M", '+', (2, 8), (100, 106), 7);
+        assert_query_to_target_matches_pairs(&record);
+        assert_eq!(
+            record.query_to_target(2).unwrap(),
+            LiftoverResult::Mapped(100)
+        );
+        assert_eq!(
+            record.query_to_target(7).unwrap(),
+            LiftoverResult::Mapped(105)
+        );
+        // inside the insertion
+        assert_eq!(
+            record.query_to_target(4).unwrap(),
+            LiftoverResult::Deleted { flank: 102 }
+        );
+        assert_eq!(
+            record.query_to_target(1).unwrap(),
+            LiftoverResult::OutOfRange
+        );
+        assert_eq!(
+            record.query_to_target(8).unwrap(),
+            LiftoverResult::OutOfRange
+        );
+    }
+
+    #[test]
+    fn test_query_to_target_minus() {
+        let record = stranded_record("2M1I2M1D1M", '-', (2, 8), (100, 106), 7);
+        assert_query_to_target_matches_pairs(&record);
+        // query_end - 1 is the first aligned base
+        assert_eq!(
+            record.query_to_target(7).unwrap(),
+            LiftoverResult::Mapped(100)
+        );
+        assert_eq!(
+            record.query_to_target(2).unwrap(),
+            LiftoverResult::Mapped(105)
+        );
+        assert_eq!(
+            record.query_to_target(5).unwrap(),
+            LiftoverResult::Deleted { flank: 102 }
+        );
+        assert_eq!(
+            record.query_to_target(8).unwrap(),
+            LiftoverResult::OutOfRange
+        );
+    }
+
+    #[test]
+    fn test_target_to_query_plus() {
+        let record = stranded_record("2M1I2M1D1M", '+', (2, 8), (100, 106), 7);
+        for (query, target) in record.aligned_pairs().unwrap() {
+            if let (Some(q), Some(t)) = (query, target) {
+                assert_eq!(
+                    record.target_to_query(t).unwrap(),
+                    LiftoverResult::Mapped(q)
+                );
+            }
+        }
+        assert_eq!(
+            record.target_to_query(100).unwrap(),
+            LiftoverResult::Mapped(2)
+        );
+        assert_eq!(
+            record.target_to_query(105).unwrap(),
+            LiftoverResult::Mapped(7)
+        );
+        // inside the deletion
+        assert_eq!(
+            record.target_to_query(104).unwrap(),
+            LiftoverResult::Deleted { flank: 7 }
+        );
+        assert_eq!(
+            record.target_to_query(99).unwrap(),
+            LiftoverResult::OutOfRange
+        );
+        assert_eq!(
+            record.target_to_query(106).unwrap(),
+            LiftoverResult::OutOfRange
+        );
+    }
+
+    #[test]
+    fn test_target_to_query_minus() {
+        let record = stranded_record("2M1I2M1D1M", '-', (2, 8), (100, 106), 7);
+        assert_eq!(
+            record.target_to_query(100).unwrap(),
+            LiftoverResult::Mapped(7)
+        );
+        assert_eq!(
+            record.target_to_query(103).unwrap(),
+            LiftoverResult::Mapped(3)
+        );
+        assert_eq!(
+            record.target_to_query(104).unwrap(),
+            LiftoverResult::Deleted { flank: 2 }
+        );
+        assert_eq!(
+            record.target_to_query(105).unwrap(),
+            LiftoverResult::Mapped(2)
+        );
+    }
+
+    #[test]
+    fn test_target_to_query_many() {
+        let mut reader = Reader::from_reader(PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let start = record.target_start() as u64 - 5;
+        let end = record.target_end() as u64 + 5;
+        // unsorted, with duplicates and out of range positions
+        let positions: Vec<u64> = (0..500)
+            .map(|i| start + (i * 7919) % (end - start))
+            .chain([start, end - 1, start + 10, start + 10])
+            .collect();
+        let many = record.target_to_query_many(&positions).unwrap();
+        for (pos, lifted) in positions.iter().zip(many) {
+            assert_eq!(record.target_to_query(*pos).unwrap(), lifted);
+        }
+    }
+
+    #[test]
+    fn test_inverted_reversed() {
+        let mut reader = Reader::from_reader(PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let fixture = record.cigar().unwrap().unwrap();
+        let cigars = [
+            Cigar::parse("2M1I2M1D1M").unwrap(),
+            Cigar::parse("3=1X10I4D2M").unwrap(),
+            Cigar::parse("").unwrap(),
+            fixture,
+        ];
+        for cigar in cigars {
+            assert_eq!(cigar.inverted().inverted(), cigar);
+            assert_eq!(cigar.reversed().reversed(), cigar);
+            assert_eq!(cigar.inverted().reversed(), cigar.reversed().inverted());
+            assert_eq!(cigar.inverted().query_len(), cigar.target_len());
+            assert_eq!(cigar.inverted().target_len(), cigar.query_len());
+            assert_eq!(cigar.reversed().query_len(), cigar.query_len());
+            assert_eq!(cigar.reversed().block_len(), cigar.block_len());
+        }
+
+        let cigar = Cigar::parse("2M1I2M3D1M").unwrap();
+        assert_eq!(cigar.inverted().to_string(), "2M1D2M3I1M");
+        assert_eq!(cigar.reversed().to_string(), "1M3D2M1I2M");
+    }
+
+    #[test]
+    fn test_split_at_target() {
+        let cigar = Cigar::parse("2M1I2M1D1M").unwrap();
+        let (head, tail) = cigar.split_at_target(3);
+        assert_eq!(head.to_string(), "2M1I1M");
+        assert_eq!(tail.to_string(), "1M1D1M");
+        // the insertion at the cut goes to the second half
+        let (head, tail) = cigar.split_at_target(2);
+        assert_eq!(head.to_string(), "2M");
+        assert_eq!(tail.to_string(), "1I2M1D1M");
+        let (head, tail) = cigar.split_at_target(0);
+        assert!(head.is_empty());
+        assert_eq!(tail, cigar);
+        let (head, tail) = cigar.split_at_target(5);
+        assert_eq!(head.to_string(), "2M1I2M1D");
+        assert_eq!(tail.to_string(), "1M");
+    }
+
+    #[test]
+    fn test_split_at_query() {
+        let cigar = Cigar::parse("2M1I2M1D1M").unwrap();
+        // the deletion at the cut goes to the second half
+        let (head, tail) = cigar.split_at_query(5);
+        assert_eq!(head.to_string(), "2M1I2M");
+        assert_eq!(tail.to_string(), "1D1M");
+        let (head, tail) = cigar.split_at_query(6);
+        assert_eq!(head.to_string(), "2M1I2M1D1M");
+        assert!(tail.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_split_past_end() {
+        Cigar::parse("5M").unwrap().split_at_target(6);
+    }
+
+    #[test]
+    fn test_split_join() {
+        let mut reader = Reader::from_reader(PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let fixture = record.cigar().unwrap().unwrap();
+        for cigar in [Cigar::parse("2M1I2M1D1M").unwrap(), fixture] {
+            for n in 0..=cigar.target_len().min(2000) {
+                let (head, tail) = cigar.split_at_target(n);
+                assert_eq!(head.target_len(), n);
+                assert_eq!(head.concat(&tail), cigar);
+            }
+            for n in 0..=cigar.query_len().min(2000) {
+                let (head, tail) = cigar.split_at_query(n);
+                assert_eq!(head.query_len(), n);
+                assert_eq!(head.concat(&tail), cigar);
+            }
+        }
+    }
+
+    #[test]
+    fn test_concat() {
+        let a = Cigar::parse("3M1I2M").unwrap();
+        let b = Cigar::parse("4M2D1M").unwrap();
+        assert_eq!(a.concat(&b).to_string(), "3M1I6M2D1M");
+        assert_eq!(a.concat(&Cigar::default()), a);
+        assert_eq!(Cigar::default().concat(&a), a);
+
+        let joined = a.concat_with_gap(5, 7, &b);
+        assert_eq!(joined.to_string(), "3M1I2M5I7D4M2D1M");
+        for (query_gap, target_gap) in [(0, 0), (5, 0), (0, 7), (5, 7)] {
+            let joined = a.concat_with_gap(query_gap, target_gap, &b);
+            assert_eq!(
+                joined.query_len(),
+                a.query_len() + query_gap + b.query_len()
+            );
+            assert_eq!(
+                joined.target_len(),
+                a.target_len() + target_gap + b.target_len()
+            );
+        }
+
+        // gaps merge into indels at the seams
+        let a = Cigar::parse("3M2I").unwrap();
+        let b = Cigar::parse("1D3M").unwrap();
+        assert_eq!(a.concat_with_gap(1, 1, &b).to_string(), "3M3I2D3M");
+
+        let spliced = a.concat_with_skip(0, 100, &Cigar::parse("3M").unwrap());
+        assert_eq!(spliced.to_string(), "3M2I100N3M");
+    }
+
+    #[test]
+    fn test_concat_long_gap() {
+        let a = Cigar::parse("1M").unwrap();
+        let joined = a.concat_with_gap(0, u32::MAX as u64 + 10, &a);
+        assert_eq!(joined.to_string(), format!("1M{}D10D1M", u32::MAX));
+        assert_eq!(joined.target_len(), u32::MAX as u64 + 12);
+    }
+
+    #[test]
+    fn test_slice_target() {
+        let cigar = Cigar::parse("2M1I2M1D1M").unwrap();
+        assert_eq!(cigar.slice_target(1..4).to_string(), "1M1I2M");
+        assert_eq!(cigar.slice_target(2..5).to_string(), "1I2M1D");
+        assert!(cigar.slice_target(3..3).is_empty());
+        assert_eq!(cigar.slice_target(0..6), cigar);
+        for start in 0..=6 {
+            for end in start..=6 {
+                assert_eq!(cigar.slice_target(start..end).target_len(), end - start);
+            }
+        }
+    }
+
+    #[test]
+    fn test_collapse_eqx() {
+        let cigar = Cigar::parse("3=1X2=2I1X4=1D2M").unwrap();
+        let collapsed = cigar.collapse_eqx();
+        assert_eq!(collapsed.to_string(), "6M2I5M1D2M");
+        assert_eq!(collapsed.query_len(), cigar.query_len());
+        assert_eq!(collapsed.target_len(), cigar.target_len());
+        assert_eq!(collapsed.collapse_eqx(), collapsed);
+    }
+
+    #[test]
+    fn test_expand_to_eqx() {
+        let cs = CsString::parse(":3*ag:2+ac*ct:4-g:2").unwrap();
+        let cigar = Cigar::parse("6M2I5M1D2M").unwrap();
+        let expanded = cigar.expand_to_eqx(&cs).unwrap();
+        assert_eq!(expanded.to_string(), "3=1X2=2I1X4=1D2=");
+        assert_eq!(expanded.query_len(), cigar.query_len());
+        assert_eq!(expanded.target_len(), cigar.target_len());
+        assert_eq!(expanded.collapse_eqx(), cigar);
+        // redone from =/X as well
+        assert_eq!(expanded.expand_to_eqx(&cs).unwrap(), expanded);
+
+        assert!(Cigar::parse("6M2I5M1D3M")
+            .unwrap()
+            .expand_to_eqx(&cs)
+            .is_err());
+        assert!(Cigar::parse("6M2I5M1D1M")
+            .unwrap()
+            .expand_to_eqx(&cs)
+            .is_err());
+    }
+
+    #[test]
+    fn test_indels() {
+        let record = small_record("3M4I2M2D1M1I1M", (10, 22), (100, 109), 14);
+        assert_eq!(
+            record.indels(0).unwrap(),
+            vec![
+                Indel {
+                    kind: IndelKind::Insertion,
+                    len: 4,
+                    target_start: 103,
+                    target_end: 103,
+                    query_start: 13,
+                    query_end: 17,
+                },
+                Indel {
+                    kind: IndelKind::Deletion,
+                    len: 2,
+                    target_start: 105,
+                    target_end: 107,
+                    query_start: 19,
+                    query_end: 19,
+                },
+                Indel {
+                    kind: IndelKind::Insertion,
+                    len: 1,
+                    target_start: 108,
+                    target_end: 108,
+                    query_start: 20,
+                    query_end: 21,
+                },
+            ]
+        );
+        assert_eq!(record.indels(2).unwrap().len(), 2);
+        assert!(record.indels(5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_indels_minus() {
+        let record = stranded_record("3M4I2M2D1M1I1M", '-', (10, 22), (100, 109), 14);
+        let indels = record.indels(2).unwrap();
+        assert_eq!(
+            (indels[0].query_start, indels[0].query_end),
+            (15, 19),
+            "insertion of offsets 3..7 walking back from 22"
+        );
+        assert_eq!((indels[1].query_start, indels[1].query_end), (13, 13));
+        assert_eq!((indels[1].target_start, indels[1].target_end), (105, 107));
+
+        // a cg tag longer than the query interval
+        let record = stranded_record("30M20I10M", '-', (10, 20), (0, 60), 60);
+        assert!(matches!(
+            record.indels(0).unwrap_err().kind(),
+            ErrorKind::InvalidRecord(_)
+        ));
+    }
+
+    #[test]
+    fn test_indels_from_cs() {
+        let line = "q\t100\t10\t22\t+\tt\t200\t100\t109\t10\t14\t60\t\
+            cs:Z::3+acgt:1*ag-tt:1+c:1\n";
+        let record = Reader::from_reader(line.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap();
+        let from_cg = small_record("3M4I2M2D1M1I1M", (10, 22), (100, 109), 14);
+        assert_eq!(record.indels(0).unwrap(), from_cg.indels(0).unwrap());
+
+        let line = "q\t100\t10\t22\t+\tt\t200\t100\t109\t10\t14\t60\n";
+        let record = Reader::from_reader(line.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap();
+        assert!(record.indels(0).is_err());
+    }
+
+    #[test]
+    fn test_long_runs() {
+        let text = format!("3M{}D300000000I1023M8I7M", u32::MAX);
+        let cigar = Cigar::parse(&text).unwrap();
+        assert_eq!(cigar.to_string(), text);
+        assert_eq!(cigar.len(), 6);
+        assert_eq!(
+            cigar.ops().collect::<Vec<_>>(),
+            vec![
+                (3, CigarOp::Match),
+                (u32::MAX, CigarOp::Deletion),
+                (300000000, CigarOp::Insertion),
+                (1023, CigarOp::Match),
+                (8, CigarOp::Insertion),
+                (7, CigarOp::Match),
+            ]
+        );
+        assert_eq!(cigar.reversed().reversed(), cigar);
+        assert_eq!(cigar.concat(&Cigar::parse("1M").unwrap()).len(), 6);
+        // 1 and 2 byte runs either side of the 7 and 10 bit boundaries
+        assert_eq!(Cigar::parse("7M8M1023M1024M").unwrap().heap_size(), 8);
+        let (head, tail) = cigar.split_at_query(3 + 300000000);
+        assert_eq!(head.concat(&tail), cigar);
+    }
+
+    #[test]
+    fn test_compact_reader() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/data/5_GD_domestica.paf");
+        let mut text = Reader::from_path(path).unwrap();
+        let mut compact = ReaderBuilder::new()
+            .compact_cigar(true)
+            .from_path(path)
+            .unwrap();
+        let (mut text_bytes, mut compact_bytes) = (0, 0);
+        let mut records = 0;
+        while let Some(record) = text.read_record().unwrap() {
+            let packed = compact.read_record().unwrap().unwrap();
+            let cg = record.cg().unwrap();
+            let cigar = packed.cigar().unwrap().unwrap();
+            // the text is regenerated byte for byte
+            assert_eq!(cigar.to_string(), cg);
+            assert_eq!(packed.cg(), None);
+            assert!(packed.cg_checked().unwrap().is_none());
+            text_bytes += cg.len();
+            compact_bytes += cigar.heap_size();
+            records += 1;
+        }
+        assert!(compact.read_record().unwrap().is_none());
+        assert_eq!(records, 20495);
+        // 1.76 MB against 3.2 MB of text at the time of writing
+        assert!(
+            compact_bytes * 5 < text_bytes * 3,
+            "packed {} bytes against {} bytes of text",
+            compact_bytes,
+            text_bytes
+        );
+    }
+
+    #[test]
+    fn test_compact_write() {
+        let mut reader = ReaderBuilder::new()
+            .compact_cigar(true)
+            .from_reader(PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let mut buffer = Vec::new();
+        crate::Writer::new(&mut buffer)
+            .write_record(&record)
+            .unwrap();
+        let reread = Reader::from_reader(&buffer[..])
+            .read_record()
+            .unwrap()
+            .unwrap();
+        let original = Reader::from_reader(PAF_RECORD_1)
+            .read_record()
+            .unwrap()
+            .unwrap();
+        assert_eq!(reread.cg(), original.cg());
+
+        let line = b"q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\tcg:Z:10Q\n";
+        let mut reader = ReaderBuilder::new()
+            .compact_cigar(true)
+            .from_reader(&line[..]);
+        assert!(reader.read_record().is_err());
+    }
+
+    #[test]
+    fn test_fixture_round_trip() {
+        let mut reader = Reader::from_reader(PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let cigar = record.cigar().unwrap().unwrap();
+        assert_eq!(cigar.to_string(), record.cg().unwrap());
+    }
+}
@@ -0,0 +1,650 @@
+/*!
+Compare alignments in two directions, as for orthology, or from two runs of
+an aligner.
+
+```
+use paf::compare::reciprocal_best_hits;
+use paf::select::Score;
+use paf::Reader;
+
+let forward = b"a1\t100\t0\t100\t+\tb1\t100\t0\t100\t95\t100\t60\n";
+let reverse = b"b1\t100\t0\t100\t+\ta1\t100\t0\t100\t95\t100\t60\n";
+let pairs = reciprocal_best_hits(
+    Reader::from_reader(&forward[..]).records(),
+    Reader::from_reader(&reverse[..]).records(),
+    Score::ResidueMatches,
+)
+.unwrap();
+assert_eq!(pairs.len(), 1);
+```
+*/
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::dedup::reciprocal_overlap;
+use crate::select::{best_per_query, Score};
+use crate::{PafRecord, Result, Tag, Type};
+
+/// Thresholds a record must meet to count as a hit.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RbhOptions {
+    /// The least [`PafRecord::blast_identity`].
+    pub min_identity: f64,
+    /// The least fraction of its query a record covers.
+    pub min_coverage: f64,
+}
+
+impl RbhOptions {
+    fn passes(&self, record: &PafRecord) -> bool {
+        let coverage = if record.query_len() == 0 {
+            0.0
+        } else {
+            record.query_end().saturating_sub(record.query_start()) as f64
+                / record.query_len() as f64
+        };
+        record.blast_identity() >= self.min_identity && coverage >= self.min_coverage
+    }
+}
+
+/// Pairs of best hits which are each other's best hit: the best target of
+/// query `q` in `forward` is `t`, and the best target of `t` in `reverse`,
+/// which aligns the other way, is `q`.
+///
+/// Each pair holds the forward and then the reverse record, in the order
+/// queries are first seen in `forward`. Ties for best are broken as for
+/// [`Score`]. Both inputs are held in memory as one best record per query.
+pub fn reciprocal_best_hits<F, R>(
+    forward: F,
+    reverse: R,
+    score: Score,
+) -> Result<Vec<(PafRecord, PafRecord)>>
+where
+    F: IntoIterator<Item = Result<PafRecord>>,
+    R: IntoIterator<Item = Result<PafRecord>>,
+{
+    reciprocal_best_hits_with(forward, reverse, score, &RbhOptions::default())
+}
+
+/// Like [`reciprocal_best_hits`], considering only records in either
+/// direction which meet the thresholds of `options`.
+pub fn reciprocal_best_hits_with<F, R>(
+    forward: F,
+    reverse: R,
+    score: Score,
+    options: &RbhOptions,
+) -> Result<Vec<(PafRecord, PafRecord)>>
+where
+    F: IntoIterator<Item = Result<PafRecord>>,
+    R: IntoIterator<Item = Result<PafRecord>>,
+{
+    let best = |records: Vec<PafRecord>| {
+        best_per_query(
+            records.into_iter().filter(|record| options.passes(record)),
+            score,
+        )
+    };
+    let forward = best(forward.into_iter().collect::<Result<_>>()?);
+    let mut reverse: HashMap<String, PafRecord> = best(reverse.into_iter().collect::<Result<_>>()?)
+        .into_iter()
+        .map(|record| (record.query_name().to_string(), record))
+        .collect();
+
+    let mut pairs = Vec::new();
+    for hit in forward {
+        let reciprocal = reverse
+            .get(hit.target_name())
+            .is_some_and(|back| back.target_name() == hit.query_name());
+        if reciprocal {
+            let back = reverse.remove(hit.target_name()).unwrap();
+            pairs.push((hit, back));
+        }
+    }
+    Ok(pairs)
+}
+
+/// How [`diff`] matches records and compares their tags.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DiffOptions {
+    /// Also match records left without an identical alignment to one on the
+    /// same query, target and strand which overlaps it by at least this
+    /// fraction of the longer of the two, on both the query and the target.
+    /// By default only identical alignments match.
+    pub min_overlap: Option<f64>,
+    /// How far a numeric tag, e.g. AS or de, can move before it counts as
+    /// changed. By default any change counts.
+    pub tolerance: f64,
+    /// Tags not compared, e.g. `cg` when only the scores matter.
+    pub ignore_tags: Vec<String>,
+}
+
+/// A tag which differs between matched records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagChange {
+    /// The tag name.
+    pub tag: String,
+    /// The value in the first file, if the record has the tag.
+    pub a: Option<String>,
+    /// The value in the second file, if the record has the tag.
+    pub b: Option<String>,
+}
+
+/// Records of the two files matched by [`diff`] which differ.
+#[derive(Debug)]
+pub struct ChangedRecord {
+    /// The record in the first file.
+    pub a: PafRecord,
+    /// The record in the second file.
+    pub b: PafRecord,
+    /// Whether the records describe the same alignment, rather than being
+    /// matched by overlap.
+    pub exact: bool,
+    /// The tags which differ, in order of name.
+    pub changes: Vec<TagChange>,
+}
+
+/// The differences between two files found by [`diff`].
+///
+/// Formats as a summary of the counts, followed by a line for each
+/// difference: `<` for records only in the first file, `>` for records
+/// only in the second, and `~` for changed records.
+#[derive(Debug, Default)]
+pub struct DiffReport {
+    /// The number of records in the first file.
+    pub records_a: u64,
+    /// The number of records in the second file.
+    pub records_b: u64,
+    /// The number of identical alignments with the same tags.
+    pub unchanged: u64,
+    /// Matched records which differ, in the order of the first file.
+    pub changed: Vec<ChangedRecord>,
+    /// The records of the first file without a match, in order.
+    pub only_in_a: Vec<PafRecord>,
+    /// The records of the second file without a match, in order.
+    pub only_in_b: Vec<PafRecord>,
+}
+
+impl DiffReport {
+    /// Whether the files hold the same alignments with the same tags.
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.only_in_a.is_empty() && self.only_in_b.is_empty()
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let by_overlap = self.changed.iter().filter(|c| !c.exact).count();
+        writeln!(f, "records in A: {}", self.records_a)?;
+        writeln!(f, "records in B: {}", self.records_b)?;
+        writeln!(f, "unchanged: {}", self.unchanged)?;
+        writeln!(
+            f,
+            "changed: {} ({} matched by overlap)",
+            self.changed.len(),
+            by_overlap
+        )?;
+        writeln!(f, "only in A: {}", self.only_in_a.len())?;
+        writeln!(f, "only in B: {}", self.only_in_b.len())?;
+        for record in &self.only_in_a {
+            writeln!(f, "< {}", location(record))?;
+        }
+        for record in &self.only_in_b {
+            writeln!(f, "> {}", location(record))?;
+        }
+        for changed in &self.changed {
+            write!(f, "~ {}", location(&changed.a))?;
+            if !changed.exact {
+                write!(f, " -> {}", location(&changed.b))?;
+            }
+            for change in &changed.changes {
+                let value = |v: &Option<String>| v.clone().unwrap_or_else(|| "-".to_string());
+                write!(
+                    f,
+                    " {}: {} -> {}",
+                    change.tag,
+                    value(&change.a),
+                    value(&change.b)
+                )?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// A record as `query:start-end strand target:start-end`.
+fn location(record: &PafRecord) -> String {
+    format!(
+        "{}:{}-{} {} {}:{}-{}",
+        record.query_name(),
+        record.query_start(),
+        record.query_end(),
+        record.strand(),
+        record.target_name(),
+        record.target_start(),
+        record.target_end()
+    )
+}
+
+/// Finds what changed between two files, e.g. from runs of an aligner
+/// with different parameters.
+///
+/// Records describing the same alignment, by
+/// [`PafRecord::alignment_key`], are matched first, and then with
+/// [`min_overlap`](DiffOptions::min_overlap) those left over by overlap,
+/// the most overlapping first. Matched records are changed if they are
+/// matched by overlap or their tags differ.
+///
+/// The files are read in step until the smaller ends, which is then held
+/// in memory while the rest of the other is read, along with the records
+/// of the other which are not identical to one held.
+pub fn diff<A, B>(a: A, b: B, options: &DiffOptions) -> Result<DiffReport>
+where
+    A: IntoIterator<Item = Result<PafRecord>>,
+    B: IntoIterator<Item = Result<PafRecord>>,
+{
+    let (mut a, mut b) = (a.into_iter(), b.into_iter());
+    let (mut held_a, mut held_b) = (Vec::new(), Vec::new());
+    let a_ended = loop {
+        match a.next() {
+            Some(record) => held_a.push(record?),
+            None => break true,
+        }
+        match b.next() {
+            Some(record) => held_b.push(record?),
+            None => break false,
+        }
+    };
+    if a_ended {
+        diff_held(held_a, held_b.into_iter().map(Ok).chain(b), false, options)
+    } else {
+        diff_held(held_b, held_a.into_iter().map(Ok).chain(a), true, options)
+    }
+}
+
+/// Diffs the records of one file held in memory against those of the other
+/// streamed, the second file if not `swapped`.
+fn diff_held<S>(
+    held: Vec<PafRecord>,
+    streamed: S,
+    swapped: bool,
+    options: &DiffOptions,
+) -> Result<DiffReport>
+where
+    S: Iterator<Item = Result<PafRecord>>,
+{
+    let mut by_key: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (i, record) in held.iter().enumerate() {
+        by_key.entry(record.alignment_key()).or_default().push(i);
+    }
+    let mut matched = vec![false; held.len()];
+    let mut unchanged = 0;
+    // changed matches as the index of the held record, the index of the
+    // streamed record, the streamed record, and whether the match is exact
+    let mut pairs: Vec<(usize, usize, PafRecord, bool)> = Vec::new();
+    let mut left: Vec<(usize, PafRecord)> = Vec::new();
+    let mut streamed_count = 0;
+    for (j, record) in streamed.enumerate() {
+        let record = record?;
+        streamed_count += 1;
+        let found = by_key
+            .get(&record.alignment_key())
+            .and_then(|indices| indices.iter().copied().find(|&i| !matched[i]));
+        match found {
+            Some(i) => {
+                matched[i] = true;
+                if tag_changes(&held[i], &record, options).is_empty() {
+                    unchanged += 1;
+                } else {
+                    pairs.push((i, j, record, true));
+                }
+            }
+            None => left.push((j, record)),
+        }
+    }
+
+    let mut only_streamed = Vec::new();
+    if let Some(min_overlap) = options.min_overlap {
+        let mut by_pair: HashMap<(&str, &str, char), Vec<usize>> = HashMap::new();
+        for (i, record) in held.iter().enumerate().filter(|&(i, _)| !matched[i]) {
+            by_pair
+                .entry((record.query_name(), record.target_name(), record.strand()))
+                .or_default()
+                .push(i);
+        }
+        for (j, record) in left {
+            let key = (record.query_name(), record.target_name(), record.strand());
+            let best = by_pair.get(&key).and_then(|indices| {
+                indices
+                    .iter()
+                    .filter(|&&i| !matched[i])
+                    .map(|&i| (overlap(&held[i], &record), i))
+                    .filter(|&(fraction, _)| fraction >= min_overlap)
+                    .max_by(|x, y| x.0.total_cmp(&y.0).then(y.1.cmp(&x.1)))
+            });
+            match best {
+                Some((_, i)) => {
+                    matched[i] = true;
+                    pairs.push((i, j, record, false));
+                }
+                None => only_streamed.push(record),
+            }
+        }
+    } else {
+        only_streamed = left.into_iter().map(|(_, record)| record).collect();
+    }
+
+    let held_count = held.len() as u64;
+    let mut held: Vec<Option<PafRecord>> = held.into_iter().map(Some).collect();
+    let mut changed: Vec<(usize, ChangedRecord)> = pairs
+        .into_iter()
+        .map(|(i, j, record, exact)| {
+            let other = held[i].take().unwrap();
+            let (a, b, index_a) = match swapped {
+                false => (other, record, i),
+                true => (record, other, j),
+            };
+            let changes = tag_changes(&a, &b, options);
+            (
+                index_a,
+                ChangedRecord {
+                    a,
+                    b,
+                    exact,
+                    changes,
+                },
+            )
+        })
+        .collect();
+    changed.sort_by_key(|&(index_a, _)| index_a);
+    let only_held: Vec<PafRecord> = held
+        .into_iter()
+        .zip(&matched)
+        .filter_map(|(record, &matched)| record.filter(|_| !matched))
+        .collect();
+
+    let mut report = DiffReport {
+        records_a: held_count,
+        records_b: streamed_count,
+        unchanged,
+        changed: changed.into_iter().map(|(_, c)| c).collect(),
+        only_in_a: only_held,
+        only_in_b: only_streamed,
+    };
+    if swapped {
+        std::mem::swap(&mut report.records_a, &mut report.records_b);
+        std::mem::swap(&mut report.only_in_a, &mut report.only_in_b);
+    }
+    Ok(report)
+}
+
+/// The overlap of two records, as the least over the query and target of
+/// the fraction of the longer interval.
+fn overlap(a: &PafRecord, b: &PafRecord) -> f64 {
+    reciprocal_overlap(
+        (a.query_start(), a.query_end()),
+        (b.query_start(), b.query_end()),
+    )
+    .min(reciprocal_overlap(
+        (a.target_start(), a.target_end()),
+        (b.target_start(), b.target_end()),
+    ))
+}
+
+/// The tags of `a` and `b` which differ, beyond the tolerance for numbers.
+fn tag_changes(a: &PafRecord, b: &PafRecord, options: &DiffOptions) -> Vec<TagChange> {
+    let names: BTreeSet<&String> = a
+        .optional_fields()
+        .keys()
+        .chain(b.optional_fields().keys())
+        .filter(|name| !options.ignore_tags.contains(name))
+        .collect();
+    let mut changes = Vec::new();
+    for name in names {
+        let (x, y) = (a.optional_fields().get(name), b.optional_fields().get(name));
+        let differs = match (x, y) {
+            (Some(x), Some(y)) => {
+                let number = |tag: &Tag| tag.value().and_then(Type::as_f64);
+                match (number(x), number(y)) {
+                    (Some(x), Some(y)) => (x - y).abs() > options.tolerance,
+                    _ => text(x) != text(y),
+                }
+            }
+            _ => true,
+        };
+        if differs {
+            changes.push(TagChange {
+                tag: name.clone(),
+                a: x.map(text),
+                b: y.map(text),
+            });
+        }
+    }
+    changes
+}
+
+/// The value of a tag as written.
+fn text(tag: &Tag) -> String {
+    match tag {
+        Tag::tp(value) => value.as_char().to_string(),
+        Tag::ts(value) => value.as_char().to_string(),
+        _ => tag.value().map(Type::to_string).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Reader;
+
+    // a1 and b1 are each other's best hit. a2's best hit is b2, whose best
+    // hit is a3. a4 ties between b3 and b4, and the tie goes to b3 by name,
+    // whose best hit is a4.
+    const FORWARD: &str = "\
+a1\t1000\t0\t1000\t+\tb1\t1000\t0\t1000\t990\t1000\t60\n\
+a1\t1000\t0\t1000\t+\tb2\t1000\t0\t1000\t800\t1000\t60\n\
+a2\t1000\t0\t1000\t+\tb2\t1000\t0\t1000\t900\t1000\t60\n\
+a3\t1000\t0\t1000\t+\tb2\t1000\t0\t1000\t950\t1000\t60\n\
+a4\t1000\t0\t1000\t+\tb4\t1000\t0\t1000\t970\t1000\t60\n\
+a4\t1000\t0\t1000\t+\tb3\t1000\t0\t1000\t970\t1000\t60\n";
+
+    const REVERSE: &str = "\
+b1\t1000\t0\t1000\t+\ta1\t1000\t0\t1000\t990\t1000\t60\n\
+b2\t1000\t0\t1000\t+\ta2\t1000\t0\t1000\t900\t1000\t60\n\
+b2\t1000\t0\t1000\t+\ta3\t1000\t0\t1000\t950\t1000\t60\n\
+b3\t1000\t0\t1000\t+\ta4\t1000\t0\t1000\t970\t1000\t60\n\
+b4\t1000\t0\t1000\t+\ta4\t1000\t0\t1000\t970\t1000\t60\n";
+
+    fn names(pairs: &[(PafRecord, PafRecord)]) -> Vec<(&str, &str)> {
+        pairs
+            .iter()
+            .map(|(forward, reverse)| {
+                assert_eq!(forward.query_name(), reverse.target_name());
+                assert_eq!(forward.target_name(), reverse.query_name());
+                (forward.query_name(), forward.target_name())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reciprocal_best_hits() {
+        let pairs = reciprocal_best_hits(
+            Reader::from_reader(FORWARD.as_bytes()).records(),
+            Reader::from_reader(REVERSE.as_bytes()).records(),
+            Score::ResidueMatches,
+        )
+        .unwrap();
+        assert_eq!(names(&pairs), [("a1", "b1"), ("a3", "b2"), ("a4", "b3")]);
+    }
+
+    #[test]
+    fn test_thresholds() {
+        // a3 to b2 covers half of a3, so a2 becomes b2's best hit and a3
+        // has none
+        let forward = FORWARD.replace(
+            "a3\t1000\t0\t1000\t+\tb2\t1000\t0\t1000\t950\t1000",
+            "a3\t1000\t0\t500\t+\tb2\t1000\t0\t500\t495\t500",
+        );
+        let reverse = REVERSE.replace(
+            "b2\t1000\t0\t1000\t+\ta3\t1000\t0\t1000\t950\t1000",
+            "b2\t1000\t0\t500\t+\ta3\t1000\t0\t500\t495\t500",
+        );
+        let options = RbhOptions {
+            min_identity: 0.9,
+            min_coverage: 0.8,
+        };
+        let pairs = reciprocal_best_hits_with(
+            Reader::from_reader(forward.as_bytes()).records(),
+            Reader::from_reader(reverse.as_bytes()).records(),
+            Score::ResidueMatches,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(names(&pairs), [("a1", "b1"), ("a2", "b2"), ("a4", "b3")]);
+
+        // a query interval ending before it starts covers none of the query
+        let paf = "a\t1000\t900\t100\t+\tb\t1000\t0\t1000\t990\t1000\t60\n";
+        let record = Reader::from_reader(paf.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap();
+        assert!(!options.passes(&record));
+    }
+
+    #[test]
+    fn test_errors() {
+        let result = reciprocal_best_hits(
+            Reader::from_reader(FORWARD.as_bytes()).records(),
+            Reader::from_reader(&b"bad\n"[..]).records(),
+            Score::As,
+        );
+        assert!(result.is_err());
+    }
+
+    const RUN_A: &str = "\
+q1\t1000\t0\t1000\t+\tt\t5000\t0\t1000\t990\t1000\t60\tAS:i:980\tde:f:0.01
+q2\t1000\t0\t1000\t-\tt\t5000\t2000\t3000\t900\t1000\t60\tAS:i:800\tde:f:0.1
+q3\t1000\t0\t500\t+\tt\t5000\t4000\t4500\t500\t500\t60\tAS:i:500
+q4\t1000\t0\t100\t+\tt\t5000\t100\t200\t100\t100\t0
+";
+
+    // q1 is the same, q2's AS moved, q3 moved 10 bases, q4 went and q5 came
+    const RUN_B: &str = "\
+q2\t1000\t0\t1000\t-\tt\t5000\t2000\t3000\t900\t1000\t60\tAS:i:850\tde:f:0.1
+q1\t1000\t0\t1000\t+\tt\t5000\t0\t1000\t990\t1000\t60\tAS:i:980\tde:f:0.01
+q3\t1000\t10\t510\t+\tt\t5000\t4010\t4510\t500\t500\t60\tAS:i:500
+q5\t1000\t0\t100\t+\tt\t5000\t100\t200\t100\t100\t0
+";
+
+    fn run_diff(a: &str, b: &str, options: &DiffOptions) -> DiffReport {
+        diff(
+            Reader::from_reader(a.as_bytes()).records(),
+            Reader::from_reader(b.as_bytes()).records(),
+            options,
+        )
+        .unwrap()
+    }
+
+    fn query_names(records: &[PafRecord]) -> Vec<&str> {
+        records.iter().map(PafRecord::query_name).collect()
+    }
+
+    #[test]
+    fn test_diff_exact() {
+        let report = run_diff(RUN_A, RUN_B, &DiffOptions::default());
+        assert_eq!((report.records_a, report.records_b), (4, 4));
+        assert_eq!(report.unchanged, 1);
+        assert_eq!(report.changed.len(), 1);
+        assert!(report.changed[0].exact);
+        assert_eq!(
+            report.changed[0].changes,
+            [TagChange {
+                tag: "AS".to_string(),
+                a: Some("800".to_string()),
+                b: Some("850".to_string()),
+            }]
+        );
+        assert_eq!(query_names(&report.only_in_a), ["q3", "q4"]);
+        assert_eq!(query_names(&report.only_in_b), ["q3", "q5"]);
+
+        // within the tolerance, q2 is unchanged
+        let options = DiffOptions {
+            tolerance: 50.0,
+            ..DiffOptions::default()
+        };
+        let report = run_diff(RUN_A, RUN_B, &options);
+        assert_eq!(report.unchanged, 2);
+        assert!(report.changed.is_empty());
+    }
+
+    #[test]
+    fn test_diff_overlap() {
+        let options = DiffOptions {
+            min_overlap: Some(0.9),
+            ..DiffOptions::default()
+        };
+        let report = run_diff(RUN_A, RUN_B, &options);
+        let moved = &report.changed[1];
+        assert!(!moved.exact);
+        assert!(moved.changes.is_empty());
+        assert_eq!((moved.a.query_start(), moved.b.query_start()), (0, 10));
+        assert_eq!(query_names(&report.only_in_a), ["q4"]);
+        assert_eq!(query_names(&report.only_in_b), ["q5"]);
+
+        // the same whichever file is the smaller, or comes first
+        let longer = format!(
+            "{}{}",
+            RUN_A,
+            RUN_A.lines().next().unwrap().replace("q1", "q9") + "\n"
+        );
+        let forward = run_diff(&longer, RUN_B, &options);
+        let backward = run_diff(RUN_B, &longer, &options);
+        assert_eq!(query_names(&forward.only_in_a), ["q4", "q9"]);
+        assert_eq!(query_names(&backward.only_in_b), ["q4", "q9"]);
+        assert_eq!(query_names(&backward.only_in_a), ["q5"]);
+        assert_eq!(forward.changed.len(), backward.changed.len());
+        assert_eq!(
+            forward.changed[1].a.alignment_key(),
+            backward.changed[1].b.alignment_key()
+        );
+        assert_eq!(forward.records_a, backward.records_b);
+
+        // an interval ending before it starts overlaps nothing
+        let report = run_diff(
+            "q\t1000\t500\t0\t+\tt\t5000\t500\t0\t500\t500\t60\n",
+            "q\t1000\t0\t500\t+\tt\t5000\t0\t500\t500\t500\t60\n",
+            &options,
+        );
+        assert!(report.changed.is_empty());
+        assert_eq!((report.only_in_a.len(), report.only_in_b.len()), (1, 1));
+    }
+
+    #[test]
+    fn test_diff_text() {
+        let options = DiffOptions {
+            min_overlap: Some(0.9),
+            ignore_tags: vec!["AS".to_string()],
+            ..DiffOptions::default()
+        };
+        let report = run_diff(RUN_A, RUN_B, &options);
+        assert_eq!(
+            report.to_string(),
+            "records in A: 4\n\
+             records in B: 4\n\
+             unchanged: 2\n\
+             changed: 1 (1 matched by overlap)\n\
+             only in A: 1\n\
+             only in B: 1\n\
+             < q4:0-100 + t:100-200\n\
+             > q5:0-100 + t:100-200\n\
+             ~ q3:0-500 + t:4000-4500 -> q3:10-510 + t:4010-4510\n"
+        );
+        assert!(run_diff(RUN_A, RUN_A, &options).is_empty());
+        let bad = diff(
+            Reader::from_reader(RUN_A.as_bytes()).records(),
+            Reader::from_reader(&b"bad\n"[..]).records(),
+            &options,
+        );
+        assert!(bad.is_err());
+    }
+}
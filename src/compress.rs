@@ -0,0 +1,310 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use flate2::{write::DeflateEncoder, Compression, Crc};
+
+/// The most uncompressed bytes put in a BGZF block, as in htslib, so that
+/// even incompressible data fits the 64 KiB block limit.
+const BGZF_BLOCK_SIZE: usize = 0xff00;
+
+/// The largest BGZF block, header and footer included.
+const BGZF_MAX_BLOCK: usize = 0x10000;
+
+/// The empty block which marks the end of a BGZF file.
+const BGZF_EOF: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Writes BGZF, blocked gzip: a series of gzip members of at most 64 KiB,
+/// each recording its compressed size, which can be indexed and read by any
+/// gzip reader.
+#[derive(Debug)]
+pub(crate) struct BgzfEncoder<W: Write> {
+    /// Only `None` once taken by `finish`.
+    writer: Option<W>,
+    buffer: Vec<u8>,
+    level: Compression,
+    /// Compresses blocks on other threads, if more than one was asked for.
+    pool: Option<Pool>,
+    finished: bool,
+}
+
+impl<W: Write> BgzfEncoder<W> {
+    /// An encoder which compresses blocks on `threads` worker threads, or
+    /// on the calling thread if `threads` is at most one.
+    pub(crate) fn with_threads(writer: W, threads: usize) -> Self {
+        BgzfEncoder {
+            writer: Some(writer),
+            buffer: Vec::with_capacity(BGZF_BLOCK_SIZE),
+            level: Compression::default(),
+            pool: (threads > 1).then(|| Pool::new(threads)),
+            finished: false,
+        }
+    }
+
+    pub(crate) fn get_ref(&self) -> &W {
+        self.writer.as_ref().unwrap()
+    }
+
+    /// Compress the buffer into a block, or hand it to the pool.
+    fn write_block(&mut self) -> io::Result<()> {
+        let writer = self.writer.as_mut().unwrap();
+        match &mut self.pool {
+            None => {
+                writer.write_all(&compress_block(&self.buffer, self.level)?)?;
+                self.buffer.clear();
+            }
+            Some(pool) => {
+                let block =
+                    std::mem::replace(&mut self.buffer, Vec::with_capacity(BGZF_BLOCK_SIZE));
+                pool.submit(block, self.level);
+                // bound the blocks in flight, writing them out in order
+                while pool.pending.len() > 2 * pool.workers.len() {
+                    writer.write_all(&pool.next_block()?)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write out all blocks handed to the pool.
+    fn drain(&mut self) -> io::Result<()> {
+        if let Some(pool) = &mut self.pool {
+            let writer = self.writer.as_mut().unwrap();
+            while !pool.pending.is_empty() {
+                writer.write_all(&pool.next_block()?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write out the last block and the end of file marker.
+    pub(crate) fn try_finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        if !self.buffer.is_empty() {
+            self.write_block()?;
+        }
+        self.drain()?;
+        let writer = self.writer.as_mut().unwrap();
+        writer.write_all(&BGZF_EOF)?;
+        writer.flush()?;
+        self.finished = true;
+        Ok(())
+    }
+
+    /// Finish the stream and return the underlying writer.
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+        Ok(self.writer.take().unwrap())
+    }
+}
+
+impl<W: Write> Write for BgzfEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() == BGZF_BLOCK_SIZE {
+            self.write_block()?;
+        }
+        let n = buf.len().min(BGZF_BLOCK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    /// Ends the current block early, so that all written so far can be read.
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            self.write_block()?;
+        }
+        self.drain()?;
+        self.writer.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for BgzfEncoder<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.try_finish();
+        }
+    }
+}
+
+/// A block to compress, and where to send the result.
+type Job = (Vec<u8>, Compression, mpsc::SyncSender<io::Result<Vec<u8>>>);
+
+/// Worker threads which compress blocks, and the blocks handed to them in
+/// the order they are to be written.
+#[derive(Debug)]
+struct Pool {
+    /// Only `None` while dropping, to stop the workers.
+    jobs: Option<mpsc::Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+    pending: VecDeque<mpsc::Receiver<io::Result<Vec<u8>>>>,
+}
+
+impl Pool {
+    fn new(threads: usize) -> Pool {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let workers = (0..threads)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || loop {
+                    // the lock is released before compressing
+                    let job = queue.lock().unwrap().recv();
+                    let Ok((data, level, result)) = job else {
+                        break;
+                    };
+                    let _ = result.send(compress_block(&data, level));
+                })
+            })
+            .collect();
+        Pool {
+            jobs: Some(jobs),
+            workers,
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn submit(&mut self, data: Vec<u8>, level: Compression) {
+        let (result, block) = mpsc::sync_channel(1);
+        // the workers only stop once the sender is dropped
+        self.jobs
+            .as_ref()
+            .unwrap()
+            .send((data, level, result))
+            .unwrap();
+        self.pending.push_back(block);
+    }
+
+    /// Wait for the oldest pending block.
+    fn next_block(&mut self) -> io::Result<Vec<u8>> {
+        let block = self.pending.pop_front().unwrap();
+        block
+            .recv()
+            .unwrap_or_else(|_| Err(io::Error::other("a compression thread panicked")))
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Compress data into a whole block, header and footer included.
+fn compress_block(data: &[u8], level: Compression) -> io::Result<Vec<u8>> {
+    let mut deflated = deflate(data, level)?;
+    // incompressible data is stored instead
+    if deflated.len() + 26 > BGZF_MAX_BLOCK {
+        deflated = deflate(data, Compression::none())?;
+    }
+    let mut crc = Crc::new();
+    crc.update(data);
+    let block_size = (deflated.len() + 25) as u16;
+
+    let mut block = Vec::with_capacity(deflated.len() + 26);
+    block.extend_from_slice(&[
+        0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, b'B', b'C', 0x02,
+        0x00,
+    ]);
+    block.extend_from_slice(&block_size.to_le_bytes());
+    block.extend_from_slice(&deflated);
+    block.extend_from_slice(&crc.sum().to_le_bytes());
+    block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    Ok(block)
+}
+
+/// Raw deflate a block.
+fn deflate(data: &[u8], level: Compression) -> io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::with_capacity(data.len() / 2), level);
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::MultiGzDecoder;
+
+    use super::*;
+
+    #[test]
+    fn test_blocks() {
+        // a little over two blocks, half of it incompressible
+        let mut data = Vec::new();
+        let mut x: u32 = 1;
+        for i in 0..140_000u32 {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            data.push(if i % 2 == 0 { b'A' } else { x as u8 });
+        }
+        let mut encoder = BgzfEncoder::with_threads(Vec::new(), 1);
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.ends_with(&BGZF_EOF));
+
+        // walk the blocks by their recorded sizes
+        let mut offset = 0;
+        let mut blocks = 0;
+        while offset < compressed.len() {
+            let block = &compressed[offset..];
+            assert_eq!(&block[..4], &[0x1f, 0x8b, 0x08, 0x04]);
+            assert_eq!(&block[12..14], b"BC");
+            offset += u16::from_le_bytes([block[16], block[17]]) as usize + 1;
+            blocks += 1;
+        }
+        assert_eq!(offset, compressed.len());
+        assert_eq!(blocks, 4);
+
+        let mut decoded = Vec::new();
+        MultiGzDecoder::new(&compressed[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_threads() {
+        // the same blocks as on one thread, in the same order
+        let data: Vec<u8> = (0..200_000u32)
+            .flat_map(|i| format!("{}\t", i).into_bytes())
+            .collect();
+        let mut single = BgzfEncoder::with_threads(Vec::new(), 1);
+        single.write_all(&data).unwrap();
+        let single = single.finish().unwrap();
+        for threads in [2, 4] {
+            let mut encoder = BgzfEncoder::with_threads(Vec::new(), threads);
+            encoder.write_all(&data).unwrap();
+            assert_eq!(encoder.finish().unwrap(), single);
+        }
+    }
+
+    #[test]
+    fn test_threads_flush() {
+        let mut encoder = BgzfEncoder::with_threads(Vec::new(), 3);
+        encoder.write_all(b"first\n").unwrap();
+        encoder.flush().unwrap();
+        let mut decoded = String::new();
+        MultiGzDecoder::new(&encoder.get_ref()[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "first\n");
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.ends_with(&BGZF_EOF));
+    }
+
+    #[test]
+    fn test_empty() {
+        let encoder = BgzfEncoder::with_threads(Vec::new(), 1);
+        assert_eq!(encoder.finish().unwrap(), BGZF_EOF);
+    }
+}
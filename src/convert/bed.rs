@@ -0,0 +1,168 @@
+use crate::cigar::{segments, QueryAxis};
+use crate::{PafRecord, Result};
+
+/// The sequence whose coordinates a conversion reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Space {
+    /// The target, with the query as the other sequence.
+    Target,
+    /// The query, on its original strand, with the target as the other
+    /// sequence.
+    Query,
+}
+
+/// The alignment as a BED6 line, without a line ending.
+///
+/// The columns are the name of the sequence in `space`, the aligned
+/// interval on it, the name of the other sequence, the mapping quality as
+/// the score, and the relative strand.
+pub fn to_bed(record: &PafRecord, space: Space) -> String {
+    let (start, end) = match space {
+        Space::Target => (record.target_start(), record.target_end()),
+        Space::Query => (record.query_start(), record.query_end()),
+    };
+    bed_line(record, space, start as u64, end as u64)
+}
+
+/// The gapless blocks of the alignment as BED6 lines, without line
+/// endings, in order along the sequence in `space`.
+///
+/// A block is a run of `M`, `=` and `X` operations in the cg tag, so
+/// insertions, deletions and introns separate blocks. The columns are as
+/// for [`to_bed`]. It is an error for the cg tag not to span the aligned
+/// intervals.
+pub fn to_bed_blocks(record: &PafRecord, space: Space) -> Result<Vec<String>> {
+    let cigar = record.require_cigar("BED blocks")?;
+    record.check_cigar_spans(&cigar)?;
+    let axis = QueryAxis::of(record);
+
+    // blocks as alignment offsets on the query, and target intervals
+    let mut blocks: Vec<(u64, u64, u64, u64)> = Vec::new();
+    let mut extends = false;
+    for segment in segments(&cigar, record.target_start() as u64) {
+        if !(segment.op.consumes_query() && segment.op.consumes_target()) {
+            extends = false;
+            continue;
+        }
+        let len = segment.len as u64;
+        match blocks.last_mut() {
+            Some(block) if extends => {
+                block.1 += len;
+                block.3 += len;
+            }
+            _ => blocks.push((
+                segment.query_offset,
+                segment.query_offset + len,
+                segment.target_pos,
+                segment.target_pos + len,
+            )),
+        }
+        extends = true;
+    }
+
+    let mut intervals: Vec<(u64, u64)> = blocks
+        .into_iter()
+        .map(|(from, to, target_start, target_end)| match space {
+            Space::Target => (target_start, target_end),
+            Space::Query => axis.interval(from, to),
+        })
+        .collect();
+    intervals.sort_unstable();
+    Ok(intervals
+        .into_iter()
+        .map(|(start, end)| bed_line(record, space, start, end))
+        .collect())
+}
+
+fn bed_line(record: &PafRecord, space: Space, start: u64, end: u64) -> String {
+    let (chrom, name) = match space {
+        Space::Target => (record.target_name(), record.query_name()),
+        Space::Query => (record.query_name(), record.target_name()),
+    };
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}",
+        chrom,
+        start,
+        end,
+        name,
+        record.mapping_quality(),
+        record.strand()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    fn record(strand: char, cg: &str) -> PafRecord {
+        test_util::record(&format!(
+            "read\t100\t10\t30\t{}\tchr1\t1000\t500\t521\t17\t23\t42\tcg:Z:{}\n",
+            strand, cg
+        ))
+    }
+
+    #[test]
+    fn test_to_bed() {
+        let plus = record('+', "5M2I3=1X4M3D5M");
+        assert_eq!(to_bed(&plus, Space::Target), "chr1\t500\t521\tread\t42\t+");
+        assert_eq!(to_bed(&plus, Space::Query), "read\t10\t30\tchr1\t42\t+");
+        let minus = record('-', "5M2I3=1X4M3D5M");
+        assert_eq!(to_bed(&minus, Space::Target), "chr1\t500\t521\tread\t42\t-");
+        assert_eq!(to_bed(&minus, Space::Query), "read\t10\t30\tchr1\t42\t-");
+    }
+
+    #[test]
+    fn test_blocks_plus() {
+        // blocks of 5, 8 (3= 1X 4M) and 5 bases
+        let plus = record('+', "5M2I3=1X4M3D5M");
+        assert_eq!(
+            to_bed_blocks(&plus, Space::Target).unwrap(),
+            vec![
+                "chr1\t500\t505\tread\t42\t+",
+                "chr1\t505\t513\tread\t42\t+",
+                "chr1\t516\t521\tread\t42\t+",
+            ]
+        );
+        assert_eq!(
+            to_bed_blocks(&plus, Space::Query).unwrap(),
+            vec![
+                "read\t10\t15\tchr1\t42\t+",
+                "read\t17\t25\tchr1\t42\t+",
+                "read\t25\t30\tchr1\t42\t+",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blocks_minus() {
+        // the alignment walks the query back from 30
+        let minus = record('-', "5M2I3=1X4M3D5M");
+        assert_eq!(
+            to_bed_blocks(&minus, Space::Target).unwrap(),
+            vec![
+                "chr1\t500\t505\tread\t42\t-",
+                "chr1\t505\t513\tread\t42\t-",
+                "chr1\t516\t521\tread\t42\t-",
+            ]
+        );
+        assert_eq!(
+            to_bed_blocks(&minus, Space::Query).unwrap(),
+            vec![
+                "read\t10\t15\tchr1\t42\t-",
+                "read\t15\t23\tchr1\t42\t-",
+                "read\t25\t30\tchr1\t42\t-",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blocks_need_cigar() {
+        let line = "read\t100\t10\t30\t+\tchr1\t1000\t500\t521\t17\t23\t42\n";
+        assert!(to_bed_blocks(&test_util::record(line), Space::Target).is_err());
+
+        // a cg tag longer than the query interval
+        let record = self::record('-', "5M30I3=1X4M3D5M");
+        assert!(to_bed_blocks(&record, Space::Query).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use std::borrow::Borrow;
+
+use crate::{Error, ErrorKind, PafRecord, Result};
+
+/// The alignment as a BEDPE line, without a line ending.
+///
+/// The first side is the query interval and the second the target interval,
+/// the name is `.`, the score is the mapping quality, and the strands are
+/// `+` for the query and the relative strand for the target.
+pub fn to_bedpe(record: &PafRecord) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t.\t{}\t+\t{}",
+        record.query_name(),
+        record.query_start(),
+        record.query_end(),
+        record.target_name(),
+        record.target_start(),
+        record.target_end(),
+        record.mapping_quality(),
+        record.strand()
+    )
+}
+
+/// The junctions between the alignments of a split query as BEDPE lines,
+/// without line endings.
+///
+/// The records, which must share a query, are sorted by query start, and
+/// each adjacent pair gives a line with a one base side on the target at
+/// either end of the junction. Strands give the orientation of each side as
+/// in other structural variant tools: `+` when the alignment lies before
+/// the junction on the target and `-` when after it, so a deletion is `+-`
+/// and an inversion `++` or `--`.
+///
+/// The name is `query:strands:qgap=N:tgap=M`, with the strands of the two
+/// records, the bases of the query between them, negative if they overlap,
+/// and the distance between the sides on the target, or `.` when they are on
+/// different targets. The score is the lower mapping quality. It is an
+/// error for a record to cover no target bases.
+pub fn breakpoints_to_bedpe<I>(records: I) -> Result<Vec<String>>
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    let records: Vec<I::Item> = records.into_iter().collect();
+    let mut records: Vec<&PafRecord> = records.iter().map(Borrow::borrow).collect();
+    if let Some(first) = records.first() {
+        if let Some(other) = records
+            .iter()
+            .find(|record| record.query_name() != first.query_name())
+        {
+            return Err(Error::new(ErrorKind::InvalidRecord(format!(
+                "breakpoints need records of one query, but found {} and {}",
+                first.query_name(),
+                other.query_name()
+            ))));
+        }
+    }
+    if let Some(empty) = records
+        .iter()
+        .find(|record| record.target_end() <= record.target_start())
+    {
+        return Err(Error::new(ErrorKind::InvalidRecord(format!(
+            "breakpoints need records aligned to target bases, but {} is aligned to {}:{}-{}",
+            empty.query_name(),
+            empty.target_name(),
+            empty.target_start(),
+            empty.target_end()
+        ))));
+    }
+    records.sort_by_key(|record| (record.query_start(), record.query_end()));
+
+    Ok(records
+        .windows(2)
+        .map(|pair| breakpoint(pair[0], pair[1]))
+        .collect())
+}
+
+/// The BEDPE line of the junction from the end of `left` on the query to the
+/// start of `right`.
+fn breakpoint(left: &PafRecord, right: &PafRecord) -> String {
+    // the query end of the left record, and start of the right
+    let (start1, strand1) = match left.strand() {
+        '-' => (left.target_start(), '-'),
+        _ => (left.target_end() - 1, '+'),
+    };
+    let (start2, strand2) = match right.strand() {
+        '-' => (right.target_end() - 1, '+'),
+        _ => (right.target_start(), '-'),
+    };
+    let query_gap = right.query_start() as i64 - left.query_end() as i64;
+    let target_gap = if left.target_name() == right.target_name() {
+        start1.abs_diff(start2).to_string()
+    } else {
+        ".".to_string()
+    };
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}:{}{}:qgap={}:tgap={}\t{}\t{}\t{}",
+        left.target_name(),
+        start1,
+        start1 + 1,
+        right.target_name(),
+        start2,
+        start2 + 1,
+        left.query_name(),
+        left.strand(),
+        right.strand(),
+        query_gap,
+        target_gap,
+        left.mapping_quality().min(right.mapping_quality()),
+        strand1,
+        strand2
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+
+    #[test]
+    fn test_to_bedpe() {
+        let record = &records("q\t100\t10\t60\t-\tchr1\t1000\t200\t250\t50\t50\t60\n")[0];
+        assert_eq!(to_bedpe(record), "q\t10\t60\tchr1\t200\t250\t.\t60\t+\t-");
+    }
+
+    #[test]
+    fn test_inversion() {
+        // 0..50 forward to 100..150, then 55..100 reversed onto 300..345,
+        // given out of query order
+        let paf = "\
+q\t100\t55\t100\t-\tchr1\t1000\t300\t345\t45\t45\t40\n\
+q\t100\t0\t50\t+\tchr1\t1000\t100\t150\t50\t50\t60\n";
+        assert_eq!(
+            breakpoints_to_bedpe(records(paf)).unwrap(),
+            vec!["chr1\t149\t150\tchr1\t344\t345\tq:+-:qgap=5:tgap=195\t40\t+\t+"]
+        );
+    }
+
+    #[test]
+    fn test_deletion_and_translocation() {
+        let paf = "\
+q\t100\t0\t40\t+\tchr1\t1000\t100\t140\t40\t40\t60\n\
+q\t100\t40\t70\t+\tchr1\t1000\t500\t530\t30\t30\t60\n\
+q\t100\t68\t100\t-\tchr2\t800\t10\t42\t32\t32\t30\n";
+        assert_eq!(
+            breakpoints_to_bedpe(records(paf)).unwrap(),
+            vec![
+                "chr1\t139\t140\tchr1\t500\t501\tq:++:qgap=0:tgap=361\t60\t+\t-",
+                "chr1\t529\t530\tchr2\t41\t42\tq:+-:qgap=-2:tgap=.\t30\t+\t+",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_one_query() {
+        assert!(breakpoints_to_bedpe(Vec::<PafRecord>::new())
+            .unwrap()
+            .is_empty());
+        let paf = "\
+a\t100\t0\t40\t+\tchr1\t1000\t100\t140\t40\t40\t60\n\
+b\t100\t40\t70\t+\tchr1\t1000\t500\t530\t30\t30\t60\n";
+        assert!(breakpoints_to_bedpe(records(paf)).is_err());
+    }
+
+    #[test]
+    fn test_empty_record() {
+        let paf = "\
+q\t100\t0\t40\t+\tchr1\t1000\t0\t0\t0\t0\t60\n\
+q\t100\t40\t70\t+\tchr1\t1000\t500\t530\t30\t30\t60\n";
+        assert!(breakpoints_to_bedpe(records(paf)).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use std::borrow::Borrow;
+use std::io::{BufWriter, Write};
+
+use crate::{Error, ErrorKind, PafRecord, Result};
+
+/// Writes records as UCSC chains, for liftOver, returning the number
+/// written.
+///
+/// The target is the chain's reference and the query its query. On the
+/// minus strand, query coordinates are on the reverse complement of the
+/// query, as chains require. The alignment data comes from the cg tag, with
+/// runs of `M`, `=` and `X` as blocks and the indels between as gaps, and
+/// `score_fn` gives each chain its score. Chains are numbered from 1.
+///
+/// ```
+/// use paf::convert::write_chain;
+/// use paf::Reader;
+///
+/// let paf = b"q\t100\t10\t60\t-\tt\t1000\t200\t250\t50\t50\t60\tcg:Z:20M2D30M\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+/// let mut chain = Vec::new();
+/// write_chain(&mut chain, &records, |record| record.residue_matches() as i64).unwrap();
+/// assert_eq!(
+///     String::from_utf8(chain).unwrap(),
+///     "chain 50 t 1000 + 200 250 q 100 - 40 90 1\n20\t2\t0\n30\n\n"
+/// );
+/// ```
+pub fn write_chain<W, I, F>(writer: W, records: I, mut score_fn: F) -> Result<u64>
+where
+    W: Write,
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+    F: FnMut(&PafRecord) -> i64,
+{
+    let mut writer = BufWriter::new(writer);
+    let mut count = 0;
+    for record in records {
+        let record = record.borrow();
+        write_one(&mut writer, record, score_fn(record), count + 1)
+            .map_err(|err| Error::new(ErrorKind::WriteRecord { index: count, err }))?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+fn write_one<W: Write>(writer: &mut W, record: &PafRecord, score: i64, id: u64) -> Result<()> {
+    let cigar = record.require_cigar("chain output")?;
+
+    // blocks of (size, target gap, query gap) with the gaps after each
+    let mut blocks: Vec<(u64, u64, u64)> = Vec::new();
+    for (len, op) in cigar.ops() {
+        let len = len as u64;
+        match (op.consumes_query(), op.consumes_target()) {
+            (true, true) => match blocks.last_mut() {
+                Some(block) if block.1 == 0 && block.2 == 0 => block.0 += len,
+                _ => blocks.push((len, 0, 0)),
+            },
+            (consumes_query, consumes_target) => {
+                let Some(block) = blocks.last_mut() else {
+                    return Err(chain_error("starts with a gap"));
+                };
+                if consumes_target {
+                    block.1 += len;
+                }
+                if consumes_query {
+                    block.2 += len;
+                }
+            }
+        }
+    }
+    match blocks.last() {
+        None => return Err(chain_error("has no aligned bases")),
+        Some(&(_, target_gap, query_gap)) if target_gap > 0 || query_gap > 0 => {
+            return Err(chain_error("ends with a gap"));
+        }
+        Some(_) => {}
+    }
+
+    let (query_start, query_end) = match record.strand() {
+        '-' => (
+            record.coordinate_sub(record.query_len(), record.query_end())?,
+            record.coordinate_sub(record.query_len(), record.query_start())?,
+        ),
+        _ => (record.query_start(), record.query_end()),
+    };
+    writeln!(
+        writer,
+        "chain {} {} {} + {} {} {} {} {} {} {} {}",
+        score,
+        record.target_name(),
+        record.target_len(),
+        record.target_start(),
+        record.target_end(),
+        record.query_name(),
+        record.query_len(),
+        record.strand(),
+        query_start,
+        query_end,
+        id
+    )?;
+    let (last, rest) = blocks.split_last().unwrap();
+    for (size, target_gap, query_gap) in rest {
+        writeln!(writer, "{}\t{}\t{}", size, target_gap, query_gap)?;
+    }
+    writeln!(writer, "{}\n", last.0)?;
+    Ok(())
+}
+
+fn chain_error(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidRecord(format!(
+        "a chain needs an alignment that starts and ends with a match, but the cg tag {}",
+        msg
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+
+    fn chain(paf: &str) -> Result<String> {
+        let mut chain = Vec::new();
+        write_chain(&mut chain, records(paf), |record| record.as_().unwrap_or(0))?;
+        Ok(String::from_utf8(chain).unwrap())
+    }
+
+    #[test]
+    fn test_golden() {
+        let paf = "\
+r1\t100\t10\t60\t+\tchr1\t1000\t200\t251\t48\t53\t60\tAS:i:48\tcg:Z:20M2I18M3D10M\n\
+r2\t80\t5\t76\t-\tchr2\t500\t100\t171\t70\t72\t60\tAS:i:9\tcg:Z:30=1I1D39=1X\n";
+        assert_eq!(
+            chain(paf).unwrap(),
+            "chain 48 chr1 1000 + 200 251 r1 100 + 10 60 1\n\
+             20\t0\t2\n\
+             18\t3\t0\n\
+             10\n\
+             \n\
+             chain 9 chr2 500 + 100 171 r2 80 - 4 75 2\n\
+             30\t1\t1\n\
+             40\n\
+             \n"
+        );
+    }
+
+    #[test]
+    fn test_minus_strand() {
+        // the query interval 0..30 is 70..100 on the reverse strand
+        let paf = "r\t100\t0\t30\t-\tt\t50\t10\t45\t30\t35\t60\tcg:Z:10M5N20M\n";
+        assert_eq!(
+            chain(paf).unwrap(),
+            "chain 0 t 50 + 10 45 r 100 - 70 100 1\n10\t5\t0\n20\n\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let paf = "\
+a\t10\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\tcg:Z:10M\n\
+b\t10\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\n";
+        let err = chain(paf).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::WriteRecord { index: 1, .. }
+        ));
+        // chains start and end with a block
+        assert!(chain("a\t10\t0\t10\t+\tt\t100\t0\t12\t10\t12\t60\tcg:Z:2D10M\n").is_err());
+        assert!(chain("a\t15\t0\t15\t+\tt\t100\t0\t10\t10\t15\t60\tcg:Z:10M5I\n").is_err());
+        // a minus strand query ending past its length
+        assert!(chain("a\t10\t0\t20\t-\tt\t100\t0\t20\t20\t20\t60\tcg:Z:20M\n").is_err());
+    }
+}
@@ -0,0 +1,295 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::io::{BufWriter, Write};
+
+use crate::reconstruct::reverse_complement;
+use crate::{Error, ErrorKind, PafRecord, Result};
+
+/// Sequences to fill in alignments from, e.g. backed by an indexed FASTA.
+pub trait SequenceSource {
+    /// The bases of the sequence `name` from `start` to `end`, 0-based and
+    /// half-open, on its forward strand.
+    fn fetch(&mut self, name: &str, start: u64, end: u64) -> Result<Vec<u8>>;
+}
+
+/// Whole sequences held in memory, by name.
+impl SequenceSource for HashMap<String, Vec<u8>> {
+    fn fetch(&mut self, name: &str, start: u64, end: u64) -> Result<Vec<u8>> {
+        let seq = self.get(name).ok_or_else(|| {
+            Error::new(ErrorKind::InvalidRecord(format!(
+                "no sequence named {}",
+                name
+            )))
+        })?;
+        if start > end || end > seq.len() as u64 {
+            return Err(Error::new(ErrorKind::InvalidRecord(format!(
+                "{}..{} is outside {}, of {} bases",
+                start,
+                end,
+                name,
+                seq.len()
+            ))));
+        }
+        Ok(seq[start as usize..end as usize].to_vec())
+    }
+}
+
+/// Writes records as MAF alignment blocks, with the bases of each aligned
+/// sequence fetched from a [`SequenceSource`].
+///
+/// Each block has an `s` line for the target and then for the query, with
+/// the gapped rows rebuilt through the cg tag, and is scored by the AS tag
+/// when the record has one. On the minus strand the query row is the
+/// reverse complement of the query, and its start is counted from the end
+/// of the query, as MAF requires.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use paf::convert::MafWriter;
+/// use paf::Reader;
+///
+/// let paf = b"q\t6\t0\t6\t+\tt\t8\t1\t8\t6\t7\t60\tcg:Z:3M1D3M\n";
+/// let record = Reader::from_reader(&paf[..]).read_record().unwrap().unwrap();
+/// let queries = HashMap::from([("q".to_string(), b"ACGTTT".to_vec())]);
+/// let targets = HashMap::from([("t".to_string(), b"GACGATTT".to_vec())]);
+/// let mut writer = MafWriter::new(Vec::new(), queries, targets);
+/// writer.write_record(&record).unwrap();
+/// assert_eq!(
+///     String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+///     "##maf version=1\n\na\ns t 1 7 + 8 ACGATTT\ns q 0 6 + 6 ACG-TTT\n\n"
+/// );
+/// ```
+pub struct MafWriter<W: Write, Q, T> {
+    writer: BufWriter<W>,
+    query_fasta: Q,
+    target_fasta: T,
+    header_written: bool,
+}
+
+impl<W: Write, Q: SequenceSource, T: SequenceSource> MafWriter<W, Q, T> {
+    /// Creates a MAF writer, fetching query bases from `query_fasta` and
+    /// target bases from `target_fasta`.
+    pub fn new(writer: W, query_fasta: Q, target_fasta: T) -> Self {
+        MafWriter {
+            writer: BufWriter::new(writer),
+            query_fasta,
+            target_fasta,
+            header_written: false,
+        }
+    }
+
+    /// Writes a series of records, returning how many were written.
+    ///
+    /// This stops at the first error, which is an
+    /// [`ErrorKind::WriteRecord`](ErrorKind::WriteRecord) giving the
+    /// index of the failed record.
+    pub fn write_records<I>(&mut self, records: I) -> Result<u64>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<PafRecord>,
+    {
+        let mut written = 0;
+        for record in records {
+            self.write_record(record.borrow()).map_err(|err| {
+                Error::new(ErrorKind::WriteRecord {
+                    index: written,
+                    err,
+                })
+            })?;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Writes a record as a MAF block, after the header if it is the first.
+    ///
+    /// It is an error for the record to have no cg tag, for its coordinates
+    /// to be out of order, for a sequence not to be fetched, or for the cg
+    /// tag not to span the aligned intervals.
+    pub fn write_record(&mut self, record: &PafRecord) -> Result<()> {
+        let cigar = record.require_cigar("MAF output")?;
+        let target_span = record.coordinate_sub(record.target_end(), record.target_start())?;
+        let query_span = record.coordinate_sub(record.query_end(), record.query_start())?;
+        // minus strand starts are on the reverse complement
+        let query_start = match record.strand() {
+            '-' => record.coordinate_sub(record.query_len(), record.query_end())?,
+            _ => record.query_start(),
+        };
+        let target = self.target_fasta.fetch(
+            record.target_name(),
+            record.target_start() as u64,
+            record.target_end() as u64,
+        )?;
+        let mut query = self.query_fasta.fetch(
+            record.query_name(),
+            record.query_start() as u64,
+            record.query_end() as u64,
+        )?;
+        if record.strand() == '-' {
+            query = reverse_complement(&query);
+        }
+        if cigar.target_len() != target.len() as u64 || cigar.query_len() != query.len() as u64 {
+            return Err(Error::new(ErrorKind::InvalidRecord(format!(
+                "cg tag of {} aligned to {} spans {} query and {} target bases, not {} and {}",
+                record.query_name(),
+                record.target_name(),
+                cigar.query_len(),
+                cigar.target_len(),
+                query.len(),
+                target.len()
+            ))));
+        }
+
+        // the gapped rows, walking both sequences along the alignment
+        let columns = cigar.block_len() as usize;
+        let (mut target_row, mut query_row) =
+            (Vec::with_capacity(columns), Vec::with_capacity(columns));
+        let (mut t, mut q) = (0, 0);
+        for (len, op) in cigar.ops() {
+            let len = len as usize;
+            match (op.consumes_query(), op.consumes_target()) {
+                (true, true) => {
+                    target_row.extend_from_slice(&target[t..t + len]);
+                    query_row.extend_from_slice(&query[q..q + len]);
+                }
+                (true, false) => {
+                    target_row.resize(target_row.len() + len, b'-');
+                    query_row.extend_from_slice(&query[q..q + len]);
+                }
+                (false, true) => {
+                    target_row.extend_from_slice(&target[t..t + len]);
+                    query_row.resize(query_row.len() + len, b'-');
+                }
+                (false, false) => {}
+            }
+            t += if op.consumes_target() { len } else { 0 };
+            q += if op.consumes_query() { len } else { 0 };
+        }
+
+        self.write_header()?;
+        match record.as_() {
+            Some(score) => writeln!(self.writer, "a score={}", score)?,
+            None => writeln!(self.writer, "a")?,
+        }
+        write!(
+            self.writer,
+            "s {} {} {} + {} ",
+            record.target_name(),
+            record.target_start(),
+            target_span,
+            record.target_len()
+        )?;
+        self.writer.write_all(&target_row)?;
+        write!(
+            self.writer,
+            "\ns {} {} {} {} {} ",
+            record.query_name(),
+            query_start,
+            query_span,
+            record.strand(),
+            record.query_len()
+        )?;
+        self.writer.write_all(&query_row)?;
+        self.writer.write_all(b"\n\n")?;
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        if !self.header_written {
+            self.writer.write_all(b"##maf version=1\n\n")?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+
+    /// Flushes the buffered output.
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    /// Writes the header if no record has, flushes and returns the
+    /// underlying writer.
+    pub fn into_inner(mut self) -> Result<W> {
+        self.write_header()?;
+        self.writer
+            .into_inner()
+            .map_err(|err| err.into_error().into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+
+    fn sequences(seqs: &[(&str, &str)]) -> HashMap<String, Vec<u8>> {
+        seqs.iter()
+            .map(|&(name, seq)| (name.to_string(), seq.as_bytes().to_vec()))
+            .collect()
+    }
+
+    fn maf(paf: &str) -> Result<String> {
+        let queries = sequences(&[("q1", "TTCGTGAAGCAT"), ("q2", "AGCTTCACGTTT")]);
+        let targets = sequences(&[("t", "ACGTACGTAAGGCCTTACGT")]);
+        let mut writer = MafWriter::new(Vec::new(), queries, targets);
+        let records = records(paf);
+        writer.write_records(&records)?;
+        Ok(String::from_utf8(writer.into_inner()?).unwrap())
+    }
+
+    #[test]
+    fn test_plus_strand() {
+        // target 5..14 is CGTAAGGCC and query 2..10 CGTGAAGC
+        let paf = "q1\t12\t2\t10\t+\tt\t20\t5\t14\t6\t10\t60\tcg:Z:3M1I2M2D2M\n";
+        assert_eq!(
+            maf(paf).unwrap(),
+            "\
+##maf version=1
+
+a
+s t 5 9 + 20 CGT-AAGGCC
+s q1 2 8 + 12 CGTGAA--GC
+
+"
+        );
+    }
+
+    #[test]
+    fn test_minus_strand() {
+        // query 1..9 is GCTTCACG, whose reverse complement is CGTGAAGC, and
+        // starts 12 - 9 = 3 bases from the end of the query
+        let paf = "q2\t12\t1\t9\t-\tt\t20\t5\t14\t6\t10\t60\tAS:i:7\tcg:Z:3M1I2M2D2M\n";
+        assert_eq!(
+            maf(paf).unwrap(),
+            "\
+##maf version=1
+
+a score=7
+s t 5 9 + 20 CGT-AAGGCC
+s q2 3 8 - 12 CGTGAA--GC
+
+"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        // no cg tag, an unknown query, a cg tag too short, a query ending
+        // past its length and a target interval ending before it starts
+        for paf in [
+            "q1\t12\t2\t10\t+\tt\t20\t5\t14\t6\t10\t60\n",
+            "q3\t12\t2\t10\t+\tt\t20\t5\t14\t6\t10\t60\tcg:Z:3M1I2M2D2M\n",
+            "q1\t12\t2\t10\t+\tt\t20\t5\t14\t6\t10\t60\tcg:Z:8M\n",
+            "q2\t8\t1\t9\t-\tt\t20\t5\t14\t6\t10\t60\tcg:Z:3M1I2M2D2M\n",
+            "q1\t12\t2\t10\t+\tt\t20\t14\t5\t6\t10\t60\tcg:Z:3M1I2M2D2M\n",
+        ] {
+            let err = maf(paf).unwrap_err();
+            assert!(matches!(
+                err.kind(),
+                ErrorKind::WriteRecord { index: 0, .. }
+            ));
+        }
+        assert_eq!(maf("").unwrap(), "##maf version=1\n\n");
+    }
+}
@@ -0,0 +1,31 @@
+/*!
+Convert records to other formats.
+
+```
+use paf::convert::{to_bed, Space};
+use paf::Reader;
+
+let paf = b"q\t100\t10\t60\t-\tchr1\t1000\t200\t250\t50\t50\t60\n";
+let record = Reader::from_reader(&paf[..]).read_record().unwrap().unwrap();
+assert_eq!(to_bed(&record, Space::Target), "chr1\t200\t250\tq\t60\t-");
+assert_eq!(to_bed(&record, Space::Query), "q\t10\t60\tchr1\t60\t-");
+```
+*/
+
+/// The bed module writes alignment intervals as BED.
+mod bed;
+
+/// The bedpe module writes alignments and their junctions as BEDPE.
+mod bedpe;
+/// The chain module writes records as UCSC chains.
+mod chain;
+/// The maf module writes alignments as MAF blocks.
+mod maf;
+/// The sam module writes records as SAM.
+mod sam;
+
+pub use bed::{to_bed, to_bed_blocks, Space};
+pub use bedpe::{breakpoints_to_bedpe, to_bedpe};
+pub use chain::write_chain;
+pub use maf::{MafWriter, SequenceSource};
+pub use sam::SamWriter;
@@ -0,0 +1,345 @@
+use std::borrow::Borrow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::{AlignmentType, Cigar, CigarOp, Error, ErrorKind, PafRecord, Result};
+
+/// The FLAG bit of a read on the reverse strand.
+const FLAG_REVERSE: u16 = 0x10;
+
+/// The FLAG bit of a secondary alignment.
+const FLAG_SECONDARY: u16 = 0x100;
+
+/// Writes records as SAM, without sequences or qualities.
+///
+/// The header has an `@SQ` line for each target, in the order they are
+/// first seen, so [`SamWriter::write_records`] collects the records before
+/// writing any. The CIGAR is the cg tag with the unaligned ends of the
+/// query soft clipped, and the NM, AS and cs tags are passed through.
+///
+/// ```
+/// use paf::convert::SamWriter;
+/// use paf::Reader;
+///
+/// let paf = b"q\t100\t10\t60\t+\tt\t1000\t200\t250\t50\t50\t60\tcg:Z:50M\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let records = reader.records();
+/// let mut writer = SamWriter::new(Vec::new());
+/// writer.write_records(records.map(Result::unwrap)).unwrap();
+/// let sam = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+/// assert!(sam.ends_with("q\t0\tt\t201\t60\t10S50M40S\t*\t0\t0\t*\t*\n"));
+/// ```
+#[derive(Debug)]
+pub struct SamWriter<W: Write> {
+    writer: BufWriter<W>,
+    approximate_cigar: bool,
+    header_written: bool,
+}
+
+impl SamWriter<File> {
+    /// Creates a SAM writer to a file path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<SamWriter<File>> {
+        Ok(SamWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> SamWriter<W> {
+    /// Creates a SAM writer.
+    pub fn new(writer: W) -> Self {
+        SamWriter {
+            writer: BufWriter::new(writer),
+            approximate_cigar: false,
+            header_written: false,
+        }
+    }
+
+    /// Write records without a cg tag with a CIGAR of a single match over
+    /// the shorter of the query and target intervals, and an insertion or
+    /// deletion for the difference, instead of returning an error.
+    ///
+    /// Default is `false`.
+    pub fn approximate_cigar(&mut self, yes: bool) -> &mut Self {
+        self.approximate_cigar = yes;
+        self
+    }
+
+    /// Writes the header, if it has not been already, then the records.
+    ///
+    /// Only the first call writes a header, so the targets of records in
+    /// later calls should appear in the first. A target seen with two
+    /// different lengths is an error. Returns the number of records written.
+    pub fn write_records<I>(&mut self, records: I) -> Result<u64>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<PafRecord>,
+    {
+        let records: Vec<I::Item> = records.into_iter().collect();
+        if !self.header_written {
+            self.write_header(records.iter().map(Borrow::borrow))?;
+            self.header_written = true;
+        }
+        for (index, record) in records.iter().enumerate() {
+            self.write_record(record.borrow()).map_err(|err| {
+                Error::new(ErrorKind::WriteRecord {
+                    index: index as u64,
+                    err,
+                })
+            })?;
+        }
+        Ok(records.len() as u64)
+    }
+
+    fn write_header<'a>(&mut self, records: impl Iterator<Item = &'a PafRecord>) -> Result<()> {
+        let mut targets: Vec<(&str, u32)> = Vec::new();
+        for record in records {
+            let name = record.target_name();
+            match targets.iter().find(|(seen, _)| *seen == name) {
+                Some(&(_, len)) if len != record.target_len() => {
+                    return Err(Error::new(ErrorKind::InvalidRecord(format!(
+                        "target {} has lengths {} and {}",
+                        name,
+                        len,
+                        record.target_len()
+                    ))));
+                }
+                Some(_) => {}
+                None => targets.push((name, record.target_len())),
+            }
+        }
+        writeln!(self.writer, "@HD\tVN:1.6\tSO:unsorted")?;
+        for (name, len) in targets {
+            writeln!(self.writer, "@SQ\tSN:{}\tLN:{}", name, len)?;
+        }
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &PafRecord) -> Result<()> {
+        let cigar = if self.approximate_cigar && record.cigar().is_none() {
+            // a match over the shorter span, and an indel for the rest
+            let target_span = record.coordinate_sub(record.target_end(), record.target_start())?;
+            let query_span = record.coordinate_sub(record.query_end(), record.query_start())?;
+            let ops = [
+                (query_span.min(target_span), CigarOp::Match),
+                (query_span.saturating_sub(target_span), CigarOp::Insertion),
+                (target_span.saturating_sub(query_span), CigarOp::Deletion),
+            ];
+            Cigar::new(ops.into_iter().filter(|&(len, _)| len > 0).collect())
+        } else {
+            record.require_cigar("SAM output")?
+        };
+        // the query is reverse complemented on the minus strand
+        let (mut lead, mut trail) = (
+            record.query_start(),
+            record.coordinate_sub(record.query_len(), record.query_end())?,
+        );
+        if record.strand() == '-' {
+            std::mem::swap(&mut lead, &mut trail);
+        }
+        let mut ops = Vec::with_capacity(cigar.len() + 2);
+        if lead > 0 {
+            ops.push((lead, CigarOp::SoftClip));
+        }
+        ops.extend(cigar.ops());
+        if trail > 0 {
+            ops.push((trail, CigarOp::SoftClip));
+        }
+
+        let mut flag = 0;
+        if record.strand() == '-' {
+            flag |= FLAG_REVERSE;
+        }
+        if matches!(
+            record.tp(),
+            Some(AlignmentType::Secondary | AlignmentType::SecondaryInversion)
+        ) {
+            flag |= FLAG_SECONDARY;
+        }
+
+        write!(
+            self.writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t*\t0\t0\t*\t*",
+            record.query_name(),
+            flag,
+            record.target_name(),
+            record.target_start() + 1,
+            record.mapping_quality(),
+            Cigar::new(ops)
+        )?;
+        if let Some(nm) = record.nm() {
+            write!(self.writer, "\tNM:i:{}", nm)?;
+        }
+        if let Some(score) = record.as_() {
+            write!(self.writer, "\tAS:i:{}", score)?;
+        }
+        if let Some(cs) = record.cs() {
+            write!(self.writer, "\tcs:Z:{}", cs)?;
+        }
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    /// Flushes the buffered output.
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn into_inner(self) -> Result<W> {
+        self.writer
+            .into_inner()
+            .map_err(|err| err.into_error().into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+
+    const PAF: &str = "\
+r1\t100\t10\t60\t+\tchr1\t1000\t200\t251\t48\t53\t60\ttp:A:P\tNM:i:5\tAS:i:80\tcg:Z:20M2I18M3D10M\n\
+r2\t80\t5\t75\t-\tchr2\t500\t100\t170\t70\t70\t3\ttp:A:S\tcs:Z::70\tcg:Z:70M\n\
+r3\t50\t0\t50\t+\tchr1\t1000\t0\t50\t50\t50\t0\ttp:A:P\n";
+
+    fn sam(records: &[PafRecord], approximate: bool) -> Result<String> {
+        let mut writer = SamWriter::new(Vec::new());
+        writer.approximate_cigar(approximate);
+        writer.write_records(records)?;
+        Ok(String::from_utf8(writer.into_inner()?).unwrap())
+    }
+
+    /// The query length consumed by a SAM CIGAR.
+    fn query_len(cigar: &str) -> u64 {
+        Cigar::parse(cigar)
+            .unwrap()
+            .ops()
+            .filter(|(_, op)| op.consumes_query() || *op == CigarOp::SoftClip)
+            .map(|(len, _)| len as u64)
+            .sum()
+    }
+
+    #[test]
+    fn test_golden() {
+        let sam = sam(&records(PAF), true).unwrap();
+        assert_eq!(
+            sam,
+            "@HD\tVN:1.6\tSO:unsorted\n\
+             @SQ\tSN:chr1\tLN:1000\n\
+             @SQ\tSN:chr2\tLN:500\n\
+             r1\t0\tchr1\t201\t60\t10S20M2I18M3D10M40S\t*\t0\t0\t*\t*\tNM:i:5\tAS:i:80\n\
+             r2\t272\tchr2\t101\t3\t5S70M5S\t*\t0\t0\t*\t*\tcs:Z::70\n\
+             r3\t0\tchr1\t1\t0\t50M\t*\t0\t0\t*\t*\n"
+        );
+    }
+
+    #[test]
+    fn test_minus_strand_clips() {
+        let paf = "r\t100\t10\t60\t-\tt\t1000\t0\t50\t50\t50\t60\tcg:Z:50M\n";
+        let sam = sam(&records(paf), false).unwrap();
+        assert!(sam.ends_with("r\t16\tt\t1\t60\t40S50M10S\t*\t0\t0\t*\t*\n"));
+    }
+
+    #[test]
+    fn test_compact_cigar() {
+        let records: Vec<_> = crate::ReaderBuilder::new()
+            .compact_cigar(true)
+            .from_reader(PAF.as_bytes())
+            .records()
+            .map(Result::unwrap)
+            .collect();
+        let sam = sam(&records, true).unwrap();
+        assert!(sam.contains("\t10S20M2I18M3D10M40S\t"));
+    }
+
+    #[test]
+    fn test_approximate_unequal_spans() {
+        // 50 query bases against 45 and 55 target bases
+        let paf = "\
+a\t100\t10\t60\t+\tt\t1000\t0\t45\t45\t50\t60
+b\t100\t10\t60\t+\tt\t1000\t0\t55\t50\t55\t60
+";
+        let sam = sam(&records(paf), true).unwrap();
+        let cigars: Vec<_> = sam
+            .lines()
+            .filter(|line| !line.starts_with('@'))
+            .map(|line| line.split('\t').nth(5).unwrap())
+            .collect();
+        assert_eq!(cigars, ["10S45M5I40S", "10S50M5D40S"]);
+        for cigar in cigars {
+            assert_eq!(query_len(cigar), 100);
+        }
+    }
+
+    #[test]
+    fn test_invalid_coordinates() {
+        // the query ends past its length, and starts after it ends
+        for paf in [
+            "r\t50\t10\t60\t+\tt\t1000\t0\t50\t50\t50\t60\tcg:Z:50M\n",
+            "r\t100\t60\t10\t+\tt\t1000\t0\t50\t50\t50\t60\n",
+        ] {
+            let err = sam(&records(paf), true).unwrap_err();
+            assert!(matches!(
+                err.kind(),
+                ErrorKind::WriteRecord { index: 0, .. }
+            ));
+        }
+    }
+
+    #[test]
+    fn test_needs_cigar() {
+        let err = sam(&records(PAF), false).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::WriteRecord { index: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn test_conflicting_target_lengths() {
+        let paf = "\
+a\t10\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\tcg:Z:10M\n\
+b\t10\t0\t10\t+\tt\t200\t0\t10\t10\t10\t60\tcg:Z:10M\n";
+        assert!(sam(&records(paf), false).is_err());
+    }
+
+    /// Checks the output against the SAM specification, field by field.
+    #[test]
+    fn test_valid_sam() {
+        let paf = std::fs::read_to_string("data/5_GD_domestica.paf").unwrap();
+        let records: Vec<PafRecord> = records(&paf).into_iter().take(500).collect();
+        let sam = sam(&records, false).unwrap();
+
+        let mut targets = std::collections::HashMap::new();
+        let mut lines = 0;
+        for line in sam.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if line.starts_with('@') {
+                if fields[0] == "@SQ" {
+                    let name = fields[1].strip_prefix("SN:").unwrap();
+                    let len: u64 = fields[2].strip_prefix("LN:").unwrap().parse().unwrap();
+                    assert!(targets.insert(name, len).is_none());
+                }
+                continue;
+            }
+            let record = &records[lines];
+            lines += 1;
+            assert!(fields.len() >= 11);
+            assert_eq!(fields[0], record.query_name());
+            let flag: u16 = fields[1].parse().unwrap();
+            assert_eq!(flag & FLAG_REVERSE != 0, record.strand() == '-');
+            let len = targets[fields[2]];
+            let pos: u64 = fields[3].parse().unwrap();
+            assert!(pos >= 1 && pos <= len);
+            assert!(fields[4].parse::<u8>().is_ok());
+            assert_eq!(query_len(fields[5]), record.query_len() as u64);
+            assert_eq!(&fields[6..11], ["*", "0", "0", "*", "*"]);
+            for tag in &fields[11..] {
+                let parts: Vec<&str> = tag.splitn(3, ':').collect();
+                assert_eq!(parts.len(), 3);
+                assert_eq!(parts[0].len(), 2);
+            }
+        }
+        assert_eq!(lines, records.len());
+    }
+}
@@ -0,0 +1,1290 @@
+/*!
+Compute the depth of alignments along each target, and how much of each
+query is aligned.
+
+```
+use paf::coverage::TargetCoverage;
+use paf::Reader;
+
+let paf = b"q1\t100\t0\t100\t+\tt\t1000\t0\t100\t100\t100\t60\n\
+            q2\t100\t0\t100\t+\tt\t1000\t50\t150\t100\t100\t60\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let mut coverage = TargetCoverage::new();
+coverage.thresholds(&[2]);
+for record in reader.records() {
+    coverage.add(&record.unwrap()).unwrap();
+}
+let targets = coverage.finish();
+assert_eq!(targets[0].covered_bases, 150);
+assert_eq!(targets[0].covered_at, vec![(2, 50)]);
+assert_eq!(targets[0].mean_depth, 0.2);
+```
+*/
+
+use std::borrow::Borrow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::io::{BufWriter, Write};
+
+use crate::convert::Space;
+use crate::{AlignmentType, Error, ErrorKind, PafRecord, Result};
+
+/// Which depth track to keep for each target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Track {
+    /// Only the totals.
+    #[default]
+    None,
+    /// The depth at every base of the target, which takes four bytes a base.
+    Full,
+    /// Runs of bases at the same depth.
+    RunLength,
+}
+
+/// The depth of each base of a target, as kept by [`Track`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DepthTrack {
+    /// The depth at every base of the target.
+    Full(Vec<u32>),
+    /// Runs of bases at the same depth, covering the whole target in order,
+    /// with no two adjacent runs at the same depth.
+    RunLength(Vec<DepthRun>),
+}
+
+/// Bases of a target at the same depth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthRun {
+    /// Start of the run on the target.
+    pub start: u64,
+    /// End of the run on the target.
+    pub end: u64,
+    /// The number of alignments covering each base of the run.
+    pub depth: u32,
+}
+
+/// The depth of alignments along one target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageSummary {
+    /// Name of the target.
+    pub target_name: String,
+    /// Length of the target.
+    pub target_len: u64,
+    /// Bases covered by at least one alignment.
+    pub covered_bases: u64,
+    /// The bases covered by at least each threshold number of alignments,
+    /// as `(threshold, bases)` in the order the thresholds were given.
+    pub covered_at: Vec<(u32, u64)>,
+    /// Aligned bases over the length of the target.
+    pub mean_depth: f64,
+    /// The depth of each base, if a [`Track`] was asked for.
+    pub track: Option<DepthTrack>,
+}
+
+/// A sweep along one target, from start to end, over the ends of the
+/// alignments covering the current position.
+#[derive(Debug)]
+struct Sweep {
+    summary: CoverageSummary,
+    /// The position up to which depth has been counted.
+    pos: u64,
+    /// The ends of the alignments covering `pos`.
+    ends: BinaryHeap<Reverse<u64>>,
+    aligned_bases: u64,
+}
+
+impl Sweep {
+    fn new(target_name: &str, target_len: u64, thresholds: &[u32], track: Track) -> Self {
+        Sweep {
+            summary: CoverageSummary {
+                target_name: target_name.to_string(),
+                target_len,
+                covered_bases: 0,
+                covered_at: thresholds.iter().map(|&t| (t, 0)).collect(),
+                mean_depth: 0.0,
+                track: match track {
+                    Track::None => None,
+                    Track::Full => Some(DepthTrack::Full(Vec::new())),
+                    Track::RunLength => Some(DepthTrack::RunLength(Vec::new())),
+                },
+            },
+            pos: 0,
+            ends: BinaryHeap::new(),
+            aligned_bases: 0,
+        }
+    }
+
+    /// Add an alignment starting at or after the start of every one before.
+    fn add(&mut self, start: u64, end: u64) {
+        self.advance_to(start);
+        if end > start {
+            self.ends.push(Reverse(end));
+        }
+    }
+
+    /// Count depth up to `to`, dropping the alignments which end before it.
+    fn advance_to(&mut self, to: u64) {
+        while let Some(&Reverse(end)) = self.ends.peek() {
+            if end > to {
+                break;
+            }
+            self.count(end);
+            self.ends.pop();
+        }
+        self.count(to);
+    }
+
+    /// Count the bases from `pos` to `to` at the current depth.
+    fn count(&mut self, to: u64) {
+        if to <= self.pos {
+            return;
+        }
+        let len = to - self.pos;
+        let depth = self.ends.len() as u32;
+        let summary = &mut self.summary;
+        self.aligned_bases += len * depth as u64;
+        if depth > 0 {
+            summary.covered_bases += len;
+        }
+        for (threshold, bases) in &mut summary.covered_at {
+            if depth >= *threshold {
+                *bases += len;
+            }
+        }
+        match &mut summary.track {
+            None => {}
+            Some(DepthTrack::Full(depths)) => {
+                depths.resize(depths.len() + len as usize, depth);
+            }
+            Some(DepthTrack::RunLength(runs)) => match runs.last_mut() {
+                Some(run) if run.depth == depth => run.end = to,
+                _ => runs.push(DepthRun {
+                    start: self.pos,
+                    end: to,
+                    depth,
+                }),
+            },
+        }
+        self.pos = to;
+    }
+
+    fn finish(mut self) -> CoverageSummary {
+        let target_len = self.summary.target_len;
+        if let Some(last) = self.ends.iter().map(|&Reverse(end)| end).max() {
+            self.advance_to(last);
+        }
+        self.count(target_len);
+        if target_len > 0 {
+            self.summary.mean_depth = self.aligned_bases as f64 / target_len as f64;
+        }
+        self.summary
+    }
+}
+
+/// The target intervals of the records on one target.
+#[derive(Debug)]
+struct Intervals {
+    target_name: String,
+    target_len: u64,
+    intervals: Vec<(u64, u64)>,
+}
+
+/// An accumulator of the depth of alignments along each target.
+///
+/// Each record covers its whole target interval, including any deletions
+/// and introns within it. Targets appear in the results in the order they
+/// are first seen, and only those with records are included.
+///
+/// [`TargetCoverage::new`] expects records sorted by target and then target
+/// start, as from `sort -k6,6 -k8,8n`, and holds only the ends of the
+/// alignments overlapping the current position, so memory does not grow
+/// with the length of the targets unless a [`Track::Full`] is kept.
+/// [`TargetCoverage::unsorted`] takes records in any order, holding the
+/// interval of every record until [`TargetCoverage::finish`].
+#[derive(Debug)]
+pub struct TargetCoverage {
+    thresholds: Vec<u32>,
+    exclude_secondary: bool,
+    track: Track,
+    sorted: bool,
+    /// The target being swept, for sorted input.
+    current: Option<Sweep>,
+    /// Targets already swept, for sorted input.
+    seen: HashSet<String>,
+    /// The intervals on each target, for unsorted input.
+    buffered: Vec<Intervals>,
+    index: HashMap<String, usize>,
+    done: Vec<CoverageSummary>,
+}
+
+impl TargetCoverage {
+    /// Create an accumulator for records sorted by target and target start.
+    pub fn new() -> Self {
+        TargetCoverage {
+            thresholds: Vec::new(),
+            exclude_secondary: false,
+            track: Track::None,
+            sorted: true,
+            current: None,
+            seen: HashSet::new(),
+            buffered: Vec::new(),
+            index: HashMap::new(),
+            done: Vec::new(),
+        }
+    }
+
+    /// Create an accumulator for records in any order.
+    pub fn unsorted() -> Self {
+        TargetCoverage {
+            sorted: false,
+            ..Self::new()
+        }
+    }
+
+    /// Count the bases covered by at least each of these numbers of
+    /// alignments, in [`CoverageSummary::covered_at`].
+    ///
+    /// Set this before adding records.
+    pub fn thresholds(&mut self, thresholds: &[u32]) -> &mut Self {
+        self.thresholds = thresholds.to_vec();
+        self
+    }
+
+    /// Leave out secondary alignments, those with a tp tag of `S` or `i`.
+    ///
+    /// Default is `false`.
+    pub fn exclude_secondary(&mut self, yes: bool) -> &mut Self {
+        self.exclude_secondary = yes;
+        self
+    }
+
+    /// Keep a depth track for each target.
+    ///
+    /// Default is [`Track::None`]. Set this before adding records.
+    pub fn track(&mut self, track: Track) -> &mut Self {
+        self.track = track;
+        self
+    }
+
+    /// Add a record.
+    ///
+    /// A target seen with two different lengths is an error, as is a record
+    /// out of order for an accumulator from [`TargetCoverage::new`].
+    pub fn add(&mut self, record: &PafRecord) -> Result<()> {
+        if self.exclude_secondary
+            && matches!(
+                record.tp(),
+                Some(AlignmentType::Secondary | AlignmentType::SecondaryInversion)
+            )
+        {
+            return Ok(());
+        }
+        let name = record.target_name();
+        let target_len = record.target_len() as u64;
+        let (start, end) = (record.target_start() as u64, record.target_end() as u64);
+
+        if !self.sorted {
+            let i = match self.index.get(name) {
+                Some(&i) => i,
+                None => {
+                    self.index.insert(name.to_string(), self.buffered.len());
+                    self.buffered.push(Intervals {
+                        target_name: name.to_string(),
+                        target_len,
+                        intervals: Vec::new(),
+                    });
+                    self.buffered.len() - 1
+                }
+            };
+            let target = &mut self.buffered[i];
+            check_len(name, target.target_len, target_len)?;
+            target.intervals.push((start, end));
+            return Ok(());
+        }
+
+        match &mut self.current {
+            Some(sweep) if sweep.summary.target_name == name => {
+                check_len(name, sweep.summary.target_len, target_len)?;
+                if start < sweep.pos {
+                    return Err(unsorted(format!(
+                        "{} starts at {} after a record starting at {}",
+                        name, start, sweep.pos
+                    )));
+                }
+            }
+            _ => {
+                if !self.seen.insert(name.to_string()) {
+                    return Err(unsorted(format!("{} seen again after other targets", name)));
+                }
+                let sweep = Sweep::new(name, target_len, &self.thresholds, self.track);
+                if let Some(done) = self.current.replace(sweep) {
+                    self.done.push(done.finish());
+                }
+            }
+        }
+        self.current.as_mut().unwrap().add(start, end);
+        Ok(())
+    }
+
+    /// The depth along each target with records.
+    pub fn finish(mut self) -> Vec<CoverageSummary> {
+        if let Some(sweep) = self.current.take() {
+            self.done.push(sweep.finish());
+        }
+        for mut target in std::mem::take(&mut self.buffered) {
+            target.intervals.sort_unstable();
+            let mut sweep = Sweep::new(
+                &target.target_name,
+                target.target_len,
+                &self.thresholds,
+                self.track,
+            );
+            for (start, end) in target.intervals {
+                sweep.add(start, end);
+            }
+            self.done.push(sweep.finish());
+        }
+        self.done
+    }
+}
+
+impl Default for TargetCoverage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How a query is aligned, as classified by [`QueryBreadth`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BreadthClass {
+    /// One alignment covers at least the full length fraction of the query.
+    FullLength,
+    /// Several alignments together cover at least the full length fraction,
+    /// but none does alone.
+    Split,
+    /// Less than the full length fraction is covered, in one stretch with
+    /// the rest of the query unaligned at its ends.
+    UnalignedEnds,
+    /// Less than the full length fraction is covered, with unaligned bases
+    /// between aligned ones.
+    Partial,
+}
+
+/// The alignments of one query, from [`QueryBreadth`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreadthSummary {
+    /// Name of the query.
+    pub query_name: String,
+    /// Length of the query.
+    pub query_len: u64,
+    /// Query bases covered by at least one alignment.
+    pub covered_bases: u64,
+    /// Covered bases over the length of the query.
+    pub covered_fraction: f64,
+    /// Number of alignments.
+    pub alignments: u64,
+    /// Number of distinct targets the alignments are on.
+    pub targets: u64,
+    /// How the query is aligned.
+    pub class: BreadthClass,
+}
+
+/// The records of the query being gathered.
+#[derive(Debug, Clone)]
+struct Query {
+    query_name: String,
+    query_len: u64,
+    intervals: Vec<(u64, u64)>,
+    targets: HashSet<String>,
+}
+
+/// A streaming accumulator of how much of each query is aligned, over
+/// records grouped by query.
+///
+/// Records of a query must be next to each other, as in minimap2 output; a
+/// query appearing again later is summarized again, as with
+/// [`select::group_by_query`](crate::select::group_by_query). Query
+/// intervals are on the query as given, whatever the strand of the
+/// alignment, and overlapping intervals are merged before counting.
+///
+/// ```
+/// use paf::coverage::{BreadthClass, QueryBreadth};
+/// use paf::Reader;
+///
+/// let paf = b"q\t100\t0\t60\t+\tt\t1000\t0\t60\t60\t60\t60\n\
+///             q\t100\t50\t100\t-\tu\t1000\t0\t50\t50\t50\t60\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let queries: Vec<_> = QueryBreadth::new()
+///     .summarize(reader.records())
+///     .map(Result::unwrap)
+///     .collect();
+/// assert_eq!(queries[0].covered_bases, 100);
+/// assert_eq!(queries[0].targets, 2);
+/// assert_eq!(queries[0].class, BreadthClass::Split);
+/// ```
+#[derive(Debug, Clone)]
+pub struct QueryBreadth {
+    full_length: f64,
+    current: Option<Query>,
+}
+
+impl QueryBreadth {
+    /// Create an accumulator with a full length fraction of 0.95.
+    pub fn new() -> Self {
+        QueryBreadth {
+            full_length: 0.95,
+            current: None,
+        }
+    }
+
+    /// The fraction of a query which must be covered for it to be
+    /// [`BreadthClass::FullLength`] or [`BreadthClass::Split`].
+    ///
+    /// Default is 0.95.
+    pub fn full_length(&mut self, fraction: f64) -> &mut Self {
+        self.full_length = fraction;
+        self
+    }
+
+    /// Add a record, returning the summary of the query before if this
+    /// record starts another.
+    ///
+    /// A query seen with two different lengths is an error.
+    pub fn add(&mut self, record: &PafRecord) -> Result<Option<BreadthSummary>> {
+        let name = record.query_name();
+        let query_len = record.query_len() as u64;
+        let mut done = None;
+        match &self.current {
+            Some(query) if query.query_name == name => {
+                if query.query_len != query_len {
+                    return Err(Error::new(ErrorKind::InvalidRecord(format!(
+                        "query {} has lengths {} and {}",
+                        name, query.query_len, query_len
+                    ))));
+                }
+            }
+            _ => {
+                let query = Query {
+                    query_name: name.to_string(),
+                    query_len,
+                    intervals: Vec::new(),
+                    targets: HashSet::new(),
+                };
+                done = self.current.replace(query).map(|query| self.summary(query));
+            }
+        }
+        let query = self.current.as_mut().unwrap();
+        // a query interval ending before it starts covers nothing
+        let (start, end) = (record.query_start() as u64, record.query_end() as u64);
+        query.intervals.push((start, end.max(start)));
+        if !query.targets.contains(record.target_name()) {
+            query.targets.insert(record.target_name().to_string());
+        }
+        Ok(done)
+    }
+
+    /// The summary of the last query, if there were any records.
+    pub fn finish(&mut self) -> Option<BreadthSummary> {
+        self.current.take().map(|query| self.summary(query))
+    }
+
+    /// Summarize each query of records grouped by query, with the settings
+    /// of this accumulator. Errors are passed through.
+    pub fn summarize<I>(&self, records: I) -> BreadthPerQuery<I::IntoIter>
+    where
+        I: IntoIterator<Item = Result<PafRecord>>,
+    {
+        BreadthPerQuery {
+            records: records.into_iter(),
+            breadth: QueryBreadth {
+                full_length: self.full_length,
+                current: None,
+            },
+        }
+    }
+
+    fn summary(&self, mut query: Query) -> BreadthSummary {
+        let full_length = |bases: u64| bases as f64 >= self.full_length * query.query_len as f64;
+        let longest = query
+            .intervals
+            .iter()
+            .map(|&(start, end)| end.saturating_sub(start))
+            .max()
+            .unwrap_or(0);
+
+        query.intervals.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::new();
+        for &(start, end) in query.intervals.iter().filter(|(start, end)| start < end) {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        let covered_bases = merged.iter().map(|(start, end)| end - start).sum();
+
+        let class = if full_length(longest) {
+            BreadthClass::FullLength
+        } else if full_length(covered_bases) {
+            BreadthClass::Split
+        } else if merged.len() == 1 {
+            BreadthClass::UnalignedEnds
+        } else {
+            BreadthClass::Partial
+        };
+        BreadthSummary {
+            covered_fraction: if query.query_len > 0 {
+                covered_bases as f64 / query.query_len as f64
+            } else {
+                0.0
+            },
+            query_name: query.query_name,
+            query_len: query.query_len,
+            covered_bases,
+            alignments: query.intervals.len() as u64,
+            targets: query.targets.len() as u64,
+            class,
+        }
+    }
+}
+
+impl Default for QueryBreadth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An iterator over the summary of each query. See
+/// [`QueryBreadth::summarize`].
+#[derive(Debug)]
+pub struct BreadthPerQuery<I> {
+    records: I,
+    breadth: QueryBreadth,
+}
+
+impl<I: Iterator<Item = Result<PafRecord>>> Iterator for BreadthPerQuery<I> {
+    type Item = Result<BreadthSummary>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.records.next() {
+                Some(Ok(record)) => match self.breadth.add(&record) {
+                    Ok(Some(summary)) => return Some(Ok(summary)),
+                    Ok(None) => {}
+                    Err(err) => return Some(Err(err)),
+                },
+                Some(Err(err)) => return Some(Err(err)),
+                None => return self.breadth.finish().map(Ok),
+            }
+        }
+    }
+}
+
+fn check_len(name: &str, len: u64, other: u64) -> Result<()> {
+    if len != other {
+        return Err(Error::new(ErrorKind::InvalidRecord(format!(
+            "target {} has lengths {} and {}",
+            name, len, other
+        ))));
+    }
+    Ok(())
+}
+
+fn unsorted(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidRecord(format!(
+        "coverage needs records sorted by target and target start, but {}",
+        msg
+    )))
+}
+
+/// The bases of each sequence covered by alignments, as sorted, disjoint
+/// intervals. See [`merged_intervals`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergedIntervals {
+    sequences: BTreeMap<String, Vec<(u64, u64)>>,
+}
+
+impl MergedIntervals {
+    /// The intervals of each sequence with any, by name.
+    pub fn as_map(&self) -> &BTreeMap<String, Vec<(u64, u64)>> {
+        &self.sequences
+    }
+
+    /// Take the intervals of each sequence with any, by name.
+    pub fn into_map(self) -> BTreeMap<String, Vec<(u64, u64)>> {
+        self.sequences
+    }
+
+    /// The intervals of the sequence `name`, empty if it has none.
+    pub fn intervals(&self, name: &str) -> &[(u64, u64)] {
+        self.sequences.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// The covered bases of the sequence `name`.
+    pub fn bases(&self, name: &str) -> u64 {
+        self.intervals(name)
+            .iter()
+            .map(|(start, end)| end - start)
+            .sum()
+    }
+
+    /// The covered bases of each sequence with any, in order of name.
+    pub fn bases_per_sequence(&self) -> impl Iterator<Item = (&str, u64)> + '_ {
+        self.sequences
+            .keys()
+            .map(|name| (name.as_str(), self.bases(name)))
+    }
+
+    /// The covered bases of all sequences.
+    pub fn total_bases(&self) -> u64 {
+        self.bases_per_sequence().map(|(_, bases)| bases).sum()
+    }
+
+    /// Writes the intervals as BED lines of name, start and end, in order
+    /// of name and then start.
+    pub fn write_bed<W: Write>(&self, writer: W) -> Result<()> {
+        let mut writer = BufWriter::new(writer);
+        for (name, intervals) in &self.sequences {
+            for (start, end) in intervals {
+                writeln!(writer, "{}\t{}\t{}", name, start, end)?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// The union of the target, or query, intervals of the records on each
+/// sequence.
+///
+/// Abutting intervals merge, and empty ones are dropped. All records count,
+/// so filter out secondary alignments first if they should not.
+///
+/// ```
+/// use paf::convert::Space;
+/// use paf::coverage::merged_intervals;
+/// use paf::Reader;
+///
+/// let paf = b"q1\t100\t0\t100\t+\tt\t1000\t0\t100\t100\t100\t60\n\
+///             q2\t100\t0\t100\t+\tt\t1000\t50\t150\t100\t100\t60\n\
+///             q3\t100\t0\t100\t+\tt\t1000\t500\t600\t100\t100\t60\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+/// let merged = merged_intervals(&records, Space::Target);
+/// assert_eq!(merged.intervals("t"), [(0, 150), (500, 600)]);
+/// assert_eq!(merged.total_bases(), 250);
+/// ```
+pub fn merged_intervals<I>(records: I, space: Space) -> MergedIntervals
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    merged_intervals_with(records, space, 1)
+}
+
+/// The intervals of each sequence covered by at least `min_depth` records,
+/// so that 2 gives the bases covered at least twice. Otherwise as for
+/// [`merged_intervals`]; a `min_depth` of 0 is taken as 1.
+pub fn merged_intervals_with<I>(records: I, space: Space, min_depth: u32) -> MergedIntervals
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    let mut events = DepthEvents::default();
+    for record in records {
+        events.add(record.borrow(), space);
+    }
+    MergedIntervals {
+        sequences: events.merge(min_depth),
+    }
+}
+
+/// The starts and ends of records on each sequence, as +1 and -1 changes
+/// in depth.
+#[derive(Debug, Default)]
+struct DepthEvents {
+    events: BTreeMap<String, Vec<(u64, i64)>>,
+}
+
+impl DepthEvents {
+    /// Adds the interval of a record, returning the name and length of the
+    /// sequence it is on.
+    fn add<'a>(&mut self, record: &'a PafRecord, space: Space) -> (&'a str, u64) {
+        let (name, len, start, end) = match space {
+            Space::Target => (
+                record.target_name(),
+                record.target_len(),
+                record.target_start(),
+                record.target_end(),
+            ),
+            Space::Query => (
+                record.query_name(),
+                record.query_len(),
+                record.query_start(),
+                record.query_end(),
+            ),
+        };
+        if start < end {
+            let changes = [(start as u64, 1), (end as u64, -1)];
+            match self.events.get_mut(name) {
+                Some(events) => events.extend(changes),
+                None => {
+                    self.events.insert(name.to_string(), changes.to_vec());
+                }
+            }
+        }
+        (name, len as u64)
+    }
+
+    /// The intervals of each sequence with any covered by at least
+    /// `min_depth` records.
+    fn merge(self, min_depth: u32) -> BTreeMap<String, Vec<(u64, u64)>> {
+        let min_depth = min_depth.max(1) as i64;
+        let mut sequences = BTreeMap::new();
+        for (name, mut events) in self.events {
+            events.sort_unstable();
+            let mut intervals = Vec::new();
+            let mut depth = 0;
+            let mut open = None;
+            for (i, &(pos, change)) in events.iter().enumerate() {
+                depth += change;
+                // the depth between positions, once all changes at one are
+                // made
+                if events.get(i + 1).is_some_and(|&(next, _)| next == pos) {
+                    continue;
+                }
+                match open {
+                    None if depth >= min_depth => open = Some(pos),
+                    Some(start) if depth < min_depth => {
+                        intervals.push((start, pos));
+                        open = None;
+                    }
+                    _ => {}
+                }
+            }
+            if !intervals.is_empty() {
+                sequences.insert(name, intervals);
+            }
+        }
+        sequences
+    }
+}
+
+/// Where [`uncovered`] takes the length of each sequence from.
+#[derive(Debug, Clone, Copy)]
+pub enum SequenceLengths<'a> {
+    /// The lengths in the records, columns 2 and 7.
+    Records,
+    /// These lengths, e.g. from a FASTA index, so that sequences without
+    /// alignments are wholly uncovered, and the lengths in the records for
+    /// sequences not given.
+    Given(&'a HashMap<String, u64>),
+}
+
+/// The bases of each sequence not covered by any alignment, as sorted,
+/// disjoint intervals. See [`uncovered`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UncoveredIntervals {
+    sequences: BTreeMap<String, Vec<(u64, u64)>>,
+    lengths: BTreeMap<String, u64>,
+    inconsistent: BTreeMap<String, Vec<u64>>,
+}
+
+impl UncoveredIntervals {
+    /// The intervals of each sequence, by name, empty for sequences covered
+    /// end to end.
+    pub fn as_map(&self) -> &BTreeMap<String, Vec<(u64, u64)>> {
+        &self.sequences
+    }
+
+    /// Take the intervals of each sequence, by name.
+    pub fn into_map(self) -> BTreeMap<String, Vec<(u64, u64)>> {
+        self.sequences
+    }
+
+    /// The intervals of the sequence `name`, empty if it has none or is
+    /// not known.
+    pub fn intervals(&self, name: &str) -> &[(u64, u64)] {
+        self.sequences.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// The length taken for the sequence `name`.
+    pub fn length(&self, name: &str) -> Option<u64> {
+        self.lengths.get(name).copied()
+    }
+
+    /// The uncovered bases of the sequence `name`.
+    pub fn bases(&self, name: &str) -> u64 {
+        self.intervals(name)
+            .iter()
+            .map(|(start, end)| end - start)
+            .sum()
+    }
+
+    /// The uncovered bases of all sequences.
+    pub fn total_bases(&self) -> u64 {
+        self.sequences.keys().map(|name| self.bases(name)).sum()
+    }
+
+    /// The length of all sequences.
+    pub fn total_len(&self) -> u64 {
+        self.lengths.values().sum()
+    }
+
+    /// The sequences given more than one length, with each length, in
+    /// order. The longest is taken, unless one was given.
+    pub fn inconsistent_lengths(&self) -> &BTreeMap<String, Vec<u64>> {
+        &self.inconsistent
+    }
+
+    /// Writes the intervals as BED lines of name, start and end, in order
+    /// of name and then start.
+    pub fn write_bed<W: Write>(&self, writer: W) -> Result<()> {
+        let mut writer = BufWriter::new(writer);
+        for (name, intervals) in &self.sequences {
+            for (start, end) in intervals {
+                writeln!(writer, "{}\t{}\t{}", name, start, end)?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// The intervals of each target, or query, not covered by the records, as
+/// for finding assembly gaps and unaligned regions of a reference.
+///
+/// Every sequence the records are on in `space` is reported, and with
+/// [`SequenceLengths::Given`] every sequence given too. Where the records
+/// give a sequence different lengths, the longest is taken and the
+/// sequence is reported among the
+/// [`inconsistent_lengths`](UncoveredIntervals::inconsistent_lengths), as
+/// is a sequence whose given length differs from those in the records.
+///
+/// ```
+/// use paf::convert::Space;
+/// use paf::coverage::{uncovered, SequenceLengths};
+/// use paf::Reader;
+///
+/// let paf = b"q1\t100\t0\t100\t+\tt\t1000\t100\t200\t100\t100\t60\n\
+///             q2\t100\t0\t100\t+\tt\t1000\t500\t600\t100\t100\t60\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+/// let gaps = uncovered(&records, SequenceLengths::Records, Space::Target);
+/// assert_eq!(gaps.intervals("t"), [(0, 100), (200, 500), (600, 1000)]);
+/// assert_eq!(gaps.total_bases(), 800);
+/// ```
+pub fn uncovered<I>(records: I, lengths: SequenceLengths, space: Space) -> UncoveredIntervals
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    let mut events = DepthEvents::default();
+    let mut seen: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+    for record in records {
+        let (name, len) = events.add(record.borrow(), space);
+        match seen.get_mut(name) {
+            Some(lens) => {
+                if !lens.contains(&len) {
+                    lens.push(len);
+                }
+            }
+            None => {
+                seen.insert(name.to_string(), vec![len]);
+            }
+        }
+    }
+    if let SequenceLengths::Given(given) = lengths {
+        for (name, &len) in given {
+            let lens = seen.entry(name.clone()).or_default();
+            if !lens.contains(&len) {
+                lens.push(len);
+            }
+        }
+    }
+
+    let covered = events.merge(1);
+    let mut result = UncoveredIntervals::default();
+    for (name, mut lens) in seen {
+        let len = match lengths {
+            SequenceLengths::Given(given) if given.contains_key(&name) => given[&name],
+            _ => lens.iter().copied().max().unwrap_or(0),
+        };
+        let mut intervals = Vec::new();
+        let mut pos = 0;
+        for &(start, end) in covered.get(&name).map_or(&[][..], Vec::as_slice) {
+            if start.min(len) > pos {
+                intervals.push((pos, start.min(len)));
+            }
+            pos = pos.max(end);
+        }
+        if pos < len {
+            intervals.push((pos, len));
+        }
+        if lens.len() > 1 {
+            lens.sort_unstable();
+            result.inconsistent.insert(name.clone(), lens);
+        }
+        result.lengths.insert(name.clone(), len);
+        result.sequences.insert(name, intervals);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+    use crate::Reader;
+
+    /// A record on target `t` of length 20, or on `u` of length 10.
+    fn record(target: &str, start: u32, end: u32, tp: &str) -> String {
+        let len = if target == "t" { 20 } else { 10 };
+        format!(
+            "q\t100\t0\t{}\t+\t{}\t{}\t{}\t{}\t{}\t{}\t60\ttp:A:{}\n",
+            end - start,
+            target,
+            len,
+            start,
+            end,
+            end - start,
+            end - start,
+            tp
+        )
+    }
+
+    fn coverage(mut coverage: TargetCoverage, paf: &[String]) -> Result<Vec<CoverageSummary>> {
+        for record in Reader::from_reader(paf.concat().as_bytes()).records() {
+            coverage.add(&record?)?;
+        }
+        Ok(coverage.finish())
+    }
+
+    fn run(start: u64, end: u64, depth: u32) -> DepthRun {
+        DepthRun { start, end, depth }
+    }
+
+    #[test]
+    fn test_overlapping_nested_abutting() {
+        // depth along t:
+        //   0..2  0
+        //   2..5  1  a
+        //   5..6  2  a b
+        //   6..8  3  a b c, c nested in b
+        //   8..10 2  a b
+        //  10..12 1  b
+        //  12..15 1  d, abutting b
+        //  15..20 0
+        let paf = [
+            record("t", 2, 10, "P"),
+            record("t", 5, 12, "P"),
+            record("t", 6, 8, "P"),
+            record("t", 12, 15, "P"),
+        ];
+        let mut builder = TargetCoverage::new();
+        builder.thresholds(&[2, 3, 4]).track(Track::RunLength);
+        let targets = coverage(builder, &paf).unwrap();
+        assert_eq!(targets.len(), 1);
+        let t = &targets[0];
+        assert_eq!(t.target_name, "t");
+        assert_eq!(t.target_len, 20);
+        assert_eq!(t.covered_bases, 13);
+        assert_eq!(t.covered_at, vec![(2, 5), (3, 2), (4, 0)]);
+        // 8 + 7 + 2 + 3 aligned bases
+        assert_eq!(t.mean_depth, 1.0);
+        assert_eq!(
+            t.track,
+            Some(DepthTrack::RunLength(vec![
+                run(0, 2, 0),
+                run(2, 5, 1),
+                run(5, 6, 2),
+                run(6, 8, 3),
+                run(8, 10, 2),
+                run(10, 15, 1),
+                run(15, 20, 0),
+            ]))
+        );
+
+        let mut builder = TargetCoverage::new();
+        builder.track(Track::Full);
+        let targets = coverage(builder, &paf).unwrap();
+        assert_eq!(
+            targets[0].track,
+            Some(DepthTrack::Full(vec![
+                0, 0, 1, 1, 1, 2, 3, 3, 2, 2, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_unsorted() {
+        let paf = [
+            record("u", 0, 10, "P"),
+            record("t", 12, 15, "P"),
+            record("t", 6, 8, "P"),
+            record("u", 4, 6, "P"),
+            record("t", 5, 12, "P"),
+            record("t", 2, 10, "P"),
+        ];
+        assert!(coverage(TargetCoverage::new(), &paf).is_err());
+
+        let mut builder = TargetCoverage::unsorted();
+        builder.thresholds(&[2, 3]);
+        let targets = coverage(builder, &paf).unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].target_name, "u");
+        assert_eq!(targets[0].covered_bases, 10);
+        assert_eq!(targets[0].covered_at, vec![(2, 2), (3, 0)]);
+        assert_eq!(targets[0].mean_depth, 1.2);
+        assert_eq!(targets[1].target_name, "t");
+        assert_eq!(targets[1].covered_bases, 13);
+        assert_eq!(targets[1].covered_at, vec![(2, 5), (3, 2)]);
+        assert_eq!(targets[1].track, None);
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        let paf = [
+            record("t", 0, 5, "P"),
+            record("u", 0, 5, "P"),
+            record("t", 6, 8, "P"),
+        ];
+        assert!(coverage(TargetCoverage::new(), &paf).is_err());
+        let paf = [record("t", 5, 8, "P"), record("t", 4, 8, "P")];
+        assert!(coverage(TargetCoverage::new(), &paf).is_err());
+    }
+
+    #[test]
+    fn test_exclude_secondary() {
+        let paf = [
+            record("t", 0, 10, "P"),
+            record("t", 5, 15, "S"),
+            record("t", 8, 12, "i"),
+        ];
+        let targets = coverage(TargetCoverage::new(), &paf).unwrap();
+        assert_eq!(targets[0].covered_bases, 15);
+
+        let mut builder = TargetCoverage::new();
+        builder.exclude_secondary(true);
+        let targets = coverage(builder, &paf).unwrap();
+        assert_eq!(targets[0].covered_bases, 10);
+        assert_eq!(targets[0].mean_depth, 0.5);
+    }
+
+    fn breadth(paf: &str, full_length: f64) -> Vec<BreadthSummary> {
+        QueryBreadth::new()
+            .full_length(full_length)
+            .summarize(Reader::from_reader(paf.as_bytes()).records())
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn test_breadth_merges_query_overlaps() {
+        // 10..40 and 30..50 merge to 10..50, nested 15..25 adds nothing,
+        // and 60..70 is apart
+        let paf = "\
+q\t100\t10\t40\t+\tt\t1000\t0\t30\t30\t30\t60\n\
+q\t100\t30\t50\t+\tt\t1000\t500\t520\t20\t20\t60\n\
+q\t100\t15\t25\t+\tu\t1000\t0\t10\t10\t10\t60\n\
+q\t100\t60\t70\t+\tt\t1000\t900\t910\t10\t10\t60\n";
+        let queries = breadth(paf, 0.95);
+        assert_eq!(queries.len(), 1);
+        let q = &queries[0];
+        assert_eq!(q.query_name, "q");
+        assert_eq!(q.covered_bases, 50);
+        assert_eq!(q.covered_fraction, 0.5);
+        assert_eq!(q.alignments, 4);
+        assert_eq!(q.targets, 2);
+        assert_eq!(q.class, BreadthClass::Partial);
+
+        // a query interval ending before it starts counts as an alignment
+        // covering nothing
+        let paf = "\
+q\t100\t0\t40\t+\tt\t1000\t0\t40\t40\t40\t60\n\
+q\t100\t60\t20\t+\tt\t1000\t100\t140\t40\t40\t60\n";
+        let q = &breadth(paf, 0.95)[0];
+        assert_eq!((q.covered_bases, q.alignments), (40, 2));
+        assert_eq!(q.class, BreadthClass::UnalignedEnds);
+    }
+
+    #[test]
+    fn test_breadth_minus_strand() {
+        // query coordinates are on the forward query whatever the strand,
+        // so these abut at 40 rather than overlapping
+        let paf = "\
+q\t100\t0\t40\t-\tt\t1000\t0\t40\t40\t40\t60\n\
+q\t100\t40\t97\t+\tt\t1000\t100\t157\t57\t57\t60\n";
+        let queries = breadth(paf, 0.95);
+        assert_eq!(queries[0].covered_bases, 97);
+        assert_eq!(queries[0].targets, 1);
+        assert_eq!(queries[0].class, BreadthClass::Split);
+    }
+
+    #[test]
+    fn test_breadth_classes() {
+        let paf = "\
+full\t100\t2\t98\t-\tt\t1000\t0\t96\t96\t96\t60\n\
+full\t100\t10\t50\t+\tu\t1000\t0\t40\t40\t40\t60\n\
+ends\t100\t20\t50\t+\tt\t1000\t0\t30\t30\t30\t60\n\
+ends\t100\t40\t80\t+\tt\t1000\t100\t140\t40\t40\t60\n\
+partial\t100\t0\t40\t+\tt\t1000\t0\t40\t40\t40\t60\n\
+partial\t100\t50\t100\t+\tt\t1000\t100\t150\t50\t50\t60\n";
+        let classes: Vec<_> = breadth(paf, 0.95)
+            .into_iter()
+            .map(|q| (q.query_name, q.covered_bases, q.class))
+            .collect();
+        assert_eq!(
+            classes,
+            vec![
+                ("full".to_string(), 96, BreadthClass::FullLength),
+                ("ends".to_string(), 60, BreadthClass::UnalignedEnds),
+                ("partial".to_string(), 90, BreadthClass::Partial),
+            ]
+        );
+        // with a lower threshold the gapped query is split
+        assert_eq!(breadth(paf, 0.9)[2].class, BreadthClass::Split);
+    }
+
+    #[test]
+    fn test_breadth_accumulator() {
+        let paf = "\
+a\t100\t0\t100\t+\tt\t1000\t0\t100\t100\t100\t60\n\
+b\t50\t0\t50\t+\tt\t1000\t0\t50\t50\t50\t60\n\
+b\t60\t0\t50\t+\tt\t1000\t0\t50\t50\t50\t60\n";
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        let mut records = reader.records().map(Result::unwrap);
+        let mut breadth = QueryBreadth::new();
+        assert_eq!(breadth.add(&records.next().unwrap()).unwrap(), None);
+        let a = breadth.add(&records.next().unwrap()).unwrap().unwrap();
+        assert_eq!(a.query_name, "a");
+        assert_eq!(a.class, BreadthClass::FullLength);
+        assert!(breadth.add(&records.next().unwrap()).is_err());
+        assert_eq!(breadth.finish().unwrap().query_name, "b");
+        assert_eq!(breadth.finish(), None);
+    }
+
+    fn merged(intervals: &[(&str, u32, u32)], min_depth: u32) -> MergedIntervals {
+        let paf: String = intervals
+            .iter()
+            .map(|&(name, start, end)| {
+                format!(
+                    "q\t1000\t{}\t{}\t+\t{}\t1000\t{}\t{}\t1\t1\t60\n",
+                    start, end, name, start, end
+                )
+            })
+            .collect();
+        let records = records(&paf);
+        merged_intervals_with(&records, Space::Target, min_depth)
+    }
+
+    #[test]
+    fn test_merged_intervals() {
+        // on t: 10..50 holds 20..30, which holds 22..25; 60..70 and 70..80
+        // abut; 100..100 is empty and 200..210 apart. On u: 5..15.
+        let intervals = [
+            ("t", 20, 30),
+            ("t", 10, 50),
+            ("t", 70, 80),
+            ("t", 22, 25),
+            ("t", 60, 70),
+            ("u", 5, 15),
+            ("t", 100, 100),
+            ("t", 200, 210),
+        ];
+        let once = merged(&intervals, 1);
+        assert_eq!(once.intervals("t"), [(10, 50), (60, 80), (200, 210)]);
+        assert_eq!(once.intervals("u"), [(5, 15)]);
+        assert!(once.intervals("v").is_empty());
+        assert_eq!(once.bases("t"), 70);
+        assert_eq!(
+            once.bases_per_sequence().collect::<Vec<_>>(),
+            [("t", 70), ("u", 10)]
+        );
+        assert_eq!(once.total_bases(), 80);
+
+        let mut bed = Vec::new();
+        once.write_bed(&mut bed).unwrap();
+        assert_eq!(
+            String::from_utf8(bed).unwrap(),
+            "t\t10\t50\nt\t60\t80\nt\t200\t210\nu\t5\t15\n"
+        );
+
+        // covered at least twice: the nested 20..30, as the chain only
+        // abuts; three times: 22..25
+        let twice = merged(&intervals, 2);
+        assert_eq!(twice.as_map().len(), 1);
+        assert_eq!(twice.intervals("t"), [(20, 30)]);
+        assert_eq!(merged(&intervals, 3).intervals("t"), [(22, 25)]);
+        assert!(merged(&intervals, 4).as_map().is_empty());
+    }
+
+    #[test]
+    fn test_merged_chain_at_depth() {
+        // overlapping pairs in a chain: twice covered at 15..20 and 25..30,
+        // and 20..25 where one ends as the next starts
+        let intervals = [("t", 10, 20), ("t", 15, 25), ("t", 20, 30), ("t", 25, 35)];
+        assert_eq!(merged(&intervals, 1).intervals("t"), [(10, 35)]);
+        assert_eq!(merged(&intervals, 2).intervals("t"), [(15, 30)]);
+        assert_eq!(merged(&intervals, 0), merged(&intervals, 1));
+
+        // on the query
+        let paf = "q\t100\t0\t10\t+\tt\t100\t50\t60\t10\t10\t60\n\
+                   q\t100\t10\t20\t+\tt\t100\t0\t10\t10\t10\t60\n";
+        let records = records(paf);
+        let merged = merged_intervals(&records, Space::Query);
+        assert_eq!(
+            merged.into_map().into_iter().collect::<Vec<_>>(),
+            [("q".to_string(), vec![(0, 20)])]
+        );
+    }
+
+    #[test]
+    fn test_uncovered() {
+        // t1 is covered end to end, t2 at 100..200 and 150..300; q2 only
+        // ever appears as a query
+        let paf = "\
+q1\t500\t0\t500\t+\tt1\t500\t0\t500\t500\t500\t60
+q2\t400\t0\t100\t+\tt2\t1000\t100\t200\t100\t100\t60
+q1\t500\t0\t150\t-\tt2\t1000\t150\t300\t150\t150\t60
+";
+        let records = records(paf);
+        let targets = uncovered(&records, SequenceLengths::Records, Space::Target);
+        assert_eq!(targets.intervals("t1"), []);
+        assert!(targets.as_map().contains_key("t1"));
+        assert_eq!(targets.intervals("t2"), [(0, 100), (300, 1000)]);
+        assert_eq!(targets.intervals("q2"), []);
+        assert!(!targets.as_map().contains_key("q2"));
+        assert_eq!(targets.total_bases(), 800);
+        assert_eq!(targets.total_len(), 1500);
+        assert!(targets.inconsistent_lengths().is_empty());
+
+        let queries = uncovered(&records, SequenceLengths::Records, Space::Query);
+        assert_eq!(queries.intervals("q2"), [(100, 400)]);
+        assert_eq!(queries.intervals("q1"), []);
+        let mut bed = Vec::new();
+        queries.write_bed(&mut bed).unwrap();
+        assert_eq!(String::from_utf8(bed).unwrap(), "q2\t100\t400\n");
+    }
+
+    #[test]
+    fn test_uncovered_lengths() {
+        // the records disagree on the length of t
+        let paf = "\
+q\t100\t0\t100\t+\tt\t1000\t0\t100\t100\t100\t60
+q\t100\t0\t100\t+\tt\t1200\t500\t600\t100\t100\t60
+";
+        let records = records(paf);
+        let gaps = uncovered(&records, SequenceLengths::Records, Space::Target);
+        assert_eq!(gaps.intervals("t"), [(100, 500), (600, 1200)]);
+        assert_eq!(gaps.length("t"), Some(1200));
+        assert_eq!(gaps.inconsistent_lengths()["t"], [1000, 1200]);
+
+        // given lengths win, and sequences without records are uncovered
+        let given: HashMap<String, u64> = [("t".to_string(), 1000), ("u".to_string(), 50)]
+            .into_iter()
+            .collect();
+        let gaps = uncovered(&records, SequenceLengths::Given(&given), Space::Target);
+        assert_eq!(gaps.intervals("t"), [(100, 500), (600, 1000)]);
+        assert_eq!(gaps.intervals("u"), [(0, 50)]);
+        assert_eq!(gaps.inconsistent_lengths().len(), 1);
+    }
+}
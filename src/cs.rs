@@ -0,0 +1,614 @@
+use std::fmt;
+
+use crate::{Cigar, CigarOp, Error, ErrorKind, PafRecord, Result};
+
+/// A single operation of a cs difference string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CsOp {
+    /// A run of identical bases, short form (`:10`).
+    Identity(u32),
+    /// A run of identical bases, long form (`=ACGT`).
+    Match(String),
+    /// A substitution (`*ag`) of the reference base `from` by the query base `to`.
+    Mismatch { from: char, to: char },
+    /// Bases present in the query but not the reference (`+ac`).
+    Insertion(String),
+    /// Bases present in the reference but not the query (`-gt`).
+    Deletion(String),
+    /// An intron (`~gt10ag`), with its donor and acceptor sites and length.
+    Splice {
+        donor: String,
+        len: u32,
+        acceptor: String,
+    },
+}
+
+impl CsOp {
+    /// The number of reference bases consumed by this operation.
+    pub fn target_len(&self) -> u32 {
+        match self {
+            CsOp::Identity(n) => *n,
+            CsOp::Match(s) | CsOp::Deletion(s) => s.len() as u32,
+            CsOp::Mismatch { .. } => 1,
+            CsOp::Insertion(_) => 0,
+            CsOp::Splice { len, .. } => *len,
+        }
+    }
+
+    /// The number of query bases consumed by this operation.
+    pub fn query_len(&self) -> u32 {
+        match self {
+            CsOp::Identity(n) => *n,
+            CsOp::Match(s) | CsOp::Insertion(s) => s.len() as u32,
+            CsOp::Mismatch { .. } => 1,
+            CsOp::Deletion(_) | CsOp::Splice { .. } => 0,
+        }
+    }
+}
+
+impl fmt::Display for CsOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsOp::Identity(n) => write!(f, ":{}", n),
+            CsOp::Match(s) => write!(f, "={}", s),
+            CsOp::Mismatch { from, to } => write!(f, "*{}{}", from, to),
+            CsOp::Insertion(s) => write!(f, "+{}", s),
+            CsOp::Deletion(s) => write!(f, "-{}", s),
+            CsOp::Splice {
+                donor,
+                len,
+                acceptor,
+            } => write!(f, "~{}{}{}", donor, len, acceptor),
+        }
+    }
+}
+
+/// Totals of each kind of operation in a cs string.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CsCounts {
+    /// Number of identical bases, from both short and long form runs.
+    pub matches: u64,
+    /// Number of substitutions.
+    pub mismatches: u64,
+    /// Number of insertion events.
+    pub insertions: u64,
+    /// Number of inserted bases.
+    pub inserted_bases: u64,
+    /// Number of deletion events.
+    pub deletions: u64,
+    /// Number of deleted bases.
+    pub deleted_bases: u64,
+    /// Number of introns.
+    pub introns: u64,
+    /// Number of intronic bases.
+    pub intron_bases: u64,
+}
+
+/// The kind of a small variant called from a cs string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VariantKind {
+    /// A single base substitution.
+    Snv,
+    /// Bases present in the query but not the target.
+    Insertion,
+    /// Bases present in the target but not the query.
+    Deletion,
+}
+
+/// A small variant of the query against the target.
+///
+/// Alleles are upper case and on the forward strand of the target. The
+/// unused allele of an indel is empty: an insertion sits before
+/// `target_pos`, and a deletion removes the bases from `target_pos` on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// Name of the target sequence.
+    pub target_name: String,
+    /// 0-based position on the target.
+    pub target_pos: u64,
+    /// The target allele.
+    pub ref_allele: String,
+    /// The query allele.
+    pub alt_allele: String,
+    /// The kind of variant.
+    pub kind: VariantKind,
+}
+
+/// A parsed cs difference string.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CsString {
+    ops: Vec<CsOp>,
+}
+
+/// Build a cs parse error at the given byte offset.
+fn cs_error(offset: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::ReadRecord(format!(
+        "Invalid cs string at byte {}: {}",
+        offset, msg
+    )))
+}
+
+impl CsString {
+    /// Create a cs string from a list of operations.
+    pub fn new(ops: Vec<CsOp>) -> Self {
+        CsString { ops }
+    }
+
+    /// Parse a cs string, in either short or long form.
+    pub fn parse(cs: &str) -> Result<Self> {
+        let bytes = cs.as_bytes();
+        let mut ops = Vec::new();
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let start = i;
+            let op = bytes[i];
+            i += 1;
+            match op {
+                b':' => {
+                    let end = i + bytes[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+                    if end == i {
+                        return Err(cs_error(start, "identity run without a length"));
+                    }
+                    let n = cs[i..end].parse::<u32>()?;
+                    if n == 0 {
+                        return Err(cs_error(start, "zero length identity run"));
+                    }
+                    ops.push(CsOp::Identity(n));
+                    i = end;
+                }
+                b'=' | b'+' | b'-' => {
+                    let end = i + bytes[i..]
+                        .iter()
+                        .take_while(|b| b.is_ascii_alphabetic())
+                        .count();
+                    if end == i {
+                        return Err(cs_error(start, "operation without bases"));
+                    }
+                    let seq = cs[i..end].to_string();
+                    ops.push(match op {
+                        b'=' => CsOp::Match(seq),
+                        b'+' => CsOp::Insertion(seq),
+                        _ => CsOp::Deletion(seq),
+                    });
+                    i = end;
+                }
+                b'*' => {
+                    if i + 2 > bytes.len()
+                        || !bytes[i].is_ascii_alphabetic()
+                        || !bytes[i + 1].is_ascii_alphabetic()
+                    {
+                        return Err(cs_error(start, "substitution needs two bases"));
+                    }
+                    ops.push(CsOp::Mismatch {
+                        from: bytes[i] as char,
+                        to: bytes[i + 1] as char,
+                    });
+                    i += 2;
+                }
+                b'~' => {
+                    let digits_start = i + 2;
+                    let digits_end = digits_start
+                        + bytes
+                            .get(digits_start..)
+                            .unwrap_or_default()
+                            .iter()
+                            .take_while(|b| b.is_ascii_digit())
+                            .count();
+                    if digits_start > bytes.len()
+                        || !bytes[i..digits_start].iter().all(u8::is_ascii_alphabetic)
+                        || digits_end == digits_start
+                        || digits_end + 2 > bytes.len()
+                        || !bytes[digits_end..digits_end + 2]
+                            .iter()
+                            .all(u8::is_ascii_alphabetic)
+                    {
+                        return Err(cs_error(start, "malformed intron"));
+                    }
+                    ops.push(CsOp::Splice {
+                        donor: cs[i..digits_start].to_string(),
+                        len: cs[digits_start..digits_end].parse::<u32>()?,
+                        acceptor: cs[digits_end..digits_end + 2].to_string(),
+                    });
+                    i = digits_end + 2;
+                }
+                _ => {
+                    return Err(cs_error(
+                        start,
+                        &format!("unknown operation {:?}", op as char),
+                    ))
+                }
+            }
+        }
+
+        Ok(CsString { ops })
+    }
+
+    /// An iterator over the operations.
+    pub fn ops(&self) -> std::slice::Iter<'_, CsOp> {
+        self.ops.iter()
+    }
+
+    /// The number of operations.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Whether there are no operations.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Whether the string is in long form, spelling out identical bases
+    /// (`=ACGT`) rather than counting them (`:4`).
+    pub fn is_long_form(&self) -> bool {
+        !self.ops.iter().any(|op| matches!(op, CsOp::Identity(_)))
+    }
+
+    /// The string in short form, with identical bases counted.
+    pub fn to_short(&self) -> CsString {
+        let mut ops: Vec<CsOp> = Vec::with_capacity(self.ops.len());
+        for op in &self.ops {
+            let n = match op {
+                CsOp::Identity(n) => *n,
+                CsOp::Match(s) => s.len() as u32,
+                op => {
+                    ops.push(op.clone());
+                    continue;
+                }
+            };
+            match ops.last_mut() {
+                Some(CsOp::Identity(last)) => *last += n,
+                _ => ops.push(CsOp::Identity(n)),
+            }
+        }
+        CsString { ops }
+    }
+
+    /// The string in long form, with identical bases spelled out.
+    ///
+    /// `target_seq` is the aligned part of the target, from the start of the
+    /// alignment on its forward strand. Substituted and deleted bases are
+    /// checked against it, so a wrong sequence is an error rather than a
+    /// silently wrong string.
+    pub fn to_long(&self, target_seq: &[u8]) -> Result<CsString> {
+        let mut ops = Vec::with_capacity(self.ops.len());
+        let mut pos = 0;
+        for op in &self.ops {
+            let len = op.target_len() as usize;
+            let bases = target_seq.get(pos..pos + len).ok_or_else(|| {
+                Error::new(ErrorKind::InvalidRecord(format!(
+                    "target sequence of length {} is shorter than the cs string",
+                    target_seq.len()
+                )))
+            })?;
+            let expected = match op {
+                CsOp::Mismatch { from, .. } => Some(from.to_string()),
+                CsOp::Deletion(s) => Some(s.clone()),
+                _ => None,
+            };
+            if let Some(expected) = expected {
+                if !expected.as_bytes().eq_ignore_ascii_case(bases) {
+                    return Err(Error::new(ErrorKind::InvalidRecord(format!(
+                        "cs has {} at target offset {} but the sequence has {}",
+                        expected,
+                        pos,
+                        String::from_utf8_lossy(bases)
+                    ))));
+                }
+            }
+            match op {
+                CsOp::Identity(_) => {
+                    let bases = String::from_utf8_lossy(bases).to_ascii_uppercase();
+                    match ops.last_mut() {
+                        Some(CsOp::Match(last)) => last.push_str(&bases),
+                        _ => ops.push(CsOp::Match(bases)),
+                    }
+                }
+                op => ops.push(op.clone()),
+            }
+            pos += len;
+        }
+        Ok(CsString { ops })
+    }
+
+    /// The CIGAR of the alignment, with matches and substitutions as `M`
+    /// and introns as `N`.
+    pub(crate) fn to_cigar(&self) -> Cigar {
+        let mut ops: Vec<(u32, CigarOp)> = Vec::with_capacity(self.ops.len());
+        for op in &self.ops {
+            let (len, op) = match op {
+                CsOp::Identity(_) | CsOp::Match(_) | CsOp::Mismatch { .. } => {
+                    (op.query_len(), CigarOp::Match)
+                }
+                CsOp::Insertion(s) => (s.len() as u32, CigarOp::Insertion),
+                CsOp::Deletion(s) => (s.len() as u32, CigarOp::Deletion),
+                CsOp::Splice { len, .. } => (*len, CigarOp::Skip),
+            };
+            match ops.last_mut() {
+                Some((last, last_op)) if *last_op == op => *last += len,
+                _ => ops.push((len, op)),
+            }
+        }
+        Cigar::new(ops)
+    }
+
+    /// Count the bases and events of each kind of operation.
+    pub fn counts(&self) -> CsCounts {
+        let mut counts = CsCounts::default();
+        for op in &self.ops {
+            match op {
+                CsOp::Identity(n) => counts.matches += *n as u64,
+                CsOp::Match(s) => counts.matches += s.len() as u64,
+                CsOp::Mismatch { .. } => counts.mismatches += 1,
+                CsOp::Insertion(s) => {
+                    counts.insertions += 1;
+                    counts.inserted_bases += s.len() as u64;
+                }
+                CsOp::Deletion(s) => {
+                    counts.deletions += 1;
+                    counts.deleted_bases += s.len() as u64;
+                }
+                CsOp::Splice { len, .. } => {
+                    counts.introns += 1;
+                    counts.intron_bases += *len as u64;
+                }
+            }
+        }
+        counts
+    }
+}
+
+impl<'a> IntoIterator for &'a CsString {
+    type Item = &'a CsOp;
+    type IntoIter = std::slice::Iter<'a, CsOp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ops.iter()
+    }
+}
+
+impl fmt::Display for CsString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for op in &self.ops {
+            write!(f, "{}", op)?;
+        }
+        Ok(())
+    }
+}
+
+impl PafRecord {
+    /// Parse the cs tag, if present.
+    pub fn cs_parsed(&self) -> Option<Result<CsString>> {
+        self.cs_checked()
+            .transpose()
+            .map(|cs| cs.and_then(CsString::parse))
+    }
+
+    /// Parse the cs tag, or fail naming what needed it.
+    fn require_cs(&self, what: &str) -> Result<CsString> {
+        self.cs_parsed().ok_or_else(|| {
+            Error::new(ErrorKind::InvalidRecord(format!("{} needs a cs tag", what)))
+        })?
+    }
+
+    /// Call substitutions, insertions and deletions from the cs tag, in
+    /// target order, like `paftools.js call`.
+    ///
+    /// minimap2 writes the cs string against the forward strand of the
+    /// target, with the query already reverse complemented for minus strand
+    /// records, so the alleles need no complementing on either strand.
+    pub fn variants(&self) -> Result<Vec<Variant>> {
+        let cs = self.require_cs("variant calling")?;
+        let mut variants = Vec::new();
+        let mut pos = self.target_start() as u64;
+        for op in &cs {
+            let (kind, ref_allele, alt_allele) = match op {
+                CsOp::Mismatch { from, to } => (
+                    VariantKind::Snv,
+                    from.to_ascii_uppercase().to_string(),
+                    to.to_ascii_uppercase().to_string(),
+                ),
+                CsOp::Insertion(bases) => (
+                    VariantKind::Insertion,
+                    String::new(),
+                    bases.to_ascii_uppercase(),
+                ),
+                CsOp::Deletion(bases) => (
+                    VariantKind::Deletion,
+                    bases.to_ascii_uppercase(),
+                    String::new(),
+                ),
+                _ => {
+                    pos += op.target_len() as u64;
+                    continue;
+                }
+            };
+            variants.push(Variant {
+                target_name: self.target_name().to_string(),
+                target_pos: pos,
+                ref_allele,
+                alt_allele,
+                kind,
+            });
+            pos += op.target_len() as u64;
+        }
+        Ok(variants)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_short_form() {
+        let cs = CsString::parse(":10*ag+ac:5-gt:3").unwrap();
+        assert_eq!(
+            cs.ops().cloned().collect::<Vec<_>>(),
+            vec![
+                CsOp::Identity(10),
+                CsOp::Mismatch { from: 'a', to: 'g' },
+                CsOp::Insertion("ac".into()),
+                CsOp::Identity(5),
+                CsOp::Deletion("gt".into()),
+                CsOp::Identity(3),
+            ]
+        );
+        assert_eq!(cs.to_string(), ":10*ag+ac:5-gt:3");
+
+        let counts = cs.counts();
+        assert_eq!(counts.matches, 18);
+        assert_eq!(counts.mismatches, 1);
+        assert_eq!(counts.insertions, 1);
+        assert_eq!(counts.inserted_bases, 2);
+        assert_eq!(counts.deletions, 1);
+        assert_eq!(counts.deleted_bases, 2);
+    }
+
+    #[test]
+    fn test_parse_long_form() {
+        let cs = CsString::parse("=ACGT*ag=TT").unwrap();
+        assert_eq!(cs.len(), 3);
+        assert_eq!(cs.counts().matches, 6);
+        assert_eq!(cs.to_string(), "=ACGT*ag=TT");
+    }
+
+    #[test]
+    fn test_parse_splice() {
+        let cs = CsString::parse(":20~gt1200ag:30").unwrap();
+        assert_eq!(
+            cs.ops().nth(1),
+            Some(&CsOp::Splice {
+                donor: "gt".into(),
+                len: 1200,
+                acceptor: "ag".into(),
+            })
+        );
+        assert_eq!(cs.counts().introns, 1);
+        assert_eq!(cs.counts().intron_bases, 1200);
+        assert_eq!(cs.to_string(), ":20~gt1200ag:30");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(CsString::parse(":").is_err());
+        assert!(CsString::parse(":0").is_err());
+        assert!(CsString::parse("*a").is_err());
+        assert!(CsString::parse("+").is_err());
+        assert!(CsString::parse("~gt12a").is_err());
+        let err = CsString::parse(":10?").unwrap_err();
+        assert!(err.to_string().contains("byte 3"));
+    }
+
+    #[test]
+    fn test_to_cigar() {
+        let cs = CsString::parse(":3*ag=TT+ac:5-gt~gt100ag:3").unwrap();
+        assert_eq!(cs.to_cigar().to_string(), "6M2I5M2D100N3M");
+    }
+
+    #[test]
+    fn test_to_short() {
+        let long = CsString::parse("=ACGT*ag=TT+c=G").unwrap();
+        assert!(long.is_long_form());
+        let short = long.to_short();
+        assert!(!short.is_long_form());
+        assert_eq!(short.to_string(), ":4*ag:2+c:1");
+        assert_eq!(short.counts(), long.counts());
+        assert_eq!(short.to_short(), short);
+        // adjacent runs of either form merge
+        let mixed = CsString::new(vec![CsOp::Identity(3), CsOp::Match("AC".into())]);
+        assert_eq!(mixed.to_short().to_string(), ":5");
+    }
+
+    #[test]
+    fn test_to_long() {
+        let target = b"acgtATTgcc";
+        let short = CsString::parse(":4*ag:2-gc:1").unwrap();
+        let long = short.to_long(target).unwrap();
+        assert!(long.is_long_form());
+        assert_eq!(long.to_string(), "=ACGT*ag=TT-gc=C");
+        assert_eq!(long.to_short(), short);
+        let long = CsString::parse("=ACGT*ag=TT-gc=C").unwrap();
+        assert_eq!(long.to_short().to_long(target).unwrap(), long);
+    }
+
+    #[test]
+    fn test_to_long_errors() {
+        let short = CsString::parse(":4*ag:2").unwrap();
+        // too short
+        assert!(short.to_long(b"ACGTA").is_err());
+        // substituted base disagrees with the sequence
+        assert!(short.to_long(b"ACGTCTT").is_err());
+        assert!(short.to_long(b"ACGTATT").is_ok());
+    }
+
+    fn cs_record(cs: &str, strand: char) -> PafRecord {
+        let line = format!(
+            "q\t100\t10\t30\t{}\tchr1\t1000\t500\t520\t16\t21\t60\tcs:Z:{}\n",
+            strand, cs
+        );
+        crate::Reader::from_reader(line.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap()
+    }
+
+    fn variant(pos: u64, ref_allele: &str, alt_allele: &str, kind: VariantKind) -> Variant {
+        Variant {
+            target_name: "chr1".into(),
+            target_pos: pos,
+            ref_allele: ref_allele.into(),
+            alt_allele: alt_allele.into(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_variants() {
+        let expected = vec![
+            variant(503, "A", "G", VariantKind::Snv),
+            variant(504, "C", "T", VariantKind::Snv),
+            variant(507, "", "AC", VariantKind::Insertion),
+            variant(509, "GT", "", VariantKind::Deletion),
+        ];
+        let record = cs_record(":3*ag*ct:2+ac:2-gt:9", '+');
+        assert_eq!(record.variants().unwrap(), expected);
+        // the cs string is on the target strand, so minus strand records
+        // report the same alleles
+        let record = cs_record(":3*ag*ct:2+ac:2-gt:9", '-');
+        assert_eq!(record.variants().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_variants_long_form_and_splice() {
+        let record = cs_record("=ACG*ag~gt100ag=TT-c=A", '+');
+        assert_eq!(
+            record.variants().unwrap(),
+            vec![
+                variant(503, "A", "G", VariantKind::Snv),
+                variant(606, "C", "", VariantKind::Deletion),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_variants_needs_cs() {
+        let line = b"q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\n";
+        let record = crate::Reader::from_reader(&line[..])
+            .read_record()
+            .unwrap()
+            .unwrap();
+        assert!(record.variants().is_err());
+    }
+
+    #[test]
+    fn test_cs_parsed() {
+        let line = b"q\t100\t0\t11\t+\tt\t100\t0\t11\t10\t11\t60\tcs:Z::5*ag:5\n";
+        let mut reader = crate::Reader::from_reader(&line[..]);
+        let record = reader.read_record().unwrap().unwrap();
+        let cs = record.cs_parsed().unwrap().unwrap();
+        assert_eq!(cs.counts().matches, 10);
+        assert_eq!(cs.counts().mismatches, 1);
+    }
+}
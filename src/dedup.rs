@@ -0,0 +1,273 @@
+/*!
+Remove duplicate records, as left by concatenating the output of reruns.
+
+Both adapters pass errors through and count the records they remove.
+
+```
+use paf::dedup;
+use paf::Reader;
+
+let paf = b"q\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t60\n\
+            q\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t60\tNM:i:2\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let mut records = dedup::exact(reader.records());
+assert_eq!(records.by_ref().count(), 1);
+assert_eq!(records.removed(), 1);
+```
+*/
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::select::Score;
+use crate::{PafRecord, Result};
+
+/// Drops records whose twelve mandatory fields match an earlier record's,
+/// whatever their tags.
+///
+/// Records are compared by [`PafRecord::alignment_key`], so this holds a
+/// 64-bit hash per distinct record rather than the records, around 16 bytes
+/// each; a hash collision between different records, which is vanishingly
+/// unlikely, would drop the later one.
+pub fn exact<I>(records: I) -> Exact<I::IntoIter>
+where
+    I: IntoIterator<Item = Result<PafRecord>>,
+{
+    Exact {
+        records: records.into_iter(),
+        seen: HashSet::new(),
+        removed: 0,
+    }
+}
+
+/// An iterator over records without exact duplicates. See [`exact`].
+#[derive(Debug)]
+pub struct Exact<I> {
+    records: I,
+    seen: HashSet<u64>,
+    removed: u64,
+}
+
+impl<I> Exact<I> {
+    /// The number of records dropped so far.
+    pub fn removed(&self) -> u64 {
+        self.removed
+    }
+}
+
+impl<I: Iterator<Item = Result<PafRecord>>> Iterator for Exact<I> {
+    type Item = Result<PafRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.records.next()? {
+                Ok(record) => {
+                    if self.seen.insert(record.alignment_key()) {
+                        return Some(Ok(record));
+                    }
+                    self.removed += 1;
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Drops near-duplicate records from input sorted by target name and start.
+///
+/// Two records are near-duplicates when they share query, target and
+/// strand, and overlap on both the query and the target by at least
+/// `min_reciprocal_overlap` of the longer interval, so that each covers
+/// that fraction of the other. Of the two, the record with the higher
+/// [`Score::AsOrResidueMatches`] is kept, or the earlier on a tie.
+///
+/// Kept records are held until no later record can overlap them, and are
+/// then passed on in input order.
+pub fn by_overlap<I>(records: I, min_reciprocal_overlap: f64) -> ByOverlap<I::IntoIter>
+where
+    I: IntoIterator<Item = Result<PafRecord>>,
+{
+    ByOverlap {
+        records: records.into_iter(),
+        min_reciprocal_overlap,
+        active: VecDeque::new(),
+        ready: VecDeque::new(),
+        removed: 0,
+    }
+}
+
+/// An iterator over records without near-duplicates. See [`by_overlap`].
+#[derive(Debug)]
+pub struct ByOverlap<I> {
+    records: I,
+    min_reciprocal_overlap: f64,
+    /// Kept records which later records may still overlap.
+    active: VecDeque<PafRecord>,
+    ready: VecDeque<PafRecord>,
+    removed: u64,
+}
+
+impl<I> ByOverlap<I> {
+    /// The number of records dropped so far.
+    pub fn removed(&self) -> u64 {
+        self.removed
+    }
+
+    fn is_duplicate(&self, a: &PafRecord, b: &PafRecord) -> bool {
+        a.query_name() == b.query_name()
+            && a.target_name() == b.target_name()
+            && a.strand() == b.strand()
+            && reciprocal_overlap(
+                (a.query_start(), a.query_end()),
+                (b.query_start(), b.query_end()),
+            ) >= self.min_reciprocal_overlap
+            && reciprocal_overlap(
+                (a.target_start(), a.target_end()),
+                (b.target_start(), b.target_end()),
+            ) >= self.min_reciprocal_overlap
+    }
+}
+
+impl<I: Iterator<Item = Result<PafRecord>>> Iterator for ByOverlap<I> {
+    type Item = Result<PafRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.ready.pop_front() {
+                return Some(Ok(record));
+            }
+            let record = match self.records.next() {
+                Some(Ok(record)) => record,
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    self.ready.append(&mut self.active);
+                    return self.ready.pop_front().map(Ok);
+                }
+            };
+
+            // records which end before this one starts are done with
+            while let Some(first) = self.active.front() {
+                if first.target_name() == record.target_name()
+                    && first.target_end() > record.target_start()
+                {
+                    break;
+                }
+                self.ready.extend(self.active.pop_front());
+            }
+
+            let duplicate = self
+                .active
+                .iter()
+                .position(|kept| self.is_duplicate(kept, &record));
+            match duplicate {
+                Some(i) => {
+                    self.removed += 1;
+                    let score = Score::AsOrResidueMatches;
+                    if score.of(&record) > score.of(&self.active[i]) {
+                        self.active.remove(i);
+                        self.active.push_back(record);
+                    }
+                }
+                None => self.active.push_back(record),
+            }
+        }
+    }
+}
+
+/// The overlap of two intervals as a fraction of the longer.
+pub(crate) fn reciprocal_overlap(a: (u32, u32), b: (u32, u32)) -> f64 {
+    let overlap = a.1.min(b.1).saturating_sub(a.0.max(b.0));
+    let longer = a.1.saturating_sub(a.0).max(b.1.saturating_sub(b.0));
+    if longer == 0 {
+        return 0.0;
+    }
+    overlap as f64 / longer as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Reader;
+
+    fn names(records: impl Iterator<Item = Result<PafRecord>>) -> Vec<String> {
+        records
+            .map(|record| record.unwrap().query_name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_exact() {
+        let paf = "\
+a\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t60\ttp:A:P\n\
+b\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t60\n\
+a\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t60\ttp:A:S\n\
+a\t100\t0\t100\t+\tt\t1000\t0\t100\t97\t100\t60\n\
+b\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t60\n";
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        let mut records = exact(reader.records());
+        assert_eq!(names(records.by_ref()), ["a", "b", "a"]);
+        assert_eq!(records.removed(), 2);
+
+        let mut reader = Reader::from_reader(&b"bad\n"[..]);
+        assert!(exact(reader.records()).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_by_overlap() {
+        // sorted by target start: a near-duplicate contained in a1 with a
+        // higher score, the same interval on the other strand, a distinct
+        // record elsewhere on the same query and an exact duplicate of it
+        let paf = "\
+a1\t2000\t100\t1100\t+\tt\t5000\t100\t1100\t950\t1000\t60\tAS:i:900\n\
+a1\t2000\t100\t1100\t-\tt\t5000\t100\t1100\t950\t1000\t60\tAS:i:900\n\
+a1\t2000\t110\t1100\t+\tt\t5000\t110\t1100\t980\t990\t60\tAS:i:950\n\
+b1\t2000\t0\t500\t+\tt\t5000\t200\t700\t490\t500\t60\n\
+a1\t2000\t1200\t1800\t+\tt\t5000\t1200\t1800\t590\t600\t60\tAS:i:500\n\
+a1\t2000\t1200\t1800\t+\tt\t5000\t1200\t1800\t590\t600\t60\tAS:i:500\n\
+a1\t2000\t100\t1100\t+\tu\t5000\t100\t1100\t950\t1000\t60\tAS:i:900\n";
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        let mut records = by_overlap(reader.records(), 0.9);
+        let kept: Vec<(String, char, u32, i64)> = records
+            .by_ref()
+            .map(|record| {
+                let record = record.unwrap();
+                (
+                    record.target_name().to_string(),
+                    record.strand(),
+                    record.target_start(),
+                    record.as_().unwrap_or(0),
+                )
+            })
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                ("t".to_string(), '-', 100, 900),
+                ("t".to_string(), '+', 110, 950),
+                ("t".to_string(), '+', 200, 0),
+                ("t".to_string(), '+', 1200, 500),
+                ("u".to_string(), '+', 100, 900),
+            ]
+        );
+        assert_eq!(records.removed(), 2);
+    }
+
+    #[test]
+    fn test_by_overlap_threshold() {
+        // overlapping by 80% of the longer interval
+        let paf = "\
+q\t2000\t0\t1000\t+\tt\t5000\t0\t1000\t950\t1000\t60\n\
+q\t2000\t200\t1000\t+\tt\t5000\t200\t1000\t790\t800\t60\n";
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        assert_eq!(by_overlap(reader.records(), 0.9).count(), 2);
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        assert_eq!(by_overlap(reader.records(), 0.8).count(), 1);
+
+        // an interval ending before it starts overlaps nothing
+        let paf = "\
+q\t2000\t0\t1000\t+\tt\t5000\t0\t1000\t950\t1000\t60\n\
+q\t2000\t1000\t0\t+\tt\t5000\t1000\t0\t950\t1000\t60\n";
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        assert_eq!(by_overlap(reader.records(), 0.8).count(), 2);
+        assert_eq!(reciprocal_overlap((10, 0), (0, 0)), 0.0);
+    }
+}
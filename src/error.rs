@@ -27,6 +27,17 @@ impl Error {
     pub fn into_kind(self) -> ErrorKind {
         *self.0
     }
+
+    /// Whether this is an I/O error from writing to a closed pipe, as when
+    /// output piped to `head` stops being read. Command line programs
+    /// usually exit quietly on this.
+    pub fn is_broken_pipe(&self) -> bool {
+        match self.kind() {
+            ErrorKind::Io(err) => err.kind() == io::ErrorKind::BrokenPipe,
+            ErrorKind::WriteRecord { err, .. } => err.is_broken_pipe(),
+            _ => false,
+        }
+    }
 }
 
 /// Specific errors that can happen.
@@ -40,6 +51,13 @@ pub enum ErrorKind {
     Float(ParseFloatError),
     /// Error whilst reading a record.
     ReadRecord(String),
+    /// An optional field did not hold a value of the expected type.
+    InvalidTag(String),
+    /// A record's fields are inconsistent with each other.
+    InvalidRecord(String),
+    /// Error whilst getting or writing one of a series of records, with
+    /// the index of the record in the series.
+    WriteRecord { index: u64, err: Error },
 }
 
 impl From<io::Error> for Error {
@@ -66,8 +84,20 @@ impl fmt::Display for Error {
             ErrorKind::Int(ref err) => write!(f, "parsing integer error - {}", err),
             ErrorKind::Float(ref err) => write!(f, "parsing float error - {}", err),
             ErrorKind::ReadRecord(ref err) => write!(f, "reading record - {}", err),
+            ErrorKind::InvalidTag(ref err) => write!(f, "invalid tag - {}", err),
+            ErrorKind::InvalidRecord(ref err) => write!(f, "invalid record - {}", err),
+            ErrorKind::WriteRecord { index, ref err } => {
+                write!(f, "writing record {} - {}", index, err)
+            }
         }
     }
 }
 
-impl StdError for Error {}
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self.0 {
+            ErrorKind::WriteRecord { ref err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,306 @@
+/*!
+Write tracks along the target for genome browsers.
+
+```
+use paf::export::{identity_bedgraph, Overlap};
+use paf::Reader;
+
+let paf = b"q\t100\t0\t20\t+\tchr1\t1000\t100\t120\t19\t20\t60\tcs:Z::5*ag:14\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+let mut track = Vec::new();
+identity_bedgraph(&mut track, &records, 10, 10, Overlap::Mean).unwrap();
+assert_eq!(
+    String::from_utf8(track).unwrap(),
+    "chr1\t100\t110\t0.9000\nchr1\t110\t120\t1.0000\n"
+);
+```
+*/
+
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{BufWriter, Write};
+
+use crate::{tags, Error, ErrorKind, PafRecord, Result};
+
+/// How [`identity_bedgraph`] combines the identities of alignments
+/// overlapping the same window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overlap {
+    /// The highest identity.
+    Max,
+    /// The mean of the identities.
+    Mean,
+    /// The identity of the first record in the input.
+    First,
+}
+
+/// The identities of the alignments in one window.
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    /// The extent of the alignments in the window.
+    start: u64,
+    end: u64,
+    first: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+}
+
+impl Window {
+    fn value(&self, overlap: Overlap) -> f64 {
+        match overlap {
+            Overlap::Max => self.max,
+            Overlap::Mean => self.sum / self.count as f64,
+            Overlap::First => self.first,
+        }
+    }
+}
+
+/// The windows of one target not yet written.
+struct Track<W: Write> {
+    writer: W,
+    window: u64,
+    step: u64,
+    overlap: Overlap,
+    target: String,
+    windows: BTreeMap<u64, Window>,
+    lines: u64,
+}
+
+impl<W: Write> Track<W> {
+    /// Writes the windows before `k`, which no later record can reach.
+    fn write_before(&mut self, k: u64) -> Result<()> {
+        let rest = self.windows.split_off(&k);
+        let done = std::mem::replace(&mut self.windows, rest);
+        for (k, window) in done {
+            // each line covers the start of its window, up to the next
+            let slot_start = k * self.step;
+            let slot_end = slot_start + self.step.min(self.window);
+            let (start, end) = (slot_start.max(window.start), slot_end.min(window.end));
+            if start < end {
+                writeln!(
+                    self.writer,
+                    "{}\t{}\t{}\t{:.4}",
+                    self.target,
+                    start,
+                    end,
+                    window.value(self.overlap)
+                )?;
+                self.lines += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the identity of the alignments along each target as bedGraph,
+/// returning the number of lines written.
+///
+/// The identity in windows is as from
+/// [`PafRecord::windowed_identity`], from the cs tag or from a cg tag with
+/// `=` and `X` operations, and records with neither tag are passed over.
+/// Where alignments overlap in a window, `overlap` chooses or combines
+/// their identities. Each line covers the bases of the target from the
+/// start of its window to the start of the next, or the whole window when
+/// `step` is longer, clipped to the alignments, so lines do not overlap.
+///
+/// Lines are written as the records pass, in order along each target, which
+/// needs the records sorted by target and target start; it is an error for
+/// them not to be, or for a record's tags not to give its identity. The
+/// error gives the index of the record as an
+/// [`ErrorKind::WriteRecord`](ErrorKind::WriteRecord).
+///
+/// # Panics
+///
+/// Panics if `window` or `step` is zero.
+pub fn identity_bedgraph<W, I>(
+    writer: W,
+    records: I,
+    window: u64,
+    step: u64,
+    overlap: Overlap,
+) -> Result<u64>
+where
+    W: Write,
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    assert!(window > 0 && step > 0, "window and step must be positive");
+    let mut track = Track {
+        writer: BufWriter::new(writer),
+        window,
+        step,
+        overlap,
+        target: String::new(),
+        windows: BTreeMap::new(),
+        lines: 0,
+    };
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut last_start = 0;
+    for (index, record) in records.into_iter().enumerate() {
+        let record = record.borrow();
+        let fields = record.optional_fields();
+        if !fields.contains_key(tags::CS) && !fields.contains_key(tags::CG) {
+            continue;
+        }
+        add(&mut track, &mut seen, &mut last_start, record).map_err(|err| {
+            Error::new(ErrorKind::WriteRecord {
+                index: index as u64,
+                err,
+            })
+        })?;
+    }
+    track.write_before(u64::MAX)?;
+    track.writer.flush()?;
+    Ok(track.lines)
+}
+
+fn add<W: Write>(
+    track: &mut Track<W>,
+    seen: &mut HashSet<String>,
+    last_start: &mut u64,
+    record: &PafRecord,
+) -> Result<()> {
+    let start = record.target_start() as u64;
+    if record.target_name() != track.target || seen.is_empty() {
+        if !seen.insert(record.target_name().to_string()) {
+            return Err(unsorted(format!(
+                "{} comes again after {}",
+                record.target_name(),
+                track.target
+            )));
+        }
+        track.write_before(u64::MAX)?;
+        track.target = record.target_name().to_string();
+    } else if start < *last_start {
+        return Err(unsorted(format!(
+            "{} starts at {} after {}",
+            record.target_name(),
+            start,
+            last_start
+        )));
+    }
+    *last_start = start;
+
+    // windows ending by this start are done
+    track.write_before(
+        (start + 1)
+            .saturating_sub(track.window)
+            .div_ceil(track.step),
+    )?;
+    for (k, identity) in record.indexed_windowed_identity(track.window, track.step)? {
+        let value = identity.identity;
+        track
+            .windows
+            .entry(k)
+            .and_modify(|window| {
+                window.start = window.start.min(identity.target_start);
+                window.end = window.end.max(identity.target_end);
+                window.max = window.max.max(value);
+                window.sum += value;
+                window.count += 1;
+            })
+            .or_insert(Window {
+                start: identity.target_start,
+                end: identity.target_end,
+                first: value,
+                max: value,
+                sum: value,
+                count: 1,
+            });
+    }
+    Ok(())
+}
+
+fn unsorted(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidRecord(format!(
+        "an identity bedGraph needs records sorted by target and target start, but {}",
+        msg
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+
+    /// A record on `target` from `start` with the cs tag `cs`.
+    fn record(target: &str, start: u32, cs: &str, len: u32) -> String {
+        format!(
+            "q\t1000\t0\t{}\t+\t{}\t1000\t{}\t{}\t{}\t{}\t60\tcs:Z:{}\n",
+            len,
+            target,
+            start,
+            start + len,
+            len,
+            len,
+            cs
+        )
+    }
+
+    fn bedgraph(paf: &[String], window: u64, step: u64, overlap: Overlap) -> Result<String> {
+        let records = records(&paf.concat());
+        let mut track = Vec::new();
+        identity_bedgraph(&mut track, &records, window, step, overlap)?;
+        Ok(String::from_utf8(track).unwrap())
+    }
+
+    fn paf() -> Vec<String> {
+        // on t, r0 has 10 mismatches in 10..20, and r1 overlaps it from 15
+        // matching throughout; on u, r2 is half mismatches
+        vec![
+            record("t", 0, &format!(":10{}:20", "*ag".repeat(10)), 40),
+            record("t", 15, ":20", 20),
+            "q\t1000\t0\t10\t+\tt\t1000\t50\t60\t10\t10\t60\n".to_string(),
+            record("u", 0, &format!(":5{}", "*ag".repeat(5)), 10),
+        ]
+    }
+
+    #[test]
+    fn test_overlap() {
+        let lines = |overlap| {
+            let track = bedgraph(&paf(), 10, 10, overlap).unwrap();
+            track.lines().map(String::from).collect::<Vec<_>>()
+        };
+        // 10..20 holds 10 mismatched bases of r0, and 5 matches of r1
+        assert_eq!(
+            lines(Overlap::First),
+            [
+                "t\t0\t10\t1.0000",
+                "t\t10\t20\t0.0000",
+                "t\t20\t30\t1.0000",
+                "t\t30\t40\t1.0000",
+                "u\t0\t10\t0.5000",
+            ]
+        );
+        assert_eq!(lines(Overlap::Max)[1], "t\t10\t20\t1.0000");
+        assert_eq!(lines(Overlap::Mean)[1], "t\t10\t20\t0.5000");
+    }
+
+    #[test]
+    fn test_step_shorter_than_window() {
+        // windows of 20 every 10 on r0 alone: 0..20 and 10..30 are half
+        // matches, and each line takes the first 10 bases of its window
+        let track = bedgraph(&paf()[..1], 20, 10, Overlap::First).unwrap();
+        assert_eq!(
+            track,
+            "t\t0\t10\t0.5000\nt\t10\t20\t0.5000\nt\t20\t30\t1.0000\nt\t30\t40\t1.0000\n"
+        );
+    }
+
+    #[test]
+    fn test_unsorted() {
+        let paf = paf();
+        let err = bedgraph(&[paf[1].clone(), paf[0].clone()], 10, 10, Overlap::Max).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::WriteRecord { index: 1, .. }
+        ));
+        let again = [paf[0].clone(), paf[3].clone(), paf[1].clone()];
+        assert!(bedgraph(&again, 10, 10, Overlap::Max).is_err());
+        // a cg tag without =/X cannot give the identity
+        let cg = "q\t10\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\tcg:Z:10M\n".to_string();
+        assert!(bedgraph(&[cg], 10, 10, Overlap::Max).is_err());
+    }
+}
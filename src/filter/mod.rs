@@ -0,0 +1,546 @@
+/*!
+Predicates for choosing records, to combine and pass to iterator filters.
+
+Each builder returns a `Fn(&PafRecord) -> bool`, so it works with
+`.filter()` over records directly. [`ok_and`] adapts one to the results of
+[`Reader::records`](crate::Reader::records), keeping errors so that they
+still reach the caller. To also count what each predicate drops, give
+them names as [`Rule`]s and read through [`FilteredRecords`].
+
+```
+use paf::filter::{all_of, min_identity, min_mapq, not, ok_and, target_is};
+use paf::Reader;
+
+let paf = b"q1\t100\t0\t100\t+\tchr1\t1000\t0\t100\t98\t100\t60\n\
+            q2\t100\t0\t100\t+\tchr1\t1000\t0\t100\t98\t100\t5\n\
+            q3\t100\t0\t100\t+\tchrM\t1000\t0\t100\t98\t100\t60\n";
+let keep = all_of(vec![
+    Box::new(min_mapq(30)),
+    Box::new(min_identity(0.95)),
+    Box::new(not(target_is("chrM"))),
+]);
+let mut reader = Reader::from_reader(&paf[..]);
+let kept: Vec<_> = reader.records().filter(ok_and(keep)).collect();
+assert_eq!(kept.len(), 1);
+assert_eq!(kept[0].as_ref().unwrap().query_name(), "q1");
+```
+*/
+
+use std::fmt;
+
+use crate::{AlignmentType, PafRecord, Result};
+
+/// The region module masks targets with regions read from BED.
+mod region;
+
+pub use region::{in_regions, not_in_regions, RegionMask};
+
+/// A boxed predicate, for combining predicates of different types.
+pub type Predicate = Box<dyn Fn(&PafRecord) -> bool + Send + Sync>;
+
+/// Records with a mapping quality of at least `mapq`.
+pub fn min_mapq(mapq: u8) -> impl Fn(&PafRecord) -> bool + Send + Sync {
+    move |record| record.mapping_quality() >= mapq
+}
+
+/// Records with an alignment block length, column 11, of at least `len`.
+pub fn min_block_len(len: u64) -> impl Fn(&PafRecord) -> bool + Send + Sync {
+    move |record| record.alignment_block_len() as u64 >= len
+}
+
+/// Records with an identity of at least `identity`, where identity is the
+/// residue matches over the alignment block length, as in
+/// [`stats`](crate::stats). A record with an empty block has identity zero.
+///
+/// This is [`IdentityDef::Blast`]; see [`min_identity_with`] for others.
+pub fn min_identity(identity: f64) -> impl Fn(&PafRecord) -> bool + Send + Sync {
+    move |record| record.blast_identity() >= identity
+}
+
+/// A definition of alignment identity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentityDef {
+    /// [`PafRecord::blast_identity`], from the mandatory columns.
+    Blast,
+    /// [`PafRecord::gap_compressed_identity`], from the de tag.
+    GapCompressed,
+    /// [`PafRecord::nm_identity`], from the NM tag.
+    FromNm,
+}
+
+/// What to do with a record missing the tag an identity needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingTag {
+    /// Leave the record out.
+    Reject,
+    /// Keep the record.
+    Accept,
+    /// Use its BLAST identity instead.
+    FallBackToBlast,
+}
+
+/// Records with an identity of at least `identity` by the definition `def`.
+///
+/// The definitions can disagree widely on one record: a single long
+/// deletion lowers BLAST identity by its length but gap-compressed identity
+/// by one difference. `missing` decides records without the de or NM tag.
+pub fn min_identity_with(
+    def: IdentityDef,
+    identity: f64,
+    missing: MissingTag,
+) -> impl Fn(&PafRecord) -> bool + Send + Sync {
+    move |record| {
+        let value = match def {
+            IdentityDef::Blast => Some(record.blast_identity()),
+            IdentityDef::GapCompressed => record.gap_compressed_identity(),
+            IdentityDef::FromNm => record.nm_identity(),
+        };
+        match (value, missing) {
+            (Some(value), _) => value >= identity,
+            (None, MissingTag::Reject) => false,
+            (None, MissingTag::Accept) => true,
+            (None, MissingTag::FallBackToBlast) => record.blast_identity() >= identity,
+        }
+    }
+}
+
+/// Primary alignments: those with a tp tag of `P` or `I`, or without a tp
+/// tag, as tools other than minimap2 do not write one.
+pub fn primary_only() -> impl Fn(&PafRecord) -> bool + Send + Sync {
+    |record| {
+        matches!(
+            record.tp(),
+            None | Some(AlignmentType::Primary | AlignmentType::Inversion)
+        )
+    }
+}
+
+/// Records aligned to the target named `name`.
+pub fn target_is(name: &str) -> impl Fn(&PafRecord) -> bool + Send + Sync {
+    let name = name.to_string();
+    move |record| record.target_name() == name
+}
+
+/// Records whose query name matches `regex` anywhere.
+#[cfg(feature = "regex")]
+pub fn query_matches(regex: regex::Regex) -> impl Fn(&PafRecord) -> bool + Send + Sync {
+    move |record| regex.is_match(record.query_name())
+}
+
+/// Records that pass every one of `predicates`, or all records if there
+/// are none.
+pub fn all_of(predicates: Vec<Predicate>) -> impl Fn(&PafRecord) -> bool + Send + Sync {
+    move |record| predicates.iter().all(|predicate| predicate(record))
+}
+
+/// Records that pass any of `predicates`, or none if there are none.
+pub fn any_of(predicates: Vec<Predicate>) -> impl Fn(&PafRecord) -> bool + Send + Sync {
+    move |record| predicates.iter().any(|predicate| predicate(record))
+}
+
+/// Records that fail `predicate`.
+pub fn not<F>(predicate: F) -> impl Fn(&PafRecord) -> bool + Send + Sync
+where
+    F: Fn(&PafRecord) -> bool + Send + Sync,
+{
+    move |record| !predicate(record)
+}
+
+/// Adapts a predicate to results of reading records, keeping records which
+/// pass it and all errors.
+pub fn ok_and<F>(predicate: F) -> impl FnMut(&Result<PafRecord>) -> bool
+where
+    F: Fn(&PafRecord) -> bool,
+{
+    move |result| result.as_ref().map_or(true, &predicate)
+}
+
+/// A predicate with a name, for reporting how many records it rejected.
+pub struct Rule {
+    name: String,
+    predicate: Predicate,
+}
+
+impl Rule {
+    /// A rule from any predicate.
+    pub fn new<F>(name: &str, predicate: F) -> Rule
+    where
+        F: Fn(&PafRecord) -> bool + Send + Sync + 'static,
+    {
+        Rule {
+            name: name.to_string(),
+            predicate: Box::new(predicate),
+        }
+    }
+
+    /// [`min_mapq`], named e.g. `min_mapq(30)`.
+    pub fn min_mapq(mapq: u8) -> Rule {
+        Rule::new(&format!("min_mapq({})", mapq), min_mapq(mapq))
+    }
+
+    /// [`min_block_len`], named e.g. `min_block_len(1000)`.
+    pub fn min_block_len(len: u64) -> Rule {
+        Rule::new(&format!("min_block_len({})", len), min_block_len(len))
+    }
+
+    /// [`min_identity`], named e.g. `min_identity(0.95)`.
+    pub fn min_identity(identity: f64) -> Rule {
+        Rule::new(
+            &format!("min_identity({})", identity),
+            min_identity(identity),
+        )
+    }
+
+    /// [`primary_only`], named `primary_only`.
+    pub fn primary_only() -> Rule {
+        Rule::new("primary_only", primary_only())
+    }
+
+    /// [`target_is`], named e.g. `target_is(chr1)`.
+    pub fn target_is(name: &str) -> Rule {
+        Rule::new(&format!("target_is({})", name), target_is(name))
+    }
+
+    /// The name of the rule, as in the report.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether a record passes the rule.
+    pub fn passes(&self, record: &PafRecord) -> bool {
+        (self.predicate)(record)
+    }
+}
+
+impl fmt::Debug for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Rule").field("name", &self.name).finish()
+    }
+}
+
+/// Counts of the records [`FilteredRecords`] has seen and rejected.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterReport {
+    /// Records read, not counting errors.
+    pub records: u64,
+    /// Records which passed every rule.
+    pub kept: u64,
+    /// Each rule's name and the number of records it rejected. A record
+    /// failing several rules counts against each.
+    pub rejected: Vec<(String, u64)>,
+}
+
+impl FilterReport {
+    /// The number of records rejected by the rule with this name.
+    pub fn rejected_by(&self, name: &str) -> Option<u64> {
+        self.rejected
+            .iter()
+            .find(|(rule, _)| rule == name)
+            .map(|&(_, count)| count)
+    }
+
+    /// Records which failed at least one rule.
+    pub fn dropped(&self) -> u64 {
+        self.records - self.kept
+    }
+}
+
+impl fmt::Display for FilterReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<24}{}", "records", self.records)?;
+        writeln!(f, "{:<24}{}", "kept", self.kept)?;
+        writeln!(f, "{:<24}{}", "dropped", self.dropped())?;
+        for (name, count) in &self.rejected {
+            writeln!(f, "{:<24}{}", name, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Passes through the records which pass every rule, counting the records
+/// each rule rejects.
+///
+/// Errors are passed through and not counted. The report is complete once
+/// the iterator is exhausted.
+///
+/// ```
+/// use paf::filter::{FilteredRecords, Rule};
+/// use paf::Reader;
+///
+/// let paf = b"q1\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t60\n\
+///             q2\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t5\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let rules = vec![Rule::min_mapq(30), Rule::min_block_len(1000)];
+/// let mut filtered = FilteredRecords::new(reader.records(), rules);
+/// assert_eq!(filtered.by_ref().count(), 0);
+/// assert_eq!(filtered.report().rejected_by("min_mapq(30)"), Some(1));
+/// assert_eq!(filtered.report().rejected_by("min_block_len(1000)"), Some(2));
+/// ```
+#[derive(Debug)]
+pub struct FilteredRecords<I> {
+    records: I,
+    rules: Vec<Rule>,
+    report: FilterReport,
+}
+
+impl<I: Iterator<Item = Result<PafRecord>>> FilteredRecords<I> {
+    /// Filters `records` by `rules`.
+    pub fn new(records: I, rules: Vec<Rule>) -> Self {
+        let report = FilterReport {
+            rejected: rules.iter().map(|rule| (rule.name.clone(), 0)).collect(),
+            ..FilterReport::default()
+        };
+        FilteredRecords {
+            records,
+            rules,
+            report,
+        }
+    }
+
+    /// The counts so far.
+    pub fn report(&self) -> &FilterReport {
+        &self.report
+    }
+
+    /// Takes the report, leaving the iterator.
+    pub fn into_report(self) -> FilterReport {
+        self.report
+    }
+}
+
+impl<I: Iterator<Item = Result<PafRecord>>> Iterator for FilteredRecords<I> {
+    type Item = Result<PafRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = match self.records.next()? {
+                Ok(record) => record,
+                Err(err) => return Some(Err(err)),
+            };
+            self.report.records += 1;
+            let mut passed = true;
+            for (rule, (_, count)) in self.rules.iter().zip(&mut self.report.rejected) {
+                if !rule.passes(&record) {
+                    *count += 1;
+                    passed = false;
+                }
+            }
+            if passed {
+                self.report.kept += 1;
+                return Some(Ok(record));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::record;
+    use crate::Reader;
+
+    #[test]
+    fn test_min_mapq() {
+        let record = record("q\t100\t0\t100\t+\tt\t1000\t0\t100\t90\t100\t30");
+        assert!(min_mapq(30)(&record));
+        assert!(!min_mapq(31)(&record));
+    }
+
+    #[test]
+    fn test_min_block_len() {
+        let record = record("q\t100\t0\t100\t+\tt\t1000\t0\t100\t90\t1000\t30");
+        assert!(min_block_len(1000)(&record));
+        assert!(!min_block_len(1001)(&record));
+    }
+
+    #[test]
+    fn test_min_identity() {
+        let record = record("q\t100\t0\t100\t+\tt\t1000\t0\t100\t90\t120\t30");
+        assert!(min_identity(0.75)(&record));
+        assert!(!min_identity(0.76)(&record));
+        let empty = self::record("q\t100\t0\t0\t+\tt\t1000\t0\t0\t0\t0\t30");
+        assert!(min_identity(0.0)(&empty));
+        assert!(!min_identity(0.1)(&empty));
+    }
+
+    #[test]
+    fn test_identity_definitions() {
+        // 900 matches over 1000 columns, an NM of 150 and a de of 0.0005
+        // give identities of 0.9, 0.85 and 0.9995
+        let record =
+            record("q\t920\t0\t920\t+\tt\t2000\t0\t1000\t900\t1000\t60\tNM:i:150\tde:f:0.0005");
+        assert_eq!(record.blast_identity(), 0.9);
+        assert_eq!(record.nm_identity(), Some(0.85));
+        assert_eq!(record.gap_compressed_identity(), Some(0.9995));
+
+        let passes = |def| min_identity_with(def, 0.95, MissingTag::Reject)(&record);
+        assert!(!passes(IdentityDef::Blast));
+        assert!(passes(IdentityDef::GapCompressed));
+        assert!(!passes(IdentityDef::FromNm));
+        let passes = |def| min_identity_with(def, 0.86, MissingTag::Reject)(&record);
+        assert!(passes(IdentityDef::Blast));
+        assert!(!passes(IdentityDef::FromNm));
+    }
+
+    #[test]
+    fn test_identity_missing_tag() {
+        let record = record("q\t100\t0\t100\t+\tt\t1000\t0\t100\t96\t100\t60");
+        assert_eq!(record.nm_identity(), None);
+        assert_eq!(record.gap_compressed_identity(), None);
+        for def in [IdentityDef::GapCompressed, IdentityDef::FromNm] {
+            assert!(!min_identity_with(def, 0.9, MissingTag::Reject)(&record));
+            assert!(min_identity_with(def, 0.99, MissingTag::Accept)(&record));
+            assert!(min_identity_with(def, 0.95, MissingTag::FallBackToBlast)(
+                &record
+            ));
+            assert!(!min_identity_with(def, 0.97, MissingTag::FallBackToBlast)(
+                &record
+            ));
+        }
+        // BLAST identity needs no tag
+        assert!(!min_identity_with(
+            IdentityDef::Blast,
+            0.97,
+            MissingTag::Accept
+        )(&record));
+    }
+
+    #[test]
+    fn test_primary_only() {
+        let line = "q\t100\t0\t100\t+\tt\t1000\t0\t100\t90\t100\t30";
+        assert!(primary_only()(&record(line)));
+        for (tp, primary) in [('P', true), ('I', true), ('S', false), ('i', false)] {
+            let record = record(&format!("{}\ttp:A:{}", line, tp));
+            assert_eq!(primary_only()(&record), primary, "tp:A:{}", tp);
+        }
+    }
+
+    #[test]
+    fn test_target_is() {
+        let record = record("q\t100\t0\t100\t+\tchr1\t1000\t0\t100\t90\t100\t30");
+        assert!(target_is("chr1")(&record));
+        assert!(!target_is("chr10")(&record));
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn test_query_matches() {
+        let record = record("read_42/ccs\t100\t0\t100\t+\tt\t1000\t0\t100\t90\t100\t30");
+        assert!(query_matches(regex::Regex::new(r"_\d+/ccs$").unwrap())(
+            &record
+        ));
+        assert!(!query_matches(regex::Regex::new(r"^ccs").unwrap())(&record));
+    }
+
+    #[test]
+    fn test_combinators() {
+        let record = record("q\t100\t0\t100\t+\tchr1\t1000\t0\t100\t90\t100\t30");
+        assert!(all_of(vec![])(&record));
+        assert!(!any_of(vec![])(&record));
+        assert!(all_of(vec![
+            Box::new(min_mapq(30)),
+            Box::new(target_is("chr1"))
+        ])(&record));
+        assert!(!all_of(vec![
+            Box::new(min_mapq(31)),
+            Box::new(target_is("chr1"))
+        ])(&record));
+        assert!(any_of(vec![
+            Box::new(min_mapq(31)),
+            Box::new(target_is("chr1"))
+        ])(&record));
+        assert!(!any_of(vec![
+            Box::new(min_mapq(31)),
+            Box::new(target_is("chr2"))
+        ])(&record));
+        assert!(not(target_is("chr2"))(&record));
+        assert!(!not(target_is("chr1"))(&record));
+    }
+
+    #[test]
+    fn test_filtered_records() {
+        let paf = "\
+keep\t5000\t0\t5000\t+\tchr1\t100000\t0\t5000\t4900\t5000\t60\ttp:A:P
+lowq\t5000\t0\t5000\t+\tchr1\t100000\t0\t5000\t4900\t5000\t10\ttp:A:P
+short\t500\t0\t500\t+\tchr1\t100000\t0\t500\t490\t500\t60\ttp:A:P
+both\t500\t0\t500\t+\tchr1\t100000\t0\t500\t490\t500\t10\ttp:A:P
+second\t5000\t0\t5000\t+\tchr1\t100000\t0\t5000\t4900\t5000\t60\ttp:A:S
+mito\t5000\t0\t5000\t+\tchrM\t16569\t0\t5000\t4900\t5000\t60\ttp:A:P
+divergent\t5000\t0\t5000\t+\tchr1\t100000\t0\t5000\t4000\t5000\t60\ttp:A:P
+also\t5000\t0\t5000\t-\tchr2\t100000\t0\t5000\t4900\t5000\t60\ttp:A:P
+";
+        let rules = vec![
+            Rule::min_mapq(30),
+            Rule::min_block_len(1000),
+            Rule::min_identity(0.9),
+            Rule::primary_only(),
+            Rule::new("not_chrM", not(target_is("chrM"))),
+        ];
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        let mut filtered = FilteredRecords::new(reader.records(), rules);
+        let kept: Vec<String> = filtered
+            .by_ref()
+            .map(|record| record.unwrap().query_name().to_string())
+            .collect();
+        assert_eq!(kept, ["keep", "also"]);
+
+        let report = filtered.into_report();
+        assert_eq!(report.records, 8);
+        assert_eq!(report.kept, 2);
+        assert_eq!(report.dropped(), 6);
+        assert_eq!(
+            report.rejected,
+            [
+                ("min_mapq(30)".to_string(), 2),
+                ("min_block_len(1000)".to_string(), 2),
+                ("min_identity(0.9)".to_string(), 1),
+                ("primary_only".to_string(), 1),
+                ("not_chrM".to_string(), 1),
+            ]
+        );
+        assert_eq!(report.rejected_by("min_mapq(30)"), Some(2));
+        assert_eq!(report.rejected_by("min_mapq(20)"), None);
+        assert!(report.to_string().contains("\nmin_block_len(1000)     2\n"));
+    }
+
+    #[test]
+    fn test_filtered_records_errors() {
+        let paf = "q\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t60\nbad\n";
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        let mut filtered = FilteredRecords::new(reader.records(), vec![Rule::min_mapq(30)]);
+        assert!(filtered.next().unwrap().is_ok());
+        assert!(filtered.next().unwrap().is_err());
+        assert!(filtered.next().is_none());
+        assert_eq!(filtered.report().records, 1);
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let keep = all_of(vec![
+            Box::new(primary_only()),
+            Box::new(min_mapq(60)),
+            Box::new(min_block_len(100_000)),
+            Box::new(min_identity(0.9)),
+        ]);
+        let mut reader = Reader::from_path("data/5_GD_domestica.paf").unwrap();
+        let records: Vec<PafRecord> = reader.records().map(Result::unwrap).collect();
+        let kept: Vec<&PafRecord> = records.iter().filter(|record| keep(record)).collect();
+        assert!(!kept.is_empty() && kept.len() < records.len());
+        for record in &kept {
+            assert!(record.mapping_quality() >= 60);
+            assert!(record.alignment_block_len() >= 100_000);
+            assert!(record.residue_matches() as f64 / record.alignment_block_len() as f64 >= 0.9);
+        }
+
+        // the same through the reader, with errors kept
+        let mut reader = Reader::from_path("data/5_GD_domestica.paf").unwrap();
+        let read: Vec<PafRecord> = reader
+            .records()
+            .filter(ok_and(&keep))
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(read.len(), kept.len());
+        let mut reader = Reader::from_reader(&b"q\tnot a number\n"[..]);
+        assert_eq!(reader.records().filter(ok_and(min_mapq(0))).count(), 1);
+    }
+}
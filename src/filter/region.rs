@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use crate::{Error, ErrorKind, PafRecord, Result};
+
+/// Regions of targets, read from BED, to compare alignments against.
+///
+/// Only the first three columns of the BED are used. Overlapping and
+/// adjacent regions are merged. Targets absent from the BED have no regions.
+#[derive(Debug, Clone, Default)]
+pub struct RegionMask {
+    /// Sorted, disjoint half-open intervals per target.
+    regions: HashMap<String, Vec<(u64, u64)>>,
+}
+
+impl RegionMask {
+    /// Read a mask from a BED file.
+    pub fn from_bed_path<P: AsRef<Path>>(path: P) -> Result<RegionMask> {
+        RegionMask::from_bed_reader(File::open(path)?)
+    }
+
+    /// Read a mask from BED, skipping blank lines, comments and `track` and
+    /// `browser` lines.
+    pub fn from_bed_reader<R: Read>(reader: R) -> Result<RegionMask> {
+        let mut regions: HashMap<String, Vec<(u64, u64)>> = HashMap::new();
+        for (i, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end();
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("track")
+                || line.starts_with("browser")
+            {
+                continue;
+            }
+            let bed_error = |msg: &str| {
+                Error::new(ErrorKind::ReadRecord(format!(
+                    "Invalid BED at line {}: {}",
+                    i + 1,
+                    msg
+                )))
+            };
+            let mut fields = line.split('\t');
+            let (Some(chrom), Some(start), Some(end)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(bed_error("fewer than three columns"));
+            };
+            let start: u64 = start
+                .parse()
+                .map_err(|_| bed_error("start is not a number"))?;
+            let end: u64 = end.parse().map_err(|_| bed_error("end is not a number"))?;
+            if end < start {
+                return Err(bed_error("end is before start"));
+            }
+            regions
+                .entry(chrom.to_string())
+                .or_default()
+                .push((start, end));
+        }
+        for intervals in regions.values_mut() {
+            intervals.sort_unstable();
+            let mut merged: Vec<(u64, u64)> = Vec::with_capacity(intervals.len());
+            for &(start, end) in intervals.iter() {
+                match merged.last_mut() {
+                    Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                    _ => merged.push((start, end)),
+                }
+            }
+            *intervals = merged;
+        }
+        Ok(RegionMask { regions })
+    }
+
+    /// The regions overlapping the target interval of a record, none when
+    /// the interval ends before it starts.
+    fn overlapping(&self, record: &PafRecord) -> impl Iterator<Item = (u64, u64)> + '_ {
+        let (start, end) = (record.target_start() as u64, record.target_end() as u64);
+        let intervals = self
+            .regions
+            .get(record.target_name())
+            .filter(|_| start < end)
+            .map_or(&[][..], |intervals| &intervals[..]);
+        let first = intervals.partition_point(|&(_, region_end)| region_end <= start);
+        intervals[first..]
+            .iter()
+            .take_while(move |&&(region_start, _)| region_start < end)
+            .copied()
+    }
+
+    /// Whether the target interval of a record overlaps any region.
+    pub fn overlaps(&self, record: &PafRecord) -> bool {
+        self.overlapping(record).next().is_some()
+    }
+
+    /// Whether the target interval of a record lies wholly within a region.
+    pub fn contained_in(&self, record: &PafRecord) -> bool {
+        let (start, end) = (record.target_start() as u64, record.target_end() as u64);
+        self.overlapping(record)
+            .any(|(region_start, region_end)| region_start <= start && end <= region_end)
+    }
+
+    /// The number of bases of the target interval of a record in regions.
+    pub fn overlap_bases(&self, record: &PafRecord) -> u64 {
+        let (start, end) = (record.target_start() as u64, record.target_end() as u64);
+        self.overlapping(record)
+            .map(|(region_start, region_end)| region_end.min(end) - region_start.max(start))
+            .sum()
+    }
+}
+
+/// Records whose target interval overlaps a region of `mask`.
+pub fn in_regions(mask: RegionMask) -> impl Fn(&PafRecord) -> bool + Send + Sync {
+    move |record| mask.overlaps(record)
+}
+
+/// Records whose target interval overlaps no region of `mask`.
+pub fn not_in_regions(mask: RegionMask) -> impl Fn(&PafRecord) -> bool + Send + Sync {
+    move |record| !mask.overlaps(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    const BED: &str = "\
+track name=genes
+# a comment
+browser position chr1:1-1000
+chr1\t100\t200\tgene1\t0\t+
+chr1\t150\t250
+chr1\t300\t400
+chr1\t400\t450
+
+chr2\t0\t10
+";
+
+    fn record(target: &str, start: u32, end: u32) -> PafRecord {
+        test_util::record(&format!(
+            "q\t1000\t0\t{}\t+\t{}\t1000\t{}\t{}\t{}\t{}\t60\n",
+            end - start,
+            target,
+            start,
+            end,
+            end - start,
+            end - start
+        ))
+    }
+
+    #[test]
+    fn test_parse_and_merge() {
+        let mask = RegionMask::from_bed_reader(BED.as_bytes()).unwrap();
+        assert_eq!(mask.regions["chr1"], [(100, 250), (300, 450)]);
+        assert_eq!(mask.regions["chr2"], [(0, 10)]);
+
+        let err = RegionMask::from_bed_reader(&b"chr1\t1\n"[..]).unwrap_err();
+        assert!(err.to_string().contains("Invalid BED at line 1"));
+        assert!(RegionMask::from_bed_reader(&b"chr1\tx\t5\n"[..]).is_err());
+        assert!(RegionMask::from_bed_reader(&b"chr1\t9\t5\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let mask = RegionMask::from_bed_reader(BED.as_bytes()).unwrap();
+        // ending where a region starts, and starting where one ends
+        assert!(!mask.overlaps(&record("chr1", 50, 100)));
+        assert!(!mask.overlaps(&record("chr1", 250, 300)));
+        // straddling the start and end of a region
+        let straddle = record("chr1", 90, 110);
+        assert!(mask.overlaps(&straddle));
+        assert!(!mask.contained_in(&straddle));
+        assert_eq!(mask.overlap_bases(&straddle), 10);
+        let straddle = record("chr1", 440, 460);
+        assert_eq!(mask.overlap_bases(&straddle), 10);
+        // spanning two regions
+        let spanning = record("chr1", 200, 350);
+        assert!(!mask.contained_in(&spanning));
+        assert_eq!(mask.overlap_bases(&spanning), 100);
+        // inside merged regions
+        assert!(mask.contained_in(&record("chr1", 100, 250)));
+        assert!(mask.contained_in(&record("chr1", 350, 450)));
+        assert_eq!(mask.overlap_bases(&record("chr1", 350, 450)), 100);
+        // ending before it starts, around the end of a region
+        let reversed = "q\t1000\t0\t10\t+\tchr1\t1000\t300\t90\t10\t10\t60\n";
+        let reversed = test_util::record(reversed);
+        assert!(!mask.overlaps(&reversed));
+        assert!(!mask.contained_in(&reversed));
+        assert_eq!(mask.overlap_bases(&reversed), 0);
+    }
+
+    #[test]
+    fn test_absent_target() {
+        let mask = RegionMask::from_bed_reader(BED.as_bytes()).unwrap();
+        let record = record("chr3", 0, 500);
+        assert!(!mask.overlaps(&record));
+        assert!(!mask.contained_in(&record));
+        assert_eq!(mask.overlap_bases(&record), 0);
+        assert!(!in_regions(mask.clone())(&record));
+        assert!(not_in_regions(mask)(&record));
+    }
+
+    #[test]
+    fn test_from_bed_path() {
+        let path = std::env::temp_dir().join(format!("paf-mask-{}.bed", std::process::id()));
+        std::fs::write(&path, BED).unwrap();
+        let mask = RegionMask::from_bed_path(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(in_regions(mask.clone())(&record("chr2", 5, 20)));
+        assert!(!not_in_regions(mask)(&record("chr2", 5, 20)));
+    }
+}
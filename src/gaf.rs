@@ -0,0 +1,630 @@
+/*!
+Read and write GAF, the graph alignment format of vg and GraphAligner.
+
+GAF has the columns of PAF, except that the target is a path through a
+graph, such as `>s1<s2>s3`: segment `s1` forward, then `s2` reversed, then
+`s3` forward. The target length and coordinates are along the path.
+
+```
+use std::collections::HashMap;
+
+use paf::gaf::GafReader;
+
+let gaf = b"read\t20\t0\t20\t+\t>s1<s2\t30\t5\t25\t20\t20\t60\tcg:Z:20=\n";
+let mut reader = GafReader::from_reader(&gaf[..]);
+let record = reader.records().next().unwrap().unwrap();
+assert_eq!(record.path().to_string(), ">s1<s2");
+
+// the same alignment, as a PAF record against each segment
+let lengths = HashMap::from([("s1".to_string(), 10), ("s2".to_string(), 20)]);
+let records = record.to_paf_per_segment(&lengths).unwrap();
+assert_eq!(records[0].target_name(), "s1");
+assert_eq!((records[1].target_start(), records[1].strand()), (5, '-'));
+```
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::result::Result as StdResult;
+use std::str::FromStr;
+
+use crate::cigar::QueryAxis;
+use crate::{Error, ErrorKind, PafRecord, Reader, ReaderBuilder, Result, Tag, Type, Writer};
+
+/// A step of a [`Path`]: a segment, and whether it is walked reversed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// The segment name.
+    pub segment: String,
+    /// Whether the path walks the reverse complement of the segment.
+    pub reverse: bool,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let orientation = if self.reverse { '<' } else { '>' };
+        write!(f, "{}{}", orientation, self.segment)
+    }
+}
+
+/// The path of a GAF record through the graph.
+///
+/// This is either oriented steps, as in `>s1<s2`, or a single stable
+/// sequence name, as in `chr1`, which is one forward step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    steps: Vec<Step>,
+    /// Whether the path is a stable name, written without orientation.
+    stable: bool,
+}
+
+impl Path {
+    /// The steps of the path, in order.
+    pub fn steps(&self) -> std::slice::Iter<'_, Step> {
+        self.steps.iter()
+    }
+
+    /// The number of steps.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Whether the path has no steps, which a parsed path never does.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a Path {
+    type Item = &'a Step;
+    type IntoIter = std::slice::Iter<'a, Step>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.steps()
+    }
+}
+
+impl FromStr for Path {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Path> {
+        let invalid = |msg: &str| {
+            Error::new(ErrorKind::ReadRecord(format!(
+                "Invalid GAF path {}: {}",
+                s, msg
+            )))
+        };
+        if s.is_empty() {
+            return Err(invalid("empty path"));
+        }
+        if !s.starts_with(['>', '<']) {
+            return Ok(Path {
+                steps: vec![Step {
+                    segment: s.to_string(),
+                    reverse: false,
+                }],
+                stable: true,
+            });
+        }
+
+        let mut steps = Vec::new();
+        let mut rest = s;
+        while let Some(orientation) = rest.chars().next() {
+            rest = &rest[1..];
+            let end = rest.find(['>', '<']).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(invalid("step without a segment name"));
+            }
+            steps.push(Step {
+                segment: rest[..end].to_string(),
+                reverse: orientation == '<',
+            });
+            rest = &rest[end..];
+        }
+        Ok(Path {
+            steps,
+            stable: false,
+        })
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.stable {
+            return write!(f, "{}", self.steps[0].segment);
+        }
+        for step in &self.steps {
+            write!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+/// A GAF record.
+///
+/// The columns and tags are held as a [`PafRecord`], whose target name is
+/// the text of the path and whose target length and coordinates are along
+/// the path.
+#[derive(Debug)]
+pub struct GafRecord {
+    record: PafRecord,
+    path: Path,
+}
+
+impl GafRecord {
+    /// The record as PAF, for the query, the alignment statistics and the
+    /// tags.
+    pub fn record(&self) -> &PafRecord {
+        &self.record
+    }
+
+    /// Drop the parsed path and return the record as PAF.
+    pub fn into_record(self) -> PafRecord {
+        self.record
+    }
+
+    /// The path through the graph.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The length of the path.
+    pub fn path_len(&self) -> u32 {
+        self.record.target_len()
+    }
+
+    /// The start of the alignment along the path (0-based).
+    pub fn path_start(&self) -> u32 {
+        self.record.target_start()
+    }
+
+    /// The end of the alignment along the path (0-based).
+    pub fn path_end(&self) -> u32 {
+        self.record.target_end()
+    }
+
+    /// The alignment projected onto each segment of the path it covers, as
+    /// PAF records against the segments, in path order.
+    ///
+    /// A step walked reversed gives a record on the opposite strand. Where
+    /// the record has a valid cg tag, each record has the part of it over
+    /// its segment, and residue matches counted from it if it has `=` and
+    /// `X` operations; otherwise the query and the statistics are divided
+    /// in proportion along the path. Other tags describe the whole
+    /// alignment, so are left off.
+    ///
+    /// Segments missing from `segment_lengths` are passed over, though the
+    /// length of a single missing segment is worked out from the path
+    /// length. A segment is only projected onto if its place along the
+    /// path is known, from the lengths of the steps before or after it. It
+    /// is an error for the lengths to add up to other than the path length,
+    /// for the coordinates to be out of order, or for the cg tag not to span
+    /// the alignment.
+    pub fn to_paf_per_segment(
+        &self,
+        segment_lengths: &HashMap<String, u32>,
+    ) -> Result<Vec<PafRecord>> {
+        let record = &self.record;
+        let steps = &self.path.steps;
+        let path_len = record.target_len() as u64;
+        let invalid = |msg: String| {
+            Error::new(ErrorKind::InvalidRecord(format!(
+                "cannot project {} onto segments: {}",
+                record.query_name(),
+                msg
+            )))
+        };
+
+        let mut lengths: Vec<Option<u64>> = steps
+            .iter()
+            .map(|step| segment_lengths.get(&step.segment).map(|&len| len as u64))
+            .collect();
+        let known: u64 = lengths.iter().flatten().sum();
+        let missing: Vec<usize> = (0..steps.len()).filter(|&i| lengths[i].is_none()).collect();
+        if (missing.is_empty() && known != path_len) || known > path_len {
+            return Err(invalid(format!(
+                "segment lengths add up to {}, not the path length {}",
+                known, path_len
+            )));
+        }
+        if let [i] = missing[..] {
+            lengths[i] = Some(path_len - known);
+        }
+
+        // each step's start along the path, from the steps before it or
+        // failing that from those after it
+        let mut starts = vec![None; steps.len()];
+        let mut offset = Some(0);
+        for (start, len) in starts.iter_mut().zip(&lengths) {
+            *start = offset;
+            offset = offset.zip(*len).map(|(offset, len)| offset + len);
+        }
+        let mut end = Some(path_len);
+        for (start, len) in starts.iter_mut().zip(&lengths).rev() {
+            end = end.zip(*len).and_then(|(end, len)| end.checked_sub(len));
+            if start.is_none() {
+                *start = end;
+            }
+        }
+
+        let cigar = record.cigar().transpose()?;
+        let (path_start, path_end) = (record.target_start() as u64, record.target_end() as u64);
+        let path_span = record.coordinate_sub(record.target_end(), record.target_start())? as u64;
+        let query_span = record.coordinate_sub(record.query_end(), record.query_start())? as u64;
+        if let Some(cigar) = &cigar {
+            if cigar.target_len() != path_span || cigar.query_len() != query_span {
+                return Err(invalid(format!(
+                    "cg tag spans {} path and {} query bases, not {} and {}",
+                    cigar.target_len(),
+                    cigar.query_len(),
+                    path_span,
+                    query_span
+                )));
+            }
+        }
+        let axis = QueryAxis::of(record);
+
+        let mut records = Vec::new();
+        for ((step, start), len) in steps.iter().zip(starts).zip(lengths) {
+            let (Some(start), Some(len)) = (start, len) else {
+                continue;
+            };
+            let (a, b) = (start.max(path_start), (start + len).min(path_end));
+            if a >= b {
+                continue;
+            }
+            // offsets along the alignment
+            let (from, to) = (a - path_start, b - path_start);
+
+            let mut optional = HashMap::new();
+            let (query_from, query_to, matches, block_len) = match &cigar {
+                Some(cigar) => {
+                    let query_from = cigar.split_at_target(from).0.query_len();
+                    let piece = cigar.slice_target(from..to);
+                    let block_len = piece.block_len();
+                    let matches = piece.stats().exact_matches.unwrap_or_else(|| {
+                        scale(
+                            record.residue_matches() as u64,
+                            block_len,
+                            record.alignment_block_len() as u64,
+                        )
+                    });
+                    let piece = if step.reverse {
+                        piece.reversed()
+                    } else {
+                        piece
+                    };
+                    optional.insert("cg".to_string(), Tag::cg(Type::String(piece.to_string())));
+                    (
+                        query_from,
+                        query_from + piece.query_len(),
+                        matches,
+                        block_len,
+                    )
+                }
+                None => (
+                    scale(query_span, from, path_span),
+                    scale(query_span, to, path_span),
+                    scale(record.residue_matches() as u64, to - from, path_span),
+                    scale(record.alignment_block_len() as u64, to - from, path_span),
+                ),
+            };
+            let (query_start, query_end) = axis.interval(query_from, query_to);
+
+            let (target_start, target_end) = if step.reverse {
+                (start + len - b, start + len - a)
+            } else {
+                (a - start, b - start)
+            };
+            let strand = match (record.strand(), step.reverse) {
+                ('+', true) => '-',
+                ('-', true) => '+',
+                (strand, _) => strand,
+            };
+            records.push(PafRecord::new(
+                record.query_name().to_string(),
+                record.query_len(),
+                query_start as u32,
+                query_end as u32,
+                strand,
+                step.segment.clone(),
+                len as u32,
+                target_start as u32,
+                target_end as u32,
+                matches as u32,
+                block_len as u32,
+                record.mapping_quality(),
+                optional,
+            ));
+        }
+        Ok(records)
+    }
+}
+
+impl TryFrom<PafRecord> for GafRecord {
+    type Error = Error;
+
+    /// Parses the target name of a record as a path.
+    fn try_from(record: PafRecord) -> Result<GafRecord> {
+        let path = record.target_name().parse()?;
+        Ok(GafRecord { record, path })
+    }
+}
+
+/// `value` scaled by `part / whole`, rounded.
+fn scale(value: u64, part: u64, whole: u64) -> u64 {
+    if whole == 0 {
+        return 0;
+    }
+    (value as f64 * part as f64 / whole as f64).round() as u64
+}
+
+/// Reads GAF records, with the mandatory columns and tags parsed as by a
+/// PAF [`Reader`].
+///
+/// Graph aligners write tags of their own, so unknown tags are kept, as
+/// [`Tag::Other`]. Build a [`Reader`] and convert it for other settings.
+pub struct GafReader<R> {
+    reader: Reader<R>,
+}
+
+impl GafReader<File> {
+    /// Creates a new GAF parser from a file path.
+    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Result<GafReader<File>> {
+        Ok(GafReader::new(File::open(path)?))
+    }
+
+    /// Creates a new GAF parser from a reader.
+    pub fn from_reader<R: io::Read>(rdr: R) -> GafReader<R> {
+        GafReader::new(rdr)
+    }
+}
+
+impl<R: io::Read> GafReader<R> {
+    /// Creates a new GAF parser from a reader, keeping unknown tags.
+    pub fn new(rdr: R) -> Self {
+        ReaderBuilder::new()
+            .keep_unknown_tags(true)
+            .from_reader(rdr)
+            .into()
+    }
+
+    /// The number of lines read so far.
+    pub fn line(&self) -> u64 {
+        self.reader.line()
+    }
+
+    /// A borrowed iterator over the records of a GAF file.
+    pub fn records(&mut self) -> GafRecordsIter<'_, R> {
+        GafRecordsIter { rdr: self }
+    }
+
+    /// Read a single record, passing over comment lines.
+    pub fn read_record(&mut self) -> Result<Option<GafRecord>> {
+        let Some(record) = self.reader.read_record()? else {
+            return Ok(None);
+        };
+        GafRecord::try_from(record)
+            .map(Some)
+            .map_err(|err| match err.kind() {
+                ErrorKind::ReadRecord(msg) => Error::new(ErrorKind::ReadRecord(format!(
+                    "{} at line {}",
+                    msg,
+                    self.reader.line()
+                ))),
+                _ => err,
+            })
+    }
+}
+
+impl<R> From<Reader<R>> for GafReader<R> {
+    /// Reads GAF with the settings of a PAF reader.
+    fn from(reader: Reader<R>) -> Self {
+        GafReader { reader }
+    }
+}
+
+/// A borrowed iterator over the records of a GAF file.
+pub struct GafRecordsIter<'r, R: 'r> {
+    rdr: &'r mut GafReader<R>,
+}
+
+impl<R: io::Read> Iterator for GafRecordsIter<'_, R> {
+    type Item = Result<GafRecord>;
+
+    fn next(&mut self) -> Option<Result<GafRecord>> {
+        self.rdr.read_record().transpose()
+    }
+}
+
+/// Writes GAF records, as a PAF [`Writer`] writes records.
+#[derive(Debug)]
+pub struct GafWriter<W: Write> {
+    writer: Writer<W>,
+}
+
+impl GafWriter<File> {
+    /// Creates a new GAF writer from a file path.
+    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Result<GafWriter<File>> {
+        Ok(Writer::from_path(path)?.into())
+    }
+}
+
+impl<W: Write> GafWriter<W> {
+    /// Creates a new GAF writer from a writer instance.
+    pub fn new(writer: W) -> Self {
+        Writer::new(writer).into()
+    }
+
+    /// Writes a single `GafRecord` to the GAF file.
+    pub fn write_record(&mut self, record: &GafRecord) -> Result<()> {
+        self.writer.write_record(&record.record)
+    }
+
+    /// Writes out the buffer and flushes the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+
+    /// Flushes and returns the underlying writer.
+    ///
+    /// If this fails, the error is returned along with this writer, so that
+    /// nothing written is lost.
+    #[allow(clippy::result_large_err)]
+    pub fn into_inner(self) -> StdResult<W, (Self, Error)> {
+        self.writer
+            .into_inner()
+            .map_err(|(writer, err)| (writer.into(), err))
+    }
+}
+
+impl<W: Write> From<Writer<W>> for GafWriter<W> {
+    /// Writes GAF with the settings of a PAF writer.
+    fn from(writer: Writer<W>) -> Self {
+        GafWriter { writer }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A read aligned across three segments, the middle one reversed. The
+    /// insertion falls at the start of the middle segment, and the deletion
+    /// within it.
+    const GAF: &str = "read\t25\t2\t23\t+\t>s1<s2>s3\t30\t5\t25\t19\t22\t60\tNM:i:3\tAS:i:10\tcg:Z:5=2I3=1D4=7=\n";
+
+    fn record(gaf: &str) -> GafRecord {
+        GafReader::from_reader(gaf.as_bytes())
+            .records()
+            .next()
+            .unwrap()
+            .unwrap()
+    }
+
+    fn lengths(lengths: &[(&str, u32)]) -> HashMap<String, u32> {
+        lengths
+            .iter()
+            .map(|&(name, len)| (name.to_string(), len))
+            .collect()
+    }
+
+    fn paf(records: &[PafRecord]) -> String {
+        let mut writer = Writer::new(Vec::new());
+        writer.write_records(records).unwrap();
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_path() {
+        let path: Path = ">s1<s2>s3".parse().unwrap();
+        let steps: Vec<_> = path
+            .steps()
+            .map(|step| (step.segment.as_str(), step.reverse))
+            .collect();
+        assert_eq!(steps, [("s1", false), ("s2", true), ("s3", false)]);
+        assert_eq!(path.to_string(), ">s1<s2>s3");
+
+        let stable: Path = "chr1".parse().unwrap();
+        assert_eq!(stable.len(), 1);
+        assert!(!stable.steps[0].reverse);
+        assert_eq!(stable.to_string(), "chr1");
+
+        for bad in ["", ">", ">s1<", ">s1<<s2"] {
+            assert!(bad.parse::<Path>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_read_write() {
+        let gaf = format!(
+            "{}read2\t10\t0\t10\t+\tchr1\t100\t20\t30\t10\t10\t0\tid:f:1\n",
+            GAF
+        );
+        let mut reader = GafReader::from_reader(gaf.as_bytes());
+        let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(records[0].path().len(), 3);
+        assert_eq!(
+            (
+                records[0].path_len(),
+                records[0].path_start(),
+                records[0].path_end()
+            ),
+            (30, 5, 25)
+        );
+        assert_eq!(records[1].path().to_string(), "chr1");
+
+        let mut writer = GafWriter::new(Vec::new());
+        for record in &records {
+            writer.write_record(record).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+            gaf
+        );
+
+        let bad = GAF.replace(">s1<s2>s3", ">s1<>s3");
+        let err = GafReader::from_reader(bad.as_bytes())
+            .read_record()
+            .unwrap_err();
+        assert!(err.to_string().contains("line 1"), "{}", err);
+    }
+
+    #[test]
+    fn test_to_paf_per_segment() {
+        let record = record(GAF);
+        let segments = record
+            .to_paf_per_segment(&lengths(&[("s1", 10), ("s2", 8), ("s3", 12)]))
+            .unwrap();
+        let expected = "\
+read\t25\t2\t7\t+\ts1\t10\t5\t10\t5\t5\t60\tcg:Z:5=
+read\t25\t7\t16\t-\ts2\t8\t0\t8\t7\t10\t60\tcg:Z:4=1D3=2I
+read\t25\t16\t23\t+\ts3\t12\t0\t7\t7\t7\t60\tcg:Z:7=
+";
+        assert_eq!(paf(&segments), expected);
+
+        // a single missing length comes from the path length
+        let inferred = record
+            .to_paf_per_segment(&lengths(&[("s1", 10), ("s3", 12)]))
+            .unwrap();
+        assert_eq!(paf(&inferred), expected);
+
+        // with two missing, only the first segment has a known place
+        let known = record.to_paf_per_segment(&lengths(&[("s1", 10)])).unwrap();
+        assert_eq!(
+            paf(&known),
+            expected.lines().next().unwrap().to_string() + "\n"
+        );
+
+        assert!(record
+            .to_paf_per_segment(&lengths(&[("s1", 10), ("s2", 8), ("s3", 13)]))
+            .is_err());
+
+        // a query interval ending before it starts, and a cg tag which does
+        // not span the query interval
+        let all = lengths(&[("s1", 10), ("s2", 8), ("s3", 12)]);
+        for gaf in [
+            GAF.replace("read\t25\t2\t23", "read\t25\t23\t2"),
+            GAF.replace("read\t25\t2\t23", "read\t25\t2\t20"),
+        ] {
+            assert!(self::record(&gaf).to_paf_per_segment(&all).is_err());
+        }
+    }
+
+    #[test]
+    fn test_to_paf_per_segment_without_cigar() {
+        let gaf = "read\t20\t0\t20\t+\t>s1<s2\t20\t0\t20\t18\t20\t60\n";
+        let segments = record(gaf)
+            .to_paf_per_segment(&lengths(&[("s1", 5), ("s2", 15)]))
+            .unwrap();
+        assert_eq!(
+            paf(&segments),
+            "\
+read\t20\t0\t5\t+\ts1\t5\t0\t5\t5\t5\t60
+read\t20\t5\t20\t-\ts2\t15\t0\t15\t14\t15\t60
+"
+        );
+    }
+}
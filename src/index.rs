@@ -0,0 +1,335 @@
+/*!
+Find the records in memory overlapping an interval.
+
+```
+use paf::index::IntervalIndex;
+use paf::Reader;
+
+let paf = b"q1\t100\t0\t100\t+\tchr1\t1000\t0\t100\t100\t100\t60\n\
+            q2\t100\t0\t100\t+\tchr1\t1000\t50\t150\t100\t100\t60\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let records = reader.records().collect::<paf::Result<Vec<_>>>().unwrap();
+let index = IntervalIndex::build(records);
+let names: Vec<_> = index.query("chr1", 90, 120).map(|r| r.query_name()).collect();
+assert_eq!(names, ["q1", "q2"]);
+assert_eq!(index.query_point("chr1", 100).count(), 1);
+```
+*/
+
+use std::collections::HashMap;
+
+use crate::convert::Space;
+use crate::PafRecord;
+
+/// Subtrees of this many levels or fewer are scanned rather than descended.
+const SCAN_LEVELS: u32 = 3;
+
+/// An interval and the index of its record.
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    start: u64,
+    end: u64,
+    /// The greatest end in the subtree rooted here.
+    max_end: u64,
+    record: usize,
+}
+
+/// The intervals on one sequence as an implicit binary search tree, as in
+/// cgranges: sorted by start, the node at index `i` is at the level of the
+/// number of trailing ones of `i`, with children `2^(level - 1)` either side
+/// of it. Each node holds the greatest end below it, so subtrees ending
+/// before a query are passed over.
+#[derive(Debug, Clone)]
+struct Tree {
+    nodes: Vec<Node>,
+    root_level: u32,
+}
+
+impl Tree {
+    fn new(mut nodes: Vec<Node>) -> Self {
+        nodes.sort_unstable_by_key(|node| (node.start, node.end, node.record));
+        let n = nodes.len();
+        if n == 0 {
+            return Tree {
+                nodes,
+                root_level: 0,
+            };
+        }
+
+        // leaves, and the end of the last node at each level, for the
+        // right children past the end of the array
+        let mut last_i = 0;
+        let mut last = 0;
+        for i in (0..n).step_by(2) {
+            nodes[i].max_end = nodes[i].end;
+            last_i = i;
+            last = nodes[i].end;
+        }
+        let mut level = 1;
+        while 1 << level <= n {
+            let x = 1 << (level - 1);
+            for i in ((x << 1) - 1..n).step_by(x << 2) {
+                let left = nodes[i - x].max_end;
+                let right = if i + x < n {
+                    nodes[i + x].max_end
+                } else {
+                    last
+                };
+                nodes[i].max_end = nodes[i].end.max(left).max(right);
+            }
+            last_i = if (last_i >> level) & 1 == 1 {
+                last_i - x
+            } else {
+                last_i + x
+            };
+            if last_i < n {
+                last = last.max(nodes[last_i].max_end);
+            }
+            level += 1;
+        }
+        Tree {
+            nodes,
+            root_level: level - 1,
+        }
+    }
+
+    /// The records of the intervals overlapping `start..end`, in order of
+    /// start.
+    fn overlapping(&self, start: u64, end: u64) -> Vec<usize> {
+        let n = self.nodes.len();
+        let mut hits = Vec::new();
+        if n == 0 || start >= end {
+            return hits;
+        }
+        // (node, level, whether its left subtree is done)
+        let mut stack = vec![((1 << self.root_level) - 1, self.root_level, false)];
+        while let Some((x, level, left_done)) = stack.pop() {
+            if level <= SCAN_LEVELS {
+                let first = x >> level << level;
+                let last = (first + (1 << (level + 1)) - 1).min(n);
+                for node in &self.nodes[first..last] {
+                    if node.start >= end {
+                        break;
+                    }
+                    if node.end > start {
+                        hits.push(node.record);
+                    }
+                }
+            } else if !left_done {
+                let left = x - (1 << (level - 1));
+                stack.push((x, level, true));
+                if left >= n || self.nodes[left].max_end > start {
+                    stack.push((left, level - 1, false));
+                }
+            } else if x < n && self.nodes[x].start < end {
+                if self.nodes[x].end > start {
+                    hits.push(self.nodes[x].record);
+                }
+                stack.push((x + (1 << (level - 1)), level - 1, false));
+            }
+        }
+        hits
+    }
+}
+
+/// Records held in memory with an interval tree for each target, or each
+/// query, for overlap queries.
+///
+/// Building takes O(n log n) time, and a query O(log n + k) for k records
+/// found. Intervals are half-open, so records which only abut an interval
+/// do not overlap it.
+#[derive(Debug)]
+pub struct IntervalIndex {
+    records: Vec<PafRecord>,
+    trees: HashMap<String, Tree>,
+}
+
+impl IntervalIndex {
+    /// Index records by their target intervals.
+    pub fn build(records: Vec<PafRecord>) -> Self {
+        Self::build_with(records, Space::Target)
+    }
+
+    /// Index records by their target or query intervals.
+    pub fn build_with(records: Vec<PafRecord>, space: Space) -> Self {
+        let mut nodes: HashMap<String, Vec<Node>> = HashMap::new();
+        for (i, record) in records.iter().enumerate() {
+            let (name, start, end) = match space {
+                Space::Target => (
+                    record.target_name(),
+                    record.target_start(),
+                    record.target_end(),
+                ),
+                Space::Query => (
+                    record.query_name(),
+                    record.query_start(),
+                    record.query_end(),
+                ),
+            };
+            let node = Node {
+                start: start as u64,
+                end: end as u64,
+                max_end: end as u64,
+                record: i,
+            };
+            match nodes.get_mut(name) {
+                Some(nodes) => nodes.push(node),
+                None => {
+                    nodes.insert(name.to_string(), vec![node]);
+                }
+            }
+        }
+        IntervalIndex {
+            records,
+            trees: nodes
+                .into_iter()
+                .map(|(name, nodes)| (name, Tree::new(nodes)))
+                .collect(),
+        }
+    }
+
+    /// The records overlapping `start..end` on the sequence `name`, in order
+    /// of their start on it.
+    pub fn query(&self, name: &str, start: u64, end: u64) -> impl Iterator<Item = &PafRecord> {
+        self.query_indices(name, start, end)
+            .into_iter()
+            .map(|i| &self.records[i])
+    }
+
+    /// The indices among the records of those [`IntervalIndex::query`]
+    /// finds.
+    pub(crate) fn query_indices(&self, name: &str, start: u64, end: u64) -> Vec<usize> {
+        match self.trees.get(name) {
+            Some(tree) => tree.overlapping(start, end),
+            None => Vec::new(),
+        }
+    }
+
+    /// The records covering base `pos` of the sequence `name`, in order of
+    /// their start on it.
+    pub fn query_point(&self, name: &str, pos: u64) -> impl Iterator<Item = &PafRecord> {
+        self.query(name, pos, pos + 1)
+    }
+
+    /// The records, in the order given.
+    pub fn records(&self) -> &[PafRecord] {
+        &self.records
+    }
+
+    /// Take back the records, in the order given.
+    pub fn into_records(self) -> Vec<PafRecord> {
+        self.records
+    }
+
+    /// The number of records.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether there are no records.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+
+    /// Records named `r` and their index, each with the same interval on
+    /// the query and on the target `chr1`.
+    fn index(intervals: &[(u32, u32)], space: Space) -> IntervalIndex {
+        let paf: String = intervals
+            .iter()
+            .enumerate()
+            .map(|(i, &(start, end))| {
+                format!(
+                    "r{}\t100000\t{}\t{}\t+\tchr1\t100000\t{}\t{}\t1\t1\t60\n",
+                    i, start, end, start, end
+                )
+            })
+            .collect();
+        let records = records(&paf);
+        IntervalIndex::build_with(records, space)
+    }
+
+    fn names<'a>(records: impl Iterator<Item = &'a PafRecord>) -> Vec<&'a str> {
+        records.map(PafRecord::query_name).collect()
+    }
+
+    #[test]
+    fn test_nested_and_overlapping() {
+        // r0 holds r1, which holds r2; r3 overlaps r0's end; r4 is apart
+        let index = index(
+            &[(100, 500), (150, 300), (200, 250), (450, 600), (700, 800)],
+            Space::Target,
+        );
+        assert_eq!(names(index.query("chr1", 210, 220)), ["r0", "r1", "r2"]);
+        assert_eq!(names(index.query("chr1", 260, 460)), ["r0", "r1", "r3"]);
+        assert_eq!(names(index.query("chr1", 0, 1000)).len(), 5);
+        assert!(names(index.query("chr1", 600, 700)).is_empty());
+        assert!(names(index.query("chr2", 0, 1000)).is_empty());
+        assert_eq!(index.len(), 5);
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let index = index(&[(100, 200), (200, 300)], Space::Target);
+        // intervals are half-open, so 200 is in r1 only
+        assert_eq!(names(index.query_point("chr1", 199)), ["r0"]);
+        assert_eq!(names(index.query_point("chr1", 200)), ["r1"]);
+        assert!(names(index.query_point("chr1", 300)).is_empty());
+        assert!(names(index.query_point("chr1", 99)).is_empty());
+        assert_eq!(names(index.query("chr1", 199, 201)), ["r0", "r1"]);
+        assert!(names(index.query("chr1", 300, 400)).is_empty());
+        assert!(names(index.query("chr1", 150, 150)).is_empty());
+    }
+
+    #[test]
+    fn test_query_space() {
+        let paf = "\
+q\t1000\t0\t100\t+\tt\t1000\t500\t600\t100\t100\t60
+q\t1000\t100\t200\t-\tu\t1000\t0\t100\t100\t100\t60
+";
+        let records = records(paf);
+        let index = IntervalIndex::build_with(records, Space::Query);
+        let targets: Vec<_> = index
+            .query("q", 50, 150)
+            .map(PafRecord::target_name)
+            .collect();
+        assert_eq!(targets, ["t", "u"]);
+        assert_eq!(index.query("t", 0, 1000).count(), 0);
+    }
+
+    #[test]
+    fn test_against_scan() {
+        // enough intervals for several levels of the tree, of varied lengths
+        let intervals: Vec<(u32, u32)> = (0..1000u32)
+            .map(|i| {
+                let start = (i * 7919) % 50000;
+                (start, start + 1 + (i * 104729) % 3000)
+            })
+            .collect();
+        let index = index(&intervals, Space::Target);
+        for q in 0..200u64 {
+            let start = (q * 2503) % 55000;
+            let end = start + (q * 613) % 2000;
+            let mut expected: Vec<usize> = (0..intervals.len())
+                .filter(|&i| (intervals[i].0 as u64) < end && (intervals[i].1 as u64) > start)
+                .collect();
+            expected.sort_by_key(|&i| intervals[i]);
+            let found: Vec<usize> = index
+                .query("chr1", start, end)
+                .map(|record| record.query_name()[1..].parse().unwrap())
+                .collect();
+            let mut sorted = found.clone();
+            sorted.sort_by_key(|&i| (intervals[i], i));
+            assert_eq!(found, sorted);
+            let mut found = found;
+            found.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(found, expected);
+        }
+    }
+}
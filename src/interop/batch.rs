@@ -0,0 +1,528 @@
+/*!
+Arrow record batches of records.
+
+```
+use paf::interop::{from_record_batch, ArrowBatcher};
+use paf::Reader;
+
+let paf = b"q1\t1000\t0\t500\t+\tchr1\t5000\t1000\t1500\t490\t500\t60\tNM:i:10\n\
+            q2\t1000\t0\t800\t-\tchr2\t5000\t0\t800\t700\t800\t5\n\
+            q3\t1000\t0\t300\t+\tchr2\t5000\t900\t1200\t300\t300\t60\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let batches: Vec<_> = ArrowBatcher::new(reader.records(), 2)
+    .map(Result::unwrap)
+    .collect();
+assert_eq!(batches.len(), 2);
+assert_eq!(batches[0].num_rows(), 2);
+
+let records = from_record_batch(&batches[0]).unwrap();
+assert_eq!(records[0].nm(), Some(10));
+```
+*/
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::array::{
+    Array, ArrayRef, AsArray, MapBuilder, StringArray, StringBuilder, UInt64Builder, UInt8Builder,
+};
+use arrow::datatypes::{DataType, Field, Fields, Schema, SchemaRef, UInt64Type, UInt8Type};
+use arrow::error::ArrowError;
+use arrow::record_batch::{RecordBatch, RecordBatchReader};
+
+use crate::{PafRecord, Result, Tag, TagOrder, Type, TypeCode};
+
+/// The schema of the batches of [`ArrowBatcher`].
+///
+/// The columns, none of them nullable but `cg`, are:
+///
+/// | column | type |
+/// |---|---|
+/// | `query_name` | `Utf8` |
+/// | `query_len`, `query_start`, `query_end` | `UInt64` |
+/// | `strand` | `Utf8`, `+` or `-` |
+/// | `target_name` | `Utf8` |
+/// | `target_len`, `target_start`, `target_end` | `UInt64` |
+/// | `residue_matches`, `alignment_block_len` | `UInt64` |
+/// | `mapping_quality` | `UInt8` |
+/// | `tags` | `Map<Utf8, Utf8>` |
+/// | `cg` | `Utf8`, nullable |
+///
+/// The `tags` map holds an entry for each optional field of the record but
+/// cg, from the name of the tag to its type and value as in a PAF file, as
+/// `NM` to `i:10`, in the order a [`Writer`](crate::Writer) writes them
+/// by default. A record without a tag has no entry for it, and a record
+/// without tags an empty map. The cg tag, longer than the rest of a record
+/// together, has a column of its own, null for records without it, so that
+/// readers of columnar files can leave it out. The schema stays the same
+/// from one release to the next, bar additions at the end.
+pub fn arrow_schema() -> SchemaRef {
+    let count = |name| Field::new(name, DataType::UInt64, false);
+    let text = |name| Field::new(name, DataType::Utf8, false);
+    let entries = Field::new(
+        "entries",
+        DataType::Struct(Fields::from(vec![
+            Field::new("keys", DataType::Utf8, false),
+            Field::new("values", DataType::Utf8, true),
+        ])),
+        false,
+    );
+    Arc::new(Schema::new(vec![
+        text("query_name"),
+        count("query_len"),
+        count("query_start"),
+        count("query_end"),
+        text("strand"),
+        text("target_name"),
+        count("target_len"),
+        count("target_start"),
+        count("target_end"),
+        count("residue_matches"),
+        count("alignment_block_len"),
+        Field::new("mapping_quality", DataType::UInt8, false),
+        Field::new("tags", DataType::Map(Arc::new(entries), false), false),
+        Field::new("cg", DataType::Utf8, true),
+    ]))
+}
+
+/// Turns records into Arrow `RecordBatch`es of [`arrow_schema`], a batch
+/// of up to a set number of records at a time, so that a file of any size
+/// streams through.
+///
+/// This is a `RecordBatchReader`, as DataFusion and Arrow IPC writers take.
+/// An error reading a record is returned as an `ExternalError` in place of
+/// the batch it would have been in, and the records read for that batch are
+/// dropped; later batches carry on after it.
+#[derive(Debug)]
+pub struct ArrowBatcher<I> {
+    records: I,
+    batch_size: usize,
+    schema: SchemaRef,
+}
+
+impl<I> ArrowBatcher<I>
+where
+    I: Iterator<Item = Result<PafRecord>>,
+{
+    /// Batches `records`, `batch_size` to a batch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `batch_size` is zero.
+    pub fn new<T>(records: T, batch_size: usize) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        assert!(batch_size > 0, "batch_size must be positive");
+        ArrowBatcher {
+            records: records.into_iter(),
+            batch_size,
+            schema: arrow_schema(),
+        }
+    }
+}
+
+impl<I> Iterator for ArrowBatcher<I>
+where
+    I: Iterator<Item = Result<PafRecord>>,
+{
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut columns = Columns::new(self.batch_size);
+        for record in self.records.by_ref().take(self.batch_size) {
+            match record {
+                Ok(record) => columns.push(&record),
+                Err(err) => return Some(Err(ArrowError::ExternalError(Box::new(err)))),
+            }
+        }
+        if columns.rows == 0 {
+            return None;
+        }
+        Some(columns.finish(self.schema.clone()))
+    }
+}
+
+impl<I> RecordBatchReader for ArrowBatcher<I>
+where
+    I: Iterator<Item = Result<PafRecord>>,
+{
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+/// The columns of a batch as it is built.
+struct Columns {
+    rows: usize,
+    query_name: StringBuilder,
+    query_len: UInt64Builder,
+    query_start: UInt64Builder,
+    query_end: UInt64Builder,
+    strand: StringBuilder,
+    target_name: StringBuilder,
+    target_len: UInt64Builder,
+    target_start: UInt64Builder,
+    target_end: UInt64Builder,
+    residue_matches: UInt64Builder,
+    alignment_block_len: UInt64Builder,
+    mapping_quality: UInt8Builder,
+    tags: MapBuilder<StringBuilder, StringBuilder>,
+    cg: StringBuilder,
+}
+
+impl Columns {
+    fn new(capacity: usize) -> Columns {
+        let count = || UInt64Builder::with_capacity(capacity);
+        let text = || StringBuilder::with_capacity(capacity, capacity * 16);
+        Columns {
+            rows: 0,
+            query_name: text(),
+            query_len: count(),
+            query_start: count(),
+            query_end: count(),
+            strand: text(),
+            target_name: text(),
+            target_len: count(),
+            target_start: count(),
+            target_end: count(),
+            residue_matches: count(),
+            alignment_block_len: count(),
+            mapping_quality: UInt8Builder::with_capacity(capacity),
+            tags: MapBuilder::new(None, StringBuilder::new(), StringBuilder::new()),
+            cg: StringBuilder::new(),
+        }
+    }
+
+    fn push(&mut self, record: &PafRecord) {
+        self.rows += 1;
+        self.query_name.append_value(record.query_name());
+        self.query_len.append_value(record.query_len() as u64);
+        self.query_start.append_value(record.query_start() as u64);
+        self.query_end.append_value(record.query_end() as u64);
+        self.strand
+            .append_value(record.strand().encode_utf8(&mut [0; 4]));
+        self.target_name.append_value(record.target_name());
+        self.target_len.append_value(record.target_len() as u64);
+        self.target_start.append_value(record.target_start() as u64);
+        self.target_end.append_value(record.target_end() as u64);
+        self.residue_matches
+            .append_value(record.residue_matches() as u64);
+        self.alignment_block_len
+            .append_value(record.alignment_block_len() as u64);
+        self.mapping_quality.append_value(record.mapping_quality());
+
+        let mut cg = None;
+        let mut fields: Vec<_> = record.optional_fields().iter().collect();
+        TagOrder::default().sort(&mut fields);
+        for (name, tag) in fields {
+            let value = match tag {
+                Tag::cg(value) => {
+                    cg = Some(value.to_string());
+                    continue;
+                }
+                Tag::tp(tp) => format!("A:{}", tp.as_char()),
+                Tag::ts(ts) => format!("A:{}", ts.as_char()),
+                tag => match tag.value() {
+                    Some(value) => format!("{}:{}", value.code(), value),
+                    None => continue,
+                },
+            };
+            self.tags.keys().append_value(name);
+            self.tags.values().append_value(value);
+        }
+        // appending a valid entry cannot fail, as keys and values match
+        self.tags.append(true).unwrap();
+        self.cg.append_option(cg);
+    }
+
+    fn finish(mut self, schema: SchemaRef) -> std::result::Result<RecordBatch, ArrowError> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.query_name.finish()),
+            Arc::new(self.query_len.finish()),
+            Arc::new(self.query_start.finish()),
+            Arc::new(self.query_end.finish()),
+            Arc::new(self.strand.finish()),
+            Arc::new(self.target_name.finish()),
+            Arc::new(self.target_len.finish()),
+            Arc::new(self.target_start.finish()),
+            Arc::new(self.target_end.finish()),
+            Arc::new(self.residue_matches.finish()),
+            Arc::new(self.alignment_block_len.finish()),
+            Arc::new(self.mapping_quality.finish()),
+            Arc::new(self.tags.finish()),
+            Arc::new(self.cg.finish()),
+        ];
+        RecordBatch::try_new(schema, columns)
+    }
+}
+
+/// Turns the rows of a batch of [`arrow_schema`] back into records.
+///
+/// Columns are found by name, so others may come between them, and the cg
+/// column may be left out, for records without the tag. It is an
+/// error for a column to be missing or of another type, for a value to be
+/// null or out of range for the field of a record, or for a tag not to
+/// parse.
+pub fn from_record_batch(batch: &RecordBatch) -> std::result::Result<Vec<PafRecord>, ArrowError> {
+    let column = |name: &str| {
+        batch
+            .column_by_name(name)
+            .ok_or_else(|| invalid(format!("no column {}", name)))
+    };
+    let text = |name: &str| -> std::result::Result<&StringArray, ArrowError> {
+        column(name)?
+            .as_string_opt::<i32>()
+            .ok_or_else(|| invalid(format!("{} is not Utf8", name)))
+    };
+    let counts = |name: &'static str| {
+        column(name)?
+            .as_primitive_opt::<UInt64Type>()
+            .map(|array| (name, array))
+            .ok_or_else(|| invalid(format!("{} is not UInt64", name)))
+    };
+    let query_name = text("query_name")?;
+    let strand = text("strand")?;
+    let target_name = text("target_name")?;
+    let numbers = [
+        counts("query_len")?,
+        counts("query_start")?,
+        counts("query_end")?,
+        counts("target_len")?,
+        counts("target_start")?,
+        counts("target_end")?,
+        counts("residue_matches")?,
+        counts("alignment_block_len")?,
+    ];
+    let mapping_quality = column("mapping_quality")?
+        .as_primitive_opt::<UInt8Type>()
+        .ok_or_else(|| invalid("mapping_quality is not UInt8".to_string()))?;
+    let tags = column("tags")?
+        .as_map_opt()
+        .ok_or_else(|| invalid("tags is not a map".to_string()))?;
+    let (keys, values) = (
+        tags.keys().as_string_opt::<i32>(),
+        tags.values().as_string_opt::<i32>(),
+    );
+    let (Some(keys), Some(values)) = (keys, values) else {
+        return Err(invalid("tags is not a map of Utf8 to Utf8".to_string()));
+    };
+    let cg = match batch.column_by_name("cg") {
+        Some(cg) => Some(
+            cg.as_string_opt::<i32>()
+                .ok_or_else(|| invalid("cg is not Utf8".to_string()))?,
+        ),
+        None => None,
+    };
+
+    let mut records = Vec::with_capacity(batch.num_rows());
+    for row in 0..batch.num_rows() {
+        let null = |name: &str| invalid(format!("{} is null in row {}", name, row));
+        let mut fields = [0u32; 8];
+        for (field, (name, array)) in fields.iter_mut().zip(&numbers) {
+            if array.is_null(row) {
+                return Err(null(name));
+            }
+            *field = u32::try_from(array.value(row)).map_err(|_| {
+                invalid(format!(
+                    "{} of {} in row {} is out of range",
+                    name,
+                    array.value(row),
+                    row
+                ))
+            })?;
+        }
+        if mapping_quality.is_null(row) {
+            return Err(null("mapping_quality"));
+        }
+
+        let mut optional = HashMap::new();
+        if tags.is_valid(row) {
+            let offsets = tags.value_offsets();
+            for entry in offsets[row] as usize..offsets[row + 1] as usize {
+                let name = keys.value(entry);
+                if values.is_null(entry) {
+                    continue;
+                }
+                let tag = tag(name, values.value(entry))
+                    .map_err(|err| ArrowError::ExternalError(Box::new(err)))?;
+                optional.insert(name.to_string(), tag);
+            }
+        }
+        if let Some(cg) = cg.filter(|cg| cg.is_valid(row)) {
+            let tag = Tag::cg(Type::String(cg.value(row).to_string()));
+            optional.insert(crate::tags::CG.to_string(), tag);
+        }
+
+        let strand = text_value(strand, row, "strand")?;
+        records.push(PafRecord::new(
+            text_value(query_name, row, "query_name")?.to_string(),
+            fields[0],
+            fields[1],
+            fields[2],
+            strand.chars().next().ok_or_else(|| null("strand"))?,
+            text_value(target_name, row, "target_name")?.to_string(),
+            fields[3],
+            fields[4],
+            fields[5],
+            fields[6],
+            fields[7],
+            mapping_quality.value(row),
+            optional,
+        ));
+    }
+    Ok(records)
+}
+
+/// The tag `name` from its type and value, as `i:10`.
+fn tag(name: &str, typed: &str) -> Result<Tag> {
+    let mut chars = typed.chars();
+    let value = match (chars.next(), chars.next(), chars.as_str()) {
+        (Some(code), Some(':'), value) if TypeCode::from_char(code).is_some() => {
+            Type::from_typed_str(code, value)?
+        }
+        (Some(code), Some(':'), value) => Type::raw(code, value),
+        _ => {
+            return Err(crate::Error::new(crate::ErrorKind::InvalidTag(format!(
+                "tag {} has no type: {}",
+                name, typed
+            ))))
+        }
+    };
+    super::tag(name, value)
+}
+
+/// The text in `row` of a column which cannot hold nulls.
+fn text_value<'a>(
+    array: &'a StringArray,
+    row: usize,
+    name: &str,
+) -> std::result::Result<&'a str, ArrowError> {
+    if array.is_null(row) {
+        return Err(invalid(format!("{} is null in row {}", name, row)));
+    }
+    Ok(array.value(row))
+}
+
+fn invalid(message: String) -> ArrowError {
+    ArrowError::InvalidArgumentError(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::tests::PAF_RECORD_1;
+    use crate::{Reader, Writer};
+
+    const PAF: &str = "\
+q1\t1000\t0\t500\t+\tchr1\t5000\t1000\t1500\t490\t500\t60\tNM:i:10\ttp:A:P\tde:f:0.02\tcg:Z:500M
+q2\t1000\t0\t800\t-\tchr2\t5000\t0\t800\t700\t800\t5
+q3\t1000\t0\t300\t+\tchr2\t5000\t900\t1200\t300\t300\t60\ttp:A:S
+";
+
+    fn batches(paf: &[u8], batch_size: usize) -> Vec<RecordBatch> {
+        let mut reader = Reader::from_reader(paf);
+        ArrowBatcher::new(reader.records(), batch_size)
+            .map(|batch| batch.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_schema() {
+        let names: Vec<_> = arrow_schema()
+            .fields()
+            .iter()
+            .map(|f| format!("{}:{}", f.name(), f.data_type()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "query_name:Utf8",
+                "query_len:UInt64",
+                "query_start:UInt64",
+                "query_end:UInt64",
+                "strand:Utf8",
+                "target_name:Utf8",
+                "target_len:UInt64",
+                "target_start:UInt64",
+                "target_end:UInt64",
+                "residue_matches:UInt64",
+                "alignment_block_len:UInt64",
+                "mapping_quality:UInt8",
+                "tags:Map(Field { name: \"entries\", data_type: Struct([Field { name: \"keys\", \
+                 data_type: Utf8, nullable: false, dict_id: 0, dict_is_ordered: false, \
+                 metadata: {} }, Field { name: \"values\", data_type: Utf8, nullable: true, \
+                 dict_id: 0, dict_is_ordered: false, metadata: {} }]), nullable: false, \
+                 dict_id: 0, dict_is_ordered: false, metadata: {} }, false)",
+                "cg:Utf8",
+            ]
+        );
+        // the same whichever tags the records have
+        for batch in batches(PAF.as_bytes(), 1)
+            .into_iter()
+            .chain(batches(PAF_RECORD_1, 10))
+        {
+            assert_eq!(batch.schema(), arrow_schema());
+        }
+    }
+
+    #[test]
+    fn test_missing_tags() {
+        let batch = &batches(PAF.as_bytes(), 10)[0];
+        assert_eq!(batch.num_rows(), 3);
+        let tags = batch.column_by_name("tags").unwrap().as_map();
+        assert_eq!(tags.null_count(), 0);
+        let entries = |row: usize| -> Vec<String> {
+            let entry = tags.value(row);
+            let (keys, values) = (entry.column(0).as_string::<i32>(), entry.column(1));
+            (0..entry.len())
+                .map(|i| format!("{}={}", keys.value(i), values.as_string::<i32>().value(i)))
+                .collect()
+        };
+        assert_eq!(entries(0), ["NM=i:10", "tp=A:P", "de=f:0.02"]);
+        let cg = batch.column_by_name("cg").unwrap().as_string::<i32>();
+        assert_eq!(cg.value(0), "500M");
+        assert!(cg.is_null(1) && cg.is_null(2));
+        assert!(entries(1).is_empty());
+        assert_eq!(entries(2), ["tp=A:S"]);
+
+        let records = from_record_batch(batch).unwrap();
+        assert_eq!(records[1].nm(), None);
+        assert!(records[1].optional_fields().is_empty());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let batches = batches(PAF.as_bytes(), 2);
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            [2, 1]
+        );
+        let mut out = Writer::new(Vec::new());
+        for batch in &batches {
+            for record in from_record_batch(batch).unwrap() {
+                out.write_record(&record).unwrap();
+            }
+        }
+        assert_eq!(String::from_utf8(out.into_inner().unwrap()).unwrap(), PAF);
+
+        // columns found by name, and checked, and cg left out
+        let batch = batches[0].project(&[12, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+        let records = from_record_batch(&batch.unwrap()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].nm(), Some(10));
+        assert!(records[0].cg().is_none());
+        let batch = batches[0].project(&[0, 1, 2, 3, 5, 6, 7, 8, 9, 10, 11, 12]);
+        assert!(from_record_batch(&batch.unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_read_error() {
+        let paf = format!("{}bad\n", PAF);
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        let results: Vec<_> = ArrowBatcher::new(reader.records(), 3).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().num_rows(), 3);
+        assert!(matches!(results[1], Err(ArrowError::ExternalError(_))));
+    }
+}
@@ -0,0 +1,232 @@
+/*!
+Parquet files of records, with the columns of the Arrow schema.
+
+```no_run
+use paf::interop::{read_parquet_without_cigar, write_parquet, ParquetOptions};
+use paf::Reader;
+
+let mut reader = Reader::from_path("alignments.paf").unwrap();
+write_parquet("alignments.parquet", reader.records(), &ParquetOptions::default()).unwrap();
+
+// leave out the cg column, for a faster read
+for record in read_parquet_without_cigar("alignments.parquet").unwrap() {
+    let record = record.unwrap();
+    assert!(record.cg().is_none());
+}
+```
+*/
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use parquet::arrow::arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder};
+use parquet::arrow::{ArrowWriter, ProjectionMask};
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use super::batch::{arrow_schema, from_record_batch, ArrowBatcher};
+use crate::{Error, ErrorKind, PafRecord, Result};
+
+/// The records to a batch as they are written and read.
+const BATCH_SIZE: usize = 8192;
+
+/// How [`write_parquet`] writes a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParquetOptions {
+    /// The compression of each column.
+    pub compression: Compression,
+    /// The most records in a row group.
+    pub row_group_size: usize,
+}
+
+impl Default for ParquetOptions {
+    /// Snappy compression, and row groups of up to a million records, as
+    /// Parquet's defaults.
+    fn default() -> Self {
+        ParquetOptions {
+            compression: Compression::SNAPPY,
+            row_group_size: 1024 * 1024,
+        }
+    }
+}
+
+/// Writes `records` to a Parquet file at `path`, with the columns of
+/// [`arrow_schema`](super::arrow_schema), returning the number of records
+/// written.
+///
+/// Records stream through a batch at a time, so the file may be of any
+/// size. It is an error for a record not to read, as well as for the file
+/// not to write; the file is left incomplete.
+pub fn write_parquet<P, I>(path: P, records: I, options: &ParquetOptions) -> Result<u64>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = Result<PafRecord>>,
+{
+    let properties = WriterProperties::builder()
+        .set_compression(options.compression)
+        .set_max_row_group_size(options.row_group_size)
+        .build();
+    let file = File::create(path)?;
+    let mut writer =
+        ArrowWriter::try_new(file, arrow_schema(), Some(properties)).map_err(write_error)?;
+
+    // stop at the first record which fails to read, and return its error
+    let mut failed = None;
+    let mut written = 0;
+    let records = records.into_iter().map_while(|record| match record {
+        Ok(record) => Some(Ok(record)),
+        Err(err) => {
+            failed = Some(err);
+            None
+        }
+    });
+    for batch in ArrowBatcher::new(records, BATCH_SIZE) {
+        let batch = batch.map_err(write_error)?;
+        written += batch.num_rows() as u64;
+        writer.write(&batch).map_err(write_error)?;
+    }
+    if let Some(err) = failed {
+        return Err(err);
+    }
+    writer.close().map_err(write_error)?;
+    Ok(written)
+}
+
+/// Reads the records of a Parquet file written by [`write_parquet`].
+pub fn read_parquet<P: AsRef<Path>>(path: P) -> Result<ParquetRecords> {
+    ParquetRecords::open(path, true)
+}
+
+/// Reads the records of a Parquet file written by [`write_parquet`],
+/// leaving out the cg column, so the records have no cg tag. This reads
+/// much less of a file of records with cg tags.
+pub fn read_parquet_without_cigar<P: AsRef<Path>>(path: P) -> Result<ParquetRecords> {
+    ParquetRecords::open(path, false)
+}
+
+/// An iterator over the records of a Parquet file, from [`read_parquet`]
+/// or [`read_parquet_without_cigar`].
+pub struct ParquetRecords {
+    batches: ParquetRecordBatchReader,
+    records: std::vec::IntoIter<PafRecord>,
+}
+
+impl ParquetRecords {
+    fn open<P: AsRef<Path>>(path: P, cigar: bool) -> Result<ParquetRecords> {
+        let builder =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(path)?).map_err(read_error)?;
+        let columns: Vec<usize> = builder
+            .schema()
+            .fields()
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| cigar || field.name() != "cg")
+            .map(|(i, _)| i)
+            .collect();
+        let mask = ProjectionMask::roots(builder.parquet_schema(), columns);
+        let batches = builder
+            .with_projection(mask)
+            .with_batch_size(BATCH_SIZE)
+            .build()
+            .map_err(read_error)?;
+        Ok(ParquetRecords {
+            batches,
+            records: Vec::new().into_iter(),
+        })
+    }
+}
+
+impl Iterator for ParquetRecords {
+    type Item = Result<PafRecord>;
+
+    fn next(&mut self) -> Option<Result<PafRecord>> {
+        loop {
+            if let Some(record) = self.records.next() {
+                return Some(Ok(record));
+            }
+            let batch = match self.batches.next()? {
+                Ok(batch) => batch,
+                Err(err) => return Some(Err(read_error(err))),
+            };
+            match from_record_batch(&batch) {
+                Ok(records) => self.records = records.into_iter(),
+                Err(err) => return Some(Err(read_error(err))),
+            }
+        }
+    }
+}
+
+fn write_error<E>(err: E) -> Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    Error::from(io::Error::other(err))
+}
+
+fn read_error<E: std::fmt::Display>(err: E) -> Error {
+    Error::new(ErrorKind::ReadRecord(format!(
+        "Invalid Parquet file: {}",
+        err
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::process;
+
+    use super::*;
+    use crate::{Reader, ReaderBuilder, Writer};
+
+    /// Records with a tag of each type, and one with no tags.
+    const PAF: &str = "\
+q1\t1000\t0\t20\t+\tchr1\t5000\t100\t120\t19\t20\t60\tNM:i:1\tms:i:30\tAS:i:30\tnn:i:0\ttp:A:P\tcm:i:3\ts1:i:20\ts2:i:0\tts:A:+\tde:f:0.05\tdv:f:0.0498\tzd:i:1\trl:i:0\tcg:Z:20M\tcs:Z::5*ag:14\tMD:Z:5A14\tSA:Z:chr2,100,+,20M,60,0;\tzz:Z:kept
+q2\t1000\t0\t800\t-\tchr2\t5000\t0\t800\t700\t800\t5
+";
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("paf-parquet-test-{}-{}", name, process::id()))
+    }
+
+    fn records() -> Vec<Result<PafRecord>> {
+        let mut reader = ReaderBuilder::new()
+            .keep_unknown_tags(true)
+            .from_reader(PAF.as_bytes());
+        reader.records().collect()
+    }
+
+    fn text(records: ParquetRecords) -> String {
+        let mut out = Writer::new(Vec::new());
+        for record in records {
+            out.write_record(&record.unwrap()).unwrap();
+        }
+        String::from_utf8(out.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = path("round-trip");
+        let options = ParquetOptions {
+            compression: Compression::ZSTD(Default::default()),
+            row_group_size: 1,
+        };
+        assert_eq!(write_parquet(&path, records(), &options).unwrap(), 2);
+        assert_eq!(text(read_parquet(&path).unwrap()), PAF);
+
+        // without the cg column, the rest is as it was
+        let pruned = text(read_parquet_without_cigar(&path).unwrap());
+        assert_eq!(pruned, PAF.replace("\tcg:Z:20M", ""));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let path = path("errors");
+        let mut reader = Reader::from_reader(&b"bad\n"[..]);
+        assert!(write_parquet(&path, reader.records(), &ParquetOptions::default()).is_err());
+        std::fs::write(&path, PAF).unwrap();
+        assert!(read_parquet(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
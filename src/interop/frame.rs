@@ -0,0 +1,358 @@
+/*!
+Polars data frames of records.
+
+```
+use paf::interop::{from_dataframe, to_dataframe, DataFrameOptions};
+use paf::Reader;
+use polars::prelude::*;
+
+let paf = b"q1\t1000\t0\t500\t+\tchr1\t5000\t1000\t1500\t490\t500\t60\tNM:i:10\n\
+            q2\t1000\t0\t800\t-\tchr2\t5000\t0\t800\t700\t800\t5\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let options = DataFrameOptions {
+    tags: vec!["NM".to_string()],
+};
+let df = to_dataframe(reader.records(), &options).unwrap();
+assert_eq!(df.shape(), (2, 13));
+
+// keep the records with mapping quality over 20
+let mapq = df.column("mapping_quality").unwrap().as_materialized_series();
+let filtered = df.filter(&mapq.gt(20).unwrap()).unwrap();
+let records = from_dataframe(&filtered).unwrap();
+assert_eq!(records.len(), 1);
+assert_eq!(records[0].nm(), Some(10));
+```
+*/
+
+use std::collections::HashMap;
+
+use polars::chunked_array::builder::CategoricalChunkedBuilder;
+use polars::prelude::*;
+
+use crate::{PafRecord, Result, Tag, TagKind, Type, TypeCode};
+
+/// The names of the columns of the mandatory fields, in order.
+const MANDATORY: [&str; 12] = [
+    "query_name",
+    "query_len",
+    "query_start",
+    "query_end",
+    "strand",
+    "target_name",
+    "target_len",
+    "target_start",
+    "target_end",
+    "residue_matches",
+    "alignment_block_len",
+    "mapping_quality",
+];
+
+/// Which tags [`to_dataframe`] makes columns of.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataFrameOptions {
+    /// The tags, by name, as `"NM"` or `"de"`, each a column after the
+    /// mandatory fields. The cg tag takes more memory than the rest of a
+    /// record together, so leave it out unless it is needed.
+    pub tags: Vec<String>,
+}
+
+/// The values of a tag column as it is built.
+enum TagColumn {
+    Int(Vec<Option<i64>>),
+    Float(Vec<Option<f64>>),
+    Text(Vec<Option<String>>),
+}
+
+impl TagColumn {
+    /// An empty column of the type of the tag `name`. Tags unknown to this
+    /// crate are held as text.
+    fn new(name: &str) -> TagColumn {
+        match TagKind::from_code(name).expected_type() {
+            Some(TypeCode::Int) => TagColumn::Int(Vec::new()),
+            Some(TypeCode::Float) => TagColumn::Float(Vec::new()),
+            _ => TagColumn::Text(Vec::new()),
+        }
+    }
+
+    fn push(&mut self, tag: Option<&Tag>) {
+        match self {
+            TagColumn::Int(values) => {
+                values.push(tag.and_then(|t| match t.value() {
+                    Some(Type::Int(v)) => Some(*v),
+                    _ => None,
+                }));
+            }
+            TagColumn::Float(values) => values.push(tag.and_then(|t| t.value()?.as_f64())),
+            TagColumn::Text(values) => values.push(tag.map(text)),
+        }
+    }
+
+    fn into_column(self, name: &str) -> Column {
+        let name = PlSmallStr::from(name);
+        match self {
+            TagColumn::Int(values) => Column::new(name, values),
+            TagColumn::Float(values) => Column::new(name, values),
+            TagColumn::Text(values) => Column::new(name, values),
+        }
+    }
+}
+
+/// The value of a tag as text, without its name and type.
+fn text(tag: &Tag) -> String {
+    match tag {
+        Tag::tp(tp) => tp.as_char().to_string(),
+        Tag::ts(ts) => ts.as_char().to_string(),
+        tag => tag.value().map(Type::to_string).unwrap_or_default(),
+    }
+}
+
+/// Builds a polars `DataFrame` of `records`, one row to a record.
+///
+/// There is a column for each of the twelve mandatory fields, named as the
+/// method of [`PafRecord`] which gets it, then a column for each tag in
+/// `options`, null for records without the tag. Integer and float tags
+/// make `Int64` and `Float64` columns, and others `String` columns of the
+/// value alone. The query and target names and the strand repeat across
+/// records, so are categorical columns.
+///
+/// Records are read in turn; an error reading one is returned as a
+/// `ComputeError`.
+pub fn to_dataframe<I>(records: I, options: &DataFrameOptions) -> PolarsResult<DataFrame>
+where
+    I: IntoIterator<Item = Result<PafRecord>>,
+{
+    // names go straight into categorical columns, each held once
+    let categorical = DataType::from_categories(Categories::global());
+    let names = |name: &str| {
+        CategoricalChunkedBuilder::<Categorical32Type>::new(name.into(), categorical.clone())
+    };
+    let mut query_names = names(MANDATORY[0]);
+    let mut query_lens = Vec::new();
+    let mut query_starts = Vec::new();
+    let mut query_ends = Vec::new();
+    let mut strands = names(MANDATORY[4]);
+    let mut target_names = names(MANDATORY[5]);
+    let mut target_lens = Vec::new();
+    let mut target_starts = Vec::new();
+    let mut target_ends = Vec::new();
+    let mut residue_matches = Vec::new();
+    let mut alignment_block_lens = Vec::new();
+    let mut mapping_qualities = Vec::new();
+    let mut tags: Vec<TagColumn> = options.tags.iter().map(|t| TagColumn::new(t)).collect();
+
+    for record in records {
+        let record = record.map_err(|err| polars_err!(ComputeError: "{}", err))?;
+        query_names.append_str(record.query_name())?;
+        query_lens.push(record.query_len());
+        query_starts.push(record.query_start());
+        query_ends.push(record.query_end());
+        strands.append_str(record.strand().encode_utf8(&mut [0; 4]))?;
+        target_names.append_str(record.target_name())?;
+        target_lens.push(record.target_len());
+        target_starts.push(record.target_start());
+        target_ends.push(record.target_end());
+        residue_matches.push(record.residue_matches());
+        alignment_block_lens.push(record.alignment_block_len());
+        mapping_qualities.push(record.mapping_quality());
+        for (column, name) in tags.iter_mut().zip(&options.tags) {
+            column.push(record.optional_fields().get(name));
+        }
+    }
+
+    let mut columns = vec![
+        query_names.finish().into_column(),
+        Column::new(MANDATORY[1].into(), query_lens),
+        Column::new(MANDATORY[2].into(), query_starts),
+        Column::new(MANDATORY[3].into(), query_ends),
+        strands.finish().into_column(),
+        target_names.finish().into_column(),
+        Column::new(MANDATORY[6].into(), target_lens),
+        Column::new(MANDATORY[7].into(), target_starts),
+        Column::new(MANDATORY[8].into(), target_ends),
+        Column::new(MANDATORY[9].into(), residue_matches),
+        Column::new(MANDATORY[10].into(), alignment_block_lens),
+        Column::new(MANDATORY[11].into(), mapping_qualities),
+    ];
+    for (column, name) in tags.into_iter().zip(&options.tags) {
+        columns.push(column.into_column(name));
+    }
+    DataFrame::new(columns)
+}
+
+/// Turns the rows of a `DataFrame` back into records, as to write a frame
+/// from [`to_dataframe`] out as PAF once filtered.
+///
+/// The frame needs the columns of the mandatory fields, by the names
+/// [`to_dataframe`] gives them, in any order and of any type which casts
+/// to theirs. Every other column is taken as a tag of the same name, left
+/// out where null; a `String` column of a tag unknown to this crate becomes
+/// a `Z` tag. It is a `ComputeError` for a mandatory field to be null or
+/// a tag not to parse.
+pub fn from_dataframe(df: &DataFrame) -> PolarsResult<Vec<PafRecord>> {
+    let text = |name: &str| -> PolarsResult<Series> {
+        df.column(name)?
+            .as_materialized_series()
+            .cast(&DataType::String)
+    };
+    let number = |name: &str| -> PolarsResult<Series> {
+        df.column(name)?
+            .as_materialized_series()
+            .cast(&DataType::UInt32)
+    };
+    let query_names = text("query_name")?;
+    let query_lens = number("query_len")?;
+    let query_starts = number("query_start")?;
+    let query_ends = number("query_end")?;
+    let strands = text("strand")?;
+    let target_names = text("target_name")?;
+    let target_lens = number("target_len")?;
+    let target_starts = number("target_start")?;
+    let target_ends = number("target_end")?;
+    let residue_matches = number("residue_matches")?;
+    let alignment_block_lens = number("alignment_block_len")?;
+    let mapping_qualities = df
+        .column("mapping_quality")?
+        .as_materialized_series()
+        .cast(&DataType::UInt8)?;
+
+    let tags: Vec<&Column> = df
+        .get_columns()
+        .iter()
+        .filter(|c| !MANDATORY.contains(&c.name().as_str()))
+        .collect();
+
+    let mut records = Vec::with_capacity(df.height());
+    for row in 0..df.height() {
+        let null = |name: &str| polars_err!(ComputeError: "{} is null in row {}", name, row);
+        let get_text = |series: &Series, name: &str| -> PolarsResult<String> {
+            series
+                .str()?
+                .get(row)
+                .map(str::to_string)
+                .ok_or_else(|| null(name))
+        };
+        let get_u32 = |series: &Series, name: &str| -> PolarsResult<u32> {
+            series.u32()?.get(row).ok_or_else(|| null(name))
+        };
+
+        let mut optional = HashMap::new();
+        for column in &tags {
+            let name = column.name().as_str();
+            if let Some(tag) = tag(name, column.get(row)?)? {
+                optional.insert(name.to_string(), tag);
+            }
+        }
+        let strand = get_text(&strands, "strand")?;
+        records.push(PafRecord::new(
+            get_text(&query_names, "query_name")?,
+            get_u32(&query_lens, "query_len")?,
+            get_u32(&query_starts, "query_start")?,
+            get_u32(&query_ends, "query_end")?,
+            strand.chars().next().ok_or_else(|| null("strand"))?,
+            get_text(&target_names, "target_name")?,
+            get_u32(&target_lens, "target_len")?,
+            get_u32(&target_starts, "target_start")?,
+            get_u32(&target_ends, "target_end")?,
+            get_u32(&residue_matches, "residue_matches")?,
+            get_u32(&alignment_block_lens, "alignment_block_len")?,
+            mapping_qualities
+                .u8()?
+                .get(row)
+                .ok_or_else(|| null("mapping_quality"))?,
+            optional,
+        ));
+    }
+    Ok(records)
+}
+
+/// The tag `name` holding a cell of its column, or `None` for a null.
+fn tag(name: &str, value: AnyValue) -> PolarsResult<Option<Tag>> {
+    let value = match value {
+        AnyValue::Null => return Ok(None),
+        AnyValue::Float32(v) => Type::float(v as f64),
+        AnyValue::Float64(v) => Type::float(v),
+        value if value.is_integer() => Type::Int(
+            value
+                .extract::<i64>()
+                .ok_or_else(|| polars_err!(ComputeError: "tag {} out of range", name))?,
+        ),
+        value => {
+            let text = match value.get_str() {
+                Some(text) => text.to_string(),
+                None => value.to_string(),
+            };
+            match TagKind::from_code(name).expected_type() {
+                Some(TypeCode::Char) => Type::Char(text.chars().next().unwrap_or(' ')),
+                _ => Type::String(text),
+            }
+        }
+    };
+    super::tag(name, value)
+        .map(Some)
+        .map_err(|err| polars_err!(ComputeError: "{}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::tests::PAF_RECORD_1;
+    use crate::{Reader, Writer};
+
+    #[test]
+    fn test_fixture_frame() {
+        let options = DataFrameOptions {
+            tags: vec!["NM".into(), "de".into(), "tp".into(), "cs".into()],
+        };
+        let mut reader = Reader::from_reader(PAF_RECORD_1);
+        let df = to_dataframe(reader.records(), &options).unwrap();
+        assert_eq!(df.shape(), (1, 16));
+        assert!(matches!(
+            df.column("query_name").unwrap().dtype(),
+            DataType::Categorical(..)
+        ));
+        assert_eq!(df.column("query_len").unwrap().dtype(), &DataType::UInt32);
+        assert_eq!(
+            df.column("mapping_quality").unwrap().dtype(),
+            &DataType::UInt8
+        );
+        assert_eq!(df.column("NM").unwrap().dtype(), &DataType::Int64);
+        assert_eq!(df.column("de").unwrap().dtype(), &DataType::Float64);
+        assert_eq!(df.column("tp").unwrap().dtype(), &DataType::String);
+
+        let cell = |name: &str| df.column(name).unwrap().get(0).unwrap();
+        assert_eq!(cell("target_name").get_str(), Some("SUPER_10"));
+        assert_eq!(cell("residue_matches"), AnyValue::UInt32(495111));
+        assert_eq!(cell("NM"), AnyValue::Int64(48730));
+        assert_eq!(cell("tp").get_str(), Some("P"));
+        // no cs tag on the record
+        assert_eq!(cell("cs"), AnyValue::Null);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let paf = "\
+q1\t1000\t0\t500\t+\tchr1\t5000\t1000\t1500\t490\t500\t60\tNM:i:10\ttp:A:P\tde:f:0.02\tcg:Z:500M
+q2\t1000\t0\t800\t-\tchr2\t5000\t0\t800\t700\t800\t5\ttp:A:S
+";
+        let options = DataFrameOptions {
+            tags: vec!["tp".into(), "NM".into(), "de".into(), "cg".into()],
+        };
+        let df = to_dataframe(Reader::from_reader(paf.as_bytes()).records(), &options).unwrap();
+        let records = from_dataframe(&df).unwrap();
+        let mut out = Writer::new(Vec::new());
+        for record in &records {
+            out.write_record(record).unwrap();
+        }
+        assert_eq!(String::from_utf8(out.into_inner().unwrap()).unwrap(), paf);
+
+        // a frame missing a mandatory column
+        let df = df.drop("strand").unwrap();
+        assert!(from_dataframe(&df).is_err());
+    }
+
+    #[test]
+    fn test_read_error() {
+        let mut reader = Reader::from_reader(&b"bad\n"[..]);
+        assert!(to_dataframe(reader.records(), &DataFrameOptions::default()).is_err());
+    }
+}
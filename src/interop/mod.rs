@@ -0,0 +1,43 @@
+/*!
+Move records to and from the columnar formats of other libraries, for
+exploratory analysis.
+
+With the `polars` feature, `to_dataframe` builds a polars `DataFrame`
+with a column for each mandatory field and for each tag asked for, and
+`from_dataframe` turns a frame back into records, as after filtering it.
+
+With the `arrow` feature, `ArrowBatcher` turns records into Arrow
+`RecordBatch`es of a fixed schema, a batch at a time, as for DataFusion or
+Arrow IPC files, and `from_record_batch` turns a batch back into records.
+With the `parquet` feature, `write_parquet` writes records to a Parquet
+file with the same columns, and `read_parquet` reads them back.
+*/
+
+/// The batch module streams records as Arrow record batches.
+#[cfg(feature = "arrow")]
+mod batch;
+/// The file module writes and reads records as Parquet files.
+#[cfg(feature = "parquet")]
+mod file;
+/// The frame module builds polars data frames of records.
+#[cfg(feature = "polars")]
+mod frame;
+
+#[cfg(feature = "arrow")]
+pub use batch::{arrow_schema, from_record_batch, ArrowBatcher};
+#[cfg(feature = "parquet")]
+pub use file::{
+    read_parquet, read_parquet_without_cigar, write_parquet, ParquetOptions, ParquetRecords,
+};
+#[cfg(feature = "polars")]
+pub use frame::{from_dataframe, to_dataframe, DataFrameOptions};
+
+use crate::{Result, Tag, TagKind, Type};
+
+/// The tag `name` holding `value`, keeping tags unknown to this crate.
+fn tag(name: &str, value: Type) -> Result<Tag> {
+    match TagKind::from_code(name) {
+        TagKind::Other => Ok(Tag::Other(name.to_string(), value)),
+        _ => Tag::parse(name, value),
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde::ser::{SerializeMap, SerializeStruct};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::value::RawValue;
+
+use crate::{Error, ErrorKind, PafRecord, Result, Tag, TagKind, TagOrder, Type};
+
+/// Writes records as JSON Lines, one object per record.
+///
+/// The mandatory fields are written under the names of their accessors,
+/// e.g. `query_name`, and the optional fields under `tags`, keyed by name,
+/// each as an object of its SAM-style `type` and its `value`. Integers and
+/// finite floats are JSON numbers, and everything else a string, including
+/// infinite and NaN floats, which JSON has no numbers for:
+///
+/// ```text
+/// {"query_name":"q",...,"mapping_quality":60,"tags":{"NM":{"type":"i","value":5},"tp":{"type":"A","value":"P"}}}
+/// ```
+///
+/// Tags come in the order minimap2 writes them. Records are serialized
+/// straight to the output, so a long cg tag is not copied on the way.
+#[derive(Debug)]
+pub struct JsonWriter<W: Write> {
+    writer: BufWriter<W>,
+}
+
+impl JsonWriter<File> {
+    /// Creates a JSON Lines writer to a file path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<JsonWriter<File>> {
+        Ok(JsonWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> JsonWriter<W> {
+    /// Creates a JSON Lines writer from a writer instance.
+    pub fn new(writer: W) -> Self {
+        JsonWriter {
+            writer: BufWriter::new(writer),
+        }
+    }
+
+    /// Writes a record as a line of JSON.
+    pub fn write_record(&mut self, record: &PafRecord) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, &JsonRecord(record)).map_err(io::Error::from)?;
+        self.writer.write_all(b"\n").map_err(Into::into)
+    }
+
+    /// Writes out the buffer and flushes the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush().map_err(Into::into)
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn into_inner(self) -> Result<W> {
+        self.writer
+            .into_inner()
+            .map_err(|err| err.into_error().into())
+    }
+}
+
+/// A record, serialized as a JSON object.
+struct JsonRecord<'a>(&'a PafRecord);
+
+impl Serialize for JsonRecord<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let record = self.0;
+        let mut object = serializer.serialize_struct("PafRecord", 13)?;
+        object.serialize_field("query_name", record.query_name())?;
+        object.serialize_field("query_len", &record.query_len())?;
+        object.serialize_field("query_start", &record.query_start())?;
+        object.serialize_field("query_end", &record.query_end())?;
+        object.serialize_field("strand", &record.strand())?;
+        object.serialize_field("target_name", record.target_name())?;
+        object.serialize_field("target_len", &record.target_len())?;
+        object.serialize_field("target_start", &record.target_start())?;
+        object.serialize_field("target_end", &record.target_end())?;
+        object.serialize_field("residue_matches", &record.residue_matches())?;
+        object.serialize_field("alignment_block_len", &record.alignment_block_len())?;
+        object.serialize_field("mapping_quality", &record.mapping_quality())?;
+        object.serialize_field("tags", &JsonTags(record))?;
+        object.end()
+    }
+}
+
+/// The optional fields of a record, serialized as a JSON object.
+struct JsonTags<'a>(&'a PafRecord);
+
+impl Serialize for JsonTags<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut fields: Vec<_> = self.0.optional_fields().iter().collect();
+        TagOrder::Minimap2.sort(&mut fields);
+        let mut object = serializer.serialize_map(Some(fields.len()))?;
+        for (name, tag) in fields {
+            object.serialize_entry(name, &JsonTag(tag))?;
+        }
+        object.end()
+    }
+}
+
+/// An optional field, serialized as its type code and value.
+struct JsonTag<'a>(&'a Tag);
+
+impl Serialize for JsonTag<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut object = serializer.serialize_struct("Tag", 2)?;
+        match self.0 {
+            Tag::tp(value) => {
+                object.serialize_field("type", &'A')?;
+                object.serialize_field("value", &value.as_char())?;
+            }
+            Tag::ts(value) => {
+                object.serialize_field("type", &'A')?;
+                object.serialize_field("value", &value.as_char())?;
+            }
+            tag => {
+                // every other tag holds a value
+                let value = tag.value().unwrap();
+                object.serialize_field("type", &value.code())?;
+                object.serialize_field("value", &JsonValue(value))?;
+            }
+        }
+        object.end()
+    }
+}
+
+/// The value of an optional field.
+struct JsonValue<'a>(&'a Type);
+
+impl Serialize for JsonValue<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self.0 {
+            Type::Int(value) => serializer.serialize_i64(*value),
+            // keep the text a float was read from, if it is a JSON number
+            Type::Float {
+                raw: Some(raw),
+                value,
+            } => match RawValue::from_string(raw.clone()) {
+                Ok(raw) if !raw.get().starts_with('"') => raw.serialize(serializer),
+                _ if value.is_finite() => serializer.serialize_f64(*value),
+                _ => serializer.collect_str(self.0),
+            },
+            Type::Float { value, raw: None } if value.is_finite() => {
+                serializer.serialize_f64(*value)
+            }
+            Type::Char(value) => serializer.serialize_char(*value),
+            value => serializer.collect_str(value),
+        }
+    }
+}
+
+/// A record as read from JSON.
+#[derive(Deserialize)]
+struct OwnedJsonRecord {
+    query_name: String,
+    query_len: u32,
+    query_start: u32,
+    query_end: u32,
+    strand: char,
+    target_name: String,
+    target_len: u32,
+    target_start: u32,
+    target_end: u32,
+    residue_matches: u32,
+    alignment_block_len: u32,
+    mapping_quality: u8,
+    #[serde(default)]
+    tags: HashMap<String, OwnedJsonTag>,
+}
+
+/// An optional field as read from JSON.
+#[derive(Deserialize)]
+struct OwnedJsonTag {
+    #[serde(rename = "type")]
+    code: char,
+    value: Box<RawValue>,
+}
+
+/// Reads records from JSON Lines written by [`JsonWriter`].
+///
+/// Tags are read back as they were written: known tags must have their
+/// usual type, and unknown tags and type codes are kept.
+#[derive(Debug)]
+pub struct JsonReader<R> {
+    reader: BufReader<R>,
+    line: u64,
+}
+
+impl JsonReader<File> {
+    /// Creates a JSON Lines reader from a file path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<JsonReader<File>> {
+        Ok(JsonReader::from_reader(File::open(path)?))
+    }
+}
+
+impl<R: io::Read> JsonReader<R> {
+    /// Creates a JSON Lines reader from a reader.
+    pub fn from_reader(rdr: R) -> Self {
+        JsonReader {
+            reader: BufReader::new(rdr),
+            line: 0,
+        }
+    }
+
+    /// Read a single record, passing over blank lines.
+    pub fn read_record(&mut self) -> Result<Option<PafRecord>> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            if !line.trim().is_empty() {
+                break;
+            }
+        }
+
+        let invalid = |msg: String| {
+            Error::new(ErrorKind::ReadRecord(format!(
+                "Invalid JSON at line {}: {}",
+                self.line, msg
+            )))
+        };
+        let json: OwnedJsonRecord =
+            serde_json::from_str(&line).map_err(|err| invalid(err.to_string()))?;
+
+        let mut optional = HashMap::new();
+        for (name, tag) in json.tags {
+            // numbers are taken as written, so floats keep their text
+            let raw = tag.value.get();
+            let text = match raw.as_bytes().first() {
+                Some(b'"') => serde_json::from_str(raw).map_err(|err| invalid(err.to_string()))?,
+                Some(b'-' | b'0'..=b'9') => raw.to_string(),
+                _ => return Err(invalid(format!("invalid value {} for tag {}", raw, name))),
+            };
+            let value = if crate::TypeCode::from_char(tag.code).is_some() {
+                Type::from_typed_str(tag.code, &text)?
+            } else {
+                Type::raw(tag.code, &text)
+            };
+            let tag = match TagKind::from_code(&name) {
+                TagKind::Other => Tag::Other(name.clone(), value),
+                _ => Tag::parse(&name, value)?,
+            };
+            optional.insert(name, tag);
+        }
+
+        Ok(Some(PafRecord::new(
+            json.query_name,
+            json.query_len,
+            json.query_start,
+            json.query_end,
+            json.strand,
+            json.target_name,
+            json.target_len,
+            json.target_start,
+            json.target_end,
+            json.residue_matches,
+            json.alignment_block_len,
+            json.mapping_quality,
+            optional,
+        )))
+    }
+
+    /// An iterator over the records.
+    pub fn records(&mut self) -> impl Iterator<Item = Result<PafRecord>> + '_ {
+        std::iter::from_fn(move || self.read_record().transpose())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cigar, ReaderBuilder, Writer};
+
+    /// A record with a tag of every type.
+    const ALL_TYPES: &str = "q\t100\t0\t10\t-\tt\t200\t5\t15\t9\t10\t60\t\
+        tp:A:I\tts:A:-\tNM:i:1\tde:f:0.0003\tcs:Z::4*ag:5\tcg:Z:10M\txa:A:x\txi:i:-7\t\
+        xf:f:1e-05\txz:Z:a \"quoted\" string\txb:B:c,1,2\n";
+
+    fn read_paf(line: &str, compact: bool) -> PafRecord {
+        ReaderBuilder::new()
+            .keep_unknown_tags(true)
+            .keep_unknown_types(true)
+            .compact_cigar(compact)
+            .from_reader(line.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap()
+    }
+
+    fn to_json(record: &PafRecord) -> String {
+        let mut writer = JsonWriter::new(Vec::new());
+        writer.write_record(record).unwrap();
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    fn to_paf(record: &PafRecord) -> String {
+        let mut writer = Writer::new(Vec::new());
+        writer.write_record(record).unwrap();
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_json() {
+        let json = to_json(&read_paf(ALL_TYPES, false));
+        assert!(json.starts_with(
+            "{\"query_name\":\"q\",\"query_len\":100,\"query_start\":0,\"query_end\":10,\
+             \"strand\":\"-\",\"target_name\":\"t\",\"target_len\":200,\"target_start\":5,\
+             \"target_end\":15,\"residue_matches\":9,\"alignment_block_len\":10,\
+             \"mapping_quality\":60,\"tags\":{\"NM\":{\"type\":\"i\",\"value\":1},\
+             \"tp\":{\"type\":\"A\",\"value\":\"I\"},\"ts\":{\"type\":\"A\",\"value\":\"-\"},\
+             \"de\":{\"type\":\"f\",\"value\":0.0003},\"cg\":{\"type\":\"Z\",\"value\":\"10M\"},"
+        ));
+        assert!(json.contains("\"xi\":{\"type\":\"i\",\"value\":-7}"));
+        assert!(json.contains("\"xb\":{\"type\":\"B\",\"value\":\"c,1,2\"}"));
+        assert!(json.contains("\"xz\":{\"type\":\"Z\",\"value\":\"a \\\"quoted\\\" string\"}"));
+        assert!(json.ends_with("}}\n"));
+        assert_eq!(json.lines().count(), 1);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for compact in [false, true] {
+            let record = read_paf(ALL_TYPES, compact);
+            let json = to_json(&record);
+            let mut reader = JsonReader::from_reader(json.as_bytes());
+            let read_back = reader.read_record().unwrap().unwrap();
+            assert!(reader.read_record().unwrap().is_none());
+            assert_eq!(to_paf(&read_back), to_paf(&record));
+            // written again, the JSON is the same
+            assert_eq!(to_json(&read_back), json);
+        }
+    }
+
+    #[test]
+    fn test_non_finite_floats() {
+        // JSON has no infinite or NaN numbers, so these are strings
+        let line = "q\t100\t0\t10\t+\tt\t200\t5\t15\t9\t10\t60\txf:f:inf\tyf:f:NaN\n";
+        let record = read_paf(line, false);
+        let json = to_json(&record);
+        assert!(json.contains("\"xf\":{\"type\":\"f\",\"value\":\"inf\"}"));
+        assert!(json.contains("\"yf\":{\"type\":\"f\",\"value\":\"NaN\"}"));
+        let read_back = JsonReader::from_reader(json.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap();
+        assert_eq!(to_paf(&read_back), line);
+
+        let mut tags = HashMap::new();
+        tags.insert(
+            "xf".to_string(),
+            Tag::Other("xf".into(), Type::float(f64::NEG_INFINITY)),
+        );
+        let record = PafRecord::new(
+            "q".into(),
+            10,
+            0,
+            10,
+            '+',
+            "t".into(),
+            9,
+            0,
+            9,
+            9,
+            10,
+            60,
+            tags,
+        );
+        let json = to_json(&record);
+        assert!(json.contains("\"value\":\"-inf\""));
+        let read_back = JsonReader::from_reader(json.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap();
+        assert_eq!(to_paf(&read_back), to_paf(&record));
+    }
+
+    #[test]
+    fn test_built_record() {
+        let mut tags = HashMap::new();
+        tags.insert("de".to_string(), Tag::de(Type::float(0.25)));
+        tags.insert(
+            "cg".to_string(),
+            Tag::cg(Type::Cigar(Cigar::parse("5M1I4M").unwrap())),
+        );
+        let record = PafRecord::new(
+            "q".into(),
+            10,
+            0,
+            10,
+            '+',
+            "t".into(),
+            9,
+            0,
+            9,
+            9,
+            10,
+            60,
+            tags,
+        );
+        let json = to_json(&record);
+        assert!(json.contains("\"tags\":{\"de\":{\"type\":\"f\",\"value\":0.25},\"cg\":{\"type\":\"Z\",\"value\":\"5M1I4M\"}}"));
+        let read_back = JsonReader::from_reader(json.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap();
+        assert_eq!(to_paf(&read_back), to_paf(&record));
+    }
+
+    #[test]
+    fn test_fixture_records() {
+        let records: Vec<PafRecord> = crate::Reader::from_path("./data/5_GD_domestica.paf")
+            .unwrap()
+            .records()
+            .take(500)
+            .map(|r| r.unwrap())
+            .collect();
+        let mut writer = JsonWriter::new(Vec::new());
+        for record in &records {
+            writer.write_record(record).unwrap();
+        }
+        let json = writer.into_inner().unwrap();
+        let mut reader = JsonReader::from_reader(&json[..]);
+        let read_back: Vec<PafRecord> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(read_back.len(), records.len());
+        for (a, b) in read_back.iter().zip(&records) {
+            assert_eq!(to_paf(a), to_paf(b));
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut reader = JsonReader::from_reader(&b"\n{\"query_name\":\"q\"}\n"[..]);
+        let err = reader.read_record().unwrap_err();
+        assert!(err.to_string().contains("Invalid JSON at line 2"));
+
+        // a known tag with the wrong type
+        let json = to_json(&read_paf(ALL_TYPES, false)).replace(
+            "\"NM\":{\"type\":\"i\",\"value\":1}",
+            "\"NM\":{\"type\":\"Z\",\"value\":\"1\"}",
+        );
+        assert!(JsonReader::from_reader(json.as_bytes())
+            .read_record()
+            .is_err());
+    }
+}
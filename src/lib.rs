@@ -11,7 +11,7 @@ use paf::Reader;
 use std::env;
 
 fn main() {
-    let data = env::args().skip(1).next();
+    let data = env::args().nth(1);
 
     if let Some(filename) = data {
         let mut reader = Reader::from_path(&filename).unwrap();
@@ -29,15 +29,108 @@ fn main() {
 
 */
 
+/// The audit module cross-checks record fields against the cs and cg tags.
+mod audit;
+/// The bio module lets records be used with rust-bio's interval types.
+#[cfg(feature = "bio")]
+pub mod bio;
+/// The chain module chains colinear alignments into synteny blocks.
+pub mod chain;
+/// The cigar module parses CIGAR strings.
+mod cigar;
+/// The compare module compares alignments made in two directions or by two
+/// runs of an aligner.
+pub mod compare;
+/// The compress module writes BGZF compressed output.
+#[cfg(feature = "bgzf")]
+mod compress;
+/// The convert module turns records into other formats.
+pub mod convert;
+/// The coverage module measures how much of each target and query is aligned.
+pub mod coverage;
+/// The cs module parses cs difference strings.
+mod cs;
+/// The dedup module removes duplicate records.
+pub mod dedup;
 /// The error module provides the error type and kind for the crate.
 mod error;
+/// The export module writes tracks along the target for genome browsers.
+pub mod export;
+/// The filter module provides predicates for choosing records.
+pub mod filter;
+/// The gaf module reads and writes GAF graph alignments.
+pub mod gaf;
+/// The index module finds the records overlapping an interval.
+pub mod index;
+/// The interop module moves records to and from the data frames of other
+/// libraries.
+#[cfg(any(feature = "polars", feature = "arrow"))]
+pub mod interop;
+/// The json module reads and writes records as JSON Lines.
+#[cfg(feature = "serde")]
+mod json;
+/// The liftover module lifts target intervals to the queries.
+pub mod liftover;
+/// The overlap module classifies overlaps between reads, as miniasm does.
+pub mod overlap;
+/// The paint module paints each query with the targets it best aligns to.
+pub mod paint;
+/// The plot module turns records into dot plot segments.
+pub mod plot;
+/// The qc module checks the alignments of reads and contigs.
+pub mod qc;
 /// The reader module provides the reader and record types.
 mod reader;
+/// The reconstruct module rebuilds the target from the query and its edits.
+mod reconstruct;
+/// The sa module parses the SA supplementary alignment tag.
+mod sa;
+/// The sample module draws reproducible random samples of records.
+pub mod sample;
+/// The select module chooses among the alignments of each query.
+pub mod select;
+/// The set module holds records in memory with lookups by name.
+mod set;
+/// The sort module sorts records in memory and files larger than memory.
+pub mod sort;
+/// The sorted module buffers records to write them in order.
+mod sorted;
+/// The stats module summarizes many records in one pass.
+pub mod stats;
+/// The sv module finds structural variant candidates.
+pub mod sv;
+/// The table module writes records as TSV or CSV with a header row.
+mod table;
+/// The tags module names the known tags.
+pub mod tags;
+/// The test_util module holds helpers shared by the tests.
+#[cfg(test)]
+mod test_util;
+/// The validate module checks whole files for invalid records.
+pub mod validate;
+/// The window module profiles alignments in windows along the target.
+mod window;
 /// The writer module provides the writer type.
 mod writer;
 
+#[cfg(feature = "serde")]
+pub use crate::json::{JsonReader, JsonWriter};
 pub use crate::{
+    audit::{AuditField, AuditReport, Discrepancy, Recomputed, RecomputedFields},
+    cigar::{AlignedPairs, Cigar, CigarOp, CigarOps, CigarStats, Indel, IndelKind, LiftoverResult},
+    cs::{CsCounts, CsOp, CsString, Variant, VariantKind},
     error::{Error, ErrorKind, Result},
-    reader::{PafRecord, Reader, RecordsIntoIter, RecordsIter, Tag, Type},
-    writer::Writer,
+    reader::{
+        AlignmentType, PafRecord, Reader, ReaderBuilder, RecordsIntoIter, RecordsIter, Strand, Tag,
+        TagKind, TranscriptStrand, Type, TypeCode, Warning,
+    },
+    sa::SupplementaryAlignment,
+    set::PafSet,
+    sorted::{SortKey, SortedWriter},
+    table::TableWriter,
+    window::WindowIdentity,
+    writer::{
+        supplementary_alignments_tag, write_indels_bed, Compression, OpenMode, TagOrder, Writer,
+        WriterBuilder,
+    },
 };
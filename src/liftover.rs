@@ -0,0 +1,698 @@
+/*!
+Lift intervals on the targets to the queries through the alignments.
+
+```
+use paf::liftover::{LiftedInterval, Liftover};
+use paf::Reader;
+
+let paf = b"q\t100\t0\t100\t+\tchr1\t1000\t200\t300\t100\t100\t60\tcg:Z:100M\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let liftover = Liftover::from_records(reader.records().map(Result::unwrap));
+let lifted = liftover.lift_interval("chr1", 250, 350).unwrap();
+assert!(matches!(
+    lifted[0],
+    LiftedInterval::Mapped { query_start: 50, query_end: 100, .. }
+));
+assert_eq!(
+    lifted[1],
+    LiftedInterval::Unmapped { target_start: 300, target_end: 350 }
+);
+```
+*/
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{BufRead, BufWriter, Write};
+
+use crate::cigar::{segments, QueryAxis};
+use crate::index::IntervalIndex;
+use crate::select::Score;
+use crate::{filter, Error, ErrorKind, PafRecord, Result};
+
+/// A part of an interval lifted by [`Liftover::lift_interval`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiftedInterval {
+    /// The part is covered by an alignment.
+    Mapped {
+        /// Start of the part on the target.
+        target_start: u64,
+        /// End of the part on the target.
+        target_end: u64,
+        /// The query of the alignment.
+        query_name: String,
+        /// Start on the query of the bases aligned to the part.
+        query_start: u64,
+        /// End on the query of the bases aligned to the part.
+        query_end: u64,
+        /// The strand of the alignment, so `-` when the part is reversed
+        /// on the query.
+        strand: char,
+        /// The fraction of the bases of the part aligned to query bases,
+        /// rather than deleted from the query.
+        mapped_fraction: f64,
+        /// The index of the alignment among the records given.
+        record: usize,
+    },
+    /// No alignment covers the part, or it is all deleted from the query.
+    Unmapped {
+        /// Start of the part on the target.
+        target_start: u64,
+        /// End of the part on the target.
+        target_end: u64,
+    },
+}
+
+impl LiftedInterval {
+    /// The part of the lifted interval on the target.
+    pub fn target_interval(&self) -> (u64, u64) {
+        match self {
+            LiftedInterval::Mapped {
+                target_start,
+                target_end,
+                ..
+            }
+            | LiftedInterval::Unmapped {
+                target_start,
+                target_end,
+            } => (*target_start, *target_end),
+        }
+    }
+}
+
+/// A position lifted by [`Liftover::lift_position`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiftedPosition {
+    /// The query of the alignment.
+    pub query_name: String,
+    /// The position on the query.
+    pub query_pos: u64,
+    /// The strand of the alignment.
+    pub strand: char,
+    /// Whether the position is deleted from the query, in which case
+    /// `query_pos` is the nearest base aligned either side of the deletion.
+    pub deleted: bool,
+    /// The index of the alignment among the records given.
+    pub record: usize,
+}
+
+/// What [`Liftover::lift_bed`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BedLiftReport {
+    /// The number of BED intervals read.
+    pub intervals: u64,
+    /// The number of lifted intervals written, more than read when
+    /// intervals are split between alignments.
+    pub written: u64,
+    /// The parts of the intervals which could not be lifted, as target
+    /// name, start and end.
+    pub unmapped: Vec<(String, u64, u64)>,
+}
+
+/// Records held in memory for lifting target intervals to the queries.
+///
+/// Each part of an interval is lifted through the best alignment covering
+/// it, chosen by a [`Score`], by default the longest block. The cg tag
+/// places every base, so records without one cannot be lifted through.
+#[derive(Debug)]
+pub struct Liftover {
+    index: IntervalIndex,
+    score: Score,
+}
+
+impl Liftover {
+    /// Index the records by their target intervals.
+    pub fn from_records<I>(records: I) -> Self
+    where
+        I: IntoIterator<Item = PafRecord>,
+    {
+        Liftover {
+            index: IntervalIndex::build(records.into_iter().collect()),
+            score: Score::BlockLen,
+        }
+    }
+
+    /// How to choose between alignments covering the same bases, e.g.
+    /// [`Score::As`] for the highest AS tag.
+    pub fn score(&mut self, score: Score) -> &mut Self {
+        self.score = score;
+        self
+    }
+
+    /// The records, in the order given.
+    pub fn records(&self) -> &[PafRecord] {
+        self.index.records()
+    }
+
+    /// Lifts `start..end` on `target` to the queries, as parts in order
+    /// along the target.
+    ///
+    /// Parts are lifted through the best alignment covering them, so an
+    /// interval across the ends of alignments is split between them, and
+    /// parts no alignment covers are [`LiftedInterval::Unmapped`]. It is an
+    /// error for an alignment used to have no valid cg tag, or one which
+    /// does not span its aligned intervals.
+    pub fn lift_interval(&self, target: &str, start: u64, end: u64) -> Result<Vec<LiftedInterval>> {
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        let records = self.index.records();
+        let mut candidates = self.index.query_indices(target, start, end);
+        candidates.sort_by(|&a, &b| self.score.compare(&records[a], &records[b]).then(a.cmp(&b)));
+
+        // give each part not yet covered to the best alignment covering it
+        let mut parts: Vec<(u64, u64, Option<usize>)> = vec![(start, end, None)];
+        for i in candidates {
+            let record = &records[i];
+            let (from, to) = (record.target_start() as u64, record.target_end() as u64);
+            let mut split = Vec::with_capacity(parts.len() + 2);
+            for (s, e, owner) in parts {
+                if owner.is_some() || to <= s || from >= e {
+                    split.push((s, e, owner));
+                    continue;
+                }
+                if s < from {
+                    split.push((s, from, None));
+                }
+                split.push((s.max(from), e.min(to), Some(i)));
+                if to < e {
+                    split.push((to, e, None));
+                }
+            }
+            parts = split;
+        }
+
+        parts
+            .into_iter()
+            .map(|(s, e, owner)| match owner {
+                Some(i) => lift_part(&records[i], i, s, e),
+                None => Ok(LiftedInterval::Unmapped {
+                    target_start: s,
+                    target_end: e,
+                }),
+            })
+            .collect()
+    }
+
+    /// Lifts position `pos` on `target` to the query, or `None` where no
+    /// alignment covers it.
+    ///
+    /// Of the alignments covering the position, it is lifted through the
+    /// primary alignments before others, then the longest block, then the
+    /// highest AS tag, whatever [`Liftover::score`] is set to. A position
+    /// deleted from the query lifts to the nearer base aligned either side,
+    /// with [`deleted`](LiftedPosition::deleted) set. It is an error for the
+    /// alignment to have no valid cg tag.
+    ///
+    /// Each call walks the cg tag from its start; for many positions use
+    /// [`Liftover::lift_positions_sorted`].
+    pub fn lift_position(&self, target: &str, pos: u64) -> Result<Option<LiftedPosition>> {
+        let records = self.records();
+        let best = self
+            .index
+            .query_indices(target, pos, pos.saturating_add(1))
+            .into_iter()
+            .min_by(|&a, &b| position_priority(&records[a], &records[b]).then(a.cmp(&b)));
+        match best {
+            Some(i) => Ok(Runs::of(&records[i])?
+                .lift(pos)
+                .map(|(query_pos, deleted)| lifted_position(&records[i], i, query_pos, deleted))),
+            None => Ok(None),
+        }
+    }
+
+    /// Lifts many positions as [`Liftover::lift_position`] does, returning
+    /// the results in the order of the input.
+    ///
+    /// The positions are sorted, and then lifted in one sweep along each
+    /// target over the alignments and their cg tags, so that each cg tag is
+    /// walked once rather than once for each position.
+    pub fn lift_positions_sorted<S: AsRef<str>>(
+        &self,
+        positions: &[(S, u64)],
+    ) -> Result<Vec<Option<LiftedPosition>>> {
+        let records = self.records();
+        let key = |i: usize| (positions[i].0.as_ref(), positions[i].1);
+        let mut order: Vec<usize> = (0..positions.len()).collect();
+        order.sort_by(|&a, &b| key(a).cmp(&key(b)));
+
+        let mut by_target: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, record) in records.iter().enumerate() {
+            by_target.entry(record.target_name()).or_default().push(i);
+        }
+        for starts in by_target.values_mut() {
+            starts.sort_by_key(|&i| records[i].target_start());
+        }
+
+        let mut results = vec![None; positions.len()];
+        for group in order.chunk_by(|&a, &b| key(a).0 == key(b).0) {
+            let Some(starts) = by_target.get(key(group[0]).0) else {
+                continue;
+            };
+            let mut waiting = starts.iter().peekable();
+            // the alignments reached which have not yet ended
+            let mut active: Vec<(usize, Runs)> = Vec::new();
+            for &p in group {
+                let pos = positions[p].1;
+                while let Some(&i) = waiting.next_if(|&&i| records[i].target_start() as u64 <= pos)
+                {
+                    if records[i].target_end() as u64 > pos {
+                        active.push((i, Runs::of(&records[i])?));
+                    }
+                }
+                active.retain(|(i, _)| records[*i].target_end() as u64 > pos);
+                let best = active.iter_mut().min_by(|(a, _), (b, _)| {
+                    position_priority(&records[*a], &records[*b]).then(a.cmp(b))
+                });
+                if let Some((i, runs)) = best {
+                    results[p] = runs.lift(pos).map(|(query_pos, deleted)| {
+                        lifted_position(&records[*i], *i, query_pos, deleted)
+                    });
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// Lifts the intervals of a BED file, writing the lifted parts as BED.
+    ///
+    /// Lifted parts take the query name and interval in place of the first
+    /// three columns, keeping the rest, with the strand in a sixth column
+    /// flipped for parts lifted through minus strand alignments. Parts which
+    /// cannot be lifted are returned in the report. Comment, `track` and
+    /// `browser` lines are written unchanged.
+    pub fn lift_bed<R: BufRead, W: Write>(&self, reader: R, writer: W) -> Result<BedLiftReport> {
+        let mut writer = BufWriter::new(writer);
+        let mut report = BedLiftReport::default();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('#') || line.starts_with("track") || line.starts_with("browser") {
+                writeln!(writer, "{}", line)?;
+                continue;
+            }
+            let columns: Vec<&str> = line.split('\t').collect();
+            let (start, end) = match columns.as_slice() {
+                [_, start, end, ..] => (start.parse::<u64>(), end.parse::<u64>()),
+                _ => return Err(invalid_bed(i + 1, "less than 3 fields")),
+            };
+            let (Ok(start), Ok(end)) = (start, end) else {
+                return Err(invalid_bed(i + 1, "the start and end are not integers"));
+            };
+            report.intervals += 1;
+
+            for part in self.lift_interval(columns[0], start, end)? {
+                match part {
+                    LiftedInterval::Mapped {
+                        query_name,
+                        query_start,
+                        query_end,
+                        strand,
+                        ..
+                    } => {
+                        write!(writer, "{}\t{}\t{}", query_name, query_start, query_end)?;
+                        for (column, value) in columns.iter().enumerate().skip(3) {
+                            let value = match (column, *value, strand) {
+                                (5, "+", '-') => "-",
+                                (5, "-", '-') => "+",
+                                _ => value,
+                            };
+                            write!(writer, "\t{}", value)?;
+                        }
+                        writeln!(writer)?;
+                        report.written += 1;
+                    }
+                    LiftedInterval::Unmapped {
+                        target_start,
+                        target_end,
+                    } => report
+                        .unmapped
+                        .push((columns[0].to_string(), target_start, target_end)),
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(report)
+    }
+}
+
+/// Lifts `start..end`, which `record` covers, through its cg tag.
+fn lift_part(record: &PafRecord, index: usize, start: u64, end: u64) -> Result<LiftedInterval> {
+    let cigar = record.require_cigar("liftover")?;
+    record.check_cigar_spans(&cigar)?;
+    let axis = QueryAxis::of(record);
+    let mut mapped = 0;
+    // the offsets along the query of the first and last bases aligned
+    let mut offsets: Option<(u64, u64)> = None;
+    for segment in segments(&cigar, record.target_start() as u64) {
+        if !segment.op.consumes_target() {
+            continue;
+        }
+        let (from, to) = (
+            segment.target_pos.max(start),
+            (segment.target_pos + segment.len as u64).min(end),
+        );
+        if from >= to || !segment.op.consumes_query() {
+            continue;
+        }
+        mapped += to - from;
+        let first = segment.query_offset + from - segment.target_pos;
+        let last = segment.query_offset + to - segment.target_pos;
+        offsets = Some(offsets.map_or((first, last), |(f, _)| (f, last)));
+    }
+    Ok(match offsets {
+        Some((first, last)) => {
+            let (query_start, query_end) = axis.interval(first, last);
+            LiftedInterval::Mapped {
+                target_start: start,
+                target_end: end,
+                query_name: record.query_name().to_string(),
+                query_start,
+                query_end,
+                strand: record.strand(),
+                mapped_fraction: mapped as f64 / (end - start) as f64,
+                record: index,
+            }
+        }
+        None => LiftedInterval::Unmapped {
+            target_start: start,
+            target_end: end,
+        },
+    })
+}
+
+/// Orders alignments for lifting positions best first: primary alignments,
+/// then the longest block, then the highest AS tag.
+fn position_priority(a: &PafRecord, b: &PafRecord) -> Ordering {
+    let primary = filter::primary_only();
+    primary(b)
+        .cmp(&primary(a))
+        .then_with(|| Score::BlockLen.of(b).cmp(&Score::BlockLen.of(a)))
+        .then_with(|| Score::As.compare(a, b))
+}
+
+fn lifted_position(
+    record: &PafRecord,
+    index: usize,
+    query_pos: u64,
+    deleted: bool,
+) -> LiftedPosition {
+    LiftedPosition {
+        query_name: record.query_name().to_string(),
+        query_pos,
+        strand: record.strand(),
+        deleted,
+        record: index,
+    }
+}
+
+/// The runs of a cg tag aligning target bases to query bases, for lifting
+/// positions in order along the target.
+struct Runs {
+    axis: QueryAxis,
+    /// The target position, query offset and length of each run.
+    runs: Vec<(u64, u64, u64)>,
+    /// The first run not wholly before the last position lifted.
+    next: usize,
+}
+
+impl Runs {
+    fn of(record: &PafRecord) -> Result<Self> {
+        let cigar = record.require_cigar("liftover")?;
+        let runs = segments(&cigar, record.target_start() as u64)
+            .filter(|s| s.op.consumes_query() && s.op.consumes_target())
+            .map(|s| (s.target_pos, s.query_offset, s.len as u64))
+            .collect();
+        Ok(Runs {
+            axis: QueryAxis::of(record),
+            runs,
+            next: 0,
+        })
+    }
+
+    /// Lifts `pos`, which must not be before the last position lifted, to
+    /// the query position and whether it is deleted, or `None` if nothing
+    /// is aligned.
+    fn lift(&mut self, pos: u64) -> Option<(u64, bool)> {
+        while let Some(&(start, _, len)) = self.runs.get(self.next) {
+            if start + len > pos {
+                break;
+            }
+            self.next += 1;
+        }
+        let after = self.runs.get(self.next).copied();
+        if let Some((start, offset, _)) = after.filter(|&(start, _, _)| start <= pos) {
+            return Some((self.axis.position(offset + pos - start), false));
+        }
+        // between runs, take the nearer flank, the one before on a tie
+        let before = self.next.checked_sub(1).map(|i| self.runs[i]);
+        let offset = match (before, after) {
+            (Some((start, offset, len)), Some((next_start, next_offset, _))) => {
+                if pos + 1 - (start + len) <= next_start - pos {
+                    offset + len - 1
+                } else {
+                    next_offset
+                }
+            }
+            (Some((_, offset, len)), None) => offset + len - 1,
+            (None, Some((_, offset, _))) => offset,
+            (None, None) => return None,
+        };
+        Some((self.axis.position(offset), true))
+    }
+}
+
+fn invalid_bed(line: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::ReadRecord(format!(
+        "Invalid BED at line {}: {}",
+        line, msg
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+
+    // a: chr1 100..200 to q1 0..100, with 10 bases of q1 deleted at 150
+    // b: chr1 190..290 to q2 reversed, overlapping a by 10 bases
+    // c: chr1 180..220 to q3, shorter than both but with the highest AS
+    const PAF: &str = "\
+q1\t100\t0\t90\t+\tchr1\t1000\t100\t200\t90\t100\t60\tAS:i:10\tcg:Z:50M10D40M
+q2\t200\t50\t150\t-\tchr1\t1000\t190\t290\t100\t100\t60\tAS:i:20\tcg:Z:100M
+q3\t40\t0\t40\t+\tchr1\t1000\t180\t220\t40\t40\t60\tAS:i:90\tcg:Z:40M
+";
+
+    fn liftover() -> Liftover {
+        Liftover::from_records(records(PAF))
+    }
+
+    fn mapped(
+        target: (u64, u64),
+        query: (&str, u64, u64),
+        strand: char,
+        fraction: f64,
+        record: usize,
+    ) -> LiftedInterval {
+        LiftedInterval::Mapped {
+            target_start: target.0,
+            target_end: target.1,
+            query_name: query.0.to_string(),
+            query_start: query.1,
+            query_end: query.2,
+            strand,
+            mapped_fraction: fraction,
+            record,
+        }
+    }
+
+    #[test]
+    fn test_contained() {
+        let liftover = liftover();
+        assert_eq!(
+            liftover.lift_interval("chr1", 110, 130).unwrap(),
+            [mapped((110, 130), ("q1", 10, 30), '+', 1.0, 0)]
+        );
+        // across the deletion, 140..170 holds 20 aligned bases
+        assert_eq!(
+            liftover.lift_interval("chr1", 140, 170).unwrap(),
+            [mapped((140, 170), ("q1", 40, 60), '+', 20.0 / 30.0, 0)]
+        );
+        // on the minus strand, 240..250 is 50..60 bases into q2 reversed
+        assert_eq!(
+            liftover.lift_interval("chr1", 240, 250).unwrap(),
+            [mapped((240, 250), ("q2", 90, 100), '-', 1.0, 1)]
+        );
+    }
+
+    #[test]
+    fn test_split() {
+        let liftover = liftover();
+        // by the longest block, a takes 170..200 and b the rest
+        assert_eq!(
+            liftover.lift_interval("chr1", 170, 230).unwrap(),
+            [
+                mapped((170, 200), ("q1", 60, 90), '+', 1.0, 0),
+                mapped((200, 230), ("q2", 110, 140), '-', 1.0, 1),
+            ]
+        );
+        // by AS, c takes 180..220
+        let mut by_as = liftover;
+        by_as.score(Score::As);
+        assert_eq!(
+            by_as.lift_interval("chr1", 170, 230).unwrap(),
+            [
+                mapped((170, 180), ("q1", 60, 70), '+', 1.0, 0),
+                mapped((180, 220), ("q3", 0, 40), '+', 1.0, 2),
+                mapped((220, 230), ("q2", 110, 120), '-', 1.0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unmappable() {
+        let liftover = liftover();
+        assert_eq!(
+            liftover.lift_interval("chr1", 50, 110).unwrap(),
+            [
+                LiftedInterval::Unmapped {
+                    target_start: 50,
+                    target_end: 100
+                },
+                mapped((100, 110), ("q1", 0, 10), '+', 1.0, 0),
+            ]
+        );
+        // wholly in the deletion
+        assert_eq!(
+            liftover.lift_interval("chr1", 152, 158).unwrap(),
+            [LiftedInterval::Unmapped {
+                target_start: 152,
+                target_end: 158
+            }]
+        );
+        assert_eq!(
+            liftover.lift_interval("chr2", 0, 10).unwrap()[0].target_interval(),
+            (0, 10)
+        );
+        assert!(liftover.lift_interval("chr1", 10, 10).unwrap().is_empty());
+
+        // a cg tag longer than the query interval
+        let paf = "q\t100\t10\t20\t-\tt\t100\t0\t60\t50\t60\t60\tcg:Z:30M20I10M\n";
+        let liftover = Liftover::from_records(records(paf));
+        assert!(liftover.lift_interval("t", 0, 60).is_err());
+    }
+
+    #[test]
+    fn test_lift_position() {
+        let liftover = liftover();
+        let position = |target, pos| {
+            liftover
+                .lift_position(target, pos)
+                .unwrap()
+                .map(|p| (p.query_name, p.query_pos, p.strand, p.deleted, p.record))
+        };
+        assert_eq!(
+            position("chr1", 120),
+            Some(("q1".into(), 20, '+', false, 0))
+        );
+        // 150..160 is deleted: 152 is nearer 149, 158 nearer 160
+        assert_eq!(position("chr1", 152), Some(("q1".into(), 49, '+', true, 0)));
+        assert_eq!(position("chr1", 158), Some(("q1".into(), 50, '+', true, 0)));
+        // b is the longest block over 195, though c has the highest AS
+        assert_eq!(
+            position("chr1", 195),
+            Some(("q2".into(), 144, '-', false, 1))
+        );
+        assert_eq!(position("chr1", 99), None);
+        assert_eq!(position("chr2", 150), None);
+
+        // a primary alignment comes before a longer secondary one
+        let paf = format!(
+            "{}{}",
+            PAF, "q4\t40\t0\t40\t+\tchr1\t1000\t190\t230\t40\t40\t60\ttp:A:P\tcg:Z:40M\n"
+        )
+        .replace("\tAS", "\ttp:A:S\tAS");
+        let liftover = Liftover::from_records(records(&paf));
+        let lifted = liftover.lift_position("chr1", 195).unwrap().unwrap();
+        assert_eq!((lifted.query_name.as_str(), lifted.query_pos), ("q4", 5));
+    }
+
+    #[test]
+    fn test_lift_positions_sorted() {
+        let liftover = liftover();
+        let positions = [
+            ("chr1", 250),
+            ("chr2", 10),
+            ("chr1", 120),
+            ("chr1", 152),
+            ("chr1", 195),
+            ("chr1", 99),
+            ("chr1", 120),
+        ];
+        let batch = liftover.lift_positions_sorted(&positions).unwrap();
+        let one_by_one: Vec<_> = positions
+            .iter()
+            .map(|&(target, pos)| liftover.lift_position(target, pos).unwrap())
+            .collect();
+        assert_eq!(batch, one_by_one);
+        assert_eq!(batch.iter().filter(|p| p.is_some()).count(), 5);
+    }
+
+    #[test]
+    fn test_lift_positions_megabase() {
+        // a megabase alignment with a deletion every 100 bases
+        let runs = 10_000;
+        let paf = format!(
+            "q\t{q}\t0\t{q}\t-\tt\t{t}\t0\t{t}\t{q}\t{t}\t60\tcg:Z:{cg}\n",
+            q = runs * 100,
+            t = runs * 101,
+            cg = "100M1D".repeat(runs),
+        );
+        let liftover = Liftover::from_records(records(&paf));
+        let positions: Vec<_> = (0..100_000u64)
+            .map(|i| ("t", (i * 7919) % (runs as u64 * 101)))
+            .collect();
+        let started = std::time::Instant::now();
+        let lifted = liftover.lift_positions_sorted(&positions).unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        for (&(_, pos), lifted) in positions.iter().zip(&lifted) {
+            let lifted = lifted.as_ref().unwrap();
+            let (run, within) = (pos / 101, pos % 101);
+            assert_eq!(lifted.deleted, within == 100);
+            let offset = run * 100 + within.min(99);
+            assert_eq!(lifted.query_pos, runs as u64 * 100 - 1 - offset);
+        }
+    }
+
+    #[test]
+    fn test_lift_bed() {
+        let bed = "\
+track name=test
+chr1\t110\t130\tfeature\t0\t+
+chr1\t240\t250\tflipped\t0\t+
+chr1\t280\t300
+chr9\t0\t10
+";
+        let mut output = Vec::new();
+        let report = liftover().lift_bed(bed.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "track name=test\n\
+             q1\t10\t30\tfeature\t0\t+\n\
+             q2\t90\t100\tflipped\t0\t-\n\
+             q2\t50\t60\n"
+        );
+        assert_eq!(report.intervals, 4);
+        assert_eq!(report.written, 3);
+        assert_eq!(
+            report.unmapped,
+            [("chr1".to_string(), 290, 300), ("chr9".to_string(), 0, 10)]
+        );
+        assert!(liftover().lift_bed(&b"chr1\t1\n"[..], Vec::new()).is_err());
+        assert!(liftover()
+            .lift_bed(&b"chr1\ta\t2\n"[..], Vec::new())
+            .is_err());
+    }
+}
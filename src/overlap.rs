@@ -0,0 +1,242 @@
+/*!
+Classify overlaps between reads, as miniasm does when building its string
+graph.
+
+```
+use paf::overlap::{classify, OverlapKind, OverlapOptions};
+use paf::Reader;
+
+// the end of read a overlaps the start of read b
+let paf = b"a\t10000\t4000\t10000\t+\tb\t10000\t0\t6000\t5900\t6000\t60\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let record = reader.records().next().unwrap().unwrap();
+assert_eq!(classify(&record, &OverlapOptions::default()), OverlapKind::Dovetail);
+```
+*/
+
+use crate::PafRecord;
+
+/// Thresholds on the unaligned ends of an overlap, as miniasm's `-h` and
+/// `-I` options.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverlapOptions {
+    /// The longest unaligned end, or overhang, of an overlap which is not
+    /// an internal match.
+    pub max_hang: u64,
+    /// The least fraction of an overlap, with its overhangs, which is
+    /// aligned for it not to be an internal match.
+    pub int_frac: f64,
+}
+
+impl Default for OverlapOptions {
+    /// miniasm's defaults, overhangs up to 1000 bases and 80% aligned.
+    fn default() -> Self {
+        OverlapOptions {
+            max_hang: 1000,
+            int_frac: 0.8,
+        }
+    }
+}
+
+/// How two reads overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverlapKind {
+    /// The alignment ends far from the ends of both reads, as from a
+    /// repeat.
+    Internal,
+    /// The query is contained in the target.
+    QueryContained,
+    /// The target is contained in the query.
+    TargetContained,
+    /// The end of one read overlaps the start of the other.
+    Dovetail,
+    /// Each read is contained in the other: their unaligned ends are the
+    /// same length, as when both are aligned end to end.
+    FullMatch,
+}
+
+/// Which read of an overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Read {
+    /// The query.
+    Query,
+    /// The target.
+    Target,
+}
+
+/// An overlap classified by [`classify_overlap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overlap {
+    /// How the reads overlap.
+    pub kind: OverlapKind,
+    /// For containment, the read contained in the other.
+    pub contained: Option<Read>,
+    /// For dovetails, the read which comes first along the sequence the
+    /// two reads make together, reading the query forward and the target on
+    /// the strand of the record. The end of this read overlaps the start of
+    /// the other.
+    pub first: Option<Read>,
+    /// The strand of the target relative to the query.
+    pub strand: char,
+}
+
+/// Classifies an overlap between two reads, as miniasm's `ma_hit2arc`.
+///
+/// The unaligned ends of the reads are measured in the direction of the
+/// query, so on the minus strand the start of the alignment on the target
+/// is its 3' overhang. The overhang at each end of the overlap is the
+/// shorter of those of the two reads there; an overlap is internal if
+/// either overhang is longer than [`max_hang`](OverlapOptions::max_hang),
+/// or less than [`int_frac`](OverlapOptions::int_frac) of the overlap is
+/// aligned on the query. Otherwise the read with no more unaligned at
+/// either end than the other is contained, or else the overlap is a
+/// dovetail.
+pub fn classify(record: &PafRecord, options: &OverlapOptions) -> OverlapKind {
+    classify_overlap(record, options).kind
+}
+
+/// Classifies an overlap as [`classify`], also telling which read is
+/// contained or which comes first.
+pub fn classify_overlap(record: &PafRecord, options: &OverlapOptions) -> Overlap {
+    let (query_len, query_start, query_end) = (
+        record.query_len() as i64,
+        record.query_start() as i64,
+        record.query_end() as i64,
+    );
+    let (target_len, target_start, target_end) = (
+        record.target_len() as i64,
+        record.target_start() as i64,
+        record.target_end() as i64,
+    );
+    let reverse = record.strand() == '-';
+    // the unaligned ends of the target before and after the alignment,
+    // along the query
+    let (target5, target3) = if reverse {
+        (target_len - target_end, target_start)
+    } else {
+        (target_start, target_len - target_end)
+    };
+    let (query5, query3) = (query_start, query_len - query_end);
+    let (ext5, ext3) = (query5.min(target5), query3.min(target3));
+
+    let mut overlap = Overlap {
+        kind: OverlapKind::Internal,
+        contained: None,
+        first: None,
+        strand: if reverse { '-' } else { '+' },
+    };
+    let max_hang = options.max_hang.min(i64::MAX as u64) as i64;
+    let aligned = query_end - query_start;
+    if ext5 > max_hang
+        || ext3 > max_hang
+        || (aligned as f64) < (aligned + ext5 + ext3) as f64 * options.int_frac
+    {
+        return overlap;
+    }
+
+    let query_contained = query5 <= target5 && query3 <= target3;
+    let target_contained = query5 >= target5 && query3 >= target3;
+    overlap.kind = match (query_contained, target_contained) {
+        (true, true) => OverlapKind::FullMatch,
+        (true, false) => {
+            overlap.contained = Some(Read::Query);
+            OverlapKind::QueryContained
+        }
+        (false, true) => {
+            overlap.contained = Some(Read::Target);
+            OverlapKind::TargetContained
+        }
+        (false, false) => {
+            // the read with more before the alignment comes first
+            overlap.first = Some(if query5 > target5 {
+                Read::Query
+            } else {
+                Read::Target
+            });
+            OverlapKind::Dovetail
+        }
+    };
+    overlap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::hit;
+
+    fn class(query: (u32, u32, u32), strand: char, target: (u32, u32, u32)) -> Overlap {
+        classify_overlap(&hit(query, strand, target), &OverlapOptions::default())
+    }
+
+    #[test]
+    fn test_dovetail() {
+        // the end of q overlaps the start of t, or the start of t's
+        // reverse complement, which is its end
+        for (strand, target) in [('+', (10000, 0, 6000)), ('-', (10000, 4000, 10000))] {
+            let overlap = class((10000, 4000, 10000), strand, target);
+            assert_eq!(overlap.kind, OverlapKind::Dovetail);
+            assert_eq!(overlap.first, Some(Read::Query));
+            assert_eq!(overlap.strand, strand);
+        }
+        for (strand, target) in [('+', (10000, 4000, 10000)), ('-', (10000, 0, 6000))] {
+            let overlap = class((10000, 0, 6000), strand, target);
+            assert_eq!(overlap.kind, OverlapKind::Dovetail);
+            assert_eq!(overlap.first, Some(Read::Target));
+        }
+        // within the overhang allowed, but not a dovetail if the strand
+        // is not taken into account
+        let overlap = class((10000, 0, 6000), '+', (10000, 0, 6000));
+        assert_eq!(overlap.kind, OverlapKind::Internal);
+        let overlap = class((10000, 500, 6000), '-', (10000, 300, 5500));
+        assert_eq!(overlap.kind, OverlapKind::Dovetail);
+        assert_eq!(overlap.first, Some(Read::Target));
+    }
+
+    #[test]
+    fn test_contained() {
+        for strand in ['+', '-'] {
+            let overlap = class((3000, 0, 3000), strand, (10000, 2000, 5000));
+            assert_eq!(overlap.kind, OverlapKind::QueryContained);
+            assert_eq!(overlap.contained, Some(Read::Query));
+            let overlap = class((10000, 2000, 5000), strand, (3000, 0, 3000));
+            assert_eq!(overlap.kind, OverlapKind::TargetContained);
+            assert_eq!(overlap.contained, Some(Read::Target));
+        }
+        // overhangs within the allowed on both ends of the query
+        let overlap = class((3200, 100, 3100), '-', (10000, 2000, 5000));
+        assert_eq!(overlap.kind, OverlapKind::QueryContained);
+        for strand in ['+', '-'] {
+            let overlap = class((5000, 0, 5000), strand, (5000, 0, 5000));
+            assert_eq!(overlap.kind, OverlapKind::FullMatch);
+            assert_eq!((overlap.contained, overlap.first), (None, None));
+        }
+    }
+
+    #[test]
+    fn test_internal() {
+        // both reads go on well past both ends
+        assert_eq!(
+            class((10000, 3000, 6000), '+', (10000, 5000, 8000)).kind,
+            OverlapKind::Internal
+        );
+        assert_eq!(
+            class((10000, 3000, 6000), '-', (10000, 2000, 5000)).kind,
+            OverlapKind::Internal
+        );
+        // overhangs of 700 and 200 each within 1000, but together more
+        // than a fifth of the overlap
+        let record = hit((2000, 700, 1500), '+', (10000, 9000, 9800));
+        let options = OverlapOptions::default();
+        assert_eq!(classify(&record, &options), OverlapKind::Internal);
+        let options = OverlapOptions {
+            int_frac: 0.4,
+            ..options
+        };
+        assert_eq!(classify(&record, &options), OverlapKind::Dovetail);
+        let options = OverlapOptions {
+            max_hang: 500,
+            ..options
+        };
+        assert_eq!(classify(&record, &options), OverlapKind::Internal);
+    }
+}
@@ -0,0 +1,328 @@
+/*!
+Paint each query with the target its bases best align to, as for
+haplotype or ancestry painting.
+
+```
+use paf::paint::paint_queries;
+use paf::select::Score;
+use paf::Reader;
+
+// the alignment to hap2 scores higher where the two overlap
+let paf = b"q\t1000\t0\t600\t+\thap1\t5000\t0\t600\t600\t600\t60\tAS:i:500\n\
+            q\t1000\t400\t900\t+\thap2\t5000\t400\t900\t500\t500\t60\tAS:i:900\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let segments: Vec<_> = paint_queries(reader.records(), Score::As)
+    .map(Result::unwrap)
+    .collect();
+let painted: Vec<_> = segments
+    .iter()
+    .map(|s| (s.query_start, s.query_end, s.source.as_ref().map(|p| p.target_name.as_str())))
+    .collect();
+assert_eq!(
+    painted,
+    [(0, 400, Some("hap1")), (400, 900, Some("hap2")), (900, 1000, None)]
+);
+```
+*/
+
+use crate::cigar::segments;
+use crate::select::{group_by_query, Score};
+use crate::{PafRecord, Result};
+
+/// The alignment a [`PaintSegment`] is painted with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaintSource {
+    /// The target name.
+    pub target_name: String,
+    /// Start on the target of the bases aligned to the segment.
+    pub target_start: u64,
+    /// End on the target of the bases aligned to the segment.
+    pub target_end: u64,
+    /// The strand of the alignment.
+    pub strand: char,
+    /// The index of the record among the records given.
+    pub record: usize,
+}
+
+/// A part of a query and the alignment it is painted with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaintSegment {
+    /// The query name.
+    pub query_name: String,
+    /// Start of the segment on the query.
+    pub query_start: u64,
+    /// End of the segment on the query.
+    pub query_end: u64,
+    /// The best alignment covering the segment, or `None` for a part of the
+    /// query no alignment covers.
+    pub source: Option<PaintSource>,
+}
+
+/// Paints the query of `records_of_one_query` with its alignments, as
+/// segments in order along it from start to end.
+///
+/// Each base of the query is painted with the best alignment covering it
+/// by `by`, ties broken as described for [`Score`] and then by order, so
+/// that where alignments overlap the better one wins over the overlap only.
+/// All records count, so filter out secondary alignments first if they
+/// should not. The target interval of a segment is placed through the cg
+/// tag, or in proportion along the alignment without a valid one, and
+/// records whose query end is not after their start paint nothing. Record
+/// indices are positions in `records_of_one_query`.
+pub fn paint_query(records_of_one_query: &[PafRecord], by: Score) -> Vec<PaintSegment> {
+    let records = records_of_one_query;
+    let Some(first) = records.first() else {
+        return Vec::new();
+    };
+    let mut order: Vec<usize> = (0..records.len()).collect();
+    order.sort_by(|&a, &b| by.compare(&records[a], &records[b]).then(a.cmp(&b)));
+
+    // give each part of the query not yet painted to the best alignment
+    // covering it
+    let query_len = first.query_len() as u64;
+    let mut parts: Vec<(u64, u64, Option<usize>)> = vec![(0, query_len, None)];
+    for i in order {
+        let (from, to) = (
+            records[i].query_start() as u64,
+            records[i].query_end() as u64,
+        );
+        if from >= to {
+            continue;
+        }
+        let mut split = Vec::with_capacity(parts.len() + 2);
+        for (s, e, owner) in parts {
+            if owner.is_some() || to <= s || from >= e {
+                split.push((s, e, owner));
+                continue;
+            }
+            if s < from {
+                split.push((s, from, None));
+            }
+            split.push((s.max(from), e.min(to), Some(i)));
+            if to < e {
+                split.push((to, e, None));
+            }
+        }
+        parts = split;
+    }
+
+    parts
+        .into_iter()
+        .filter(|&(s, e, _)| s < e)
+        .map(|(s, e, owner)| PaintSegment {
+            query_name: first.query_name().to_string(),
+            query_start: s,
+            query_end: e,
+            source: owner.map(|i| {
+                let record = &records[i];
+                let (target_start, target_end) = target_interval(record, s, e);
+                PaintSource {
+                    target_name: record.target_name().to_string(),
+                    target_start,
+                    target_end,
+                    strand: record.strand(),
+                    record: i,
+                }
+            }),
+        })
+        .collect()
+}
+
+/// Paints each query of input grouped by query, as [`paint_query`], one
+/// query at a time. Record indices are positions in the whole input.
+pub fn paint_queries<I>(records: I, by: Score) -> impl Iterator<Item = Result<PaintSegment>>
+where
+    I: IntoIterator<Item = Result<PafRecord>>,
+{
+    let mut seen = 0;
+    group_by_query(records).flat_map(move |group| {
+        let segments = match group {
+            Ok(group) => {
+                let mut segments = paint_query(&group, by);
+                for source in segments.iter_mut().filter_map(|s| s.source.as_mut()) {
+                    source.record += seen;
+                }
+                seen += group.len();
+                segments.into_iter().map(Ok).collect()
+            }
+            Err(err) => vec![Err(err)],
+        };
+        segments.into_iter()
+    })
+}
+
+/// The target interval aligned to `query_start..query_end`, which the
+/// record covers.
+fn target_interval(record: &PafRecord, query_start: u64, query_end: u64) -> (u64, u64) {
+    let (start, end) = (record.query_start() as u64, record.query_end() as u64);
+    // the part as offsets along the aligned strand of the query
+    let (from, to) = if record.strand() == '-' {
+        (
+            end.saturating_sub(query_end),
+            end.saturating_sub(query_start),
+        )
+    } else {
+        (
+            query_start.saturating_sub(start),
+            query_end.saturating_sub(start),
+        )
+    };
+    let target_start = record.target_start() as u64;
+
+    if let Some(Ok(cigar)) = record.cigar() {
+        let mut interval: Option<(u64, u64)> = None;
+        let mut flank = None;
+        for segment in segments(&cigar, target_start) {
+            if !segment.op.consumes_query() {
+                continue;
+            }
+            let (a, b) = (
+                segment.query_offset.max(from),
+                (segment.query_offset + segment.len as u64).min(to),
+            );
+            if a >= b {
+                continue;
+            }
+            if segment.op.consumes_target() {
+                let (t0, t1) = (
+                    segment.target_pos + a - segment.query_offset,
+                    segment.target_pos + b - segment.query_offset,
+                );
+                interval = Some(interval.map_or((t0, t1), |(s, _)| (s, t1)));
+            } else if flank.is_none() {
+                flank = Some(segment.target_pos);
+            }
+        }
+        if let Some(interval) = interval.or(flank.map(|t| (t, t))) {
+            return interval;
+        }
+    }
+
+    let query_span = end.saturating_sub(start).max(1) as f64;
+    let target_span = record.target_end().saturating_sub(record.target_start()) as f64;
+    let scale =
+        |offset: u64| target_start + (offset as f64 * target_span / query_span).round() as u64;
+    (scale(from), scale(to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+    use crate::Reader;
+
+    /// A segment as query interval, and target name, interval and record.
+    type Painted<'a> = (u64, u64, Option<(&'a str, u64, u64, usize)>);
+
+    fn painted(segments: &[PaintSegment]) -> Vec<Painted<'_>> {
+        segments
+            .iter()
+            .map(|s| {
+                (
+                    s.query_start,
+                    s.query_end,
+                    s.source.as_ref().map(|p| {
+                        (
+                            p.target_name.as_str(),
+                            p.target_start,
+                            p.target_end,
+                            p.record,
+                        )
+                    }),
+                )
+            })
+            .collect()
+    }
+
+    fn paint(paf: &str, by: Score) -> Vec<PaintSegment> {
+        paint_queries(Reader::from_reader(paf.as_bytes()).records(), by)
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn test_overlap() {
+        // t1 covers 100..600 and t2 400..1000 of 1200 bases, overlapping by
+        // 200; t2 has the higher AS, t1 the more matches
+        let paf = "\
+q\t1200\t100\t600\t+\tt1\t5000\t1000\t1500\t500\t500\t60\tAS:i:400
+q\t1200\t400\t1000\t-\tt2\t5000\t2000\t2600\t600\t600\t60\tAS:i:600
+";
+        assert_eq!(
+            painted(&paint(paf, Score::As)),
+            [
+                (0, 100, None),
+                (100, 400, Some(("t1", 1000, 1300, 0))),
+                (400, 1000, Some(("t2", 2000, 2600, 1))),
+                (1000, 1200, None),
+            ]
+        );
+        // by residue matches t1 wins instead; t2 is on the minus strand, so
+        // the query it keeps is at the start of its target interval
+        let paf = paf.replace("600\t600\t60\tAS:i:600", "450\t600\t60\tAS:i:600");
+        assert_eq!(
+            painted(&paint(&paf, Score::ResidueMatches)),
+            [
+                (0, 100, None),
+                (100, 600, Some(("t1", 1000, 1500, 0))),
+                (600, 1000, Some(("t2", 2000, 2400, 1))),
+                (1000, 1200, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_contained_and_cigar() {
+        // a better alignment inside a longer one splits it in two; the cg
+        // tag places a deletion of 10 and an insertion of 5 on the target
+        let paf = "\
+q\t1000\t0\t1000\t+\tt1\t5000\t0\t1005\t990\t1010\t60\tAS:i:900\tcg:Z:300M10D300M5I395M
+q\t1000\t290\t310\t+\tt2\t5000\t0\t20\t20\t20\t60\tAS:i:950
+";
+        assert_eq!(
+            painted(&paint(paf, Score::As)),
+            [
+                (0, 290, Some(("t1", 0, 290, 0))),
+                (290, 310, Some(("t2", 0, 20, 1))),
+                (310, 1000, Some(("t1", 320, 1005, 0))),
+            ]
+        );
+        // a part wholly within the insertion has an empty target interval
+        let records = records(paf);
+        assert_eq!(paint_query(&records, Score::As).len(), 3);
+        assert_eq!(target_interval(&records[0], 601, 604), (610, 610));
+    }
+
+    #[test]
+    fn test_queries_stream() {
+        let paf = "\
+q1\t100\t0\t100\t+\tt\t5000\t0\t100\t100\t100\t60
+q1\t100\t0\t50\t+\tt\t5000\t500\t550\t50\t50\t60
+q2\t100\t50\t100\t-\tt\t5000\t1000\t1050\t50\t50\t60
+";
+        assert_eq!(
+            painted(&paint(paf, Score::BlockLen)),
+            [
+                (0, 100, Some(("t", 0, 100, 0))),
+                (0, 50, None),
+                (50, 100, Some(("t", 1000, 1050, 2))),
+            ]
+        );
+        let mut reader = Reader::from_reader(&b"bad\n"[..]);
+        let mut errors = paint_queries(reader.records(), Score::As);
+        assert!(errors.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_reversed_coordinates() {
+        // a target interval ending before it starts is placed as empty, and
+        // a query interval ending before it starts paints nothing
+        let paf = "q\t100\t0\t100\t+\tt\t100\t60\t50\t100\t100\t60\n";
+        assert_eq!(
+            painted(&paint(paf, Score::As)),
+            [(0, 100, Some(("t", 60, 60, 0)))]
+        );
+        let paf = "q\t100\t60\t20\t+\tt\t100\t0\t40\t40\t40\t60\n";
+        assert_eq!(painted(&paint(paf, Score::As)), [(0, 100, None)]);
+    }
+}
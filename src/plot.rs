@@ -0,0 +1,306 @@
+/*!
+Turn records into segments for dot plots.
+
+```
+use paf::plot::{dotplot_segments, DotplotOptions};
+use paf::Reader;
+
+let paf = b"q\t1000\t100\t300\t-\tt\t5000\t2000\t2200\t190\t200\t60\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+let segments = dotplot_segments(&records, &DotplotOptions::default()).unwrap();
+// on the minus strand the query runs down as the target runs up
+assert_eq!((segments[0].x0, segments[0].x1), (2000, 2200));
+assert_eq!((segments[0].y0, segments[0].y1), (300, 100));
+```
+*/
+
+use std::borrow::Borrow;
+use std::io::{BufWriter, Write};
+
+use crate::{Cigar, Error, ErrorKind, PafRecord, Result};
+
+/// How [`dotplot_segments`] draws each record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DotplotOptions {
+    /// Split records at insertions and deletions longer than this, from
+    /// the cg tag, so that the plot shows them. Records without a cg tag are
+    /// not split. By default no record is split.
+    pub split_indels: Option<u64>,
+}
+
+/// A line of a dot plot, with the target along the x axis and the query
+/// along the y axis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    /// The target name.
+    pub target_name: String,
+    /// The query name.
+    pub query_name: String,
+    /// Where the segment starts on the target.
+    pub x0: u64,
+    /// Where the segment ends on the target, never before `x0`.
+    pub x1: u64,
+    /// The query position at `x0`.
+    pub y0: u64,
+    /// The query position at `x1`, before `y0` on the minus strand.
+    pub y1: u64,
+    /// The strand of the record.
+    pub strand: char,
+    /// The BLAST identity of the record, for colouring.
+    pub identity: f64,
+    /// The index of the record among the records given.
+    pub record: usize,
+}
+
+/// The dot plot segments of records, in order of the records.
+///
+/// Each record gives one segment, or with
+/// [`split_indels`](DotplotOptions::split_indels) one for each part between
+/// long indels. Segments on the minus strand run from the query end down to
+/// the query start, so that they slope down. It is an error for a record
+/// being split to have an invalid cg tag.
+pub fn dotplot_segments<I>(records: I, options: &DotplotOptions) -> Result<Vec<Segment>>
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    let mut segments = Vec::new();
+    for (i, record) in records.into_iter().enumerate() {
+        let record = record.borrow();
+        let segment = |target: (u64, u64), query: (u64, u64)| {
+            let (y0, y1) = match record.strand() {
+                '-' => (
+                    (record.query_end() as u64).saturating_sub(query.0),
+                    (record.query_end() as u64).saturating_sub(query.1),
+                ),
+                _ => (
+                    record.query_start() as u64 + query.0,
+                    record.query_start() as u64 + query.1,
+                ),
+            };
+            Segment {
+                target_name: record.target_name().to_string(),
+                query_name: record.query_name().to_string(),
+                x0: record.target_start() as u64 + target.0,
+                x1: record.target_start() as u64 + target.1,
+                y0,
+                y1,
+                strand: record.strand(),
+                identity: record.blast_identity(),
+                record: i,
+            }
+        };
+        let whole = (
+            (
+                0,
+                record.target_end().saturating_sub(record.target_start()) as u64,
+            ),
+            (
+                0,
+                record.query_end().saturating_sub(record.query_start()) as u64,
+            ),
+        );
+        let pieces = match (options.split_indels, record.cigar()) {
+            (Some(max), Some(cigar)) => split(&cigar?, max),
+            _ => vec![whole],
+        };
+        segments.extend(pieces.into_iter().map(|(t, q)| segment(t, q)));
+    }
+    Ok(segments)
+}
+
+/// The parts of an alignment between indels longer than `max`, as offsets
+/// along the target and along the aligned strand of the query.
+fn split(cigar: &Cigar, max: u64) -> Vec<((u64, u64), (u64, u64))> {
+    let mut pieces = Vec::new();
+    let (mut target, mut query) = (0, 0);
+    let mut start = None;
+    for (len, op) in cigar.ops() {
+        let len = len as u64;
+        let (consumes_query, consumes_target) = (op.consumes_query(), op.consumes_target());
+        let long_gap = consumes_query != consumes_target && len > max;
+        if long_gap {
+            if let Some((target_start, query_start)) = start.take() {
+                pieces.push(((target_start, target), (query_start, query)));
+            }
+        } else if consumes_query && consumes_target && start.is_none() {
+            start = Some((target, query));
+        }
+        if consumes_target {
+            target += len;
+        }
+        if consumes_query {
+            query += len;
+        }
+    }
+    if let Some((target_start, query_start)) = start {
+        pieces.push(((target_start, target), (query_start, query)));
+    }
+    pieces
+}
+
+/// Writes segments as TSV with a header row.
+pub fn write_segments_tsv<W: Write>(writer: W, segments: &[Segment]) -> Result<()> {
+    let mut writer = BufWriter::new(writer);
+    writeln!(
+        writer,
+        "target_name\tquery_name\tx0\tx1\ty0\ty1\tstrand\tidentity\trecord"
+    )?;
+    for s in segments {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.4}\t{}",
+            s.target_name, s.query_name, s.x0, s.x1, s.y0, s.y1, s.strand, s.identity, s.record
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes segments as a JSON array of objects with the fields of
+/// [`Segment`], one to a line, as plotting libraries take for their data.
+pub fn write_segments_json<W: Write>(writer: W, segments: &[Segment]) -> Result<()> {
+    let mut writer = BufWriter::new(writer);
+    writeln!(writer, "[")?;
+    for (i, s) in segments.iter().enumerate() {
+        if !s.identity.is_finite() {
+            return Err(Error::new(ErrorKind::WriteRecord {
+                index: i as u64,
+                err: Error::new(ErrorKind::InvalidRecord(format!(
+                    "JSON cannot hold the identity {}",
+                    s.identity
+                ))),
+            }));
+        }
+        writeln!(
+            writer,
+            "{{\"target_name\":{},\"query_name\":{},\"x0\":{},\"x1\":{},\"y0\":{},\"y1\":{},\
+             \"strand\":\"{}\",\"identity\":{},\"record\":{}}}{}",
+            json_string(&s.target_name),
+            json_string(&s.query_name),
+            s.x0,
+            s.x1,
+            s.y0,
+            s.y1,
+            s.strand,
+            s.identity,
+            s.record,
+            if i + 1 < segments.len() { "," } else { "" }
+        )?;
+    }
+    writeln!(writer, "]")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// A JSON string literal of `s`.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+
+    const PAF: &str = "\
+q1\t1000\t100\t410\t+\tt\t5000\t1000\t1320\t300\t330\t60\tcg:Z:100M20D100M10I100M
+q2\t1000\t100\t410\t-\tt\t5000\t3000\t3320\t300\t330\t60\tcg:Z:100M20D100M10I100M
+";
+
+    fn segments(split_indels: Option<u64>) -> Vec<Segment> {
+        let records = records(PAF);
+        dotplot_segments(&records, &DotplotOptions { split_indels }).unwrap()
+    }
+
+    fn coordinates(segments: &[Segment]) -> Vec<(u64, u64, u64, u64, usize)> {
+        segments
+            .iter()
+            .map(|s| (s.x0, s.x1, s.y0, s.y1, s.record))
+            .collect()
+    }
+
+    #[test]
+    fn test_orientation() {
+        let segments = segments(None);
+        // the plus strand slopes up, and the minus strand down
+        assert_eq!(
+            coordinates(&segments),
+            [(1000, 1320, 100, 410, 0), (3000, 3320, 410, 100, 1)]
+        );
+        assert!((segments[0].identity - 300.0 / 330.0).abs() < 1e-9);
+        assert_eq!(segments[1].strand, '-');
+    }
+
+    #[test]
+    fn test_reversed_coordinates() {
+        // intervals ending before they start give empty segments
+        let paf = "q\t1000\t410\t100\t-\tt\t5000\t1320\t1000\t300\t330\t60\n";
+        let records = records(paf);
+        let segments = dotplot_segments(&records, &DotplotOptions::default()).unwrap();
+        assert_eq!(coordinates(&segments), [(1320, 1320, 100, 100, 0)]);
+    }
+
+    #[test]
+    fn test_split_indels() {
+        // the 20 base deletion splits, and the 10 base insertion does not
+        assert_eq!(
+            coordinates(&segments(Some(15))),
+            [
+                (1000, 1100, 100, 200, 0),
+                (1120, 1320, 200, 410, 0),
+                (3000, 3100, 410, 310, 1),
+                (3120, 3320, 310, 100, 1),
+            ]
+        );
+        // both split
+        assert_eq!(
+            coordinates(&segments(Some(5)))[..3],
+            [
+                (1000, 1100, 100, 200, 0),
+                (1120, 1220, 200, 300, 0),
+                (1220, 1320, 310, 410, 0),
+            ]
+        );
+        assert_eq!(segments(Some(50)).len(), 2);
+    }
+
+    #[test]
+    fn test_writers() {
+        let segments = segments(None);
+        let mut tsv = Vec::new();
+        write_segments_tsv(&mut tsv, &segments).unwrap();
+        assert_eq!(
+            String::from_utf8(tsv).unwrap(),
+            "target_name\tquery_name\tx0\tx1\ty0\ty1\tstrand\tidentity\trecord\n\
+             t\tq1\t1000\t1320\t100\t410\t+\t0.9091\t0\n\
+             t\tq2\t3000\t3320\t410\t100\t-\t0.9091\t1\n"
+        );
+
+        let mut segments = segments;
+        segments[1].query_name = "q\"2".to_string();
+        segments[1].identity = 1.0;
+        let mut json = Vec::new();
+        write_segments_json(&mut json, &segments).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert_eq!(json.lines().count(), 4);
+        assert!(json.ends_with(
+            "{\"target_name\":\"t\",\"query_name\":\"q\\\"2\",\"x0\":3000,\"x1\":3320,\
+             \"y0\":410,\"y1\":100,\"strand\":\"-\",\"identity\":1,\"record\":1}\n]\n"
+        ));
+        segments[0].identity = f64::NAN;
+        assert!(write_segments_json(Vec::new(), &segments).is_err());
+    }
+}
@@ -0,0 +1,348 @@
+/*!
+Quality checks on the alignments of reads and contigs.
+
+```
+use paf::qc::{detect_chimeras, ChimeraOptions};
+use paf::select::group_by_query;
+use paf::Reader;
+
+// the two halves of the read align to different chromosomes
+let paf = b"read\t10000\t0\t5000\t+\tchr1\t1000000\t20000\t25000\t4900\t5000\t60\n\
+            read\t10000\t5000\t10000\t+\tchr2\t1000000\t70000\t75000\t4900\t5000\t60\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let calls = detect_chimeras(group_by_query(reader.records()), &ChimeraOptions::default()).unwrap();
+assert_eq!(calls.len(), 1);
+assert_eq!(calls[0].segments[1].target_name, "chr2");
+```
+*/
+
+use crate::{filter, PafRecord, Result};
+
+/// What counts as a segment of a chimera, and how far apart segments can
+/// be and still be one split alignment. See [`detect_chimeras`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChimeraOptions {
+    /// The least length of a segment on the query.
+    pub min_segment_len: u64,
+    /// The most two segments can overlap on the query.
+    pub max_query_overlap: u64,
+    /// The least mapping quality of a segment.
+    pub min_mapq: u8,
+    /// The longest gap or overlap on the target between neighbouring
+    /// segments of a split alignment.
+    pub max_target_gap: u64,
+}
+
+impl Default for ChimeraOptions {
+    /// Segments of at least 1 kb with mapping quality 20, overlapping by
+    /// up to 100 bases, and split alignments over gaps up to 10 kb.
+    fn default() -> Self {
+        ChimeraOptions {
+            min_segment_len: 1000,
+            max_query_overlap: 100,
+            min_mapq: 20,
+            max_target_gap: 10_000,
+        }
+    }
+}
+
+/// An alignment of part of a chimeric query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChimeraSegment {
+    /// Start of the segment on the query.
+    pub query_start: u64,
+    /// End of the segment on the query.
+    pub query_end: u64,
+    /// The target name.
+    pub target_name: String,
+    /// Start of the segment on the target.
+    pub target_start: u64,
+    /// End of the segment on the target.
+    pub target_end: u64,
+    /// The strand.
+    pub strand: char,
+    /// The mapping quality.
+    pub mapq: u8,
+    /// The index of the record among those of its query.
+    pub record: usize,
+}
+
+/// A query whose alignments imply it is chimeric.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChimeraCall {
+    /// The query name.
+    pub query_name: String,
+    /// The query length.
+    pub query_len: u64,
+    /// The segments of the query, in order along it.
+    pub segments: Vec<ChimeraSegment>,
+    /// From 0 to 1, the fraction of the query the segments cover, scaled
+    /// down for the least mapping quality of the segments below 60.
+    pub confidence: f64,
+}
+
+/// Finds queries whose alignments imply they are chimeric, from their
+/// records grouped by query, as from
+/// [`select::group_by_query`](crate::select::group_by_query).
+///
+/// The segments of a query are its primary alignments, as in
+/// [`filter::primary_only`], which pass the thresholds of `options`, longest
+/// first, leaving out any overlapping a longer one on the query by more than
+/// [`max_query_overlap`](ChimeraOptions::max_query_overlap). A query is
+/// chimeric if neighbouring segments along it are on different targets or
+/// strands, or on the target out of order or further apart than
+/// [`max_target_gap`](ChimeraOptions::max_target_gap); otherwise they are
+/// one alignment split over a gap in the reference, or the query.
+pub fn detect_chimeras<I>(query_groups: I, options: &ChimeraOptions) -> Result<Vec<ChimeraCall>>
+where
+    I: IntoIterator<Item = Result<Vec<PafRecord>>>,
+{
+    let mut calls = Vec::new();
+    for group in query_groups {
+        let group = group?;
+        if let Some(call) = chimera(&group, options) {
+            calls.push(call);
+        }
+    }
+    Ok(calls)
+}
+
+/// The call for the records of one query, if it is chimeric.
+fn chimera(records: &[PafRecord], options: &ChimeraOptions) -> Option<ChimeraCall> {
+    let primary = filter::primary_only();
+    let mut candidates: Vec<usize> = (0..records.len())
+        .filter(|&i| {
+            let record = &records[i];
+            primary(record)
+                && record.mapping_quality() >= options.min_mapq
+                && record.query_end().saturating_sub(record.query_start()) as u64
+                    >= options.min_segment_len
+        })
+        .collect();
+    candidates.sort_by_key(|&i| {
+        let record = &records[i];
+        (
+            std::cmp::Reverse(record.query_end().saturating_sub(record.query_start())),
+            record.query_start(),
+            i,
+        )
+    });
+    let mut kept: Vec<usize> = Vec::new();
+    for i in candidates {
+        let record = &records[i];
+        let overlaps = kept.iter().any(|&k| {
+            let shared = record
+                .query_end()
+                .min(records[k].query_end())
+                .saturating_sub(record.query_start().max(records[k].query_start()));
+            shared as u64 > options.max_query_overlap
+        });
+        if !overlaps {
+            kept.push(i);
+        }
+    }
+    kept.sort_by_key(|&i| (records[i].query_start(), records[i].query_end()));
+
+    let split = kept
+        .windows(2)
+        .all(|pair| split_alignment(&records[pair[0]], &records[pair[1]], options));
+    if kept.len() < 2 || split {
+        return None;
+    }
+
+    let first = &records[kept[0]];
+    let query_len = first.query_len() as u64;
+    let covered: u64 = kept
+        .iter()
+        .map(|&i| {
+            records[i]
+                .query_end()
+                .saturating_sub(records[i].query_start()) as u64
+        })
+        .sum();
+    let mapq = kept
+        .iter()
+        .map(|&i| records[i].mapping_quality())
+        .min()
+        .unwrap_or(0);
+    let coverage = if query_len == 0 {
+        0.0
+    } else {
+        (covered as f64 / query_len as f64).min(1.0)
+    };
+    Some(ChimeraCall {
+        query_name: first.query_name().to_string(),
+        query_len,
+        segments: kept
+            .iter()
+            .map(|&i| {
+                let record = &records[i];
+                ChimeraSegment {
+                    query_start: record.query_start() as u64,
+                    query_end: record.query_end() as u64,
+                    target_name: record.target_name().to_string(),
+                    target_start: record.target_start() as u64,
+                    target_end: record.target_end() as u64,
+                    strand: record.strand(),
+                    mapq: record.mapping_quality(),
+                    record: i,
+                }
+            })
+            .collect(),
+        confidence: coverage * (mapq.min(60) as f64 / 60.0),
+    })
+}
+
+/// Whether `next`, after `prev` along the query, continues the same
+/// alignment across a gap.
+fn split_alignment(prev: &PafRecord, next: &PafRecord, options: &ChimeraOptions) -> bool {
+    if prev.target_name() != next.target_name() || prev.strand() != next.strand() {
+        return false;
+    }
+    // the gap along the target in the direction of the query, negative
+    // where the two overlap
+    let gap = if prev.strand() == '-' {
+        prev.target_start() as i64 - next.target_end() as i64
+    } else {
+        next.target_start() as i64 - prev.target_end() as i64
+    };
+    let max = options.max_target_gap.min(i64::MAX as u64) as i64;
+    // a next segment reaching no further along the target is out of order
+    let backwards = if prev.strand() == '-' {
+        next.target_start() >= prev.target_start()
+    } else {
+        next.target_end() <= prev.target_end()
+    };
+    gap.abs() <= max && !backwards
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::select::group_by_query;
+    use crate::Reader;
+
+    /// A record of `query`, 10 kb long, as query interval, strand, target
+    /// and target interval, and mapping quality.
+    fn line(
+        query: &str,
+        qs: u32,
+        qe: u32,
+        strand: char,
+        target: (&str, u32, u32),
+        mapq: u8,
+    ) -> String {
+        format!(
+            "{}\t10000\t{}\t{}\t{}\t{}\t1000000\t{}\t{}\t{}\t{}\t{}\n",
+            query,
+            qs,
+            qe,
+            strand,
+            target.0,
+            target.1,
+            target.2,
+            qe - qs,
+            qe - qs,
+            mapq
+        )
+    }
+
+    fn calls(lines: &[String], options: &ChimeraOptions) -> Vec<ChimeraCall> {
+        let paf = lines.concat();
+        detect_chimeras(
+            group_by_query(Reader::from_reader(paf.as_bytes()).records()),
+            options,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_clean_and_split() {
+        let options = ChimeraOptions::default();
+        // one alignment end to end
+        let clean = [line("clean", 0, 10000, '+', ("chr1", 5000, 15000), 60)];
+        assert!(calls(&clean, &options).is_empty());
+        // split across a 2 kb gap in the reference, on either strand
+        let split = [
+            line("split", 0, 6000, '+', ("chr1", 5000, 11000), 60),
+            line("split", 6000, 10000, '+', ("chr1", 13000, 17000), 60),
+            line("minus", 0, 6000, '-', ("chr1", 13000, 19000), 60),
+            line("minus", 6000, 10000, '-', ("chr1", 7000, 11000), 60),
+        ];
+        assert!(calls(&split, &options).is_empty());
+        // but not across a gap beyond the allowed
+        let options = ChimeraOptions {
+            max_target_gap: 1000,
+            ..options
+        };
+        assert_eq!(calls(&split, &options).len(), 2);
+    }
+
+    #[test]
+    fn test_reversed_coordinates() {
+        // a query interval ending before it starts spans nothing
+        let lines = [
+            line("r", 0, 5000, '+', ("chr1", 0, 5000), 60),
+            line("r", 0, 10000, '+', ("chr2", 0, 10000), 60)
+                .replace("\t0\t10000\t+", "\t9000\t8000\t+"),
+        ];
+        assert!(calls(&lines, &ChimeraOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_chimera() {
+        let options = ChimeraOptions::default();
+        let lines = [
+            // two chromosomes, with a secondary alignment which is ignored
+            line("a", 0, 4000, '+', ("chr1", 5000, 9000), 60),
+            line("a", 4000, 10000, '-', ("chr2", 1000, 7000), 30),
+            line("a", 4000, 10000, '+', ("chr1", 9000, 15000), 0).replace("\t0\n", "\t0\ttp:A:S\n"),
+            // the same target, but the second half comes back
+            line("b", 0, 5000, '+', ("chr1", 50000, 55000), 60),
+            line("b", 5000, 10000, '+', ("chr1", 40000, 45000), 60),
+            // a short segment and one with low mapping quality
+            line("c", 0, 9500, '+', ("chr1", 0, 9500), 60),
+            line("c", 9500, 10000, '+', ("chr3", 0, 500), 60),
+            line("d", 0, 5000, '+', ("chr1", 0, 5000), 60),
+            line("d", 5000, 10000, '+', ("chr3", 0, 5000), 5),
+        ];
+        let found = calls(&lines, &options);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].query_name, "a");
+        let segments: Vec<_> = found[0]
+            .segments
+            .iter()
+            .map(|s| {
+                (
+                    s.query_start,
+                    s.query_end,
+                    s.target_name.as_str(),
+                    s.strand,
+                    s.record,
+                )
+            })
+            .collect();
+        assert_eq!(
+            segments,
+            [(0, 4000, "chr1", '+', 0), (4000, 10000, "chr2", '-', 1)]
+        );
+        assert!((found[0].confidence - 0.5).abs() < 1e-9);
+        assert_eq!(found[1].query_name, "b");
+        assert!((found[1].confidence - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_query_overlap() {
+        // the shorter alignment overlaps the longer by 500 on the query
+        let lines = [
+            line("q", 0, 6000, '+', ("chr1", 0, 6000), 60),
+            line("q", 5500, 10000, '+', ("chr2", 0, 4500), 60),
+        ];
+        assert!(calls(&lines, &ChimeraOptions::default()).is_empty());
+        let options = ChimeraOptions {
+            max_query_overlap: 500,
+            ..ChimeraOptions::default()
+        };
+        assert_eq!(calls(&lines, &options).len(), 1);
+    }
+}
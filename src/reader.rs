@@ -1,27 +1,83 @@
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::{self, BufRead};
 use std::path::Path;
+use std::str::FromStr;
 
-use crate::{Error, ErrorKind, Result};
+use crate::tags::{self, TypedTag};
+use crate::{Cigar, Error, ErrorKind, Result};
 
 /// Enum representing the possible types of optional fields.
 #[derive(Debug)]
 pub enum Type {
     Int(i64),
-    Float(f64),
+    /// A float, along with its original text if it was parsed from a file,
+    /// so that it can be written back out unchanged.
+    Float {
+        value: f64,
+        raw: Option<String>,
+    },
     String(String),
     Char(char),
+    /// A value with an unrecognized type code, holding the code and value
+    /// verbatim (e.g. `X:foo`). Only produced when reading with
+    /// [`ReaderBuilder::keep_unknown_types`].
+    Raw(String),
+    /// A cg tag parsed while reading, held in compact form and written back
+    /// out as text. Only produced when reading with
+    /// [`ReaderBuilder::compact_cigar`].
+    Cigar(Cigar),
 }
 
 impl Type {
-    fn parse(field_type: &str, value: &str) -> Option<Self> {
-        match field_type {
-            "i" => value.parse::<i64>().ok().map(Type::Int),
-            "f" => value.parse::<f64>().ok().map(Type::Float),
-            "Z" => Some(Type::String(value.to_string())),
-            "A" => value.chars().next().map(Type::Char),
-            _ => Some(Type::String(value.to_string())), // Default to string
+    /// Parse a value given its SAM-style type code (`i`, `f`, `Z` or `A`).
+    pub fn from_typed_str(code: char, value: &str) -> Result<Self> {
+        match code {
+            'i' => Ok(Type::Int(value.parse::<i64>()?)),
+            'f' => Ok(Type::Float {
+                value: value.parse::<f64>()?,
+                raw: Some(value.to_string()),
+            }),
+            'Z' => Ok(Type::String(value.to_string())),
+            'A' => {
+                let mut chars = value.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(Type::Char(c)),
+                    _ => Err(Error::new(ErrorKind::ReadRecord(format!(
+                        "Invalid char value: {:?}",
+                        value
+                    )))),
+                }
+            }
+            _ => Err(Error::new(ErrorKind::ReadRecord(format!(
+                "Invalid type code: {}",
+                code
+            )))),
+        }
+    }
+
+    /// Keep a value with an unrecognized type code verbatim.
+    pub fn raw(code: char, value: &str) -> Self {
+        Type::Raw(format!("{}:{}", code, value))
+    }
+
+    /// Create a float value with no original text.
+    pub fn float(value: f64) -> Self {
+        Type::Float { value, raw: None }
+    }
+
+    /// The SAM-style type code of this value.
+    pub fn code(&self) -> char {
+        match self {
+            Type::Int(_) => 'i',
+            Type::Float { .. } => 'f',
+            Type::String(_) | Type::Cigar(_) => 'Z',
+            Type::Char(_) => 'A',
+            Type::Raw(raw) => raw.chars().next().unwrap_or_default(),
         }
     }
 
@@ -36,7 +92,7 @@ impl Type {
     /// Get the inner float out.
     pub fn get_float(&self) -> Option<&f64> {
         match self {
-            Type::Float(v) => Some(v),
+            Type::Float { value, .. } => Some(value),
             _ => None,
         }
     }
@@ -56,6 +112,398 @@ impl Type {
             _ => None,
         }
     }
+
+    /// The value as an integer, converting a float with no fractional part.
+    ///
+    /// Unlike [`Type::get_int`], which is strict about the type, this helps
+    /// when the same tag arrives as `i` in some files and `f` in others.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Type::Int(v) => Some(*v),
+            Type::Float { value, .. }
+                if value.fract() == 0.0
+                    && *value >= i64::MIN as f64
+                    && *value < i64::MAX as f64 =>
+            {
+                Some(*value as i64)
+            }
+            _ => None,
+        }
+    }
+
+    /// The value as a float, converting an integer.
+    ///
+    /// Unlike [`Type::get_float`], which is strict about the type, this
+    /// converts any integer. Integers beyond 2^53 in magnitude lose
+    /// precision, e.g. `i64::MAX` becomes 2^63.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Type::Int(v) => Some(*v as f64),
+            Type::Float { value, .. } => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value as a string, for strings, chars and parsed CIGARs.
+    pub fn as_str(&self) -> Option<Cow<'_, str>> {
+        match self {
+            Type::String(v) => Some(Cow::Borrowed(v)),
+            Type::Char(v) => Some(Cow::Owned(v.to_string())),
+            Type::Cigar(v) => Some(Cow::Owned(v.to_string())),
+            _ => None,
+        }
+    }
+
+    /// Get the verbatim type code and value of a raw value out.
+    pub fn get_raw(&self) -> Option<&str> {
+        match self {
+            Type::Raw(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a value of the form `code:value`, e.g. `i:42`.
+impl FromStr for Type {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(code), Some(':')) => Type::from_typed_str(code, chars.as_str()),
+            _ => Err(Error::new(ErrorKind::ReadRecord(format!(
+                "Invalid typed value: {}",
+                s
+            )))),
+        }
+    }
+}
+
+/// Formats the value exactly as it is written in a PAF file, without the
+/// type code.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Int(v) => write!(f, "{}", v),
+            Type::Float { raw: Some(raw), .. } => write!(f, "{}", raw),
+            // the shortest representation which parses back to the same value
+            Type::Float { value, raw: None } => write!(f, "{}", value),
+            Type::String(v) => write!(f, "{}", v),
+            Type::Char(v) => write!(f, "{}", v),
+            Type::Raw(v) => write!(f, "{}", v.split_once(':').map_or("", |(_, v)| v)),
+            Type::Cigar(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// The type of an alignment, as recorded in the `tp` tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignmentType {
+    /// A primary alignment (`P`).
+    Primary,
+    /// A secondary alignment (`S`).
+    Secondary,
+    /// A primary inversion (`I`).
+    Inversion,
+    /// A secondary inversion (`i`).
+    SecondaryInversion,
+    /// Any other alignment type, holding the raw character.
+    Other(char),
+}
+
+impl AlignmentType {
+    /// The character representing this alignment type in a PAF file.
+    pub fn as_char(&self) -> char {
+        match self {
+            AlignmentType::Primary => 'P',
+            AlignmentType::Secondary => 'S',
+            AlignmentType::Inversion => 'I',
+            AlignmentType::SecondaryInversion => 'i',
+            AlignmentType::Other(c) => *c,
+        }
+    }
+}
+
+impl From<char> for AlignmentType {
+    fn from(c: char) -> Self {
+        match c {
+            'P' => AlignmentType::Primary,
+            'S' => AlignmentType::Secondary,
+            'I' => AlignmentType::Inversion,
+            'i' => AlignmentType::SecondaryInversion,
+            c => AlignmentType::Other(c),
+        }
+    }
+}
+
+/// The strand of the query relative to the target, as recorded in the
+/// strand column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strand {
+    /// The query aligns as it is (`+`).
+    Forward,
+    /// The reverse complement of the query aligns (`-`).
+    Reverse,
+}
+
+impl Strand {
+    /// The character representing this strand in a PAF file.
+    pub fn as_char(&self) -> char {
+        match self {
+            Strand::Forward => '+',
+            Strand::Reverse => '-',
+        }
+    }
+}
+
+impl TryFrom<char> for Strand {
+    type Error = Error;
+
+    fn try_from(c: char) -> Result<Self> {
+        match c {
+            '+' => Ok(Strand::Forward),
+            '-' => Ok(Strand::Reverse),
+            c => Err(Error::new(ErrorKind::ReadRecord(format!(
+                "Invalid strand: {}",
+                c
+            )))),
+        }
+    }
+}
+
+/// The transcript strand of a spliced alignment, as recorded in the `ts` tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptStrand {
+    /// The transcript is on the forward strand (`+`).
+    Forward,
+    /// The transcript is on the reverse strand (`-`).
+    Reverse,
+    /// The transcript strand could not be determined (`.`).
+    Unknown,
+}
+
+impl TranscriptStrand {
+    /// The character representing this strand in a PAF file.
+    pub fn as_char(&self) -> char {
+        match self {
+            TranscriptStrand::Forward => '+',
+            TranscriptStrand::Reverse => '-',
+            TranscriptStrand::Unknown => '.',
+        }
+    }
+}
+
+impl TryFrom<char> for TranscriptStrand {
+    type Error = Error;
+
+    fn try_from(c: char) -> Result<Self> {
+        match c {
+            '+' => Ok(TranscriptStrand::Forward),
+            '-' => Ok(TranscriptStrand::Reverse),
+            '.' => Ok(TranscriptStrand::Unknown),
+            c => Err(Error::new(ErrorKind::ReadRecord(format!(
+                "Invalid transcript strand: {}",
+                c
+            )))),
+        }
+    }
+}
+
+/// The SAM-style type codes of optional field values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypeCode {
+    /// A single character (`A`).
+    Char,
+    /// A signed integer (`i`).
+    Int,
+    /// A float (`f`).
+    Float,
+    /// A string (`Z`).
+    String,
+}
+
+impl TypeCode {
+    /// Look up a type code by its character.
+    pub fn from_char(c: char) -> Option<TypeCode> {
+        match c {
+            'A' => Some(TypeCode::Char),
+            'i' => Some(TypeCode::Int),
+            'f' => Some(TypeCode::Float),
+            'Z' => Some(TypeCode::String),
+            _ => None,
+        }
+    }
+
+    /// The character representing this type in a PAF file.
+    pub fn as_char(&self) -> char {
+        match self {
+            TypeCode::Char => 'A',
+            TypeCode::Int => 'i',
+            TypeCode::Float => 'f',
+            TypeCode::String => 'Z',
+        }
+    }
+}
+
+/// The kinds of tag known to this crate, without their values.
+///
+/// This is the single table of tag names, types and descriptions which the
+/// parser consults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TagKind {
+    /// Type of aln: P/primary, S/secondary and I,i/inversion.
+    Tp,
+    /// Number of minimizers on the chain.
+    Cm,
+    /// Chaining score.
+    S1,
+    /// Chaining score of the best secondary chain.
+    S2,
+    /// Total number of mismatches and gaps in the alignment.
+    Nm,
+    /// To generate the ref sequence in the alignment.
+    Md,
+    /// DP alignment score.
+    As,
+    /// List of other supplementary alignments.
+    Sa,
+    /// DP score of the max scoring segment in the alignment.
+    Ms,
+    /// Number of ambiguous bases in the alignment.
+    Nn,
+    /// Transcript strand (splice mode only).
+    Ts,
+    /// CIGAR string.
+    Cg,
+    /// Difference string.
+    Cs,
+    /// Approximate per-base sequence divergence.
+    Dv,
+    /// Gap-compressed per-base sequence divergence.
+    De,
+    /// Length of query regions harboring repetitive seeds.
+    Rl,
+    /// ZD?
+    Zd,
+    /// A tag not known to this crate.
+    Other,
+}
+
+impl TagKind {
+    /// Every known tag kind, excluding [`TagKind::Other`].
+    pub fn all() -> &'static [TagKind] {
+        &[
+            TagKind::Tp,
+            TagKind::Cm,
+            TagKind::S1,
+            TagKind::S2,
+            TagKind::Nm,
+            TagKind::Md,
+            TagKind::As,
+            TagKind::Sa,
+            TagKind::Ms,
+            TagKind::Nn,
+            TagKind::Ts,
+            TagKind::Cg,
+            TagKind::Cs,
+            TagKind::Dv,
+            TagKind::De,
+            TagKind::Rl,
+            TagKind::Zd,
+        ]
+    }
+
+    /// Look up a tag kind by its two letter name.
+    pub fn from_code(code: &str) -> TagKind {
+        match code {
+            tags::TP => TagKind::Tp,
+            tags::CM => TagKind::Cm,
+            tags::S1 => TagKind::S1,
+            tags::S2 => TagKind::S2,
+            tags::NM => TagKind::Nm,
+            tags::MD => TagKind::Md,
+            tags::AS => TagKind::As,
+            tags::SA => TagKind::Sa,
+            tags::MS => TagKind::Ms,
+            tags::NN => TagKind::Nn,
+            tags::TS => TagKind::Ts,
+            tags::CG => TagKind::Cg,
+            tags::CS => TagKind::Cs,
+            tags::DV => TagKind::Dv,
+            tags::DE => TagKind::De,
+            tags::RL => TagKind::Rl,
+            tags::ZD => TagKind::Zd,
+            _ => TagKind::Other,
+        }
+    }
+
+    /// The two letter name of this tag, or an empty string for
+    /// [`TagKind::Other`].
+    pub fn code(&self) -> &'static str {
+        match self {
+            TagKind::Tp => tags::TP,
+            TagKind::Cm => tags::CM,
+            TagKind::S1 => tags::S1,
+            TagKind::S2 => tags::S2,
+            TagKind::Nm => tags::NM,
+            TagKind::Md => tags::MD,
+            TagKind::As => tags::AS,
+            TagKind::Sa => tags::SA,
+            TagKind::Ms => tags::MS,
+            TagKind::Nn => tags::NN,
+            TagKind::Ts => tags::TS,
+            TagKind::Cg => tags::CG,
+            TagKind::Cs => tags::CS,
+            TagKind::Dv => tags::DV,
+            TagKind::De => tags::DE,
+            TagKind::Rl => tags::RL,
+            TagKind::Zd => tags::ZD,
+            TagKind::Other => "",
+        }
+    }
+
+    /// The type a value of this tag must have, if it is known.
+    pub fn expected_type(&self) -> Option<TypeCode> {
+        match self {
+            TagKind::Tp | TagKind::Ts => Some(TypeCode::Char),
+            TagKind::Cm
+            | TagKind::S1
+            | TagKind::S2
+            | TagKind::Nm
+            | TagKind::As
+            | TagKind::Ms
+            | TagKind::Nn
+            | TagKind::Rl
+            | TagKind::Zd => Some(TypeCode::Int),
+            TagKind::Md | TagKind::Sa | TagKind::Cg | TagKind::Cs => Some(TypeCode::String),
+            TagKind::Dv | TagKind::De => Some(TypeCode::Float),
+            TagKind::Other => None,
+        }
+    }
+
+    /// A human readable description of this tag.
+    pub fn description(&self) -> &'static str {
+        match self {
+            TagKind::Tp => "Type of aln: P/primary, S/secondary and I,i/inversion.",
+            TagKind::Cm => "Number of minimizers on the chain.",
+            TagKind::S1 => "Chaining score.",
+            TagKind::S2 => "Chaining score of the best secondary chain.",
+            TagKind::Nm => "Total number of mismatches and gaps in the alignment.",
+            TagKind::Md => "To generate the ref sequence in the alignment.",
+            TagKind::As => "DP alignment score.",
+            TagKind::Sa => "List of other supplementary alignments.",
+            TagKind::Ms => "DP score of the max scoring segment in the alignment.",
+            TagKind::Nn => "Number of ambiguous bases in the alignment.",
+            TagKind::Ts => "Transcript strand (splice mode only).",
+            TagKind::Cg => "CIGAR string.",
+            TagKind::Cs => "Difference string.",
+            TagKind::Dv => "Approximate per-base sequence divergence.",
+            TagKind::De => "Gap-compressed per-base sequence divergence.",
+            TagKind::Rl => "Length of query regions harboring repetitive seeds.",
+            TagKind::Zd => "ZD?",
+            TagKind::Other => "Unknown tag.",
+        }
+    }
 }
 
 /// Enum representing the possible types of tags.
@@ -63,7 +511,7 @@ impl Type {
 #[allow(non_camel_case_types)]
 pub enum Tag {
     /// Type of aln: P/primary, S/secondary and I,i/inversion.
-    tp(Type),
+    tp(AlignmentType),
     /// Number of minimizers on the chain.
     cm(Type),
     /// Chaining score.
@@ -83,7 +531,7 @@ pub enum Tag {
     /// Number of ambiguous bases in the alignment.
     nn(Type),
     /// Transcript strand (splice mode only).
-    ts(Type),
+    ts(TranscriptStrand),
     /// CIGAR string.
     cg(Type),
     /// Difference string.
@@ -96,29 +544,55 @@ pub enum Tag {
     rl(Type),
     /// ZD?
     zd(Type),
+    /// A tag not known to this crate, holding its name. Only produced when
+    /// reading with [`ReaderBuilder::keep_unknown_tags`].
+    Other(String, Type),
 }
 
 impl Tag {
     /// Parse a tag from a string.
+    ///
+    /// Known tags have a fixed type (e.g. `NM` is always an integer), and
+    /// a value of any other type is an error. [`Type::Raw`] values are let
+    /// through, except for tp and ts which must be a char. Unknown tags are
+    /// an error.
     pub fn parse(tag: &str, value: Type) -> Result<Self> {
-        match tag {
-            "tp" => Ok(Tag::tp(value)),
-            "cm" => Ok(Tag::cm(value)),
-            "s1" => Ok(Tag::s1(value)),
-            "s2" => Ok(Tag::s2(value)),
-            "NM" => Ok(Tag::NM(value)),
-            "MD" => Ok(Tag::MD(value)),
-            "AS" => Ok(Tag::AS(value)),
-            "SA" => Ok(Tag::SA(value)),
-            "ms" => Ok(Tag::ms(value)),
-            "nn" => Ok(Tag::nn(value)),
-            "ts" => Ok(Tag::ts(value)),
-            "cg" => Ok(Tag::cg(value)),
-            "cs" => Ok(Tag::cs(value)),
-            "dv" => Ok(Tag::dv(value)),
-            "de" => Ok(Tag::de(value)),
-            "rl" => Ok(Tag::rl(value)),
-            "zd" => Ok(Tag::zd(value)),
+        let kind = TagKind::from_code(tag);
+        if let Some(expected) = kind.expected_type() {
+            if !matches!(value, Type::Raw(_)) && value.code() != expected.as_char() {
+                return Err(Error::new(ErrorKind::ReadRecord(format!(
+                    "Invalid type for PAF tag {}: expected {}, found {}",
+                    tag,
+                    expected.as_char(),
+                    value.code()
+                ))));
+            }
+        }
+
+        match (kind, value) {
+            (TagKind::Tp, Type::Char(c)) => Ok(Tag::tp(AlignmentType::from(c))),
+            (TagKind::Ts, Type::Char(c)) => Ok(Tag::ts(TranscriptStrand::try_from(c)?)),
+            (TagKind::Tp | TagKind::Ts, value) => Err(Error::new(ErrorKind::ReadRecord(format!(
+                "Invalid value for PAF tag {}: {}:{}",
+                tag,
+                value.code(),
+                value
+            )))),
+            (TagKind::Cm, value) => Ok(Tag::cm(value)),
+            (TagKind::S1, value) => Ok(Tag::s1(value)),
+            (TagKind::S2, value) => Ok(Tag::s2(value)),
+            (TagKind::Nm, value) => Ok(Tag::NM(value)),
+            (TagKind::Md, value) => Ok(Tag::MD(value)),
+            (TagKind::As, value) => Ok(Tag::AS(value)),
+            (TagKind::Sa, value) => Ok(Tag::SA(value)),
+            (TagKind::Ms, value) => Ok(Tag::ms(value)),
+            (TagKind::Nn, value) => Ok(Tag::nn(value)),
+            (TagKind::Cg, value) => Ok(Tag::cg(value)),
+            (TagKind::Cs, value) => Ok(Tag::cs(value)),
+            (TagKind::Dv, value) => Ok(Tag::dv(value)),
+            (TagKind::De, value) => Ok(Tag::de(value)),
+            (TagKind::Rl, value) => Ok(Tag::rl(value)),
+            (TagKind::Zd, value) => Ok(Tag::zd(value)),
             _ => Err(Error::new(ErrorKind::ReadRecord(format!(
                 "Invalid PAF tag: {}",
                 tag
@@ -126,26 +600,58 @@ impl Tag {
         }
     }
 
-    /// Tag to string function.
-    fn to_string(&self) -> String {
+    /// The value held by this tag, if it is stored as a plain `Type`.
+    pub(crate) fn value(&self) -> Option<&Type> {
+        match self {
+            Tag::tp(_) | Tag::ts(_) => None,
+            Tag::cm(v)
+            | Tag::s1(v)
+            | Tag::s2(v)
+            | Tag::NM(v)
+            | Tag::MD(v)
+            | Tag::AS(v)
+            | Tag::SA(v)
+            | Tag::ms(v)
+            | Tag::nn(v)
+            | Tag::cg(v)
+            | Tag::cs(v)
+            | Tag::dv(v)
+            | Tag::de(v)
+            | Tag::rl(v)
+            | Tag::zd(v)
+            | Tag::Other(_, v) => Some(v),
+        }
+    }
+
+    /// The kind of this tag.
+    pub fn kind(&self) -> TagKind {
+        match self {
+            Tag::tp(_) => TagKind::Tp,
+            Tag::cm(_) => TagKind::Cm,
+            Tag::s1(_) => TagKind::S1,
+            Tag::s2(_) => TagKind::S2,
+            Tag::NM(_) => TagKind::Nm,
+            Tag::MD(_) => TagKind::Md,
+            Tag::AS(_) => TagKind::As,
+            Tag::SA(_) => TagKind::Sa,
+            Tag::ms(_) => TagKind::Ms,
+            Tag::nn(_) => TagKind::Nn,
+            Tag::ts(_) => TagKind::Ts,
+            Tag::cg(_) => TagKind::Cg,
+            Tag::cs(_) => TagKind::Cs,
+            Tag::dv(_) => TagKind::Dv,
+            Tag::de(_) => TagKind::De,
+            Tag::rl(_) => TagKind::Rl,
+            Tag::zd(_) => TagKind::Zd,
+            Tag::Other(..) => TagKind::Other,
+        }
+    }
+
+    /// The two letter name of this tag.
+    fn name(&self) -> &str {
         match self {
-            Tag::tp(_) => "tp".into(),
-            Tag::cm(_) => "cm".into(),
-            Tag::s1(_) => "s1".into(),
-            Tag::s2(_) => "s2".into(),
-            Tag::NM(_) => "NM".into(),
-            Tag::MD(_) => "MD".into(),
-            Tag::AS(_) => "AS".into(),
-            Tag::SA(_) => "SA".into(),
-            Tag::ms(_) => "ms".into(),
-            Tag::nn(_) => "nn".into(),
-            Tag::ts(_) => "ts".into(),
-            Tag::cg(_) => "cg".into(),
-            Tag::cs(_) => "cs".into(),
-            Tag::dv(_) => "dv".into(),
-            Tag::de(_) => "de".into(),
-            Tag::rl(_) => "rl".into(),
-            Tag::zd(_) => "zd".into(),
+            Tag::Other(name, _) => name,
+            tag => tag.kind().code(),
         }
     }
 }
@@ -184,6 +690,7 @@ pub struct PafRecord {
 
 impl PafRecord {
     /// Create a new PAF record.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         query_name: String,
         query_len: u32,
@@ -268,117 +775,490 @@ impl PafRecord {
     pub fn optional_fields(&self) -> &HashMap<String, Tag> {
         &self.optional
     }
-    /// Get type of aln: P/primary, S/secondary and I,i/inversion.
-    pub fn tp(&self) -> Option<&char> {
-        self.optional.get("tp").map(|tag| match tag {
-            Tag::tp(t) => t.get_char().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    /// Replace the residue matches and alignment block length.
+    pub(crate) fn set_matches(&mut self, residue_matches: u32, alignment_block_len: u32) {
+        self.residue_matches = residue_matches;
+        self.alignment_block_len = alignment_block_len;
+    }
+    /// Add an optional field, replacing any of the same name.
+    pub(crate) fn set_tag(&mut self, tag: Tag) {
+        self.optional.insert(tag.name().to_string(), tag);
+    }
+    /// Check the record is internally consistent.
+    ///
+    /// This checks that the coordinates lie within the sequences, that the
+    /// strand is `+` or `-`, that there are no more residue matches than
+    /// alignment columns, that any dv and de divergences lie in [0, 1], and
+    /// that any cg tag agrees with the coordinates (see
+    /// [`PafRecord::check_cigar`]). The first failure is returned.
+    pub fn validate(&self) -> Result<()> {
+        self.check_fields()?;
+        self.check_divergence()?;
+        self.check_cigar()
+    }
+
+    /// Check the mandatory fields agree with each other.
+    pub(crate) fn check_fields(&self) -> Result<()> {
+        let invalid = |msg: String| Err(Error::new(ErrorKind::InvalidRecord(msg)));
+
+        if self.query_start > self.query_end || self.query_end > self.query_len {
+            return invalid(format!(
+                "query coordinates {}-{} are not within a query of length {}",
+                self.query_start, self.query_end, self.query_len
+            ));
+        }
+        if self.target_start > self.target_end || self.target_end > self.target_len {
+            return invalid(format!(
+                "target coordinates {}-{} are not within a target of length {}",
+                self.target_start, self.target_end, self.target_len
+            ));
+        }
+        if self.strand != '+' && self.strand != '-' {
+            return invalid(format!("invalid strand {}", self.strand));
+        }
+        if self.residue_matches > self.alignment_block_len {
+            return invalid(format!(
+                "residue matches ({}) exceed the alignment block length ({})",
+                self.residue_matches, self.alignment_block_len
+            ));
+        }
+        Ok(())
     }
-    /// Get number of minimizers on the chain
-    pub fn cm(&self) -> Option<&i64> {
-        self.optional.get("cm").map(|tag| match tag {
-            Tag::cm(t) => t.get_int().unwrap(),
-            _ => panic!("Invalid tag"),
+
+    /// `a - b` for two coordinates of this record, or an error if `b` is
+    /// the greater, as it may be in a record which was not validated.
+    pub(crate) fn coordinate_sub(&self, a: u32, b: u32) -> Result<u32> {
+        a.checked_sub(b).ok_or_else(|| {
+            Error::new(ErrorKind::InvalidRecord(format!(
+                "coordinates of {} aligned to {} are out of order: {} is before {}",
+                self.query_name, self.target_name, a, b
+            )))
         })
     }
+
+    /// Check any dv and de divergences lie in [0, 1].
+    pub(crate) fn check_divergence(&self) -> Result<()> {
+        for (tag, value) in [(tags::DV, self.dv()), (tags::DE, self.de())] {
+            match value {
+                Some(v) if !(0.0..=1.0).contains(&v) => {
+                    return Err(Error::new(ErrorKind::InvalidRecord(format!(
+                        "{} value {} out of range [0, 1]",
+                        tag, v
+                    ))));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The numbers of unaligned query bases before and after the alignment,
+    /// in the direction of the target.
+    ///
+    /// PAF has no clipping operations, so these come from the coordinates.
+    /// For a `+` record they are `query_start` and `query_len - query_end`.
+    /// A `-` record reads the query backwards along the target, so its ends
+    /// swap: the bases before the alignment are `query_len - query_end`.
+    /// This is the orientation needed to compare them with the unaligned
+    /// target bases, `target_start` and `target_len - target_end`.
+    pub fn query_clipping(&self) -> (u64, u64) {
+        let start = self.query_start as u64;
+        let end = (self.query_len as u64).saturating_sub(self.query_end as u64);
+        if self.strand == '-' {
+            (end, start)
+        } else {
+            (start, end)
+        }
+    }
+
+    /// Whether the alignment reaches the end of one of the sequences, to
+    /// within `max_clip` bases, at both of its ends.
+    ///
+    /// This holds for dovetail overlaps, where the alignment runs off one
+    /// end of the query and the other end of the target, and for one
+    /// sequence contained in the other. It fails for internal matches, which
+    /// leave both sequences unaligned past one end of the alignment, as
+    /// repeats and chimeras do.
+    pub fn has_terminal_alignment(&self, max_clip: u64) -> bool {
+        let (query_before, query_after) = self.query_clipping();
+        let target_before = self.target_start as u64;
+        let target_after = (self.target_len as u64).saturating_sub(self.target_end as u64);
+        (query_before <= max_clip || target_before <= max_clip)
+            && (query_after <= max_clip || target_after <= max_clip)
+    }
+
+    /// BLAST identity: residue matches over the alignment block length,
+    /// counting every gap base as a difference. Zero for an empty block.
+    pub fn blast_identity(&self) -> f64 {
+        if self.alignment_block_len == 0 {
+            return 0.0;
+        }
+        self.residue_matches as f64 / self.alignment_block_len as f64
+    }
+
+    /// Gap-compressed identity, `1 - de`, which counts each gap as one
+    /// difference whatever its length. `None` without a de tag.
+    pub fn gap_compressed_identity(&self) -> Option<f64> {
+        self.de().map(|de| 1.0 - de)
+    }
+
+    /// Identity from the edit distance, `1 - NM / alignment_block_len`.
+    /// `None` without an NM tag or with an empty block.
+    pub fn nm_identity(&self) -> Option<f64> {
+        let nm = self.nm()?;
+        if self.alignment_block_len == 0 {
+            return None;
+        }
+        Some(1.0 - nm as f64 / self.alignment_block_len as f64)
+    }
+
+    /// A hash of the twelve mandatory fields, equal for records which
+    /// describe the same alignment whatever their tags.
+    ///
+    /// The hash is the same within a build of a program but may change
+    /// between Rust versions, so it should not be stored.
+    pub fn alignment_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (
+            &self.query_name,
+            self.query_len,
+            self.query_start,
+            self.query_end,
+            self.strand,
+            &self.target_name,
+            self.target_len,
+            self.target_start,
+            self.target_end,
+            self.residue_matches,
+            self.alignment_block_len,
+            self.mapping_quality,
+        )
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Get the value of a known tag, with its type fixed by the tag.
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn get<T: TypedTag>(&self) -> Option<T::Value<'_>> {
+        self.get_checked::<T>().ok().flatten()
+    }
+    /// Like [`PafRecord::get`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn get_checked<T: TypedTag>(&self) -> Result<Option<T::Value<'_>>> {
+        let tag = match self.optional.get(T::NAME) {
+            Some(tag) => tag,
+            None => return Ok(None),
+        };
+        if tag.name() != T::NAME {
+            return Err(Error::new(ErrorKind::InvalidTag(format!(
+                "optional field {} holds a {} tag",
+                T::NAME,
+                tag.name()
+            ))));
+        }
+        match T::extract(tag) {
+            Some(v) => Ok(Some(v)),
+            None => Err(Error::new(ErrorKind::InvalidTag(format!(
+                "tag {} has unexpected type {}",
+                T::NAME,
+                tag.value().map_or('A', Type::code)
+            )))),
+        }
+    }
+    /// Get type of aln: P/primary, S/secondary and I,i/inversion.
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn tp(&self) -> Option<AlignmentType> {
+        self.tp_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::tp`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn tp_checked(&self) -> Result<Option<AlignmentType>> {
+        self.get_checked::<tags::Tp>()
+    }
+    /// Get number of minimizers on the chain.
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn cm(&self) -> Option<i64> {
+        self.cm_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::cm`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn cm_checked(&self) -> Result<Option<i64>> {
+        self.get_checked::<tags::Cm>()
+    }
     /// Get chaining score.
-    pub fn s1(&self) -> Option<&i64> {
-        self.optional.get("s1").map(|tag| match tag {
-            Tag::s1(t) => t.get_int().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn s1(&self) -> Option<i64> {
+        self.s1_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::s1`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn s1_checked(&self) -> Result<Option<i64>> {
+        self.get_checked::<tags::S1>()
     }
     /// Get chaining score of the best secondary chain.
-    pub fn s2(&self) -> Option<&i64> {
-        self.optional.get("s2").map(|tag| match tag {
-            Tag::s2(t) => t.get_int().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn s2(&self) -> Option<i64> {
+        self.s2_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::s2`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn s2_checked(&self) -> Result<Option<i64>> {
+        self.get_checked::<tags::S2>()
     }
     /// Get total number of mismatches and gaps in the alignment.
-    pub fn nm(&self) -> Option<&i64> {
-        self.optional.get("NM").map(|tag| match tag {
-            Tag::NM(t) => t.get_int().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn nm(&self) -> Option<i64> {
+        self.nm_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::nm`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn nm_checked(&self) -> Result<Option<i64>> {
+        self.get_checked::<tags::Nm>()
     }
     /// Get the ref sequence in the alignment.
-    pub fn md(&self) -> Option<&String> {
-        self.optional.get("MD").map(|tag| match tag {
-            Tag::MD(t) => t.get_string().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn md(&self) -> Option<&str> {
+        self.md_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::md`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn md_checked(&self) -> Result<Option<&str>> {
+        self.get_checked::<tags::Md>()
     }
     /// Get DP alignment score.
-    pub fn as_(&self) -> Option<&i64> {
-        self.optional.get("AS").map(|tag| match tag {
-            Tag::AS(t) => t.get_int().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn as_(&self) -> Option<i64> {
+        self.as_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::as_`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn as_checked(&self) -> Result<Option<i64>> {
+        self.get_checked::<tags::As>()
     }
     /// Get a list of other supplementary alignments.
-    pub fn sa(&self) -> Option<&String> {
-        self.optional.get("SA").map(|tag| match tag {
-            Tag::SA(t) => t.get_string().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn sa(&self) -> Option<&str> {
+        self.sa_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::sa`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn sa_checked(&self) -> Result<Option<&str>> {
+        self.get_checked::<tags::Sa>()
     }
     /// Get DP score of the max scoring segment in the alignment.
-    pub fn ms(&self) -> Option<&i64> {
-        self.optional.get("ms").map(|tag| match tag {
-            Tag::ms(t) => t.get_int().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn ms(&self) -> Option<i64> {
+        self.ms_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::ms`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn ms_checked(&self) -> Result<Option<i64>> {
+        self.get_checked::<tags::Ms>()
     }
     /// Get number of ambiguous bases in the alignment.
-    pub fn nn(&self) -> Option<&i64> {
-        self.optional.get("nn").map(|tag| match tag {
-            Tag::nn(t) => t.get_int().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn nn(&self) -> Option<i64> {
+        self.nn_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::nn`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn nn_checked(&self) -> Result<Option<i64>> {
+        self.get_checked::<tags::Nn>()
     }
     /// Get transcript strand (splice mode only).
-    pub fn ts(&self) -> Option<&char> {
-        self.optional.get("ts").map(|tag| match tag {
-            Tag::ts(t) => t.get_char().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn ts(&self) -> Option<TranscriptStrand> {
+        self.ts_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::ts`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn ts_checked(&self) -> Result<Option<TranscriptStrand>> {
+        self.get_checked::<tags::Ts>()
     }
     /// Get CIGAR string (only in PAF).
-    pub fn cg(&self) -> Option<&String> {
-        self.optional.get("cg").map(|tag| match tag {
-            Tag::cg(t) => t.get_string().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type,
+    /// and also for a cg tag held packed, as read with
+    /// [`ReaderBuilder::compact_cigar`], which has no text to borrow. Use
+    /// [`PafRecord::cigar`] to get the CIGAR whichever way it is held.
+    pub fn cg(&self) -> Option<&str> {
+        self.cg_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::cg`], but returns an error if the tag holds a
+    /// value of the wrong type. A packed cg tag is not of the wrong type,
+    /// so gives `None` as for [`PafRecord::cg`].
+    pub fn cg_checked(&self) -> Result<Option<&str>> {
+        if let Some(Tag::cg(Type::Cigar(_))) = self.optional.get(tags::CG) {
+            return Ok(None);
+        }
+        self.get_checked::<tags::Cg>()
     }
     /// Get difference string.
-    pub fn cs(&self) -> Option<&String> {
-        self.optional.get("cs").map(|tag| match tag {
-            Tag::cs(t) => t.get_string().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn cs(&self) -> Option<&str> {
+        self.cs_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::cs`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn cs_checked(&self) -> Result<Option<&str>> {
+        self.get_checked::<tags::Cs>()
     }
     /// Get approximate per-base sequence divergence.
-    pub fn dv(&self) -> Option<&f64> {
-        self.optional.get("dv").map(|tag| match tag {
-            Tag::dv(t) => t.get_float().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn dv(&self) -> Option<f64> {
+        self.dv_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::dv`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn dv_checked(&self) -> Result<Option<f64>> {
+        self.get_checked::<tags::Dv>()
     }
     /// Get gap-compressed per-base sequence divergence.
-    pub fn de(&self) -> Option<&f64> {
-        self.optional.get("de").map(|tag| match tag {
-            Tag::de(t) => t.get_float().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn de(&self) -> Option<f64> {
+        self.de_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::de`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn de_checked(&self) -> Result<Option<f64>> {
+        self.get_checked::<tags::De>()
     }
     /// Get length of query regions harboring repetitive seeds.
-    pub fn rl(&self) -> Option<&i64> {
-        self.optional.get("rl").map(|tag| match tag {
-            Tag::rl(t) => t.get_int().unwrap(),
-            _ => panic!("Invalid tag"),
-        })
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn rl(&self) -> Option<i64> {
+        self.rl_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::rl`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn rl_checked(&self) -> Result<Option<i64>> {
+        self.get_checked::<tags::Rl>()
+    }
+    /// Get the zd tag.
+    ///
+    /// Returns `None` if the tag is absent or holds a value of the wrong type.
+    pub fn zd(&self) -> Option<i64> {
+        self.zd_checked().ok().flatten()
+    }
+    /// Like [`PafRecord::zd`], but returns an error if the tag holds a
+    /// value of the wrong type.
+    pub fn zd_checked(&self) -> Result<Option<i64>> {
+        self.get_checked::<tags::Zd>()
+    }
+}
+
+/// A problem found in a record which was not severe enough to stop reading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    line: u64,
+    message: String,
+}
+
+impl Warning {
+    /// The line the problem was found on.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+    /// A description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Builds a PAF reader with various configuration knobs.
+#[derive(Debug, Clone, Default)]
+pub struct ReaderBuilder {
+    validate: bool,
+    keep_unknown_tags: bool,
+    keep_unknown_types: bool,
+    compact_cigar: bool,
+}
+
+impl ReaderBuilder {
+    /// Create a new builder with the default configuration.
+    pub fn new() -> ReaderBuilder {
+        ReaderBuilder::default()
+    }
+
+    /// Whether to reject records which parse but hold invalid values.
+    ///
+    /// When enabled, such records are an error. When disabled (the default),
+    /// they are read as normal and a [`Warning`] is recorded on the reader.
+    ///
+    /// Currently this checks that the dv and de divergences lie in [0, 1].
+    pub fn validate(&mut self, yes: bool) -> &mut ReaderBuilder {
+        self.validate = yes;
+        self
+    }
+
+    /// Whether to keep tags not known to this crate as [`Tag::Other`],
+    /// rather than failing with an error (the default).
+    pub fn keep_unknown_tags(&mut self, yes: bool) -> &mut ReaderBuilder {
+        self.keep_unknown_tags = yes;
+        self
+    }
+
+    /// Whether to keep values with an unrecognized type code as
+    /// [`Type::Raw`], rather than failing with an error (the default).
+    ///
+    /// This composes with [`ReaderBuilder::keep_unknown_tags`]: a raw value
+    /// on a known tag is kept (except for tp and ts, which must be a char),
+    /// while a raw value on an unknown tag is only kept if unknown tags are.
+    pub fn keep_unknown_types(&mut self, yes: bool) -> &mut ReaderBuilder {
+        self.keep_unknown_types = yes;
+        self
+    }
+
+    /// Whether to parse cg tags while reading and hold them as a packed
+    /// [`Cigar`], rather than as text (the default).
+    ///
+    /// A packed CIGAR takes well under the memory of its text, which adds up
+    /// when keeping many long alignments, and is written back out unchanged.
+    /// Malformed cg tags become a read error. [`PafRecord::cigar`] then
+    /// returns the parsed CIGAR without parsing, while [`PafRecord::cg`]
+    /// returns `None` as there is no text to borrow.
+    pub fn compact_cigar(&mut self, yes: bool) -> &mut ReaderBuilder {
+        self.compact_cigar = yes;
+        self
+    }
+
+    /// Build a PAF reader from a file path.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_path<P: AsRef<Path>>(&self, path: P) -> Result<Reader<File>> {
+        Ok(self.from_reader(File::open(path)?))
+    }
+
+    /// Build a PAF reader from a reader.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_reader<R: io::Read>(&self, rdr: R) -> Reader<R> {
+        Reader {
+            reader: io::BufReader::new(rdr),
+            line: 0,
+            config: self.clone(),
+            warnings: Vec::new(),
+            comments: Vec::new(),
+        }
     }
 }
 
@@ -386,6 +1266,9 @@ impl PafRecord {
 pub struct Reader<R> {
     reader: io::BufReader<R>,
     line: u64,
+    config: ReaderBuilder,
+    warnings: Vec<Warning>,
+    comments: Vec<String>,
 }
 
 impl Reader<File> {
@@ -401,12 +1284,12 @@ impl Reader<File> {
 }
 
 /// Parse optional fields from the PAF line.
-fn parse_optional_fields(fields: &[&str]) -> Result<HashMap<String, Tag>> {
+fn parse_optional_fields(fields: &[&str], config: &ReaderBuilder) -> Result<HashMap<String, Tag>> {
     let mut map = HashMap::new();
 
     // NM:i:48730
     for field in fields {
-        let parts: Vec<&str> = field.split(':').collect();
+        let parts: Vec<&str> = field.splitn(3, ':').collect();
         if parts.len() < 3 {
             return Err(Error::new(ErrorKind::ReadRecord(
                 "Invalid PAF line: invalid optional field - too few parts".into(),
@@ -417,16 +1300,36 @@ fn parse_optional_fields(fields: &[&str]) -> Result<HashMap<String, Tag>> {
         let type_ = parts[1];
         let inner = parts[2];
 
-        let type_ = Type::parse(type_, inner).ok_or_else(|| {
-            Error::new(ErrorKind::ReadRecord(format!(
-                "Invalid PAF line: invalid optional field type: {}",
-                type_
-            )))
-        })?;
+        let mut code = type_.chars();
+        let type_ = match (code.next(), code.next()) {
+            (Some(c), None) if TypeCode::from_char(c).is_some() => Type::from_typed_str(c, inner)?,
+            (Some(c), None) if config.keep_unknown_types => Type::raw(c, inner),
+            (Some(c), None) => {
+                return Err(Error::new(ErrorKind::ReadRecord(format!(
+                    "Invalid PAF line: unknown type code {} for tag {}",
+                    c, tag
+                ))))
+            }
+            _ => {
+                return Err(Error::new(ErrorKind::ReadRecord(format!(
+                    "Invalid PAF line: invalid optional field type: {}",
+                    type_
+                ))))
+            }
+        };
 
-        let tag = Tag::parse(tag, type_)?;
+        let tag = match TagKind::from_code(tag) {
+            TagKind::Other if config.keep_unknown_tags => Tag::Other(tag.to_string(), type_),
+            _ => Tag::parse(tag, type_)?,
+        };
+        let tag = match tag {
+            Tag::cg(Type::String(cg)) if config.compact_cigar => {
+                Tag::cg(Type::Cigar(Cigar::parse(&cg)?))
+            }
+            tag => tag,
+        };
 
-        map.insert(tag.to_string(), tag);
+        map.insert(tag.name().to_string(), tag);
     }
     Ok(map)
 }
@@ -434,14 +1337,69 @@ fn parse_optional_fields(fields: &[&str]) -> Result<HashMap<String, Tag>> {
 impl<R: io::Read> Reader<R> {
     /// Creates a new PAF parser from a buffered reader.
     pub fn new(rdr: R) -> Self {
-        Reader {
-            reader: io::BufReader::new(rdr),
-            line: 0,
+        ReaderBuilder::new().from_reader(rdr)
+    }
+
+    /// The number of lines read so far.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// Warnings about records which were read despite holding invalid values.
+    ///
+    /// These accumulate until taken with [`Reader::take_warnings`].
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Take the warnings recorded so far, leaving none behind.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Comment lines, those starting with `#`, passed over so far, without
+    /// their line endings.
+    ///
+    /// These accumulate until taken with [`Reader::take_comments`].
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// Take the comments read so far, leaving none behind.
+    ///
+    /// Taken after each record, these are the comments which came before
+    /// it in the file.
+    pub fn take_comments(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.comments)
+    }
+
+    /// Check values which parse but may still be invalid.
+    ///
+    /// Returns an error when validating, otherwise records a warning.
+    fn check_record(&mut self, record: &PafRecord) -> Result<()> {
+        for (tag, value) in [("dv", record.dv()), ("de", record.de())] {
+            match value {
+                Some(v) if !(0.0..=1.0).contains(&v) => {
+                    let message = format!("{} value {} out of range [0, 1]", tag, v);
+                    if self.config.validate {
+                        return Err(Error::new(ErrorKind::ReadRecord(format!(
+                            "Invalid PAF at line {}: {}",
+                            self.line, message
+                        ))));
+                    }
+                    self.warnings.push(Warning {
+                        line: self.line,
+                        message,
+                    });
+                }
+                _ => {}
+            }
         }
+        Ok(())
     }
 
     /// A borrowed iterator over the records of a PAF file.
-    pub fn records(&mut self) -> RecordsIter<R> {
+    pub fn records(&mut self) -> RecordsIter<'_, R> {
         RecordsIter::new(self)
     }
 
@@ -450,16 +1408,27 @@ impl<R: io::Read> Reader<R> {
         RecordsIntoIter::new(self)
     }
 
-    /// Read a single record.
+    /// Read a single record, passing over comment lines.
     pub fn read_record(&mut self) -> Result<Option<PafRecord>> {
         let mut line = String::new();
-        let bytes_read = match self.reader.read_line(&mut line) {
-            Ok(b) => b,
-            Err(e) => return Err(Error::new(ErrorKind::Io(e))),
-        };
+        loop {
+            line.clear();
+            let bytes_read = match self.reader.read_line(&mut line) {
+                Ok(b) => b,
+                Err(e) => return Err(Error::new(ErrorKind::Io(e))),
+            };
+
+            if bytes_read == 0 {
+                return Ok(None); // EOF
+            }
+            self.line += 1;
 
-        if bytes_read == 0 {
-            return Ok(None); // EOF
+            if !line.starts_with('#') {
+                break;
+            }
+            let comment = line.strip_suffix('\n').unwrap_or(&line);
+            let comment = comment.strip_suffix('\r').unwrap_or(comment);
+            self.comments.push(comment.to_string());
         }
 
         let columns: Vec<&str> = line.trim().split('\t').collect();
@@ -495,7 +1464,7 @@ impl<R: io::Read> Reader<R> {
         let alignment_block_len = columns[10].parse::<u32>()?;
         let mapping_quality = columns[11].parse::<u8>()?;
 
-        let optional = parse_optional_fields(&columns[12..])?;
+        let optional = parse_optional_fields(&columns[12..], &self.config)?;
 
         let record = PafRecord {
             query_name,
@@ -512,6 +1481,7 @@ impl<R: io::Read> Reader<R> {
             mapping_quality,
             optional,
         };
+        self.check_record(&record)?;
 
         Ok(Some(record))
     }
@@ -544,10 +1514,7 @@ impl<'r, R: io::Read> Iterator for RecordsIter<'r, R> {
 
     fn next(&mut self) -> Option<Result<PafRecord>> {
         match self.rdr.read_record() {
-            Ok(Some(r)) => {
-                self.rdr.line += 1;
-                Some(Ok(r))
-            }
+            Ok(Some(r)) => Some(Ok(r)),
             Ok(None) => None,
             Err(e) => Some(Err(e)),
         }
@@ -586,10 +1553,7 @@ impl<R: io::Read> Iterator for RecordsIntoIter<R> {
 
     fn next(&mut self) -> Option<Result<PafRecord>> {
         match self.rdr.read_record() {
-            Ok(Some(r)) => {
-                self.rdr.line += 1;
-                Some(Ok(r))
-            }
+            Ok(Some(r)) => Some(Ok(r)),
             Ok(None) => None,
             Err(e) => Some(Err(e)),
         }
@@ -597,14 +1561,49 @@ impl<R: io::Read> Iterator for RecordsIntoIter<R> {
 }
 
 #[cfg(test)]
-mod tests {
-    use super::Reader;
+pub(crate) mod tests {
+    use std::collections::HashMap;
+
+    use super::{
+        tags, AlignmentType, PafRecord, Reader, ReaderBuilder, Tag, TagKind, TranscriptStrand,
+        Type, TypeCode,
+    };
+    use crate::test_util::hit;
+    use crate::ErrorKind;
+
+    pub(crate) const PAF_RECORD_1: &[u8] = b"NC_041798.1	41841605	28850796	29394458	+	SUPER_10	44636193	31974877	32470190	495111	515145	60	NM:i:48730	ms:i:488389	AS:i:439775	nn:i:28696	tp:A:P	cm:i:46495	s1:i:466570	s2:i:10896	de:f:0.0003	zd:i:3	rl:i:3568165	cg:Z:770M1D945M1D389M1I9141M1I356M1D196M1I30268M2D789M3I992M2D1819M1D7M1D7M1I10M6D2922M1D17899M2D1010M4D12324M1I1376M1D5549M6D1839M1I2206M1D770M1D2287M1D16103M1D3238M1D2014M1D140M5I14M1D8496M2I2151M1I335M1D14424M1D1093M1I567M1D1835M2D1995M1D5257M1D639M1I699M1I133M1I52M1I99M2I26M1I195M1I1543M1I240M1I176M1I412M2D159M1I261M1D1158M1I933M2D12836M1D993M1D12263M2D4975M2I16452M3I396M1I3924M2D929M3I3015M1D225M1D4225M1D717M2D752M1D2051M1D5110M1D15073M1D1053M2D4369M1D619M3I13564M2I4386M1D1431M2D617M1I612M2I3445M2I252M1D220M1D237M1I903M1I145M1I53M1I197M1I1280M1D4201M1D1736M1D1289M1I3344M2D5456M1D488M1I1655M2D1830M1D796M1I19341M2D1165M1D1926M1D6041M1D2170M1D3917M1D926M1D759M1D400M2I8802M1I836M1I381M48451I166M1I4896M2D1522M49D2729M1D947M2D927M6D911M2D800M2D3040M1D13213M1D8999M3D847M1D220M1I673M1D165M1I901M1I2887M1I105M2I597M1I1201M1I53M2I494M1I23M1D99M1I146M1D29906M1D5661M1I27598M1D520M1I166M2D11600M1D388M1D844M1D4583M1D8390M1D5789M2D3773M1D4494M1D448M1D846M3D531M";
+
+    #[test]
+    fn test_query_clipping() {
+        let record = hit((1000, 100, 900), '+', (5000, 0, 800));
+        assert_eq!(record.query_clipping(), (100, 100));
+        let record = hit((1000, 10, 600), '+', (5000, 0, 590));
+        assert_eq!(record.query_clipping(), (10, 400));
+        let record = hit((1000, 10, 600), '-', (5000, 0, 590));
+        assert_eq!(record.query_clipping(), (400, 10));
+    }
 
-    const PAF_RECORD_1: &[u8] = b"NC_041798.1	41841605	28850796	29394458	+	SUPER_10	44636193	31974877	32470190	495111	515145	60	NM:i:48730	ms:i:488389	AS:i:439775	nn:i:28696	tp:A:P	cm:i:46495	s1:i:466570	s2:i:10896	de:f:0.0003	zd:i:3	rl:i:3568165	cg:Z:770M1D945M1D389M1I9141M1I356M1D196M1I30268M2D789M3I992M2D1819M1D7M1D7M1I10M6D2922M1D17899M2D1010M4D12324M1I1376M1D5549M6D1839M1I2206M1D770M1D2287M1D16103M1D3238M1D2014M1D140M5I14M1D8496M2I2151M1I335M1D14424M1D1093M1I567M1D1835M2D1995M1D5257M1D639M1I699M1I133M1I52M1I99M2I26M1I195M1I1543M1I240M1I176M1I412M2D159M1I261M1D1158M1I933M2D12836M1D993M1D12263M2D4975M2I16452M3I396M1I3924M2D929M3I3015M1D225M1D4225M1D717M2D752M1D2051M1D5110M1D15073M1D1053M2D4369M1D619M3I13564M2I4386M1D1431M2D617M1I612M2I3445M2I252M1D220M1D237M1I903M1I145M1I53M1I197M1I1280M1D4201M1D1736M1D1289M1I3344M2D5456M1D488M1I1655M2D1830M1D796M1I19341M2D1165M1D1926M1D6041M1D2170M1D3917M1D926M1D759M1D400M2I8802M1I836M1I381M48451I166M1I4896M2D1522M49D2729M1D947M2D927M6D911M2D800M2D3040M1D13213M1D8999M3D847M1D220M1I673M1D165M1I901M1I2887M1I105M2I597M1I1201M1I53M2I494M1I23M1D99M1I146M1D29906M1D5661M1I27598M1D520M1I166M2D11600M1D388M1D844M1D4583M1D8390M1D5789M2D3773M1D4494M1D448M1D846M3D531M";
+    #[test]
+    fn test_has_terminal_alignment() {
+        // the query's end overlaps the target's start
+        let record = hit((1000, 400, 995), '+', (5000, 3, 600));
+        assert!(record.has_terminal_alignment(5));
+        // on the minus strand, the query's start lies after the alignment,
+        // where it runs off the query
+        let record = hit((1000, 5, 600), '-', (5000, 2, 597));
+        assert!(record.has_terminal_alignment(5));
+        // on the plus strand the same coordinates are an internal match
+        let record = hit((1000, 5, 600), '+', (5000, 2, 597));
+        assert!(!record.has_terminal_alignment(5));
+        // containment
+        let record = hit((1000, 2, 998), '-', (5000, 1000, 2000));
+        assert!(record.has_terminal_alignment(5));
+        assert!(!record.has_terminal_alignment(1));
+    }
 
     #[test]
     fn test_read_record() {
-        let mut parser = Reader::from_reader(&PAF_RECORD_1[..]);
+        let mut parser = Reader::from_reader(PAF_RECORD_1);
         let record = parser.read_record().unwrap().unwrap();
 
         assert_eq!(record.query_name(), "NC_041798.1");
@@ -620,7 +1619,415 @@ mod tests {
         assert_eq!(record.alignment_block_len(), 515145);
         assert_eq!(record.mapping_quality(), 60);
 
-        let nm = record.nm().unwrap();
-        assert_eq!(nm, &48730);
+        assert_eq!(record.nm(), Some(48730));
+        assert_eq!(record.tp(), Some(AlignmentType::Primary));
+        assert_eq!(record.de(), Some(0.0003));
+        assert!(record.cg().unwrap().starts_with("770M1D945M"));
+        assert_eq!(record.cs(), None);
+    }
+
+    fn assert_wrong_type(tag: &str, value: Type, expected: char, found: char) {
+        let err = Tag::parse(tag, value).unwrap_err();
+        match err.kind() {
+            ErrorKind::ReadRecord(msg) => {
+                assert!(msg.contains(tag), "{}", msg);
+                assert!(msg.contains(&format!("expected {}", expected)), "{}", msg);
+                assert!(msg.contains(&format!("found {}", found)), "{}", msg);
+            }
+            e => panic!("unexpected error kind: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_tag_wrong_type_char() {
+        assert_wrong_type("tp", Type::Int(1), 'A', 'i');
+        assert_wrong_type("ts", Type::String("+".into()), 'A', 'Z');
+    }
+
+    #[test]
+    fn test_tag_wrong_type_int() {
+        assert_wrong_type("cm", Type::float(3.5), 'i', 'f');
+        assert_wrong_type("zd", Type::Char('3'), 'i', 'A');
+    }
+
+    #[test]
+    fn test_tag_wrong_type_float() {
+        assert_wrong_type("de", Type::String("0.1".into()), 'f', 'Z');
+        assert_wrong_type("dv", Type::Int(0), 'f', 'i');
+    }
+
+    #[test]
+    fn test_tag_wrong_type_string() {
+        assert_wrong_type("cg", Type::Int(5), 'Z', 'i');
+        assert_wrong_type("MD", Type::Char('A'), 'Z', 'A');
+    }
+
+    #[test]
+    fn test_read_record_wrong_tag_type() {
+        let line = b"q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\tcm:f:3.5\n";
+        let mut parser = Reader::from_reader(&line[..]);
+        assert!(parser.read_record().is_err());
+    }
+
+    fn record_with_tags(tags: Vec<(&str, Tag)>) -> PafRecord {
+        let optional: HashMap<String, Tag> =
+            tags.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+        PafRecord::new(
+            "q".into(),
+            100,
+            0,
+            10,
+            '+',
+            "t".into(),
+            100,
+            0,
+            10,
+            10,
+            10,
+            60,
+            optional,
+        )
+    }
+
+    #[test]
+    fn test_accessor_wrong_type() {
+        let record = record_with_tags(vec![
+            ("cm", Tag::cm(Type::float(3.5))),
+            ("tp", Tag::cm(Type::Int(1))),
+            ("cg", Tag::cg(Type::Char('M'))),
+            ("de", Tag::de(Type::String("x".into()))),
+        ]);
+
+        assert_eq!(record.cm(), None);
+        assert_eq!(record.tp(), None);
+        assert_eq!(record.cg(), None);
+        assert_eq!(record.de(), None);
+
+        for err in [
+            record.cm_checked().unwrap_err(),
+            record.tp_checked().unwrap_err(),
+            record.cg_checked().unwrap_err(),
+            record.de_checked().unwrap_err(),
+        ] {
+            assert!(matches!(err.kind(), ErrorKind::InvalidTag(_)));
+        }
+    }
+
+    #[test]
+    fn test_accessor_mismatched_key() {
+        let record = record_with_tags(vec![("NM", Tag::s1(Type::Int(3)))]);
+        assert_eq!(record.nm(), None);
+        assert!(record.nm_checked().is_err());
+    }
+
+    #[test]
+    fn test_accessor_absent() {
+        let record = record_with_tags(vec![("NM", Tag::NM(Type::Int(3)))]);
+        assert_eq!(record.nm(), Some(3));
+        assert_eq!(record.nm_checked().unwrap(), Some(3));
+        assert_eq!(record.as_(), None);
+        assert_eq!(record.as_checked().unwrap(), None);
+    }
+
+    #[test]
+    fn test_alignment_type() {
+        let cases = [
+            ('P', AlignmentType::Primary),
+            ('S', AlignmentType::Secondary),
+            ('I', AlignmentType::Inversion),
+            ('i', AlignmentType::SecondaryInversion),
+            ('p', AlignmentType::Other('p')),
+        ];
+        for (c, expected) in cases {
+            let line = format!("q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\ttp:A:{}\n", c);
+            let mut parser = Reader::from_reader(line.as_bytes());
+            let record = parser.read_record().unwrap().unwrap();
+            assert_eq!(record.tp(), Some(expected));
+            assert_eq!(record.tp().unwrap().as_char(), c);
+        }
+    }
+
+    #[test]
+    fn test_transcript_strand() {
+        let cases = [
+            ('+', TranscriptStrand::Forward),
+            ('-', TranscriptStrand::Reverse),
+            ('.', TranscriptStrand::Unknown),
+        ];
+        for (c, expected) in cases {
+            let line = format!("q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\tts:A:{}\n", c);
+            let mut parser = Reader::from_reader(line.as_bytes());
+            let record = parser.read_record().unwrap().unwrap();
+            assert_eq!(record.ts(), Some(expected));
+            assert_eq!(record.ts().unwrap().as_char(), c);
+        }
+
+        let line = b"q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\tts:A:x\n";
+        let mut parser = Reader::from_reader(&line[..]);
+        assert!(parser.read_record().is_err());
+    }
+
+    #[test]
+    fn test_type_from_typed_str() {
+        assert_eq!(
+            Type::from_typed_str('i', "-42").unwrap().get_int(),
+            Some(&-42)
+        );
+        assert_eq!(
+            Type::from_typed_str('f', "0.5").unwrap().get_float(),
+            Some(&0.5)
+        );
+        assert_eq!(
+            Type::from_typed_str('Z', "a:b").unwrap().get_string(),
+            Some(&"a:b".to_string())
+        );
+        assert_eq!(
+            Type::from_typed_str('A', "P").unwrap().get_char(),
+            Some(&'P')
+        );
+
+        assert!(Type::from_typed_str('i', "4.5").is_err());
+        assert!(Type::from_typed_str('f', "abc").is_err());
+        assert!(Type::from_typed_str('A', "PS").is_err());
+        assert!(Type::from_typed_str('A', "").is_err());
+        assert!(Type::from_typed_str('q', "5").is_err());
+    }
+
+    #[test]
+    fn test_type_from_str() {
+        let t: Type = "i:42".parse().unwrap();
+        assert_eq!(t.get_int(), Some(&42));
+        let t: Type = "Z:cs:Z::5".parse().unwrap();
+        assert_eq!(t.get_string(), Some(&"cs:Z::5".to_string()));
+        assert!("i42".parse::<Type>().is_err());
+        assert!("".parse::<Type>().is_err());
+    }
+
+    #[test]
+    fn test_type_display_round_trip() {
+        for s in ["i:-42", "f:0.0003", "Z:770M1D945M", "A:P"] {
+            let t: Type = s.parse().unwrap();
+            assert_eq!(format!("{}:{}", t.code(), t), s);
+        }
+    }
+
+    #[test]
+    fn test_float_keeps_raw_text() {
+        for raw in ["1e-05", "0.0003", "0", "0.10"] {
+            let t = Type::from_typed_str('f', raw).unwrap();
+            assert_eq!(t.to_string(), raw);
+        }
+        assert_eq!(Type::float(1e-05).to_string(), "0.00001");
+        assert_eq!(Type::float(0.0).to_string(), "0");
+        assert_eq!(Type::float(0.0003).to_string(), "0.0003");
+    }
+
+    fn divergence_line(tag: &str, value: &str) -> String {
+        format!(
+            "q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\t{}:f:{}\n",
+            tag, value
+        )
+    }
+
+    #[test]
+    fn test_divergence_out_of_range() {
+        for (tag, value) in [("de", "3.7e+12"), ("dv", "-0.01")] {
+            let line = divergence_line(tag, value);
+            let mut parser = ReaderBuilder::new()
+                .validate(true)
+                .from_reader(line.as_bytes());
+            let err = parser.read_record().unwrap_err();
+            let msg = err.to_string();
+            assert!(msg.contains(tag), "{}", msg);
+            assert!(msg.contains("line 1"), "{}", msg);
+
+            let mut parser = Reader::from_reader(line.as_bytes());
+            assert!(parser.read_record().unwrap().is_some());
+            assert_eq!(parser.warnings().len(), 1);
+            assert_eq!(parser.warnings()[0].line(), 1);
+            assert!(parser.warnings()[0].message().contains(tag));
+            assert_eq!(parser.take_warnings().len(), 1);
+            assert!(parser.warnings().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_divergence_boundaries() {
+        for tag in ["de", "dv"] {
+            for value in ["0.0", "1.0"] {
+                let line = divergence_line(tag, value);
+                let mut parser = ReaderBuilder::new()
+                    .validate(true)
+                    .from_reader(line.as_bytes());
+                assert!(parser.read_record().unwrap().is_some());
+                assert!(parser.warnings().is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_tag_kind_round_trip() {
+        assert_eq!(TagKind::all().len(), 17);
+        for kind in TagKind::all() {
+            let value = match kind.expected_type().unwrap() {
+                TypeCode::Char => Type::Char('+'),
+                TypeCode::Int => Type::Int(1),
+                TypeCode::Float => Type::float(0.5),
+                TypeCode::String => Type::String("1M".into()),
+            };
+            let tag = Tag::parse(kind.code(), value).unwrap();
+            assert_eq!(tag.kind(), *kind);
+            assert_eq!(TagKind::from_code(kind.code()), *kind);
+            assert!(!kind.description().is_empty());
+        }
+        assert_eq!(TagKind::from_code("xx"), TagKind::Other);
+        assert_eq!(TagKind::Other.expected_type(), None);
+    }
+
+    fn line_with_tag(tag: &str) -> String {
+        format!("q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\t{}\n", tag)
+    }
+
+    #[test]
+    fn test_unknown_type_code() {
+        for tag in ["NM:q:5", "cg:X:10M"] {
+            let line = line_with_tag(tag);
+            let mut parser = Reader::from_reader(line.as_bytes());
+            let err = parser.read_record().unwrap_err();
+            let msg = err.to_string();
+            assert!(msg.contains(&tag[..2]), "{}", msg);
+            assert!(
+                msg.contains(&format!("type code {}", &tag[3..4])),
+                "{}",
+                msg
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_type_code_kept() {
+        let line = line_with_tag("cg:X:10M");
+        let mut parser = ReaderBuilder::new()
+            .keep_unknown_types(true)
+            .from_reader(line.as_bytes());
+        let record = parser.read_record().unwrap().unwrap();
+        assert_eq!(record.cg(), None);
+        assert!(record.cg_checked().is_err());
+        assert_eq!(
+            record.optional_fields()["cg"].value().unwrap().get_raw(),
+            Some("X:10M")
+        );
+
+        // tp and ts must still be chars
+        let line = line_with_tag("tp:X:P");
+        let mut parser = ReaderBuilder::new()
+            .keep_unknown_types(true)
+            .from_reader(line.as_bytes());
+        assert!(parser.read_record().is_err());
+    }
+
+    #[test]
+    fn test_unknown_tags() {
+        let line = line_with_tag("xy:i:5");
+        let mut parser = Reader::from_reader(line.as_bytes());
+        assert!(parser.read_record().is_err());
+
+        let mut parser = ReaderBuilder::new()
+            .keep_unknown_tags(true)
+            .from_reader(line.as_bytes());
+        let record = parser.read_record().unwrap().unwrap();
+        let tag = &record.optional_fields()["xy"];
+        assert_eq!(tag.kind(), TagKind::Other);
+        assert_eq!(tag.value().unwrap().get_int(), Some(&5));
+
+        // an unknown type on an unknown tag needs both switches
+        let line = line_with_tag("xy:X:5");
+        let mut parser = ReaderBuilder::new()
+            .keep_unknown_tags(true)
+            .from_reader(line.as_bytes());
+        assert!(parser.read_record().is_err());
+        let mut parser = ReaderBuilder::new()
+            .keep_unknown_types(true)
+            .from_reader(line.as_bytes());
+        assert!(parser.read_record().is_err());
+        let mut parser = ReaderBuilder::new()
+            .keep_unknown_tags(true)
+            .keep_unknown_types(true)
+            .from_reader(line.as_bytes());
+        assert!(parser.read_record().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_type_coercion() {
+        assert_eq!(Type::float(99.0).as_i64(), Some(99));
+        assert_eq!(Type::float(-3.0).as_i64(), Some(-3));
+        assert_eq!(Type::float(99.5).as_i64(), None);
+        assert_eq!(Type::float(f64::NAN).as_i64(), None);
+        assert_eq!(Type::float(1e300).as_i64(), None);
+        assert_eq!(Type::Int(99).as_i64(), Some(99));
+        assert_eq!(Type::String("99".into()).as_i64(), None);
+
+        assert_eq!(Type::Int(99).as_f64(), Some(99.0));
+        assert_eq!(Type::float(0.5).as_f64(), Some(0.5));
+        // precision is lost beyond 2^53
+        assert_eq!(Type::Int(i64::MAX).as_f64(), Some(2f64.powi(63)));
+        assert_eq!(Type::Char('1').as_f64(), None);
+
+        // the strict getters are unchanged
+        assert_eq!(Type::float(99.0).get_int(), None);
+        assert_eq!(Type::Int(99).get_float(), None);
+
+        assert_eq!(Type::String("abc".into()).as_str().as_deref(), Some("abc"));
+        assert_eq!(Type::Char('P').as_str().as_deref(), Some("P"));
+        assert_eq!(Type::Int(1).as_str(), None);
+    }
+
+    #[test]
+    fn test_typed_get() {
+        let mut parser = Reader::from_reader(PAF_RECORD_1);
+        let record = parser.read_record().unwrap().unwrap();
+
+        let nm: Option<i64> = record.get::<tags::Nm>();
+        assert_eq!(nm, Some(48730));
+        let de: Option<f64> = record.get::<tags::De>();
+        assert_eq!(de, Some(0.0003));
+        let cg: Option<&str> = record.get::<tags::Cg>();
+        assert!(cg.unwrap().starts_with("770M"));
+        let tp: Option<AlignmentType> = record.get::<tags::Tp>();
+        assert_eq!(tp, Some(AlignmentType::Primary));
+        assert_eq!(record.get::<tags::Cs>(), None);
+        assert!(record.optional_fields().contains_key(tags::NM));
+
+        let record = record_with_tags(vec![(tags::DE, Tag::de(Type::Char('x')))]);
+        assert_eq!(record.get::<tags::De>(), None);
+        assert!(record.get_checked::<tags::De>().is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let line = |fields: &str| {
+            let line = format!("q\t100\t{}\t60\n", fields);
+            Reader::from_reader(line.as_bytes())
+                .read_record()
+                .unwrap()
+                .unwrap()
+        };
+        assert!(line("0\t10\t+\tt\t100\t0\t10\t10\t10").validate().is_ok());
+        // query end before start
+        let err = line("10\t0\t+\tt\t100\t0\t10\t10\t10")
+            .validate()
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidRecord(_)));
+        assert!(err.to_string().contains("query"));
+        // target end beyond target length
+        let err = line("0\t10\t+\tt\t100\t0\t110\t10\t10")
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("target"));
+        // more matches than columns
+        let err = line("0\t10\t+\tt\t100\t0\t10\t11\t10")
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("residue matches"));
     }
 }
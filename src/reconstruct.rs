@@ -0,0 +1,294 @@
+use crate::{CigarOp, CsOp, Error, ErrorKind, PafRecord, Result};
+
+/// The reverse complement of a sequence, keeping case. Bases other than
+/// A, C, G and T are kept as they are.
+pub(crate) fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|&base| match base {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' => b'A',
+            b'a' => b't',
+            b'c' => b'g',
+            b'g' => b'c',
+            b't' => b'a',
+            other => other,
+        })
+        .collect()
+}
+
+/// A single operation of an MD string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MdOp {
+    /// Matching bases.
+    Match(u32),
+    /// A substituted target base.
+    Mismatch(u8),
+    /// Deleted target bases.
+    Deletion(Vec<u8>),
+}
+
+/// Parse an MD string, e.g. `10A5^AC6`, leaving out zero length matches.
+fn parse_md(md: &str) -> Result<Vec<MdOp>> {
+    let bytes = md.as_bytes();
+    let mut ops = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        if bytes[i].is_ascii_digit() {
+            i += bytes[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+            let n = md[start..i].parse::<u32>()?;
+            if n > 0 {
+                ops.push(MdOp::Match(n));
+            }
+        } else if bytes[i] == b'^' {
+            i += 1;
+            i += bytes[i..]
+                .iter()
+                .take_while(|b| b.is_ascii_alphabetic())
+                .count();
+            if i == start + 1 {
+                return Err(md_error(start, "deletion without bases"));
+            }
+            ops.push(MdOp::Deletion(bytes[start + 1..i].to_ascii_uppercase()));
+        } else if bytes[i].is_ascii_alphabetic() {
+            ops.push(MdOp::Mismatch(bytes[i].to_ascii_uppercase()));
+            i += 1;
+        } else {
+            return Err(md_error(start, "unexpected character"));
+        }
+    }
+    Ok(ops)
+}
+
+/// Build an MD parse error at the given byte offset.
+fn md_error(offset: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::ReadRecord(format!(
+        "Invalid MD string at byte {}: {}",
+        offset, msg
+    )))
+}
+
+/// Build an error for edits which don't fit the query or each other.
+fn mismatch_error(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidRecord(format!(
+        "cannot reconstruct the target: {}",
+        msg
+    )))
+}
+
+/// Takes aligned query bases in target order.
+struct Query<'a> {
+    bases: &'a [u8],
+}
+
+impl<'a> Query<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if n > self.bases.len() {
+            return Err(mismatch_error(
+                "the edits use more query bases than aligned",
+            ));
+        }
+        let (taken, rest) = self.bases.split_at(n);
+        self.bases = rest;
+        Ok(taken)
+    }
+}
+
+impl PafRecord {
+    /// Rebuild the aligned part of the target from the whole query sequence
+    /// and the edits in the cs tag, or in the MD tag with the cg tag placing
+    /// the insertions.
+    ///
+    /// The result is on the forward strand of the target; for a `-` record
+    /// the aligned query is reverse complemented first. Bases copied from
+    /// the query keep their case, while those from the tags are upper case.
+    /// It is an error for `query_seq` not to be `query_len` bases long, or
+    /// for the edits not to fit it. Introns in a cs tag are an error too, as
+    /// their bases are not recorded.
+    pub fn reconstruct_target(&self, query_seq: &[u8]) -> Result<Vec<u8>> {
+        if query_seq.len() != self.query_len() as usize {
+            return Err(mismatch_error(&format!(
+                "the query sequence has {} bases but the record a query length of {}",
+                query_seq.len(),
+                self.query_len()
+            )));
+        }
+        let aligned = query_seq
+            .get(self.query_start() as usize..self.query_end() as usize)
+            .ok_or_else(|| mismatch_error("the query coordinates are out of range"))?;
+        let aligned = if self.strand() == '-' {
+            reverse_complement(aligned)
+        } else {
+            aligned.to_vec()
+        };
+        let mut query = Query { bases: &aligned };
+        let mut target =
+            Vec::with_capacity(self.target_end().saturating_sub(self.target_start()) as usize);
+
+        if let Some(cs) = self.cs_parsed() {
+            for op in &cs? {
+                match op {
+                    CsOp::Identity(n) => target.extend_from_slice(query.take(*n as usize)?),
+                    CsOp::Match(s) => target.extend_from_slice(query.take(s.len())?),
+                    CsOp::Mismatch { from, .. } => {
+                        query.take(1)?;
+                        target.push(from.to_ascii_uppercase() as u8);
+                    }
+                    CsOp::Insertion(s) => {
+                        query.take(s.len())?;
+                    }
+                    CsOp::Deletion(s) => {
+                        target.extend_from_slice(s.to_ascii_uppercase().as_bytes())
+                    }
+                    CsOp::Splice { .. } => {
+                        return Err(mismatch_error("the cs tag has an intron"));
+                    }
+                }
+            }
+        } else if let Some(md) = self.md() {
+            let md = parse_md(md)?;
+            let cigar = self.require_cigar("reconstructing the target from MD")?;
+            let mut md = md.into_iter();
+            let mut pending: Option<MdOp> = None;
+            for (len, op) in &cigar {
+                match op {
+                    CigarOp::Match | CigarOp::Equal | CigarOp::Diff => {
+                        let mut remaining = len;
+                        while remaining > 0 {
+                            match pending.take().or_else(|| md.next()) {
+                                Some(MdOp::Match(n)) => {
+                                    let taken = n.min(remaining);
+                                    target.extend_from_slice(query.take(taken as usize)?);
+                                    remaining -= taken;
+                                    if taken < n {
+                                        pending = Some(MdOp::Match(n - taken));
+                                    }
+                                }
+                                Some(MdOp::Mismatch(base)) => {
+                                    query.take(1)?;
+                                    target.push(base);
+                                    remaining -= 1;
+                                }
+                                _ => return Err(mismatch_error("MD does not fit the cg tag")),
+                            }
+                        }
+                    }
+                    CigarOp::Insertion => {
+                        query.take(len as usize)?;
+                    }
+                    CigarOp::Deletion => match pending.take().or_else(|| md.next()) {
+                        Some(MdOp::Deletion(bases)) if bases.len() == len as usize => {
+                            target.extend_from_slice(&bases)
+                        }
+                        _ => return Err(mismatch_error("MD does not fit the cg tag")),
+                    },
+                    _ => return Err(mismatch_error("the cg tag has skips or clips")),
+                }
+            }
+            if pending.is_some() || md.next().is_some() {
+                return Err(mismatch_error("MD does not fit the cg tag"));
+            }
+        } else {
+            return Err(Error::new(ErrorKind::InvalidRecord(
+                "reconstructing the target needs a cs or MD tag".to_string(),
+            )));
+        }
+
+        if !query.bases.is_empty() {
+            return Err(mismatch_error(
+                "the edits use fewer query bases than aligned",
+            ));
+        }
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Reader;
+
+    // the target, with the aligned part in upper case
+    const TARGET: &[u8] = b"ggACGTACCTTGACAAAGTCCAtt";
+    // the aligned target with a substitution (C>G at 5), an insertion of TT
+    // after 9 and a deletion of AA at 12
+    const QUERY: &[u8] = b"cACGTAGCTTGTTACAGTCCAtt";
+
+    fn aligned(strand: char, query: (u32, u32), tags: &str) -> PafRecord {
+        let line = format!(
+            "q\t{}\t{}\t{}\t{}\tt\t{}\t2\t22\t17\t22\t60\t{}\n",
+            QUERY.len(),
+            query.0,
+            query.1,
+            strand,
+            TARGET.len(),
+            tags
+        );
+        Reader::from_reader(line.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(reverse_complement(b"ACGTNacgt"), b"acgtNACGT");
+    }
+
+    #[test]
+    fn test_parse_md() {
+        assert_eq!(
+            parse_md("10A0C5^AC6").unwrap(),
+            vec![
+                MdOp::Match(10),
+                MdOp::Mismatch(b'A'),
+                MdOp::Mismatch(b'C'),
+                MdOp::Match(5),
+                MdOp::Deletion(b"AC".to_vec()),
+                MdOp::Match(6),
+            ]
+        );
+        assert!(parse_md("5^3").is_err());
+        assert!(parse_md("5*3").is_err());
+    }
+
+    #[test]
+    fn test_plus_strand() {
+        let expected = &TARGET[2..22];
+        let record = aligned('+', (1, 21), "cs:Z::5*cg:4+tt:2-aa:6");
+        assert_eq!(record.reconstruct_target(QUERY).unwrap(), expected);
+        let record = aligned('+', (1, 21), "MD:Z:5C6^AA6\tcg:Z:10M2I2M2D6M");
+        assert_eq!(record.reconstruct_target(QUERY).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_minus_strand() {
+        let query = reverse_complement(QUERY);
+        let expected = &TARGET[2..22];
+        let record = aligned('-', (2, 22), "cs:Z::5*cg:4+tt:2-aa:6");
+        assert_eq!(record.reconstruct_target(&query).unwrap(), expected);
+        let record = aligned('-', (2, 22), "MD:Z:5C6^AA6\tcg:Z:10M2I2M2D6M");
+        assert_eq!(record.reconstruct_target(&query).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_errors() {
+        let record = aligned('+', (1, 21), "cs:Z::5*cg:4+tt:2-aa:6");
+        // wrong query length
+        assert!(record.reconstruct_target(&QUERY[1..]).is_err());
+        // edits which don't use the whole aligned query
+        let short = aligned('+', (1, 21), "cs:Z::5*cg:4+tt:2-aa:5");
+        assert!(short.reconstruct_target(QUERY).is_err());
+        // MD without cg, and neither tag
+        let md = aligned('+', (1, 21), "MD:Z:5C6^AA6");
+        assert!(md.reconstruct_target(QUERY).is_err());
+        let bare = aligned('+', (1, 21), "");
+        assert!(bare.reconstruct_target(QUERY).is_err());
+        // MD which disagrees with the cg tag
+        let md = aligned('+', (1, 21), "MD:Z:5C6^A7\tcg:Z:10M2I2M2D6M");
+        assert!(md.reconstruct_target(QUERY).is_err());
+    }
+}
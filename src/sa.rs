@@ -0,0 +1,132 @@
+use std::fmt;
+
+use crate::{Cigar, Error, ErrorKind, PafRecord, Result};
+
+/// A single entry of the SA (supplementary alignment) tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupplementaryAlignment {
+    /// Reference sequence name.
+    pub rname: String,
+    /// 1-based leftmost position on the reference.
+    pub pos: u64,
+    /// `+` or `-`.
+    pub strand: char,
+    /// CIGAR of the alignment.
+    pub cigar: Cigar,
+    /// Mapping quality.
+    pub mapq: u8,
+    /// Edit distance.
+    pub nm: u32,
+}
+
+impl SupplementaryAlignment {
+    /// Parse a single `rname,pos,strand,CIGAR,mapQ,NM` entry.
+    fn parse(entry: &str) -> Result<Self> {
+        let fields: Vec<&str> = entry.split(',').collect();
+        if fields.len() != 6 {
+            return Err(Error::new(ErrorKind::ReadRecord(format!(
+                "Invalid SA entry: expected 6 comma separated fields, found {}: {}",
+                fields.len(),
+                entry
+            ))));
+        }
+
+        let strand = match fields[2] {
+            "+" => '+',
+            "-" => '-',
+            s => {
+                return Err(Error::new(ErrorKind::ReadRecord(format!(
+                    "Invalid SA entry: invalid strand {}: {}",
+                    s, entry
+                ))))
+            }
+        };
+
+        Ok(SupplementaryAlignment {
+            rname: fields[0].to_string(),
+            pos: fields[1].parse::<u64>()?,
+            strand,
+            cigar: Cigar::parse(fields[3])?,
+            mapq: fields[4].parse::<u8>()?,
+            nm: fields[5].parse::<u32>()?,
+        })
+    }
+}
+
+impl fmt::Display for SupplementaryAlignment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{};",
+            self.rname, self.pos, self.strand, self.cigar, self.mapq, self.nm
+        )
+    }
+}
+
+/// Parse the value of an SA tag into its entries.
+///
+/// Entries are terminated by semicolons; the trailing semicolon is optional.
+pub(crate) fn parse_supplementary_alignments(sa: &str) -> Result<Vec<SupplementaryAlignment>> {
+    let sa = sa.strip_suffix(';').unwrap_or(sa);
+    if sa.is_empty() {
+        return Ok(Vec::new());
+    }
+    sa.split(';').map(SupplementaryAlignment::parse).collect()
+}
+
+impl PafRecord {
+    /// Parse the SA tag into a list of supplementary alignments.
+    ///
+    /// Returns an empty list if the tag is absent.
+    pub fn supplementary_alignments(&self) -> Result<Vec<SupplementaryAlignment>> {
+        match self.sa_checked()? {
+            Some(sa) => parse_supplementary_alignments(sa),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_supplementary_alignments() {
+        let sa = "chr1,100,+,50M10S,60,2;chr2,2000,-,10S50M,13,0;";
+        let parsed = parse_supplementary_alignments(sa).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(
+            parsed[0],
+            SupplementaryAlignment {
+                rname: "chr1".into(),
+                pos: 100,
+                strand: '+',
+                cigar: Cigar::parse("50M10S").unwrap(),
+                mapq: 60,
+                nm: 2,
+            }
+        );
+        assert_eq!(parsed[1].rname, "chr2");
+        assert_eq!(parsed[1].strand, '-');
+
+        // the trailing semicolon is optional
+        let parsed = parse_supplementary_alignments(sa.trim_end_matches(';')).unwrap();
+        assert_eq!(parsed.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_supplementary_alignments_truncated() {
+        assert!(parse_supplementary_alignments("chr1,100,+,50M10S,60;").is_err());
+        assert!(parse_supplementary_alignments("chr1,100,+,50M10S,60,2;;").is_err());
+        assert!(parse_supplementary_alignments("chr1,100,x,50M10S,60,2;").is_err());
+        assert!(parse_supplementary_alignments("chr1,100,+,50Q,60,2;").is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let sa = "chr1,100,+,50M10S,60,2;chr2,2000,-,10S50M,13,0;";
+        let parsed = parse_supplementary_alignments(sa).unwrap();
+        let joined: String = parsed.iter().map(|s| s.to_string()).collect();
+        assert_eq!(joined, sa);
+    }
+}
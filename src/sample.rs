@@ -0,0 +1,223 @@
+/*!
+Draw reproducible random samples of records.
+
+The same seed always selects the same records from the same input, on any
+platform, as sampling uses its own small generator rather than the
+system's.
+
+```
+use paf::sample::subsample;
+use paf::Reader;
+
+let paf = b"q1\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t60\n\
+            q2\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t60\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let sample: Vec<_> = subsample(reader.records(), 0.5, 42).collect();
+assert!(sample.len() <= 2);
+```
+*/
+
+use crate::{PafRecord, Result};
+
+/// SplitMix64, a small, fast generator which is good enough for sampling.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A number in `[0, n)`.
+    fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+}
+
+/// Keeps each record with probability `fraction`, independently, so the
+/// sample size varies around `fraction` times the number of records.
+///
+/// Errors are passed through and do not use up random numbers, so the
+/// records kept do not depend on where errors occur.
+pub fn subsample<I>(records: I, fraction: f64, seed: u64) -> Subsample<I::IntoIter>
+where
+    I: IntoIterator<Item = Result<PafRecord>>,
+{
+    Subsample {
+        records: records.into_iter(),
+        fraction,
+        rng: Rng(seed),
+    }
+}
+
+/// An iterator over a random sample of records. See [`subsample`].
+#[derive(Debug)]
+pub struct Subsample<I> {
+    records: I,
+    fraction: f64,
+    rng: Rng,
+}
+
+impl<I: Iterator<Item = Result<PafRecord>>> Iterator for Subsample<I> {
+    type Item = Result<PafRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.records.next()? {
+                Ok(record) => {
+                    if self.rng.next_f64() < self.fraction {
+                        return Some(Ok(record));
+                    }
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Exactly `k` records chosen uniformly at random, or all of them if there
+/// are fewer, in their input order.
+///
+/// This reads all the records but holds only `k` at once. The first error
+/// is returned instead of a sample.
+pub fn reservoir_sample<I>(records: I, k: usize, seed: u64) -> Result<Vec<PafRecord>>
+where
+    I: IntoIterator<Item = Result<PafRecord>>,
+{
+    let mut rng = Rng(seed);
+    // k may be far more than there are records
+    let mut reservoir: Vec<(u64, PafRecord)> = Vec::with_capacity(k.min(1024));
+    for (i, record) in records.into_iter().enumerate() {
+        let record = record?;
+        let i = i as u64;
+        if reservoir.len() < k {
+            reservoir.push((i, record));
+            continue;
+        }
+        let j = rng.below(i + 1);
+        if j < k as u64 {
+            reservoir[j as usize] = (i, record);
+        }
+    }
+    reservoir.sort_unstable_by_key(|&(i, _)| i);
+    Ok(reservoir.into_iter().map(|(_, record)| record).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Reader;
+
+    /// Records named by their index.
+    fn records(n: usize) -> impl Iterator<Item = Result<PafRecord>> {
+        let paf: String = (0..n)
+            .map(|i| format!("{}\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t60\n", i))
+            .collect();
+        let records: Vec<Result<PafRecord>> =
+            Reader::from_reader(paf.as_bytes()).records().collect();
+        records.into_iter()
+    }
+
+    fn indices(records: impl IntoIterator<Item = Result<PafRecord>>) -> Vec<usize> {
+        records
+            .into_iter()
+            .map(|record| record.unwrap().query_name().parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_rng() {
+        // the reference SplitMix64 sequence for this seed
+        let mut rng = Rng(1234567);
+        assert_eq!(rng.next_u64(), 6457827717110365317);
+        assert_eq!(rng.next_u64(), 3203168211198807973);
+    }
+
+    #[test]
+    fn test_subsample_seeded() {
+        let sample = indices(subsample(records(20), 0.25, 7));
+        assert_eq!(sample, indices(subsample(records(20), 0.25, 7)));
+        assert_eq!(sample, [1, 5, 8, 10]);
+        assert_ne!(sample, indices(subsample(records(20), 0.25, 8)));
+        assert!(indices(subsample(records(20), 0.0, 7)).is_empty());
+        assert_eq!(indices(subsample(records(20), 1.0, 7)).len(), 20);
+    }
+
+    #[test]
+    fn test_subsample_fraction() {
+        for seed in 0..5 {
+            let n = subsample(records(10_000), 0.5, seed).count();
+            // five standard deviations either side of 5000
+            assert!((4750..=5250).contains(&n), "seed {} kept {}", seed, n);
+        }
+    }
+
+    #[test]
+    fn test_subsample_errors() {
+        let paf = "bad\nq\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t60\n";
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        let sample: Vec<_> = subsample(reader.records(), 1.0, 0).collect();
+        assert_eq!(sample.len(), 2);
+        assert!(sample[0].is_err() && sample[1].is_ok());
+    }
+
+    #[test]
+    fn test_reservoir_seeded() {
+        let sample = indices(
+            reservoir_sample(records(100), 5, 7)
+                .unwrap()
+                .into_iter()
+                .map(Ok),
+        );
+        assert_eq!(sample, [6, 12, 49, 57, 89]);
+        assert_eq!(
+            indices(
+                reservoir_sample(records(3), 5, 7)
+                    .unwrap()
+                    .into_iter()
+                    .map(Ok)
+            ),
+            [0, 1, 2]
+        );
+        assert!(reservoir_sample(records(3), 0, 7).unwrap().is_empty());
+        assert_eq!(
+            reservoir_sample(records(3), usize::MAX, 7).unwrap().len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_reservoir_uniform() {
+        // each of 10 records is picked about half the time for k = 5
+        let mut counts = [0; 10];
+        for seed in 0..2000 {
+            for i in indices(
+                reservoir_sample(records(10), 5, seed)
+                    .unwrap()
+                    .into_iter()
+                    .map(Ok),
+            ) {
+                counts[i] += 1;
+            }
+        }
+        for count in counts {
+            assert!((850..=1150).contains(&count), "{:?}", counts);
+        }
+    }
+
+    #[test]
+    fn test_reservoir_errors() {
+        let paf = "q\t100\t0\t100\t+\tt\t1000\t0\t100\t98\t100\t60\nbad\n";
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        assert!(reservoir_sample(reader.records(), 5, 0).is_err());
+    }
+}
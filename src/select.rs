@@ -0,0 +1,670 @@
+/*!
+Choose among the alignments of each query.
+
+Most selections work on the records of one query at a time. Input grouped
+by query, as minimap2 writes it, can be streamed with [`group_by_query`];
+other input is gathered per query in memory.
+
+```
+use paf::select::{best_per_query_grouped, Score};
+use paf::Reader;
+
+let paf = b"q1\t100\t0\t50\t+\tt\t1000\t0\t50\t40\t50\t60\n\
+            q1\t100\t0\t90\t+\tt\t1000\t100\t190\t85\t90\t60\n\
+            q2\t100\t0\t100\t+\tt\t1000\t500\t600\t99\t100\t60\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let best: Vec<_> = best_per_query_grouped(reader.records(), Score::ResidueMatches)
+    .map(|record| record.unwrap())
+    .collect();
+assert_eq!(best.len(), 2);
+assert_eq!(best[0].residue_matches(), 85);
+```
+*/
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::iter::Peekable;
+
+use crate::{filter, PafRecord, Result};
+
+/// How to score records to choose the best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    /// The AS tag, with records without one scoring lowest.
+    As,
+    /// Residue matches, column 10.
+    ResidueMatches,
+    /// Alignment block length, column 11.
+    BlockLen,
+    /// The AS tag, or residue matches for records without one.
+    AsOrResidueMatches,
+}
+
+impl Score {
+    pub(crate) fn of(&self, record: &PafRecord) -> i64 {
+        match self {
+            Score::As => record.as_().unwrap_or(i64::MIN),
+            Score::ResidueMatches => record.residue_matches() as i64,
+            Score::BlockLen => record.alignment_block_len() as i64,
+            Score::AsOrResidueMatches => record
+                .as_()
+                .unwrap_or_else(|| record.residue_matches() as i64),
+        }
+    }
+
+    /// Orders records best first: by score, highest first, then by target
+    /// name, target and query coordinates, and strand, so that the order
+    /// does not depend on the order of the input.
+    pub(crate) fn compare(&self, a: &PafRecord, b: &PafRecord) -> Ordering {
+        self.of(b)
+            .cmp(&self.of(a))
+            .then_with(|| tie_key(a).cmp(&tie_key(b)))
+    }
+}
+
+/// The fields which break ties between equally scored records.
+fn tie_key(record: &PafRecord) -> (&str, u32, u32, u32, u32, char) {
+    (
+        record.target_name(),
+        record.target_start(),
+        record.target_end(),
+        record.query_start(),
+        record.query_end(),
+        record.strand(),
+    )
+}
+
+/// Groups consecutive records with the same query name.
+///
+/// Records of a query must be next to each other, as in minimap2 output; a
+/// query appearing again later starts another group. An error ends the
+/// group being gathered and is passed through on its own.
+pub fn group_by_query<I>(records: I) -> GroupByQuery<I::IntoIter>
+where
+    I: IntoIterator<Item = Result<PafRecord>>,
+{
+    GroupByQuery {
+        records: records.into_iter().peekable(),
+    }
+}
+
+/// An iterator over the records of each query. See [`group_by_query`].
+#[derive(Debug)]
+pub struct GroupByQuery<I: Iterator<Item = Result<PafRecord>>> {
+    records: Peekable<I>,
+}
+
+impl<I: Iterator<Item = Result<PafRecord>>> Iterator for GroupByQuery<I> {
+    type Item = Result<Vec<PafRecord>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = match self.records.next()? {
+            Ok(record) => record,
+            Err(err) => return Some(Err(err)),
+        };
+        let mut group = vec![first];
+        while let Some(Ok(record)) = self.records.peek() {
+            if record.query_name() != group[0].query_name() {
+                break;
+            }
+            group.push(self.records.next().unwrap().unwrap());
+        }
+        Some(Ok(group))
+    }
+}
+
+/// The primary alignments of one query, in order along the query.
+///
+/// Split alignments give a query several primary records, which together
+/// are its chain. Primary means as in [`filter::primary_only`]: a tp tag of
+/// `P` or `I`, or no tp tag.
+pub fn primary_chain<I>(records_of_one_query: I) -> Vec<PafRecord>
+where
+    I: IntoIterator<Item = PafRecord>,
+{
+    let primary = filter::primary_only();
+    let mut chain: Vec<PafRecord> = records_of_one_query
+        .into_iter()
+        .filter(|record| primary(record))
+        .collect();
+    chain.sort_by_key(|record| (record.query_start(), record.query_end()));
+    chain
+}
+
+/// The primary chain of each query in input grouped by query. See
+/// [`primary_chain`] and [`group_by_query`].
+pub fn primary_chains_grouped<I>(records: I) -> impl Iterator<Item = Result<Vec<PafRecord>>>
+where
+    I: IntoIterator<Item = Result<PafRecord>>,
+{
+    group_by_query(records).map(|group| group.map(primary_chain))
+}
+
+/// The best record of each query, in the order queries are first seen.
+///
+/// Ties are broken as described for [`Score`], so the same record is chosen
+/// whatever the input order. This holds one record per query in memory;
+/// for input grouped by query, [`best_per_query_grouped`] holds one query's.
+pub fn best_per_query<I>(records: I, by: Score) -> Vec<PafRecord>
+where
+    I: IntoIterator<Item = PafRecord>,
+{
+    let mut best: Vec<PafRecord> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for record in records {
+        match index.get(record.query_name()) {
+            Some(&i) => {
+                if by.compare(&record, &best[i]) == Ordering::Less {
+                    best[i] = record;
+                }
+            }
+            None => {
+                index.insert(record.query_name().to_string(), best.len());
+                best.push(record);
+            }
+        }
+    }
+    best
+}
+
+/// The best record of each query in input grouped by query. See
+/// [`best_per_query`] and [`group_by_query`].
+pub fn best_per_query_grouped<I>(records: I, by: Score) -> impl Iterator<Item = Result<PafRecord>>
+where
+    I: IntoIterator<Item = Result<PafRecord>>,
+{
+    group_by_query(records).map(move |group| {
+        let group = group?;
+        Ok(group
+            .into_iter()
+            .min_by(|a, b| by.compare(a, b))
+            .expect("groups are never empty"))
+    })
+}
+
+/// The `n` best records of each query in input grouped by query, best
+/// first. Queries with fewer than `n` records keep them all.
+///
+/// Ties are broken as described for [`Score`]. See [`group_by_query`]; for
+/// other input, use [`top_n_per_query_unsorted`].
+pub fn top_n_per_query<I>(
+    records: I,
+    n: usize,
+    by: Score,
+) -> impl Iterator<Item = Result<PafRecord>>
+where
+    I: IntoIterator<Item = Result<PafRecord>>,
+{
+    group_by_query(records).flat_map(move |group| {
+        let group = match group {
+            Ok(mut group) => {
+                group.sort_by(|a, b| by.compare(a, b));
+                group.truncate(n);
+                group.into_iter().map(Ok).collect()
+            }
+            Err(err) => vec![Err(err)],
+        };
+        group.into_iter()
+    })
+}
+
+/// A record ranked by a score, greatest when worst, so that a heap of them
+/// has the worst on top.
+struct Ranked {
+    record: PafRecord,
+    by: Score,
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Ranked) -> Ordering {
+        self.by.compare(&self.record, &other.record)
+    }
+}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Ranked) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Ranked) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+/// The `n` best records of each query in any order of input, best first,
+/// and queries in the order they are first seen.
+///
+/// This holds up to `n` records per query in memory at once, plus each
+/// query name, so memory grows with the number of queries. For input
+/// grouped by query, [`top_n_per_query`] holds one query's records.
+pub fn top_n_per_query_unsorted<I>(records: I, n: usize, by: Score) -> Vec<PafRecord>
+where
+    I: IntoIterator<Item = PafRecord>,
+{
+    let mut heaps: Vec<BinaryHeap<Ranked>> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for record in records {
+        let i = match index.get(record.query_name()) {
+            Some(&i) => i,
+            None => {
+                index.insert(record.query_name().to_string(), heaps.len());
+                heaps.push(BinaryHeap::with_capacity(n + 1));
+                heaps.len() - 1
+            }
+        };
+        heaps[i].push(Ranked { record, by });
+        if heaps[i].len() > n {
+            heaps[i].pop();
+        }
+    }
+    heaps
+        .into_iter()
+        .flat_map(|heap| {
+            heap.into_sorted_vec()
+                .into_iter()
+                .map(|ranked| ranked.record)
+        })
+        .collect()
+}
+
+/// Orders records longest first: by alignment block length, then residue
+/// matches, then as for ties in [`Score`].
+fn compare_longest(a: &PafRecord, b: &PafRecord) -> Ordering {
+    let len = |r: &PafRecord| (r.alignment_block_len(), r.residue_matches());
+    len(b)
+        .cmp(&len(a))
+        .then_with(|| tie_key(a).cmp(&tie_key(b)))
+}
+
+/// The record with the longest alignment block for each query and target
+/// pair, in the order pairs are first seen.
+///
+/// Ties are broken by residue matches, then by coordinates as for
+/// [`Score`]. This holds one record and both names per pair in memory, so
+/// all-vs-all input can hold up to queries times targets records. For input
+/// grouped by query, [`longest_per_pair_grouped`] holds one query's.
+pub fn longest_per_pair<I>(records: I) -> Vec<PafRecord>
+where
+    I: IntoIterator<Item = PafRecord>,
+{
+    let mut longest: Vec<PafRecord> = Vec::new();
+    let mut index: HashMap<(String, String), usize> = HashMap::new();
+    for record in records {
+        let pair = (
+            record.query_name().to_string(),
+            record.target_name().to_string(),
+        );
+        match index.get(&pair) {
+            Some(&i) => {
+                if compare_longest(&record, &longest[i]) == Ordering::Less {
+                    longest[i] = record;
+                }
+            }
+            None => {
+                index.insert(pair, longest.len());
+                longest.push(record);
+            }
+        }
+    }
+    longest
+}
+
+/// The longest record for each query and target pair in input grouped by
+/// query. See [`longest_per_pair`] and [`group_by_query`].
+pub fn longest_per_pair_grouped<I>(records: I) -> impl Iterator<Item = Result<PafRecord>>
+where
+    I: IntoIterator<Item = Result<PafRecord>>,
+{
+    group_by_query(records).flat_map(|group| {
+        let group = match group {
+            Ok(group) => longest_per_pair(group).into_iter().map(Ok).collect(),
+            Err(err) => vec![Err(err)],
+        };
+        group.into_iter()
+    })
+}
+
+/// How much records kept by [`one_to_one`] can overlap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OneToOneOptions {
+    /// The greatest fraction of a record's interval, on the query or on the
+    /// target, which can overlap records already kept.
+    pub max_overlap: f64,
+}
+
+impl Default for OneToOneOptions {
+    /// Up to a tenth of each interval.
+    fn default() -> Self {
+        OneToOneOptions { max_overlap: 0.1 }
+    }
+}
+
+/// The intervals of one sequence covered by records kept.
+#[derive(Debug, Default)]
+struct Mask {
+    /// Disjoint intervals, end by start.
+    intervals: BTreeMap<u64, u64>,
+}
+
+impl Mask {
+    /// The bases of `start..end` covered, which must not end before it
+    /// starts.
+    fn covered(&self, start: u64, end: u64) -> u64 {
+        let before = self
+            .intervals
+            .range(..start)
+            .next_back()
+            .map_or(0, |(_, &e)| e.min(end).saturating_sub(start));
+        let within: u64 = self
+            .intervals
+            .range(start..end)
+            .map(|(&s, &e)| e.min(end) - s)
+            .sum();
+        before + within
+    }
+
+    /// Covers `start..end`, which is nothing when it ends before it starts.
+    fn insert(&mut self, mut start: u64, mut end: u64) {
+        if end <= start {
+            return;
+        }
+        if let Some((&s, &e)) = self.intervals.range(..start).next_back() {
+            if e >= start {
+                start = s;
+                end = end.max(e);
+            }
+        }
+        let touching: Vec<u64> = self.intervals.range(start..=end).map(|(&s, _)| s).collect();
+        for s in touching {
+            end = end.max(self.intervals.remove(&s).unwrap());
+        }
+        self.intervals.insert(start, end);
+    }
+}
+
+/// Reduces records to a one-to-one tiling of the queries and targets, as
+/// for all-vs-all or haplotype-duplicated alignments, in the order given.
+///
+/// Records are taken greedily best first by `by`, ties broken as described
+/// for [`Score`] and then by input order, so the result does not depend on
+/// the order of records of different score. A record is kept unless more
+/// than [`max_overlap`](OneToOneOptions::max_overlap) of its interval on
+/// the query, or of its interval on the target, is covered by records kept
+/// before it; each record kept then covers its intervals on both. This
+/// holds all records in memory.
+pub fn one_to_one<I>(records: I, by: Score, options: &OneToOneOptions) -> Vec<PafRecord>
+where
+    I: IntoIterator<Item = PafRecord>,
+{
+    let records: Vec<PafRecord> = records.into_iter().collect();
+    let mut order: Vec<usize> = (0..records.len()).collect();
+    order.sort_by(|&a, &b| by.compare(&records[a], &records[b]).then(a.cmp(&b)));
+
+    let mut queries: HashMap<&str, Mask> = HashMap::new();
+    let mut targets: HashMap<&str, Mask> = HashMap::new();
+    let mut keep = vec![false; records.len()];
+    let overlaps = |mask: Option<&Mask>, start: u32, end: u32| {
+        let (start, end) = (start as u64, end as u64);
+        if end <= start {
+            return false;
+        }
+        let covered = mask.map_or(0, |mask| mask.covered(start, end));
+        covered as f64 > (end - start) as f64 * options.max_overlap
+    };
+    for i in order {
+        let record = &records[i];
+        let query = queries.get(record.query_name());
+        let target = targets.get(record.target_name());
+        if overlaps(query, record.query_start(), record.query_end())
+            || overlaps(target, record.target_start(), record.target_end())
+        {
+            continue;
+        }
+        keep[i] = true;
+        queries
+            .entry(record.query_name())
+            .or_default()
+            .insert(record.query_start() as u64, record.query_end() as u64);
+        targets
+            .entry(record.target_name())
+            .or_default()
+            .insert(record.target_start() as u64, record.target_end() as u64);
+    }
+    records
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(record, keep)| keep.then_some(record))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+    use crate::Reader;
+
+    // two primaries split along the query, and three secondaries, one of
+    // which scores highest by residue matches
+    const PAF: &str = "\
+q1\t1000\t500\t1000\t+\tchr1\t10000\t5000\t5500\t480\t500\t60\ttp:A:P\tAS:i:900\n\
+q1\t1000\t0\t450\t-\tchr2\t10000\t100\t550\t440\t450\t60\ttp:A:P\tAS:i:850\n\
+q1\t1000\t0\t1000\t+\tchr3\t10000\t0\t1000\t600\t1000\t0\ttp:A:S\tAS:i:400\n\
+q1\t1000\t500\t1000\t+\tchr4\t10000\t0\t500\t470\t500\t0\ttp:A:S\tAS:i:880\n\
+q1\t1000\t500\t1000\t+\tchr5\t10000\t0\t500\t470\t500\t0\ttp:A:S\tAS:i:880\n\
+q2\t300\t0\t300\t+\tchr1\t10000\t0\t300\t290\t300\t60\ttp:A:P\tAS:i:500\n";
+
+    fn targets(records: &[PafRecord]) -> Vec<&str> {
+        records.iter().map(|r| r.target_name()).collect()
+    }
+
+    #[test]
+    fn test_group_by_query() {
+        let paf = format!("{}q1\t10\t0\t10\t+\tt\t10\t0\t10\t10\t10\t60\n", PAF);
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        let sizes: Vec<usize> = group_by_query(reader.records())
+            .map(|group| group.unwrap().len())
+            .collect();
+        assert_eq!(sizes, [5, 1, 1]);
+
+        let mut reader =
+            Reader::from_reader(&b"q\t10\t0\t10\t+\tt\t10\t0\t10\t10\t10\t60\nbad\n"[..]);
+        let groups: Vec<_> = group_by_query(reader.records()).collect();
+        assert_eq!(groups.len(), 2);
+        assert!(groups[0].is_ok() && groups[1].is_err());
+    }
+
+    #[test]
+    fn test_primary_chain() {
+        let chain = primary_chain(records(PAF).into_iter().take(5));
+        // in query order
+        assert_eq!(targets(&chain), ["chr2", "chr1"]);
+
+        let mut reader = Reader::from_reader(PAF.as_bytes());
+        let chains: Vec<Vec<PafRecord>> = primary_chains_grouped(reader.records())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(chains.len(), 2);
+        assert_eq!(targets(&chains[0]), ["chr2", "chr1"]);
+        assert_eq!(targets(&chains[1]), ["chr1"]);
+    }
+
+    #[test]
+    fn test_best_per_query() {
+        for (by, best) in [
+            (Score::As, "chr1"),
+            (Score::ResidueMatches, "chr3"),
+            (Score::BlockLen, "chr3"),
+        ] {
+            let chosen = best_per_query(records(PAF), by);
+            assert_eq!(targets(&chosen), [best, "chr1"], "{:?}", by);
+
+            let mut reader = Reader::from_reader(PAF.as_bytes());
+            let streamed: Vec<PafRecord> = best_per_query_grouped(reader.records(), by)
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(targets(&streamed), [best, "chr1"], "{:?}", by);
+        }
+    }
+
+    #[test]
+    fn test_top_n() {
+        let top = |n| {
+            let mut reader = Reader::from_reader(PAF.as_bytes());
+            let streamed: Vec<PafRecord> = top_n_per_query(reader.records(), n, Score::As)
+                .map(Result::unwrap)
+                .collect();
+            // q2 interleaved with q1, and q1 reversed
+            let mut shuffled = records(PAF);
+            shuffled[..5].reverse();
+            shuffled.swap(3, 5);
+            let unsorted = top_n_per_query_unsorted(shuffled, n, Score::As);
+            assert_eq!(targets(&streamed), targets(&unsorted));
+            targets(&streamed)
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+        assert!(top(0).is_empty());
+        assert_eq!(top(1), ["chr1", "chr1"]);
+        // chr4 and chr5 tie on AS and break by target name
+        assert_eq!(top(3), ["chr1", "chr4", "chr5", "chr1"]);
+        // q2 has fewer than n records
+        assert_eq!(top(10), ["chr1", "chr4", "chr5", "chr2", "chr3", "chr1"]);
+    }
+
+    #[test]
+    fn test_as_or_residue_matches() {
+        let paf = "\
+q\t100\t0\t100\t+\ta\t1000\t0\t100\t90\t100\t60\tAS:i:80\n\
+q\t100\t0\t100\t+\tb\t1000\t0\t100\t85\t100\t60\n\
+q\t100\t0\t100\t+\tc\t1000\t0\t100\t95\t100\t60\tAS:i:70\n";
+        let top = top_n_per_query_unsorted(records(paf), 3, Score::AsOrResidueMatches);
+        assert_eq!(targets(&top), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_longest_per_pair() {
+        let paf = "\
+q1\t1000\t0\t300\t+\tt1\t5000\t0\t300\t280\t300\t60\n\
+q1\t1000\t0\t500\t+\tt2\t5000\t0\t500\t450\t500\t60\n\
+q1\t1000\t300\t700\t+\tt1\t5000\t900\t1300\t380\t400\t60\n\
+q1\t1000\t500\t1000\t-\tt2\t5000\t2000\t2500\t470\t500\t60\n\
+q2\t1000\t0\t400\t+\tt1\t5000\t3000\t3400\t390\t400\t60\n\
+q2\t1000\t0\t400\t+\tt1\t5000\t1000\t1400\t390\t400\t60\n\
+q2\t1000\t500\t900\t+\tt1\t5000\t4000\t4400\t380\t400\t60\n";
+        let starts = |records: &[PafRecord]| -> Vec<(String, u32)> {
+            records
+                .iter()
+                .map(|r| (r.target_name().to_string(), r.target_start()))
+                .collect()
+        };
+        // q1 to t2 ties on length and breaks on residue matches, q2 to t1
+        // ties on both and breaks on target start
+        let expected = [
+            ("t1".to_string(), 900),
+            ("t2".to_string(), 2000),
+            ("t1".to_string(), 1000),
+        ];
+        assert_eq!(starts(&longest_per_pair(records(paf))), expected);
+        assert_eq!(
+            starts(&longest_per_pair(records(paf).into_iter().rev())),
+            [
+                ("t1".to_string(), 1000),
+                ("t2".to_string(), 2000),
+                ("t1".to_string(), 900),
+            ]
+        );
+
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        let streamed: Vec<PafRecord> = longest_per_pair_grouped(reader.records())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(starts(&streamed), expected);
+    }
+
+    #[test]
+    fn test_ties() {
+        // chr4 and chr5 tie on every score, and chr4 wins in either order
+        let tied: Vec<PafRecord> = records(PAF).into_iter().skip(3).take(2).collect();
+        assert_eq!(targets(&best_per_query(tied, Score::As)), ["chr4"]);
+        let tied = records(PAF).into_iter().skip(3).take(2).rev();
+        assert_eq!(targets(&best_per_query(tied, Score::As)), ["chr4"]);
+
+        // records without AS score lowest
+        let paf = "\
+q\t10\t0\t10\t+\tb\t10\t0\t10\t10\t10\t60\n\
+q\t10\t0\t10\t+\ta\t10\t0\t10\t10\t10\t60\tAS:i:-5\n";
+        assert_eq!(targets(&best_per_query(records(paf), Score::As)), ["a"]);
+    }
+
+    #[test]
+    fn test_one_to_one() {
+        // hap2 duplicates hap1, and both map to the same region of t1, with
+        // hap1 scoring higher; hap1 also maps to t2, scoring lower
+        let paf = "\
+hap2\t1000\t0\t1000\t+\tt1\t10000\t0\t1000\t950\t1000\t60\tAS:i:900
+hap1\t1000\t0\t1000\t+\tt1\t10000\t0\t1000\t990\t1000\t60\tAS:i:980
+hap1\t1000\t0\t1000\t-\tt2\t10000\t5000\t6000\t900\t1000\t60\tAS:i:800
+hap2\t1000\t0\t1000\t+\tt3\t10000\t0\t1000\t900\t1000\t60\tAS:i:850
+";
+        let kept = one_to_one(records(paf), Score::As, &OneToOneOptions::default());
+        let pairs: Vec<_> = kept
+            .iter()
+            .map(|r| (r.query_name(), r.target_name()))
+            .collect();
+        assert_eq!(pairs, [("hap1", "t1"), ("hap2", "t3")]);
+
+        // the same whatever the order of the input
+        let reversed: String = paf
+            .lines()
+            .rev()
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let kept = one_to_one(records(&reversed), Score::As, &OneToOneOptions::default());
+        let pairs: Vec<_> = kept
+            .iter()
+            .map(|r| (r.query_name(), r.target_name()))
+            .collect();
+        assert_eq!(pairs, [("hap2", "t3"), ("hap1", "t1")]);
+    }
+
+    #[test]
+    fn test_one_to_one_overlap() {
+        // b overlaps a on the target by 100 of its 1000 bases, and c overlaps
+        // b by 300
+        let paf = "\
+a\t1000\t0\t1000\t+\tt\t10000\t0\t1000\t1000\t1000\t60
+b\t1000\t0\t1000\t+\tt\t10000\t900\t1900\t900\t1000\t60
+c\t1000\t0\t1000\t+\tt\t10000\t1600\t2600\t800\t1000\t60
+";
+        let names = |max_overlap| {
+            let options = OneToOneOptions { max_overlap };
+            one_to_one(records(paf), Score::ResidueMatches, &options)
+                .iter()
+                .map(|r| r.query_name().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(0.1), ["a", "b"]);
+        assert_eq!(names(0.0), ["a", "c"]);
+        assert_eq!(names(0.3), ["a", "b", "c"]);
+
+        // a query interval ending before it starts overlaps nothing, and
+        // covers nothing once kept
+        let paf = "\
+a\t1000\t0\t1000\t+\tt\t10000\t0\t1000\t1000\t1000\t60
+a\t1000\t600\t200\t+\tu\t10000\t0\t400\t400\t400\t60
+a\t1000\t300\t500\t+\tv\t10000\t0\t200\t200\t200\t60
+";
+        let kept = one_to_one(
+            records(paf),
+            Score::ResidueMatches,
+            &OneToOneOptions::default(),
+        );
+        let targets: Vec<_> = kept.iter().map(|r| r.target_name()).collect();
+        assert_eq!(targets, ["t", "u"]);
+    }
+}
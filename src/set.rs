@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use crate::{PafRecord, Reader, Result, SortKey};
+
+/// Sequence names, each held once, and the records of each.
+#[derive(Debug, Default)]
+struct NameIndex {
+    ids: HashMap<Arc<str>, usize>,
+    /// Names in the order first seen, with the indices of their records.
+    names: Vec<(Arc<str>, Vec<usize>)>,
+}
+
+impl NameIndex {
+    fn add(&mut self, name: &str, record: usize) {
+        match self.ids.get(name) {
+            Some(&id) => self.names[id].1.push(record),
+            None => {
+                let name: Arc<str> = Arc::from(name);
+                self.ids.insert(Arc::clone(&name), self.names.len());
+                self.names.push((name, vec![record]));
+            }
+        }
+    }
+
+    fn get(&self, name: &str) -> &[usize] {
+        match self.ids.get(name) {
+            Some(&id) => &self.names[id].1,
+            None => &[],
+        }
+    }
+}
+
+/// Records held in memory, with the records of each query and target at
+/// hand.
+///
+/// Each distinct sequence name is held once by the lookups, whatever the
+/// number of records. The lookups are rebuilt by [`PafSet::retain`] and
+/// [`PafSet::sort_by`], so they always agree with the records, at the cost
+/// of a pass over all of them.
+///
+/// ```
+/// use paf::{PafSet, Reader};
+///
+/// let paf = b"q1\t100\t0\t100\t+\tchr1\t1000\t0\t100\t95\t100\t60\n\
+///             q2\t100\t0\t100\t+\tchr2\t1000\t0\t100\t95\t100\t60\n\
+///             q1\t100\t0\t100\t-\tchr2\t1000\t500\t600\t90\t100\t60\n";
+/// let set = PafSet::from_reader(&mut Reader::from_reader(&paf[..])).unwrap();
+/// assert_eq!(set.records_for_query("q1").count(), 2);
+/// assert_eq!(set.targets().collect::<Vec<_>>(), ["chr1", "chr2"]);
+/// ```
+#[derive(Debug, Default)]
+pub struct PafSet {
+    records: Vec<PafRecord>,
+    targets: NameIndex,
+    queries: NameIndex,
+}
+
+impl PafSet {
+    /// Read the rest of the records of a reader.
+    pub fn from_reader<R: io::Read>(reader: &mut Reader<R>) -> Result<Self> {
+        Ok(Self::from_records(
+            reader.records().collect::<Result<Vec<_>>>()?,
+        ))
+    }
+
+    /// Hold the given records.
+    pub fn from_records(records: Vec<PafRecord>) -> Self {
+        let mut set = PafSet {
+            records,
+            ..PafSet::default()
+        };
+        set.rebuild();
+        set
+    }
+
+    fn rebuild(&mut self) {
+        self.targets = NameIndex::default();
+        self.queries = NameIndex::default();
+        for (i, record) in self.records.iter().enumerate() {
+            self.targets.add(record.target_name(), i);
+            self.queries.add(record.query_name(), i);
+        }
+    }
+
+    /// All the records, in order.
+    pub fn records(&self) -> &[PafRecord] {
+        &self.records
+    }
+
+    /// Take back the records, in order.
+    pub fn into_records(self) -> Vec<PafRecord> {
+        self.records
+    }
+
+    /// The records aligned to a target, in order.
+    pub fn records_for_target(&self, name: &str) -> impl Iterator<Item = &PafRecord> {
+        self.targets.get(name).iter().map(|&i| &self.records[i])
+    }
+
+    /// The records of a query, in order.
+    pub fn records_for_query(&self, name: &str) -> impl Iterator<Item = &PafRecord> {
+        self.queries.get(name).iter().map(|&i| &self.records[i])
+    }
+
+    /// The names of the targets, in the order first seen in the records.
+    pub fn targets(&self) -> impl Iterator<Item = &str> {
+        self.targets.names.iter().map(|(name, _)| &**name)
+    }
+
+    /// The names of the queries, in the order first seen in the records.
+    pub fn queries(&self) -> impl Iterator<Item = &str> {
+        self.queries.names.iter().map(|(name, _)| &**name)
+    }
+
+    /// The number of records.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether there are no records.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Keep only the records for which `predicate` is true, in order, and
+    /// rebuild the lookups. Names left without records are dropped.
+    pub fn retain<F: FnMut(&PafRecord) -> bool>(&mut self, predicate: F) {
+        self.records.retain(predicate);
+        self.rebuild();
+    }
+
+    /// Sort the records, keeping the order of those which compare equal, and
+    /// rebuild the lookups, so that the records of each name and the names
+    /// follow the new order.
+    pub fn sort_by(&mut self, key: SortKey) {
+        self.records.sort_by(|a, b| key.compare(a, b));
+        self.rebuild();
+    }
+}
+
+impl FromIterator<PafRecord> for PafSet {
+    fn from_iter<I: IntoIterator<Item = PafRecord>>(records: I) -> Self {
+        Self::from_records(records.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAF: &str = "\
+q1\t100\t0\t100\t+\tchr2\t1000\t500\t600\t95\t100\t60\tAS:i:90
+q2\t100\t0\t100\t+\tchr1\t1000\t0\t100\t95\t100\t60\tAS:i:80
+q1\t100\t0\t100\t-\tchr1\t1000\t300\t400\t90\t100\t5\tAS:i:70
+q3\t100\t0\t100\t+\tchr2\t1000\t100\t200\t95\t100\t60\tAS:i:95
+";
+
+    fn set() -> PafSet {
+        PafSet::from_reader(&mut Reader::from_reader(PAF.as_bytes())).unwrap()
+    }
+
+    fn starts<'a>(records: impl Iterator<Item = &'a PafRecord>) -> Vec<u32> {
+        records.map(PafRecord::target_start).collect()
+    }
+
+    #[test]
+    fn test_lookups() {
+        let set = set();
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.targets().collect::<Vec<_>>(), ["chr2", "chr1"]);
+        assert_eq!(set.queries().collect::<Vec<_>>(), ["q1", "q2", "q3"]);
+        assert_eq!(starts(set.records_for_target("chr2")), [500, 100]);
+        assert_eq!(starts(set.records_for_query("q1")), [500, 300]);
+        assert_eq!(set.records_for_target("chr3").count(), 0);
+        // each name is held once, by the map and the list of names
+        assert_eq!(Arc::strong_count(&set.targets.names[0].0), 2);
+    }
+
+    #[test]
+    fn test_retain_rebuilds() {
+        let mut set = set();
+        set.retain(|record| record.mapping_quality() == 60 && record.query_name() != "q2");
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.targets().collect::<Vec<_>>(), ["chr2"]);
+        assert_eq!(set.queries().collect::<Vec<_>>(), ["q1", "q3"]);
+        assert_eq!(starts(set.records_for_query("q1")), [500]);
+        assert_eq!(set.records_for_target("chr1").count(), 0);
+        assert_eq!(starts(set.records_for_target("chr2")), [500, 100]);
+    }
+
+    #[test]
+    fn test_sort_rebuilds() {
+        let mut set = set();
+        set.sort_by(SortKey::TargetCoordinate);
+        assert_eq!(starts(set.records().iter()), [0, 300, 100, 500]);
+        assert_eq!(set.targets().collect::<Vec<_>>(), ["chr1", "chr2"]);
+        assert_eq!(set.queries().collect::<Vec<_>>(), ["q2", "q1", "q3"]);
+        assert_eq!(starts(set.records_for_target("chr2")), [100, 500]);
+        assert_eq!(starts(set.records_for_query("q1")), [300, 500]);
+
+        set.sort_by(SortKey::ScoreDescending);
+        assert_eq!(starts(set.records_for_target("chr2")), [100, 500]);
+        assert_eq!(starts(set.records_for_query("q1")), [500, 300]);
+    }
+}
@@ -0,0 +1,540 @@
+/*!
+Sort PAF records in memory, and files larger than memory.
+
+[`sort_records`] sorts records in memory into an order that depends only on
+the records, and [`is_sorted`] checks an order. [`external_sort`] reads
+records until a memory budget is reached, sorts them and writes them to a
+temporary file, a run, then merges the runs into the output. Files that fit
+within the budget are sorted in memory.
+
+```
+use paf::sort::{external_sort, ExternalSortOptions};
+use paf::{Reader, SortKey, Writer};
+
+let paf = b"a\t10\t0\t10\t+\tt\t100\t50\t60\t10\t10\t60\n\
+            b\t10\t0\t10\t+\tt\t100\t20\t30\t10\t10\t60\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let mut writer = Writer::new(Vec::new());
+let report = external_sort(
+    &mut reader,
+    &mut writer,
+    SortKey::TargetCoordinate,
+    &ExternalSortOptions::default(),
+)
+.unwrap();
+assert_eq!((report.records, report.runs), (2, 0));
+assert!(writer.into_inner().unwrap().starts_with(b"b\t"));
+```
+*/
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::sorted::record_size;
+use crate::{PafRecord, Reader, ReaderBuilder, Result, SortKey, Writer};
+
+/// Runs are merged this many at a time, to bound the open files.
+const MAX_OPEN_RUNS: usize = 256;
+
+/// Tells apart the spill directories made by one process.
+static SPILL_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+/// Sorts records by `key`, breaking ties on every other field.
+///
+/// After the key, records compare by target name, target start and end,
+/// query name, start and end, strand, the lengths, residue matches, block
+/// length and mapping quality, then by their tags sorted by name. Only
+/// records that would be written the same can tie, so any order of the
+/// same records sorts to the same output.
+///
+/// ```
+/// use paf::sort::{is_sorted, sort_records};
+/// use paf::{Reader, SortKey};
+///
+/// let paf = b"q\t10\t0\t10\t+\tt\t100\t50\t60\t10\t10\t60\n\
+///             q\t10\t0\t10\t+\tt\t100\t20\t30\t10\t10\t60\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let mut records = reader.records().collect::<paf::Result<Vec<_>>>().unwrap();
+/// // both records tie on the query name
+/// assert!(is_sorted(&records, SortKey::QueryName));
+/// sort_records(&mut records, SortKey::QueryName);
+/// assert_eq!(records[0].target_start(), 20);
+/// ```
+pub fn sort_records(records: &mut [PafRecord], key: SortKey) {
+    records.sort_unstable_by(|a, b| key.compare_total(a, b));
+}
+
+/// Whether `records` are in order by `key`.
+///
+/// Only the fields of the key are compared, so this holds for the output of
+/// [`sort_records`] and of stable sorts alike.
+pub fn is_sorted(records: &[PafRecord], key: SortKey) -> bool {
+    records
+        .windows(2)
+        .all(|pair| key.compare(&pair[0], &pair[1]) != Ordering::Greater)
+}
+
+/// How [`external_sort`] uses memory and disk.
+#[derive(Debug, Clone)]
+pub struct ExternalSortOptions {
+    /// The memory for the records of each run, in bytes, as estimated by
+    /// [`SortedWriter::memory_estimate`](crate::SortedWriter::memory_estimate).
+    /// The default is 1 GiB.
+    pub memory_budget: usize,
+    /// The directory for the runs, by default [`std::env::temp_dir`]. A
+    /// directory of its own is made in it, and removed when done.
+    pub temp_dir: Option<PathBuf>,
+    /// Whether to gzip compress the runs, trading time for disk space.
+    #[cfg(feature = "gzip")]
+    pub compress_runs: bool,
+}
+
+impl Default for ExternalSortOptions {
+    fn default() -> Self {
+        ExternalSortOptions {
+            memory_budget: 1 << 30,
+            temp_dir: None,
+            #[cfg(feature = "gzip")]
+            compress_runs: false,
+        }
+    }
+}
+
+impl ExternalSortOptions {
+    fn compress_runs(&self) -> bool {
+        #[cfg(feature = "gzip")]
+        return self.compress_runs;
+        #[cfg(not(feature = "gzip"))]
+        false
+    }
+}
+
+/// What an [`external_sort`] did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExternalSortReport {
+    /// The number of records sorted.
+    pub records: u64,
+    /// The number of runs written to disk, 0 if sorted in memory.
+    pub runs: u64,
+    /// The bytes written to disk, counting runs made by merging too.
+    pub bytes_spilled: u64,
+}
+
+/// Sorts the records of `input` by `key` and writes them to `output`, using
+/// temporary files when they outgrow the memory budget.
+///
+/// Records that compare equal keep their order in the input, as with
+/// [`SortedWriter`](crate::SortedWriter). When there are more runs than
+/// can be open at once, they are merged in groups first. The temporary
+/// files are removed whether or not the sort succeeds.
+///
+/// Runs are read back keeping unknown tags, so that nothing in the input
+/// is lost, whatever the options of `input`. The output is flushed but not
+/// finished, so a compressed `output` still needs
+/// [`Writer::into_inner`].
+pub fn external_sort<R: io::Read, W: Write>(
+    input: &mut Reader<R>,
+    output: &mut Writer<W>,
+    key: SortKey,
+    options: &ExternalSortOptions,
+) -> Result<ExternalSortReport> {
+    let mut report = ExternalSortReport::default();
+    let mut chunk = Vec::new();
+    let mut memory = 0;
+    let mut spill: Option<SpillDir> = None;
+    for record in input.records() {
+        let record = record?;
+        memory += record_size(&record);
+        chunk.push(record);
+        report.records += 1;
+        if memory >= options.memory_budget {
+            if spill.is_none() {
+                spill = Some(SpillDir::new(options)?);
+            }
+            let dir = spill.as_mut().unwrap();
+            chunk.sort_by(|a, b| key.compare(a, b));
+            let run = dir.write_run(&chunk)?;
+            report.bytes_spilled += fs::metadata(&run)?.len();
+            dir.runs.push(run);
+            chunk.clear();
+            memory = 0;
+        }
+    }
+
+    match spill {
+        None => {
+            chunk.sort_by(|a, b| key.compare(a, b));
+            output.write_records(&chunk)?;
+        }
+        Some(mut dir) => {
+            if !chunk.is_empty() {
+                chunk.sort_by(|a, b| key.compare(a, b));
+                let run = dir.write_run(&chunk)?;
+                report.bytes_spilled += fs::metadata(&run)?.len();
+                dir.runs.push(run);
+            }
+            drop(chunk);
+            report.runs = dir.runs.len() as u64;
+
+            // merging neighbouring runs keeps records that compare equal in
+            // order, as ties go to the earlier run
+            while dir.runs.len() > MAX_OPEN_RUNS {
+                let runs = std::mem::take(&mut dir.runs);
+                for group in runs.chunks(MAX_OPEN_RUNS) {
+                    let mut writer = dir.run_writer()?;
+                    merge(group, &mut writer.1, key, dir.compress)?;
+                    let run = dir.finish_run(writer)?;
+                    report.bytes_spilled += fs::metadata(&run)?.len();
+                    dir.runs.push(run);
+                    for path in group {
+                        fs::remove_file(path)?;
+                    }
+                }
+            }
+            merge(&dir.runs, output, key, dir.compress)?;
+        }
+    }
+    output.flush()?;
+    Ok(report)
+}
+
+/// A directory of runs, removed with all in it when dropped.
+#[derive(Debug)]
+struct SpillDir {
+    path: PathBuf,
+    runs: Vec<PathBuf>,
+    made: usize,
+    compress: bool,
+}
+
+impl SpillDir {
+    fn new(options: &ExternalSortOptions) -> Result<Self> {
+        let parent = options.temp_dir.clone().unwrap_or_else(std::env::temp_dir);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.subsec_nanos());
+        let path = parent.join(format!(
+            "paf-sort-{}-{}-{}",
+            process::id(),
+            nanos,
+            SPILL_DIRS.fetch_add(1, AtomicOrdering::Relaxed)
+        ));
+        fs::create_dir(&path)?;
+        Ok(SpillDir {
+            path,
+            runs: Vec::new(),
+            made: 0,
+            compress: options.compress_runs(),
+        })
+    }
+
+    /// A writer to a new run file, and its path.
+    fn run_writer(&mut self) -> Result<(PathBuf, Writer<File>)> {
+        let extension = if self.compress { "paf.gz" } else { "paf" };
+        let path = self.path.join(format!("run-{}.{}", self.made, extension));
+        self.made += 1;
+        let file = File::create(&path)?;
+        #[cfg(feature = "gzip")]
+        if self.compress {
+            return Ok((path, Writer::gzip(file, 1)));
+        }
+        Ok((path, Writer::new(file)))
+    }
+
+    fn finish_run(&self, (path, writer): (PathBuf, Writer<File>)) -> Result<PathBuf> {
+        writer.into_inner().map_err(|(_, err)| err)?;
+        Ok(path)
+    }
+
+    /// Writes sorted records as a new run, returning its path.
+    fn write_run(&mut self, records: &[PafRecord]) -> Result<PathBuf> {
+        let mut writer = self.run_writer()?;
+        writer.1.write_records(records)?;
+        self.finish_run(writer)
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+fn open_run(path: &Path, compressed: bool) -> Result<Reader<Box<dyn io::Read>>> {
+    let file = BufReader::new(File::open(path)?);
+    #[cfg(feature = "gzip")]
+    let file: Box<dyn io::Read> = if compressed {
+        Box::new(flate2::read::MultiGzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    #[cfg(not(feature = "gzip"))]
+    let file: Box<dyn io::Read> = {
+        let _ = compressed;
+        Box::new(file)
+    };
+    Ok(ReaderBuilder::new()
+        .keep_unknown_tags(true)
+        .keep_unknown_types(true)
+        .from_reader(file))
+}
+
+/// The next record of a run, ordered for a min-heap on the key, then on the
+/// run.
+struct Head {
+    record: PafRecord,
+    run: usize,
+    key: SortKey,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key
+            .compare(&other.record, &self.record)
+            .then(other.run.cmp(&self.run))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+/// Merges sorted runs into `output`.
+fn merge<W: Write>(
+    runs: &[PathBuf],
+    output: &mut Writer<W>,
+    key: SortKey,
+    compressed: bool,
+) -> Result<()> {
+    let mut readers = runs
+        .iter()
+        .map(|path| open_run(path, compressed))
+        .collect::<Result<Vec<_>>>()?;
+    let mut heap = BinaryHeap::with_capacity(readers.len());
+    for (run, reader) in readers.iter_mut().enumerate() {
+        if let Some(record) = reader.read_record()? {
+            heap.push(Head { record, run, key });
+        }
+    }
+    while let Some(Head { record, run, .. }) = heap.pop() {
+        output.write_record(&record)?;
+        if let Some(record) = readers[run].read_record()? {
+            heap.push(Head { record, run, key });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+
+    /// A temporary directory of the test's own, holding nothing.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("paf-sort-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Records `r0..rn` in a shuffled order, on a few targets at a few
+    /// starts, so that many compare equal.
+    fn shuffled(n: usize) -> String {
+        let mut order: Vec<usize> = (0..n).collect();
+        let mut state: u64 = 42;
+        for i in (1..n).rev() {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            order.swap(i, (state >> 33) as usize % (i + 1));
+        }
+        order
+            .iter()
+            .map(|&i| {
+                let start = (i * 7919) % 50 * 100;
+                format!(
+                    "r{}\t1000\t0\t100\t+\tchr{}\t10000\t{}\t{}\t100\t100\t60\tNM:i:{}\n",
+                    i,
+                    i % 3,
+                    start,
+                    start + 100,
+                    i % 7
+                )
+            })
+            .collect()
+    }
+
+    // the update is needless without the gzip feature
+    #[allow(clippy::needless_update)]
+    fn spill_options(memory_budget: usize, temp_dir: PathBuf) -> ExternalSortOptions {
+        ExternalSortOptions {
+            memory_budget,
+            temp_dir: Some(temp_dir),
+            ..Default::default()
+        }
+    }
+
+    fn sort(paf: &str, options: &ExternalSortOptions) -> Result<(String, ExternalSortReport)> {
+        let mut reader = Reader::from_reader(paf.as_bytes());
+        let mut writer = Writer::new(Vec::new());
+        let report = external_sort(&mut reader, &mut writer, SortKey::TargetCoordinate, options)?;
+        let output = writer.into_inner().unwrap();
+        Ok((String::from_utf8(output).unwrap(), report))
+    }
+
+    fn in_memory(paf: &str) -> String {
+        let mut lines: Vec<&str> = paf.lines().collect();
+        lines.sort_by_key(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            (
+                fields[5],
+                fields[7].parse::<u32>().unwrap(),
+                fields[8].parse::<u32>().unwrap(),
+            )
+        });
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    fn written(records: &[PafRecord]) -> String {
+        let mut writer = Writer::new(Vec::new());
+        writer.write_records(records).unwrap();
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_sort_records_reproducible() {
+        // all tie on the target coordinates, and the records with tags tie
+        // on every field but them, the last being the same as the second
+        let lines = [
+            "q1\t100\t0\t50\t+\tt\t1000\t100\t150\t50\t50\t60\tNM:i:2",
+            "q1\t100\t0\t50\t+\tt\t1000\t100\t150\t50\t50\t60\tNM:i:1",
+            "q1\t100\t0\t50\t+\tt\t1000\t100\t150\t50\t50\t60\tNM:i:1\tAS:i:9",
+            "q1\t100\t0\t50\t-\tt\t1000\t100\t150\t50\t50\t60",
+            "q0\t100\t0\t50\t+\tt\t1000\t100\t150\t50\t50\t60",
+            "q1\t100\t0\t50\t+\tt\t1000\t100\t150\t49\t50\t60",
+            "q1\t100\t0\t50\t+\tt\t1000\t100\t150\t50\t50\t60\tNM:i:1",
+        ];
+        let forward: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        let backward: String = lines
+            .iter()
+            .rev()
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let shuffled = shuffled(500);
+        let mut other: Vec<&str> = shuffled.lines().collect();
+        other.reverse();
+        let other: String = other.iter().map(|line| format!("{}\n", line)).collect();
+
+        for key in [
+            SortKey::TargetCoordinate,
+            SortKey::QueryCoordinate,
+            SortKey::QueryName,
+            SortKey::ScoreDescending,
+        ] {
+            let mut a = records(&forward);
+            let mut b = records(&backward);
+            sort_records(&mut a, key);
+            sort_records(&mut b, key);
+            assert!(is_sorted(&a, key));
+            assert_eq!(written(&a), written(&b), "{:?}", key);
+
+            let mut a = records(&shuffled);
+            let mut b = records(&other);
+            sort_records(&mut a, key);
+            sort_records(&mut b, key);
+            assert_eq!(written(&a), written(&b), "{:?}", key);
+        }
+
+        let mut sorted = records(&forward);
+        sort_records(&mut sorted, SortKey::TargetCoordinate);
+        let order: Vec<String> = written(&sorted).lines().map(String::from).collect();
+        assert_eq!(
+            order,
+            [lines[4], lines[5], lines[2], lines[1], lines[6], lines[0], lines[3]]
+        );
+    }
+
+    #[test]
+    fn test_is_sorted() {
+        let records = records(&shuffled(100));
+        assert!(!is_sorted(&records, SortKey::TargetCoordinate));
+        assert!(is_sorted(&records[..1], SortKey::TargetCoordinate));
+        assert!(is_sorted(&[], SortKey::QueryName));
+    }
+
+    #[test]
+    fn test_many_runs() {
+        let paf = shuffled(50_000);
+        let dir = temp_dir("many");
+        let options = spill_options(16 * 1024, dir.clone());
+        let (output, report) = sort(&paf, &options).unwrap();
+        assert_eq!(report.records, 50_000);
+        // enough runs to be merged in groups first
+        assert!(report.runs > MAX_OPEN_RUNS as u64, "{:?}", report);
+        assert!(report.bytes_spilled > paf.len() as u64);
+        // records with the same coordinates keep their input order
+        assert_eq!(output, in_memory(&paf));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_in_memory() {
+        let paf = shuffled(100);
+        let dir = temp_dir("memory");
+        let options = spill_options(1 << 30, dir.clone());
+        let (output, report) = sort(&paf, &options).unwrap();
+        assert_eq!(report.runs, 0);
+        assert_eq!(report.bytes_spilled, 0);
+        assert_eq!(output, in_memory(&paf));
+        assert_eq!(sort("", &options).unwrap().0, "");
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cleanup_on_failure() {
+        let mut paf = shuffled(2000);
+        paf.push_str("broken\n");
+        let dir = temp_dir("failure");
+        let options = spill_options(4096, dir.clone());
+        assert!(sort(&paf, &options).is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+
+        // a temporary directory that is missing
+        let options = spill_options(4096, dir.join("missing"));
+        assert!(sort(&paf, &options).is_err());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_compressed_runs() {
+        let paf = shuffled(5000);
+        let dir = temp_dir("gzip");
+        let mut options = spill_options(16 * 1024, dir.clone());
+        options.compress_runs = true;
+        let (output, report) = sort(&paf, &options).unwrap();
+        assert!(report.runs > 1);
+        assert!(report.bytes_spilled < paf.len() as u64);
+        assert_eq!(output, in_memory(&paf));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+}
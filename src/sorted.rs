@@ -0,0 +1,272 @@
+use std::cmp::Ordering;
+use std::io::Write;
+use std::mem::size_of;
+
+use crate::{PafRecord, Result, Tag, Type, Writer};
+
+/// The order in which records are sorted.
+///
+/// Each key compares only the fields it names, so a stable sort, as by a
+/// [`SortedWriter`] or [`external_sort`](crate::sort::external_sort), keeps
+/// records that tie in the order they came. Names compare by their bytes,
+/// and coordinates as numbers. [`sort_records`](crate::sort::sort_records)
+/// breaks ties on all the fields instead, so that its output does not
+/// depend on the order of its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// By target name, then target start and end.
+    TargetCoordinate,
+    /// By query name, then query start and end.
+    QueryCoordinate,
+    /// By query name only, keeping the records of each query together.
+    QueryName,
+    /// By the AS tag, highest first, with records without one last.
+    ScoreDescending,
+}
+
+impl SortKey {
+    pub(crate) fn compare(&self, a: &PafRecord, b: &PafRecord) -> Ordering {
+        match self {
+            SortKey::TargetCoordinate => (a.target_name(), a.target_start(), a.target_end())
+                .cmp(&(b.target_name(), b.target_start(), b.target_end())),
+            SortKey::QueryCoordinate => (a.query_name(), a.query_start(), a.query_end()).cmp(&(
+                b.query_name(),
+                b.query_start(),
+                b.query_end(),
+            )),
+            SortKey::QueryName => a.query_name().cmp(b.query_name()),
+            // None sorts before Some, so reversing puts it last
+            SortKey::ScoreDescending => b.as_().cmp(&a.as_()),
+        }
+    }
+
+    /// Compares by this key, then breaks ties on the twelve fields in
+    /// target coordinate order and then on the tags, sorted by name, so
+    /// that only records written the same compare equal.
+    pub(crate) fn compare_total(&self, a: &PafRecord, b: &PafRecord) -> Ordering {
+        self.compare(a, b)
+            .then_with(|| fields(a).cmp(&fields(b)))
+            .then_with(|| tags(a).cmp(&tags(b)))
+    }
+}
+
+type Fields<'a> = (
+    &'a str,
+    u32,
+    u32,
+    &'a str,
+    u32,
+    u32,
+    char,
+    u32,
+    u32,
+    u32,
+    u32,
+    u8,
+);
+
+fn fields(record: &PafRecord) -> Fields<'_> {
+    (
+        record.target_name(),
+        record.target_start(),
+        record.target_end(),
+        record.query_name(),
+        record.query_start(),
+        record.query_end(),
+        record.strand(),
+        record.query_len(),
+        record.target_len(),
+        record.residue_matches(),
+        record.alignment_block_len(),
+        record.mapping_quality(),
+    )
+}
+
+/// The tags as written, sorted by name.
+fn tags(record: &PafRecord) -> Vec<(&str, char, String)> {
+    let mut tags: Vec<_> = record
+        .optional_fields()
+        .iter()
+        .map(|(name, tag)| {
+            let value = tag.value();
+            (
+                name.as_str(),
+                value.map_or(' ', Type::code),
+                value.map(Type::to_string).unwrap_or_default(),
+            )
+        })
+        .collect();
+    tags.sort_unstable();
+    tags
+}
+
+/// Buffers records in memory and writes them sorted when finished.
+///
+/// Records that compare equal keep the order they were written in. All
+/// records are held until [`SortedWriter::finish`], so this suits files
+/// that fit in memory; beyond that, sort the PAF text externally, e.g.
+/// `sort -k6,6 -k8,8n -k9,9n` for target coordinate order.
+///
+/// ```
+/// use paf::{Reader, SortKey, SortedWriter, Writer};
+///
+/// let paf = b"a\t10\t0\t10\t+\tt\t100\t50\t60\t10\t10\t60\n\
+///             b\t10\t0\t10\t+\tt\t100\t20\t30\t10\t10\t60\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let mut writer = SortedWriter::new(Writer::new(Vec::new()), SortKey::TargetCoordinate);
+/// for record in reader.records() {
+///     writer.write_record(record.unwrap());
+/// }
+/// assert_eq!(writer.len(), 2);
+/// let output = writer.finish().unwrap().into_inner().unwrap();
+/// assert!(output.starts_with(b"b\t"));
+/// ```
+#[derive(Debug)]
+pub struct SortedWriter<W: Write> {
+    writer: Writer<W>,
+    key: SortKey,
+    records: Vec<PafRecord>,
+    memory: usize,
+}
+
+impl<W: Write> SortedWriter<W> {
+    /// Creates a sorted writer which writes through `writer` when finished.
+    pub fn new(writer: Writer<W>, key: SortKey) -> Self {
+        SortedWriter {
+            writer,
+            key,
+            records: Vec::new(),
+            memory: 0,
+        }
+    }
+
+    /// Buffers a record.
+    pub fn write_record(&mut self, record: PafRecord) {
+        self.memory += record_size(&record);
+        self.records.push(record);
+    }
+
+    /// The number of buffered records.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether no records are buffered.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// An estimate of the memory held by the buffered records, in bytes.
+    ///
+    /// This counts the records, their names and their tag values, but not
+    /// the spare capacity of the buffer or of the tag maps.
+    pub fn memory_estimate(&self) -> usize {
+        self.memory
+    }
+
+    /// Sorts and writes the buffered records, then flushes and returns the
+    /// writer.
+    pub fn finish(mut self) -> Result<Writer<W>> {
+        let key = self.key;
+        self.records.sort_by(|a, b| key.compare(a, b));
+        self.writer.write_records(&self.records)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// The approximate memory held by a record, in bytes.
+pub(crate) fn record_size(record: &PafRecord) -> usize {
+    let tags: usize = record
+        .optional_fields()
+        .iter()
+        .map(|(name, tag)| {
+            let value = match tag.value() {
+                Some(Type::String(s) | Type::Raw(s)) => s.len(),
+                Some(Type::Float { raw: Some(s), .. }) => s.len(),
+                Some(Type::Cigar(cigar)) => cigar.heap_size(),
+                _ => 0,
+            };
+            let other = match tag {
+                Tag::Other(name, _) => name.len(),
+                _ => 0,
+            };
+            size_of::<(String, Tag)>() + name.len() + value + other
+        })
+        .sum();
+    size_of::<PafRecord>() + record.query_name().len() + record.target_name().len() + tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Reader;
+
+    const PAF: &str = "\
+r3\t100\t0\t50\t+\tchr2\t1000\t10\t60\t50\t50\t60\tAS:i:50\n\
+r1\t100\t50\t100\t+\tchr1\t1000\t500\t550\t50\t50\t60\tAS:i:90\n\
+r2\t100\t0\t40\t-\tchr1\t1000\t100\t140\t40\t40\t60\n\
+r1\t100\t0\t50\t+\tchr1\t1000\t100\t150\t50\t50\t60\tAS:i:50\n\
+r4\t100\t0\t50\t+\tchr1\t1000\t100\t150\t50\t50\t60\tAS:i:70\n";
+
+    fn sorted(key: SortKey) -> Vec<String> {
+        let mut writer = SortedWriter::new(Writer::new(Vec::new()), key);
+        for record in Reader::from_reader(PAF.as_bytes()).records() {
+            writer.write_record(record.unwrap());
+        }
+        let output = writer.finish().unwrap().into_inner().unwrap();
+        Reader::from_reader(&output[..])
+            .records()
+            .map(|record| {
+                let record = record.unwrap();
+                format!("{}:{}", record.query_name(), record.target_start())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_target_coordinate() {
+        // r1 and r4 tie, and keep their written order
+        assert_eq!(
+            sorted(SortKey::TargetCoordinate),
+            ["r2:100", "r1:100", "r4:100", "r1:500", "r3:10"]
+        );
+    }
+
+    #[test]
+    fn test_query_coordinate() {
+        assert_eq!(
+            sorted(SortKey::QueryCoordinate),
+            ["r1:100", "r1:500", "r2:100", "r3:10", "r4:100"]
+        );
+    }
+
+    #[test]
+    fn test_query_name() {
+        // the records of r1 keep their written order
+        assert_eq!(
+            sorted(SortKey::QueryName),
+            ["r1:500", "r1:100", "r2:100", "r3:10", "r4:100"]
+        );
+    }
+
+    #[test]
+    fn test_score_descending() {
+        assert_eq!(
+            sorted(SortKey::ScoreDescending),
+            ["r1:500", "r4:100", "r3:10", "r1:100", "r2:100"]
+        );
+    }
+
+    #[test]
+    fn test_len_and_memory() {
+        let mut writer = SortedWriter::new(Writer::new(Vec::new()), SortKey::QueryName);
+        assert!(writer.is_empty());
+        assert_eq!(writer.memory_estimate(), 0);
+        for record in Reader::from_reader(PAF.as_bytes()).records() {
+            writer.write_record(record.unwrap());
+        }
+        assert_eq!(writer.len(), 5);
+        assert!(writer.memory_estimate() > 5 * size_of::<PafRecord>());
+    }
+}
@@ -0,0 +1,1838 @@
+/*!
+Summarize the alignments of a PAF file in a single pass.
+
+```no_run
+use paf::{stats::AlignmentStats, Reader};
+
+let mut reader = Reader::from_path("alignments.paf").unwrap();
+let mut stats = AlignmentStats::new();
+for record in reader.records() {
+    stats.add(&record.unwrap());
+}
+println!("{}", stats.finish());
+```
+
+For a report of a whole file like that of `paftools.js stat`, see
+[`summary`].
+*/
+
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io::{BufWriter, Write};
+
+use crate::{AlignmentType, Cigar, CigarOp, Error, ErrorKind, PafRecord, Result};
+
+/// The default lower edges of the gap length bins.
+const DEFAULT_GAP_BINS: [u64; 8] = [1, 2, 5, 10, 50, 100, 1000, 10000];
+
+/// Identities are binned to this many steps between 0 and 1 for the median.
+const IDENTITY_STEPS: usize = 10000;
+
+/// The identities of records, binned to [`IDENTITY_STEPS`].
+#[derive(Debug, Clone)]
+struct Identities {
+    sum: f64,
+    counts: Vec<u64>,
+}
+
+impl Identities {
+    fn new() -> Self {
+        Identities {
+            sum: 0.0,
+            counts: vec![0; IDENTITY_STEPS + 1],
+        }
+    }
+
+    /// Add the identity of a record, residue matches over alignment block
+    /// length, unless its block length is zero.
+    fn add(&mut self, record: &PafRecord) {
+        if record.alignment_block_len() > 0 {
+            let identity = record.residue_matches() as f64 / record.alignment_block_len() as f64;
+            let identity = identity.clamp(0.0, 1.0);
+            self.sum += identity;
+            self.counts[(identity * IDENTITY_STEPS as f64).round() as usize] += 1;
+        }
+    }
+
+    fn len(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    fn mean(&self) -> Option<f64> {
+        let len = self.len();
+        (len > 0).then(|| self.sum / len as f64)
+    }
+
+    /// The lower median for an even count.
+    fn median(&self) -> Option<f64> {
+        let rank = self.len().div_ceil(2);
+        let mut seen = 0;
+        for (step, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank && seen > 0 {
+                return Some(step as f64 / IDENTITY_STEPS as f64);
+            }
+        }
+        None
+    }
+}
+
+/// A streaming accumulator of alignment statistics.
+///
+/// Records are added one at a time and not kept, so memory use does not
+/// grow with the number of records.
+#[derive(Debug, Clone)]
+pub struct AlignmentStats {
+    summary: AlignmentSummary,
+    identities: Identities,
+}
+
+/// The totals of an [`AlignmentStats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignmentSummary {
+    /// Number of records.
+    pub records: u64,
+    /// Query bases within the alignments.
+    pub query_aligned_bases: u64,
+    /// Target bases within the alignments.
+    pub target_aligned_bases: u64,
+    /// Matching bases, from the residue matches column.
+    pub matches: u64,
+    /// Mismatching bases, from records with a cs tag, an NM tag and gaps, or
+    /// a cg tag with `X` operations.
+    pub mismatches: u64,
+    /// Number of records counted in `mismatches`.
+    pub records_with_mismatches: u64,
+    /// Number of insertion events.
+    pub insertions: u64,
+    /// Number of inserted bases.
+    pub inserted_bases: u64,
+    /// Number of deletion events.
+    pub deletions: u64,
+    /// Number of deleted bases.
+    pub deleted_bases: u64,
+    /// Number of records with a cg or cs tag, counted in the gap totals.
+    pub records_with_gaps: u64,
+    /// Insertions and deletions, binned by length.
+    pub gap_histogram: Vec<GapBin>,
+    /// Mean identity of the records, residue matches over alignment block
+    /// length.
+    pub mean_identity: Option<f64>,
+    /// Median identity of the records, to four decimal places.
+    pub median_identity: Option<f64>,
+}
+
+/// A bin of the gap length histogram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GapBin {
+    /// The shortest gap in the bin.
+    pub min_len: u64,
+    /// The length of the shortest gap in the next bin, if there is one.
+    pub max_len: Option<u64>,
+    /// The number of insertions and deletions in the bin.
+    pub count: u64,
+}
+
+impl GapBin {
+    /// The lengths in the bin, as `1`, `2-4` or `10+`.
+    fn range(&self) -> String {
+        match self.max_len {
+            Some(max_len) if max_len == self.min_len + 1 => self.min_len.to_string(),
+            Some(max_len) => format!("{}-{}", self.min_len, max_len - 1),
+            None => format!("{}+", self.min_len),
+        }
+    }
+}
+
+/// Empty gap bins starting at each of the edges.
+///
+/// # Panics
+///
+/// Panics if the edges are not increasing.
+fn gap_bins(edges: &[u64]) -> Vec<GapBin> {
+    assert!(
+        edges.windows(2).all(|w| w[0] < w[1]),
+        "gap bin edges must be increasing"
+    );
+    edges
+        .iter()
+        .enumerate()
+        .map(|(i, &min_len)| GapBin {
+            min_len,
+            max_len: edges.get(i + 1).copied(),
+            count: 0,
+        })
+        .collect()
+}
+
+/// Count a gap in the last bin starting at or below its length, if any.
+fn count_gap(bins: &mut [GapBin], len: u64) {
+    if let Some(bin) = bins.iter_mut().rev().find(|bin| bin.min_len <= len) {
+        bin.count += 1;
+    }
+}
+
+impl AlignmentStats {
+    /// Create an accumulator with gap length bins starting at 1, 2, 5, 10,
+    /// 50, 100, 1000 and 10000 bases.
+    pub fn new() -> Self {
+        Self::with_gap_bins(&DEFAULT_GAP_BINS)
+    }
+
+    /// Create an accumulator with gap length bins starting at each of the
+    /// given lengths, the last bin taking all longer gaps.
+    ///
+    /// Gaps shorter than the first edge are not binned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the edges are not increasing.
+    pub fn with_gap_bins(edges: &[u64]) -> Self {
+        let gap_histogram = gap_bins(edges);
+        AlignmentStats {
+            summary: AlignmentSummary {
+                records: 0,
+                query_aligned_bases: 0,
+                target_aligned_bases: 0,
+                matches: 0,
+                mismatches: 0,
+                records_with_mismatches: 0,
+                insertions: 0,
+                inserted_bases: 0,
+                deletions: 0,
+                deleted_bases: 0,
+                records_with_gaps: 0,
+                gap_histogram,
+                mean_identity: None,
+                median_identity: None,
+            },
+            identities: Identities::new(),
+        }
+    }
+
+    /// Add a record.
+    ///
+    /// Gaps come from the cg tag, or the cs tag without one. A tag which
+    /// fails to parse is treated as absent.
+    pub fn add(&mut self, record: &PafRecord) {
+        let summary = &mut self.summary;
+        summary.records += 1;
+        summary.query_aligned_bases +=
+            record.query_end().saturating_sub(record.query_start()) as u64;
+        summary.target_aligned_bases +=
+            record.target_end().saturating_sub(record.target_start()) as u64;
+        summary.matches += record.residue_matches() as u64;
+
+        let cs = record.cs_parsed().and_then(|cs| cs.ok());
+        let cigar: Option<Cigar> = match record.cigar() {
+            Some(Ok(cigar)) => Some(cigar),
+            _ => cs.as_ref().map(|cs| cs.to_cigar()),
+        };
+
+        if let Some(cigar) = &cigar {
+            summary.records_with_gaps += 1;
+            for (len, op) in cigar {
+                match op {
+                    CigarOp::Insertion => {
+                        summary.insertions += 1;
+                        summary.inserted_bases += len as u64;
+                    }
+                    CigarOp::Deletion => {
+                        summary.deletions += 1;
+                        summary.deleted_bases += len as u64;
+                    }
+                    _ => continue,
+                }
+                count_gap(&mut summary.gap_histogram, len as u64);
+            }
+        }
+
+        let mismatches = match (&cs, &cigar, record.nm()) {
+            (Some(cs), _, _) => Some(cs.counts().mismatches),
+            (None, Some(cigar), nm) => {
+                let stats = cigar.stats();
+                stats.mismatches.or_else(|| {
+                    let nm = u64::try_from(nm?).ok()?;
+                    nm.checked_sub(stats.inserted_bases + stats.deleted_bases)
+                })
+            }
+            (None, None, _) => None,
+        };
+        if let Some(mismatches) = mismatches {
+            summary.mismatches += mismatches;
+            summary.records_with_mismatches += 1;
+        }
+
+        self.identities.add(record);
+    }
+
+    /// The totals of the records added so far.
+    pub fn finish(&self) -> AlignmentSummary {
+        let mut summary = self.summary.clone();
+        summary.mean_identity = self.identities.mean();
+        summary.median_identity = self.identities.median();
+        summary
+    }
+}
+
+impl Default for AlignmentStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Formats the totals as a two column table.
+impl fmt::Display for AlignmentSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let optional = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.4}", v));
+        let rows = [
+            ("records", self.records.to_string()),
+            ("query aligned bases", self.query_aligned_bases.to_string()),
+            (
+                "target aligned bases",
+                self.target_aligned_bases.to_string(),
+            ),
+            ("matches", self.matches.to_string()),
+            (
+                "mismatches",
+                format!(
+                    "{} (from {} records)",
+                    self.mismatches, self.records_with_mismatches
+                ),
+            ),
+            ("insertions", self.insertions.to_string()),
+            ("inserted bases", self.inserted_bases.to_string()),
+            ("deletions", self.deletions.to_string()),
+            ("deleted bases", self.deleted_bases.to_string()),
+            ("records with gaps", self.records_with_gaps.to_string()),
+            ("mean identity", optional(self.mean_identity)),
+            ("median identity", optional(self.median_identity)),
+        ];
+        for (name, value) in rows {
+            writeln!(f, "{:<24}{}", name, value)?;
+        }
+        for bin in &self.gap_histogram {
+            writeln!(f, "{:<24}{}", format!("gaps {}", bin.range()), bin.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The file-level totals from [`summary`], as reported by `paftools.js
+/// stat`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    /// Number of records.
+    pub records: u64,
+    /// Number of distinct query names.
+    pub queries: u64,
+    /// Number of distinct target names.
+    pub targets: u64,
+    /// Bases of the queries within alignments, counting bases covered by
+    /// several alignments of a query once.
+    pub query_aligned_bases: u64,
+    /// Bases of the targets within alignments, counting bases covered by
+    /// several alignments on a target once.
+    pub target_aligned_bases: u64,
+    /// Mean identity of the records, residue matches over alignment block
+    /// length.
+    pub mean_identity: Option<f64>,
+    /// Median identity of the records, to four decimal places.
+    pub median_identity: Option<f64>,
+    /// Records with a tp tag of `P` or `I`, or no tp tag.
+    pub primary: u64,
+    /// Records with a tp tag of `S` or `i`.
+    pub secondary: u64,
+    /// Records with a tp tag of `I` or `i`.
+    pub inversions: u64,
+    /// Number of records with a cg tag, counted in the gap totals.
+    pub records_with_cigar: u64,
+    /// Inserted bases, from the cg tags.
+    pub inserted_bases: u64,
+    /// Deleted bases, from the cg tags, not counting introns.
+    pub deleted_bases: u64,
+}
+
+/// The disjoint intervals of one sequence covered by alignments.
+#[derive(Debug, Default)]
+struct Covered {
+    /// Intervals by start.
+    intervals: BTreeMap<u64, u64>,
+}
+
+impl Covered {
+    fn insert(&mut self, mut start: u64, mut end: u64) {
+        if start >= end {
+            return;
+        }
+        // an interval starting before this one may reach into it
+        if let Some((&before, &before_end)) = self.intervals.range(..=start).next_back() {
+            if before_end >= start {
+                start = before;
+                end = end.max(before_end);
+            }
+        }
+        let overlapping: Vec<u64> = self
+            .intervals
+            .range(start..=end)
+            .map(|(&start, _)| start)
+            .collect();
+        for key in overlapping {
+            end = end.max(self.intervals.remove(&key).unwrap());
+        }
+        self.intervals.insert(start, end);
+    }
+
+    fn bases(&self) -> u64 {
+        self.intervals.iter().map(|(start, end)| end - start).sum()
+    }
+}
+
+/// Summarize the records of a file in one pass, returning the first error.
+///
+/// Rather than the records, this holds the merged intervals aligned on each
+/// query and target, so memory grows with the number of distinct sequences
+/// and the disjoint stretches of each covered, however many records
+/// overlap them.
+///
+/// ```
+/// use paf::{stats, Reader};
+///
+/// let paf = b"q\t100\t0\t100\t+\tt\t1000\t0\t100\t90\t100\t60\ttp:A:P\n\
+///             q\t100\t0\t50\t-\tt\t1000\t50\t100\t40\t50\t0\ttp:A:S\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let summary = stats::summary(reader.records()).unwrap();
+/// assert_eq!(summary.records, 2);
+/// assert_eq!(summary.query_aligned_bases, 100);
+/// assert_eq!((summary.primary, summary.secondary), (1, 1));
+/// ```
+pub fn summary<I>(records: I) -> Result<Summary>
+where
+    I: IntoIterator<Item = Result<PafRecord>>,
+{
+    let mut summary = Summary {
+        records: 0,
+        queries: 0,
+        targets: 0,
+        query_aligned_bases: 0,
+        target_aligned_bases: 0,
+        mean_identity: None,
+        median_identity: None,
+        primary: 0,
+        secondary: 0,
+        inversions: 0,
+        records_with_cigar: 0,
+        inserted_bases: 0,
+        deleted_bases: 0,
+    };
+    let mut queries: HashMap<String, Covered> = HashMap::new();
+    let mut targets: HashMap<String, Covered> = HashMap::new();
+    let mut identities = Identities::new();
+
+    for record in records {
+        let record = record?;
+        summary.records += 1;
+        covered(&mut queries, record.query_name())
+            .insert(record.query_start() as u64, record.query_end() as u64);
+        covered(&mut targets, record.target_name())
+            .insert(record.target_start() as u64, record.target_end() as u64);
+        identities.add(&record);
+
+        match record.tp() {
+            None | Some(AlignmentType::Primary) => summary.primary += 1,
+            Some(AlignmentType::Inversion) => {
+                summary.primary += 1;
+                summary.inversions += 1;
+            }
+            Some(AlignmentType::Secondary) => summary.secondary += 1,
+            Some(AlignmentType::SecondaryInversion) => {
+                summary.secondary += 1;
+                summary.inversions += 1;
+            }
+            Some(AlignmentType::Other(_)) => {}
+        }
+
+        if let Some(Ok(cigar)) = record.cigar() {
+            summary.records_with_cigar += 1;
+            let stats = cigar.stats();
+            summary.inserted_bases += stats.inserted_bases;
+            summary.deleted_bases += stats.deleted_bases;
+        }
+    }
+
+    summary.queries = queries.len() as u64;
+    summary.targets = targets.len() as u64;
+    summary.query_aligned_bases = queries.values().map(Covered::bases).sum();
+    summary.target_aligned_bases = targets.values().map(Covered::bases).sum();
+    summary.mean_identity = identities.mean();
+    summary.median_identity = identities.median();
+    Ok(summary)
+}
+
+fn covered<'a>(sequences: &'a mut HashMap<String, Covered>, name: &str) -> &'a mut Covered {
+    if !sequences.contains_key(name) {
+        sequences.insert(name.to_string(), Covered::default());
+    }
+    sequences.get_mut(name).unwrap()
+}
+
+/// Formats the totals as lines of `Number of ...: value`, like
+/// `paftools.js stat`.
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let optional = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.4}", v));
+        writeln!(f, "Number of records: {}", self.records)?;
+        writeln!(f, "Number of mapped queries: {}", self.queries)?;
+        writeln!(f, "Number of targets: {}", self.targets)?;
+        writeln!(f, "Number of primary alignments: {}", self.primary)?;
+        writeln!(f, "Number of secondary alignments: {}", self.secondary)?;
+        writeln!(f, "Number of inversions: {}", self.inversions)?;
+        writeln!(
+            f,
+            "Number of aligned query bases: {}",
+            self.query_aligned_bases
+        )?;
+        writeln!(
+            f,
+            "Number of aligned target bases: {}",
+            self.target_aligned_bases
+        )?;
+        writeln!(f, "Mean identity: {}", optional(self.mean_identity))?;
+        writeln!(f, "Median identity: {}", optional(self.median_identity))?;
+        writeln!(
+            f,
+            "Number of inserted bases: {} (from {} records with a cg tag)",
+            self.inserted_bases, self.records_with_cigar
+        )?;
+        writeln!(
+            f,
+            "Number of deleted bases: {} (from {} records with a cg tag)",
+            self.deleted_bases, self.records_with_cigar
+        )
+    }
+}
+
+/// The lengths of many alignments, for Nx values.
+///
+/// Only the lengths are held, eight bytes per record.
+///
+/// ```
+/// use paf::stats::Lengths;
+///
+/// let lengths = Lengths::from(vec![80, 70, 50, 40, 30, 20, 10]);
+/// assert_eq!(lengths.nx(&[0.5, 0.9]), vec![(0.5, 70), (0.9, 30)]);
+/// assert_eq!(lengths.total(), 300);
+/// assert_eq!(lengths.aun(), 56.0);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lengths {
+    /// Lengths, longest first.
+    lengths: Vec<u64>,
+}
+
+impl Lengths {
+    /// The alignment block lengths of the records.
+    pub fn from_block_lens<I>(records: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<PafRecord>,
+    {
+        Self::from_iter(
+            records
+                .into_iter()
+                .map(|record| record.borrow().alignment_block_len() as u64),
+        )
+    }
+
+    /// The lengths of the records' query intervals.
+    pub fn from_query_spans<I>(records: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<PafRecord>,
+    {
+        Self::from_iter(records.into_iter().map(|record| {
+            let record = record.borrow();
+            record.query_end().saturating_sub(record.query_start()) as u64
+        }))
+    }
+
+    /// The number of lengths.
+    pub fn count(&self) -> u64 {
+        self.lengths.len() as u64
+    }
+
+    /// The sum of the lengths.
+    pub fn total(&self) -> u64 {
+        self.lengths.iter().sum()
+    }
+
+    /// The longest length, or `None` if there are none.
+    pub fn max(&self) -> Option<u64> {
+        self.lengths.first().copied()
+    }
+
+    /// The mean length, or `None` if there are none.
+    pub fn mean(&self) -> Option<f64> {
+        (!self.lengths.is_empty()).then(|| self.total() as f64 / self.count() as f64)
+    }
+
+    /// The Nx value for each fraction, as `(fraction, length)`: the length
+    /// for which lengths at least as long sum to that fraction of the total,
+    /// so 0.5 gives the N50. Lengths are zero if there are none.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a fraction is not between 0 and 1.
+    pub fn nx(&self, fractions: &[f64]) -> Vec<(f64, u64)> {
+        assert!(
+            fractions.iter().all(|f| (0.0..=1.0).contains(f)),
+            "fractions must be between 0 and 1"
+        );
+        let total = self.total() as f64;
+        fractions
+            .iter()
+            .map(|&fraction| {
+                let mut sum = 0;
+                let length = self
+                    .lengths
+                    .iter()
+                    .find(|&&length| {
+                        sum += length;
+                        sum as f64 >= fraction * total
+                    })
+                    .copied()
+                    .unwrap_or(0);
+                (fraction, length)
+            })
+            .collect()
+    }
+
+    /// The area under the Nx curve, the sum of the squared lengths over
+    /// their total, or zero if there are none.
+    pub fn aun(&self) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            return 0.0;
+        }
+        let squares: f64 = self.lengths.iter().map(|&l| l as f64 * l as f64).sum();
+        squares / total as f64
+    }
+}
+
+impl From<Vec<u64>> for Lengths {
+    fn from(mut lengths: Vec<u64>) -> Self {
+        lengths.sort_unstable_by(|a, b| b.cmp(a));
+        Lengths { lengths }
+    }
+}
+
+impl FromIterator<u64> for Lengths {
+    fn from_iter<I: IntoIterator<Item = u64>>(lengths: I) -> Self {
+        Self::from(lengths.into_iter().collect::<Vec<_>>())
+    }
+}
+
+/// The Nx values of the alignment block lengths of the records. See
+/// [`Lengths::nx`].
+pub fn length_nx<I>(records: I, fractions: &[f64]) -> Vec<(f64, u64)>
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    Lengths::from_block_lens(records).nx(fractions)
+}
+
+/// The Nx values of the lengths of the records' query intervals. See
+/// [`Lengths::nx`].
+pub fn query_span_nx<I>(records: I, fractions: &[f64]) -> Vec<(f64, u64)>
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    Lengths::from_query_spans(records).nx(fractions)
+}
+
+/// Counts of values in bins.
+///
+/// Each bin holds values from its start up to but not including its end,
+/// except the last, which also holds values equal to its end, so that an
+/// identity of 1 falls in the last bin of edges from 0 to 1. Values below
+/// the first edge are underflow and above the last overflow; NaN is not
+/// counted.
+///
+/// ```
+/// use paf::stats::Histogram;
+///
+/// let mut histogram = Histogram::linear(0.0, 30.0, 3);
+/// for value in [0.0, 5.0, 10.0, 30.0, 31.0] {
+///     histogram.add(value);
+/// }
+/// assert_eq!(
+///     histogram.bins(),
+///     vec![(0.0, 10.0, 2), (10.0, 20.0, 1), (20.0, 30.0, 1)]
+/// );
+/// assert_eq!(histogram.overflow(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    edges: Vec<f64>,
+    counts: Vec<u64>,
+    underflow: u64,
+    overflow: u64,
+}
+
+impl Histogram {
+    /// Create a histogram with bins between consecutive edges.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than two edges or they are not increasing.
+    pub fn new(edges: &[f64]) -> Self {
+        assert!(edges.len() >= 2, "a histogram needs at least two edges");
+        assert!(
+            edges.windows(2).all(|w| w[0] < w[1]),
+            "histogram edges must be increasing"
+        );
+        Histogram {
+            edges: edges.to_vec(),
+            counts: vec![0; edges.len() - 1],
+            underflow: 0,
+            overflow: 0,
+        }
+    }
+
+    /// Create a histogram of `bins` bins of equal width from `min` to `max`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bins` is zero or `max` is not above `min`.
+    pub fn linear(min: f64, max: f64, bins: usize) -> Self {
+        assert!(bins > 0, "a histogram needs at least one bin");
+        let width = (max - min) / bins as f64;
+        let mut edges: Vec<f64> = (0..bins).map(|i| min + width * i as f64).collect();
+        edges.push(max);
+        Self::new(&edges)
+    }
+
+    /// Create a histogram of `bins` bins from `min` to `max`, with edges
+    /// evenly spaced on a log scale.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bins` is zero, `min` is not positive, or `max` is not
+    /// above `min`.
+    pub fn log(min: f64, max: f64, bins: usize) -> Self {
+        assert!(bins > 0, "a histogram needs at least one bin");
+        assert!(min > 0.0, "log-spaced edges must be positive");
+        let ratio = (max / min).powf(1.0 / bins as f64);
+        let mut edges: Vec<f64> = (0..bins).map(|i| min * ratio.powi(i as i32)).collect();
+        edges.push(max);
+        Self::new(&edges)
+    }
+
+    /// Count a value.
+    pub fn add(&mut self, value: f64) {
+        let last = self.edges.len() - 1;
+        if value < self.edges[0] {
+            self.underflow += 1;
+        } else if value > self.edges[last] {
+            self.overflow += 1;
+        } else if value == self.edges[last] {
+            self.counts[last - 1] += 1;
+        } else if value >= self.edges[0] {
+            // the number of edges at or below the value
+            let i = self.edges.partition_point(|&edge| edge <= value);
+            self.counts[i - 1] += 1;
+        }
+    }
+
+    /// The bins, as `(start, end, count)`.
+    pub fn bins(&self) -> Vec<(f64, f64, u64)> {
+        self.edges
+            .windows(2)
+            .zip(&self.counts)
+            .map(|(edges, &count)| (edges[0], edges[1], count))
+            .collect()
+    }
+
+    /// The number of values below the first edge.
+    pub fn underflow(&self) -> u64 {
+        self.underflow
+    }
+
+    /// The number of values above the last edge.
+    pub fn overflow(&self) -> u64 {
+        self.overflow
+    }
+
+    /// The number of values counted, including underflow and overflow.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum::<u64>() + self.underflow + self.overflow
+    }
+}
+
+/// Formats the bins as lines of range, count and a bar, leaving out empty
+/// bins before the first value and after the last, then the underflow and
+/// overflow if there are any.
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const BAR_WIDTH: u64 = 40;
+        let bins = self.bins();
+        let first = bins.iter().position(|bin| bin.2 > 0).unwrap_or(0);
+        let last = bins.iter().rposition(|bin| bin.2 > 0).unwrap_or(0);
+        let bins = &bins[first..=last];
+        let max = bins.iter().map(|bin| bin.2).max().unwrap_or(0).max(1);
+
+        let ranges: Vec<String> = bins
+            .iter()
+            .map(|&(start, end, _)| format!("{}-{}", edge(start), edge(end)))
+            .collect();
+        let width = ranges.iter().map(String::len).max().unwrap_or(0);
+        for (range, &(_, _, count)) in ranges.iter().zip(bins) {
+            let bar = "#".repeat((count * BAR_WIDTH).div_ceil(max) as usize);
+            writeln!(
+                f,
+                "{:<width$}  {:>10}  {}",
+                range,
+                count,
+                bar,
+                width = width
+            )?;
+        }
+        if self.underflow > 0 {
+            writeln!(f, "underflow  {}", self.underflow)?;
+        }
+        if self.overflow > 0 {
+            writeln!(f, "overflow  {}", self.overflow)?;
+        }
+        Ok(())
+    }
+}
+
+/// A bin edge to at most four decimal places.
+fn edge(value: f64) -> String {
+    let s = format!("{:.4}", value);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// A histogram of the alignment block lengths of the records, in the bins
+/// of `bins`.
+///
+/// ```
+/// use paf::stats::{length_histogram, Histogram};
+///
+/// # let records: Vec<paf::PafRecord> = Vec::new();
+/// let histogram = length_histogram(&records, Histogram::log(100.0, 1e6, 20));
+/// println!("{}", histogram);
+/// ```
+pub fn length_histogram<I>(records: I, mut bins: Histogram) -> Histogram
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    for record in records {
+        bins.add(record.borrow().alignment_block_len() as f64);
+    }
+    bins
+}
+
+/// A histogram of the identities of the records, residue matches over
+/// alignment block length, in the bins of `bins`. Records with a block
+/// length of zero are left out.
+pub fn identity_histogram<I>(records: I, mut bins: Histogram) -> Histogram
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    for record in records {
+        let record = record.borrow();
+        if record.alignment_block_len() > 0 {
+            bins.add(record.residue_matches() as f64 / record.alignment_block_len() as f64);
+        }
+    }
+    bins
+}
+
+/// A histogram of the mapping qualities of the records, one bin for each
+/// from 0 to 255.
+pub fn mapq_histogram<I>(records: I) -> Histogram
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    let edges: Vec<f64> = (0..=256).map(f64::from).collect();
+    let mut histogram = Histogram::new(&edges);
+    for record in records {
+        histogram.add(record.borrow().mapping_quality() as f64);
+    }
+    histogram
+}
+
+/// The quantiles reported by [`divergence_summary`].
+const DIVERGENCE_QUANTILES: [f64; 5] = [0.05, 0.25, 0.5, 0.75, 0.95];
+
+/// Where the divergence of a record came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DivergenceSource {
+    /// The gap-compressed divergence in the de tag.
+    De,
+    /// The approximate divergence in the dv tag.
+    Dv,
+    /// One less residue matches over alignment block length.
+    BlockLen,
+}
+
+/// The divergence of a record, from the de tag, else the dv tag, else the
+/// residue matches and alignment block length. `None` if there are no tags
+/// and the block length is zero.
+pub fn divergence(record: &PafRecord) -> Option<(f64, DivergenceSource)> {
+    if let Some(de) = record.de() {
+        Some((de, DivergenceSource::De))
+    } else if let Some(dv) = record.dv() {
+        Some((dv, DivergenceSource::Dv))
+    } else if record.alignment_block_len() > 0 {
+        let block_len = record.alignment_block_len() as f64;
+        let divergence = (block_len - record.residue_matches() as f64) / block_len;
+        Some((divergence.max(0.0), DivergenceSource::BlockLen))
+    } else {
+        None
+    }
+}
+
+/// The distribution of divergence over many records, from
+/// [`divergence_summary`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DivergenceSummary {
+    /// Number of records with a divergence.
+    pub records: u64,
+    /// Number of records whose divergence came from the de tag.
+    pub from_de: u64,
+    /// Number of records whose divergence came from the dv tag.
+    pub from_dv: u64,
+    /// Number of records whose divergence came from the block length.
+    pub from_block_len: u64,
+    /// Mean divergence.
+    pub mean: Option<f64>,
+    /// Median divergence, the lower for an even count.
+    pub median: Option<f64>,
+    /// The divergence at the 5th, 25th, 50th, 75th and 95th percentiles,
+    /// as `(quantile, divergence)`, each the lowest divergence with at
+    /// least that fraction of records at or below it. Empty if there are no
+    /// records.
+    pub quantiles: Vec<(f64, f64)>,
+    /// Divergence in bins of 0.01 from 0 to 1.
+    pub histogram: Histogram,
+}
+
+/// The distribution of divergence over the records, each from the source
+/// given by [`divergence`].
+///
+/// Divergence from different sources is not comparable, de counting each
+/// gap once and dv estimated from minimizers, so the counts from each source
+/// are kept to show when the records mix them. This holds the divergence of
+/// each record, eight bytes apiece, for the quantiles.
+///
+/// ```
+/// use paf::stats::divergence_summary;
+/// use paf::Reader;
+///
+/// let paf = b"q\t100\t0\t100\t+\tt\t1000\t0\t100\t90\t100\t60\tde:f:0.02\n\
+///             q\t100\t0\t100\t+\tt\t1000\t0\t100\t90\t100\t60\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+/// let summary = divergence_summary(&records);
+/// assert_eq!((summary.from_de, summary.from_block_len), (1, 1));
+/// ```
+pub fn divergence_summary<I>(records: I) -> DivergenceSummary
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    let mut summary = DivergenceSummary {
+        records: 0,
+        from_de: 0,
+        from_dv: 0,
+        from_block_len: 0,
+        mean: None,
+        median: None,
+        quantiles: Vec::new(),
+        histogram: Histogram::linear(0.0, 1.0, 100),
+    };
+    let mut values = Vec::new();
+    for record in records {
+        let Some((value, source)) = divergence(record.borrow()) else {
+            continue;
+        };
+        match source {
+            DivergenceSource::De => summary.from_de += 1,
+            DivergenceSource::Dv => summary.from_dv += 1,
+            DivergenceSource::BlockLen => summary.from_block_len += 1,
+        }
+        summary.histogram.add(value);
+        values.push(value);
+    }
+    if values.is_empty() {
+        return summary;
+    }
+
+    values.sort_unstable_by(f64::total_cmp);
+    let quantile = |q: f64| values[((q * values.len() as f64).ceil() as usize).max(1) - 1];
+    summary.records = values.len() as u64;
+    summary.mean = Some(values.iter().sum::<f64>() / values.len() as f64);
+    summary.median = Some(quantile(0.5));
+    summary.quantiles = DIVERGENCE_QUANTILES
+        .iter()
+        .map(|&q| (q, quantile(q)))
+        .collect();
+    summary
+}
+
+/// The alignments on one target, from [`per_target`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetSummary {
+    /// Name of the target.
+    pub target_name: String,
+    /// Length of the target.
+    pub target_len: u64,
+    /// Number of alignments.
+    pub alignments: u64,
+    /// Bases of the target within alignments, counting bases covered by
+    /// several alignments once.
+    pub covered_bases: u64,
+    /// Covered bases over the length of the target.
+    pub covered_fraction: f64,
+    /// The most target bases within one alignment.
+    pub largest_alignment: u64,
+    /// Residue matches over alignment block length, summed over the
+    /// alignments, so that each is weighted by its block length.
+    pub mean_identity: Option<f64>,
+    /// Number of distinct queries aligned.
+    pub queries: u64,
+}
+
+/// The totals of one target while gathering them.
+#[derive(Debug)]
+struct TargetTotals {
+    summary: TargetSummary,
+    covered: Covered,
+    matches: u64,
+    block_len: u64,
+    queries: HashSet<String>,
+}
+
+/// Summarize the alignments on each target, in the order targets are first
+/// seen.
+///
+/// Records may be in any order. This holds the merged intervals covered on
+/// each target and the names of the queries aligned to it.
+///
+/// ```
+/// use paf::stats::per_target;
+/// use paf::Reader;
+///
+/// let paf = b"q1\t100\t0\t100\t+\tchr1\t1000\t0\t100\t90\t100\t60\n\
+///             q2\t100\t0\t100\t+\tchr1\t1000\t50\t150\t100\t100\t60\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+/// let targets = per_target(&records);
+/// assert_eq!(targets[0].covered_bases, 150);
+/// assert_eq!(targets[0].mean_identity, Some(0.95));
+/// ```
+pub fn per_target<I>(records: I) -> Vec<TargetSummary>
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    let mut targets: Vec<TargetTotals> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for record in records {
+        let record = record.borrow();
+        let i = *index
+            .entry(record.target_name().to_string())
+            .or_insert_with(|| {
+                targets.push(TargetTotals {
+                    summary: TargetSummary {
+                        target_name: record.target_name().to_string(),
+                        target_len: record.target_len() as u64,
+                        alignments: 0,
+                        covered_bases: 0,
+                        covered_fraction: 0.0,
+                        largest_alignment: 0,
+                        mean_identity: None,
+                        queries: 0,
+                    },
+                    covered: Covered::default(),
+                    matches: 0,
+                    block_len: 0,
+                    queries: HashSet::new(),
+                });
+                targets.len() - 1
+            });
+        let target = &mut targets[i];
+        let (start, end) = (record.target_start() as u64, record.target_end() as u64);
+        target.summary.alignments += 1;
+        target.summary.largest_alignment = target
+            .summary
+            .largest_alignment
+            .max(end.saturating_sub(start));
+        target.covered.insert(start, end);
+        target.matches += record.residue_matches() as u64;
+        target.block_len += record.alignment_block_len() as u64;
+        if !target.queries.contains(record.query_name()) {
+            target.queries.insert(record.query_name().to_string());
+        }
+    }
+
+    targets
+        .into_iter()
+        .map(|target| {
+            let mut summary = target.summary;
+            summary.covered_bases = target.covered.bases();
+            if summary.target_len > 0 {
+                // alignments past the end of a malformed record's target
+                // could otherwise take this over 1
+                summary.covered_fraction =
+                    (summary.covered_bases as f64 / summary.target_len as f64).min(1.0);
+            }
+            if target.block_len > 0 {
+                summary.mean_identity = Some(target.matches as f64 / target.block_len as f64);
+            }
+            summary.queries = target.queries.len() as u64;
+            summary
+        })
+        .collect()
+}
+
+/// Writes the target summaries as a tab separated table with a header row.
+/// Fractions are written to four decimal places, and a missing identity as
+/// an empty cell.
+pub fn write_target_tsv<W: Write>(writer: W, targets: &[TargetSummary]) -> Result<()> {
+    let mut writer = BufWriter::new(writer);
+    writeln!(
+        writer,
+        "target_name\ttarget_len\talignments\tcovered_bases\tcovered_fraction\t\
+         largest_alignment\tmean_identity\tqueries"
+    )?;
+    for target in targets {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{:.4}\t{}\t{}\t{}",
+            target.target_name,
+            target.target_len,
+            target.alignments,
+            target.covered_bases,
+            target.covered_fraction,
+            target.largest_alignment,
+            target
+                .mean_identity
+                .map_or(String::new(), |identity| format!("{:.4}", identity)),
+            target.queries
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// The default lower edges of the [`gap_spectrum`] bins.
+const SPECTRUM_GAP_BINS: [u64; 8] = [1, 2, 6, 11, 51, 101, 1001, 10001];
+
+/// The lengths of insertions and deletions over many records, from
+/// [`gap_spectrum`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GapSpectrum {
+    /// Insertions, binned by length.
+    pub insertions: Vec<GapBin>,
+    /// Deletions, binned by length. Introns are not counted.
+    pub deletions: Vec<GapBin>,
+    /// Number of records with a cg tag, whose gaps are counted.
+    pub records_with_cigar: u64,
+    /// Number of records without a cg tag, or with one which fails to
+    /// parse.
+    pub records_without_cigar: u64,
+}
+
+/// The lengths of the insertions and deletions in the cg tags of the
+/// records, in bins of 1, 2-5, 6-10, 11-50, 51-100, 101-1000, 1001-10000
+/// and 10001+ bases.
+///
+/// ```
+/// use paf::stats::gap_spectrum;
+/// use paf::Reader;
+///
+/// let paf = b"q\t100\t0\t100\t+\tt\t1000\t0\t103\t95\t103\t60\tcg:Z:50M3D50M\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+/// let spectrum = gap_spectrum(&records);
+/// assert_eq!(spectrum.deletions[1].count, 1);
+/// println!("{}", spectrum);
+/// ```
+pub fn gap_spectrum<I>(records: I) -> GapSpectrum
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    gap_spectrum_with_bins(records, &SPECTRUM_GAP_BINS)
+}
+
+/// Like [`gap_spectrum`], but with bins starting at each of the given
+/// lengths, the last bin taking all longer gaps.
+///
+/// Gaps shorter than the first edge are not binned.
+///
+/// # Panics
+///
+/// Panics if the edges are not increasing.
+pub fn gap_spectrum_with_bins<I>(records: I, edges: &[u64]) -> GapSpectrum
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    let mut spectrum = GapSpectrum {
+        insertions: gap_bins(edges),
+        deletions: gap_bins(edges),
+        records_with_cigar: 0,
+        records_without_cigar: 0,
+    };
+    for record in records {
+        let Some(Ok(cigar)) = record.borrow().cigar() else {
+            spectrum.records_without_cigar += 1;
+            continue;
+        };
+        spectrum.records_with_cigar += 1;
+        for (len, op) in &cigar {
+            match op {
+                CigarOp::Insertion => count_gap(&mut spectrum.insertions, len as u64),
+                CigarOp::Deletion => count_gap(&mut spectrum.deletions, len as u64),
+                _ => {}
+            }
+        }
+    }
+    spectrum
+}
+
+/// Formats the spectrum as a table of length, insertions and deletions,
+/// then the records counted.
+impl fmt::Display for GapSpectrum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<12}{:>12}{:>12}", "length", "insertions", "deletions")?;
+        for (insertions, deletions) in self.insertions.iter().zip(&self.deletions) {
+            writeln!(
+                f,
+                "{:<12}{:>12}{:>12}",
+                insertions.range(),
+                insertions.count,
+                deletions.count
+            )?;
+        }
+        writeln!(
+            f,
+            "from {} records with a cg tag, {} without",
+            self.records_with_cigar, self.records_without_cigar
+        )
+    }
+}
+
+/// The default greatest distance between the records of a [`StrandSwitch`].
+const DEFAULT_SWITCH_DISTANCE: u64 = 10_000;
+
+/// The intervals of one record beside a [`StrandSwitch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flank {
+    /// Start of the query interval.
+    pub query_start: u64,
+    /// End of the query interval.
+    pub query_end: u64,
+    /// Start of the target interval.
+    pub target_start: u64,
+    /// End of the target interval.
+    pub target_end: u64,
+    /// The relative strand.
+    pub strand: char,
+}
+
+impl Flank {
+    fn new(record: &PafRecord) -> Self {
+        Flank {
+            query_start: record.query_start() as u64,
+            query_end: record.query_end() as u64,
+            target_start: record.target_start() as u64,
+            target_end: record.target_end() as u64,
+            strand: record.strand(),
+        }
+    }
+}
+
+/// Consecutive alignments of a query along a target on different strands,
+/// a candidate inversion breakpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StrandSwitch {
+    /// Name of the query.
+    pub query_name: String,
+    /// The target position halfway between the end of the first record and
+    /// the start of the second.
+    pub position: u64,
+    /// The record before the switch on the target.
+    pub before: Flank,
+    /// The record after the switch on the target.
+    pub after: Flank,
+}
+
+/// The strands of the alignments on one target, from [`strand_summary`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StrandSummary {
+    /// Name of the target.
+    pub target_name: String,
+    /// Target bases within alignments on the forward strand, counting
+    /// bases in several alignments each time.
+    pub forward_bases: u64,
+    /// Target bases within alignments on the reverse strand, counting bases
+    /// in several alignments each time.
+    pub reverse_bases: u64,
+    /// Strand switches, in order along the target.
+    pub switches: Vec<StrandSwitch>,
+}
+
+/// The strands of the alignments on each target, in the order targets are
+/// first seen, with the strand switches within 10 kb. See
+/// [`strand_summary_with`].
+pub fn strand_summary<I>(records: I) -> Result<Vec<StrandSummary>>
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    strand_summary_with(records, DEFAULT_SWITCH_DISTANCE)
+}
+
+/// The strands of the alignments on each target, in the order targets are
+/// first seen.
+///
+/// A strand switch is two records of a query, consecutive among that
+/// query's records along the target, on different strands, and no more
+/// than `max_distance` bases apart on both the query and the target, or
+/// overlapping. The records of each target must be sorted by target start,
+/// as from `sort -k6,6 -k8,8n`, though targets may be interleaved; a record
+/// starting before the one before it on its target is an error.
+///
+/// ```
+/// use paf::stats::strand_summary_with;
+/// use paf::Reader;
+///
+/// let paf = b"q\t300\t0\t100\t+\tt\t1000\t0\t100\t100\t100\t60\n\
+///             q\t300\t100\t200\t-\tt\t1000\t100\t200\t100\t100\t60\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+/// let targets = strand_summary_with(&records, 10).unwrap();
+/// assert_eq!(targets[0].switches[0].position, 100);
+/// ```
+pub fn strand_summary_with<I>(records: I, max_distance: u64) -> Result<Vec<StrandSummary>>
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    let mut targets: Vec<StrandSummary> = Vec::new();
+    // the index of each target, and the start of its last record
+    let mut index: HashMap<String, (usize, u64)> = HashMap::new();
+    // the last record of each query on each target
+    let mut last: HashMap<(usize, String), Flank> = HashMap::new();
+
+    for record in records {
+        let record = record.borrow();
+        let flank = Flank::new(record);
+        let (i, last_start) = index
+            .entry(record.target_name().to_string())
+            .or_insert_with(|| {
+                targets.push(StrandSummary {
+                    target_name: record.target_name().to_string(),
+                    forward_bases: 0,
+                    reverse_bases: 0,
+                    switches: Vec::new(),
+                });
+                (targets.len() - 1, 0)
+            });
+        if flank.target_start < *last_start {
+            return Err(Error::new(ErrorKind::InvalidRecord(format!(
+                "strand switches need records sorted by target start, but on {} a record \
+                 starts at {} after one starting at {}",
+                record.target_name(),
+                flank.target_start,
+                last_start
+            ))));
+        }
+        *last_start = flank.target_start;
+        let target = &mut targets[*i];
+
+        let bases = flank.target_end.saturating_sub(flank.target_start);
+        match flank.strand {
+            '-' => target.reverse_bases += bases,
+            _ => target.forward_bases += bases,
+        }
+
+        let key = (*i, record.query_name().to_string());
+        if let Some(before) = last.insert(key, flank) {
+            let gap = |end: u64, start: u64| start.saturating_sub(end);
+            let query_gap = gap(before.query_end, flank.query_start)
+                .max(gap(flank.query_end, before.query_start));
+            let target_gap = gap(before.target_end, flank.target_start);
+            if before.strand != flank.strand
+                && query_gap <= max_distance
+                && target_gap <= max_distance
+            {
+                target.switches.push(StrandSwitch {
+                    query_name: record.query_name().to_string(),
+                    position: (before.target_end + flank.target_start) / 2,
+                    before,
+                    after: flank,
+                });
+            }
+        }
+    }
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+    use crate::Reader;
+
+    const FIXTURE: &str = "\
+r1\t100\t0\t22\t+\tt\t1000\t100\t123\t18\t25\t60\tNM:i:7\tcg:Z:10=1X2I5=3D1X3=
+r2\t100\t10\t40\t-\tt\t1000\t500\t520\t20\t30\t60\tNM:i:10\tcg:Z:10M10I10M
+r3\t100\t0\t12\t+\tt\t1000\t700\t712\t10\t12\t60\tcs:Z::5*ac*gt:5
+r4\t100\t0\t10\t+\tt\t1000\t800\t810\t10\t10\t60
+";
+
+    fn stats(edges: &[u64]) -> AlignmentSummary {
+        let mut stats = AlignmentStats::with_gap_bins(edges);
+        for record in Reader::from_reader(FIXTURE.as_bytes()).records() {
+            stats.add(&record.unwrap());
+        }
+        stats.finish()
+    }
+
+    #[test]
+    fn test_totals() {
+        let summary = stats(&[1, 3, 10]);
+        assert_eq!(summary.records, 4);
+        assert_eq!(summary.query_aligned_bases, 22 + 30 + 12 + 10);
+        assert_eq!(summary.target_aligned_bases, 23 + 20 + 12 + 10);
+        assert_eq!(summary.matches, 18 + 20 + 10 + 10);
+        // r1 from X, r2 from NM less its 10 inserted bases, r3 from cs
+        assert_eq!(summary.mismatches, 4);
+        assert_eq!(summary.records_with_mismatches, 3);
+        assert_eq!(summary.insertions, 2);
+        assert_eq!(summary.inserted_bases, 12);
+        assert_eq!(summary.deletions, 1);
+        assert_eq!(summary.deleted_bases, 3);
+        assert_eq!(summary.records_with_gaps, 3);
+        assert_eq!(
+            summary.gap_histogram,
+            vec![
+                GapBin {
+                    min_len: 1,
+                    max_len: Some(3),
+                    count: 1
+                },
+                GapBin {
+                    min_len: 3,
+                    max_len: Some(10),
+                    count: 1
+                },
+                GapBin {
+                    min_len: 10,
+                    max_len: None,
+                    count: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_identity() {
+        let summary = stats(&DEFAULT_GAP_BINS);
+        // 18/25, 20/30, 10/12 and 10/10
+        let identities = [0.72, 2.0 / 3.0, 10.0 / 12.0, 1.0];
+        let mean = identities.iter().sum::<f64>() / 4.0;
+        assert!((summary.mean_identity.unwrap() - mean).abs() < 1e-12);
+        assert_eq!(summary.median_identity, Some(0.72));
+    }
+
+    #[test]
+    fn test_empty() {
+        let summary = AlignmentStats::new().finish();
+        assert_eq!(summary.records, 0);
+        assert_eq!(summary.mean_identity, None);
+        assert_eq!(summary.median_identity, None);
+        assert_eq!(summary.gap_histogram.len(), DEFAULT_GAP_BINS.len());
+        assert!(summary.to_string().contains("mean identity           -"));
+    }
+
+    #[test]
+    fn test_display() {
+        let table = stats(&[1, 3, 10]).to_string();
+        assert!(table.starts_with("records                 4\n"));
+        assert!(table.contains("mismatches              4 (from 3 records)\n"));
+        assert!(table.contains("gaps 3-9                1\n"));
+        assert!(table.ends_with("gaps 10+                1\n"));
+    }
+
+    #[test]
+    fn test_summary_golden() {
+        let paf = "\
+a\t100\t0\t100\t+\tt1\t1000\t0\t100\t95\t100\t60\ttp:A:P\tcg:Z:100M
+a\t100\t90\t100\t+\tt2\t500\t0\t10\t10\t10\t5\ttp:A:S
+b\t200\t0\t120\t+\tt1\t1000\t50\t175\t110\t125\t60\ttp:A:P\tcg:Z:60M5D60M
+b\t200\t100\t200\t-\tt1\t1000\t400\t498\t90\t100\t60\ttp:A:I\tcg:Z:50M2I48M
+c\t50\t0\t50\t+\tt2\t500\t100\t150\t50\t50\t60\ttp:A:P
+c\t50\t10\t40\t-\tt2\t500\t120\t150\t25\t30\t0\ttp:A:i
+d\t80\t0\t80\t+\tt3\t300\t0\t77\t72\t80\t60\tcg:Z:40M3I37M
+e\t60\t0\t60\t+\tt3\t300\t70\t130\t54\t60\t60\ttp:A:P
+e\t60\t0\t30\t+\tt3\t300\t200\t230\t30\t30\t0\ttp:A:S
+f\t40\t0\t40\t+\tt1\t1000\t90\t130\t40\t40\t60\ttp:A:P\tcg:Z:40M
+";
+        let summary = summary(Reader::from_reader(paf.as_bytes()).records()).unwrap();
+        // queries merge to a 0..100, b 0..200, c 0..50, d, e and f whole;
+        // targets to t1 0..175 and 400..498, t2 0..10 and 100..150, and t3
+        // 0..130 and 200..230
+        assert_eq!(
+            summary.to_string(),
+            "\
+Number of records: 10
+Number of mapped queries: 6
+Number of targets: 3
+Number of primary alignments: 7
+Number of secondary alignments: 3
+Number of inversions: 2
+Number of aligned query bases: 530
+Number of aligned target bases: 493
+Mean identity: 0.9363
+Median identity: 0.9000
+Number of inserted bases: 5 (from 5 records with a cg tag)
+Number of deleted bases: 5 (from 5 records with a cg tag)
+"
+        );
+    }
+
+    #[test]
+    fn test_covered() {
+        let mut covered = Covered::default();
+        for (start, end) in [(10, 20), (30, 40), (15, 32), (50, 60), (60, 70), (0, 5)] {
+            covered.insert(start, end);
+        }
+        assert_eq!(
+            covered.intervals.into_iter().collect::<Vec<_>>(),
+            vec![(0, 5), (10, 40), (50, 70)]
+        );
+    }
+
+    #[test]
+    fn test_summary_error() {
+        let paf = "a\t100\t0\t100\t+\tt\t1000\t0\t100\t95\t100\t60\nb\tx\n";
+        assert!(summary(Reader::from_reader(paf.as_bytes()).records()).is_err());
+    }
+
+    #[test]
+    fn test_nx() {
+        // total 300: 80 + 70 = 150 reaches half, and 270 of 300 needs down
+        // to 30
+        let lengths = Lengths::from(vec![10, 30, 80, 20, 50, 70, 40]);
+        assert_eq!(
+            lengths.nx(&[0.0, 0.5, 0.9, 1.0]),
+            vec![(0.0, 80), (0.5, 70), (0.9, 30), (1.0, 10)]
+        );
+        assert_eq!(lengths.count(), 7);
+        assert_eq!(lengths.max(), Some(80));
+        assert_eq!(lengths.mean(), Some(300.0 / 7.0));
+        // (6400 + 4900 + 2500 + 1600 + 900 + 400 + 100) / 300
+        assert_eq!(lengths.aun(), 56.0);
+    }
+
+    #[test]
+    fn test_nx_degenerate() {
+        let paf = "q\t100\t10\t60\t+\tt\t1000\t0\t55\t50\t55\t60\n";
+        let records = records(paf);
+        assert_eq!(length_nx(&records, &[0.5, 0.9]), vec![(0.5, 55), (0.9, 55)]);
+        assert_eq!(query_span_nx(&records, &[0.5]), vec![(0.5, 50)]);
+        assert_eq!(Lengths::from_block_lens(&records).aun(), 55.0);
+
+        let empty = Lengths::default();
+        assert_eq!(empty.nx(&[0.5]), vec![(0.5, 0)]);
+        assert_eq!((empty.max(), empty.mean(), empty.aun()), (None, None, 0.0));
+    }
+
+    #[test]
+    fn test_histogram_edges() {
+        let mut histogram = Histogram::new(&[1.0, 2.0, 4.0, 8.0]);
+        for value in [0.5, 1.0, 1.999, 2.0, 4.0, 7.0, 8.0, 8.5, f64::NAN] {
+            histogram.add(value);
+        }
+        // each edge starts a bin, except the last, which ends one
+        assert_eq!(
+            histogram.bins(),
+            vec![(1.0, 2.0, 2), (2.0, 4.0, 1), (4.0, 8.0, 3)]
+        );
+        assert_eq!(histogram.underflow(), 1);
+        assert_eq!(histogram.overflow(), 1);
+        assert_eq!(histogram.total(), 8);
+    }
+
+    #[test]
+    fn test_histogram_spacing() {
+        let edges = |histogram: Histogram| -> Vec<f64> {
+            let bins = histogram.bins();
+            let mut edges: Vec<f64> = bins.iter().map(|bin| bin.0).collect();
+            edges.push(bins.last().unwrap().1);
+            edges
+        };
+        assert_eq!(
+            edges(Histogram::linear(0.0, 1.0, 4)),
+            vec![0.0, 0.25, 0.5, 0.75, 1.0]
+        );
+        let log = edges(Histogram::log(1.0, 1000.0, 3));
+        assert_eq!(log.len(), 4);
+        for (edge, expected) in log.iter().zip([1.0, 10.0, 100.0, 1000.0]) {
+            assert!((edge - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_record_histograms() {
+        let records = records(FIXTURE);
+        // block lengths 25, 30, 12 and 10
+        let lengths = length_histogram(&records, Histogram::new(&[10.0, 20.0, 30.0]));
+        assert_eq!(lengths.bins(), vec![(10.0, 20.0, 2), (20.0, 30.0, 2)]);
+        // identities 0.72, 0.67, 0.83 and 1
+        let identities = identity_histogram(&records, Histogram::linear(0.0, 1.0, 10));
+        let counts: Vec<u64> = identities.bins().iter().map(|bin| bin.2).collect();
+        assert_eq!(counts, vec![0, 0, 0, 0, 0, 0, 1, 1, 1, 1]);
+
+        let mapq = mapq_histogram(&records);
+        assert_eq!(mapq.bins().len(), 256);
+        assert_eq!(mapq.bins()[60], (60.0, 61.0, 4));
+        assert_eq!(mapq.overflow(), 0);
+    }
+
+    #[test]
+    fn test_histogram_display() {
+        let mut histogram = Histogram::linear(0.0, 1.0, 4);
+        for value in [0.3, 0.6, 0.7, 1.0, 1.5] {
+            histogram.add(value);
+        }
+        assert_eq!(
+            histogram.to_string(),
+            "\
+0.25-0.5           1  ####################
+0.5-0.75           2  ########################################
+0.75-1             1  ####################
+overflow  1
+"
+        );
+    }
+
+    #[test]
+    fn test_divergence_sources() {
+        let paf = "\
+both\t100\t0\t100\t+\tt\t1000\t0\t100\t90\t100\t60\tdv:f:0.05\tde:f:0.04
+dv\t100\t0\t100\t+\tt\t1000\t0\t100\t90\t100\t60\tdv:f:0.01
+none\t100\t0\t100\t+\tt\t1000\t0\t100\t80\t100\t60
+de\t100\t0\t100\t+\tt\t1000\t0\t100\t90\t100\t60\tde:f:0.03
+empty\t100\t0\t0\t+\tt\t1000\t0\t0\t0\t0\t60
+";
+        let records = records(paf);
+        let sources: Vec<_> = records.iter().map(divergence).collect();
+        assert_eq!(
+            sources,
+            vec![
+                Some((0.04, DivergenceSource::De)),
+                Some((0.01, DivergenceSource::Dv)),
+                Some((0.2, DivergenceSource::BlockLen)),
+                Some((0.03, DivergenceSource::De)),
+                None,
+            ]
+        );
+
+        let summary = divergence_summary(&records);
+        assert_eq!(summary.records, 4);
+        assert_eq!(
+            (summary.from_de, summary.from_dv, summary.from_block_len),
+            (2, 1, 1)
+        );
+        assert!((summary.mean.unwrap() - 0.07).abs() < 1e-9);
+        // sorted 0.01, 0.03, 0.04, 0.2
+        assert_eq!(summary.median, Some(0.03));
+        assert_eq!(
+            summary.quantiles,
+            vec![
+                (0.05, 0.01),
+                (0.25, 0.01),
+                (0.5, 0.03),
+                (0.75, 0.04),
+                (0.95, 0.2)
+            ]
+        );
+        assert_eq!(summary.histogram.total(), 4);
+        assert_eq!(summary.histogram.bins()[20].2, 1);
+    }
+
+    #[test]
+    fn test_divergence_empty() {
+        let summary = divergence_summary(Vec::<PafRecord>::new());
+        assert_eq!(summary.records, 0);
+        assert_eq!((summary.mean, summary.median), (None, None));
+        assert!(summary.quantiles.is_empty());
+    }
+
+    #[test]
+    fn test_per_target() {
+        // chr1 is covered end to end by overlapping alignments, and chr2
+        // only in part
+        let paf = "\
+a\t500\t0\t400\t+\tchr1\t600\t0\t400\t380\t400\t60
+b\t300\t0\t300\t+\tchr2\t1000\t100\t400\t270\t300\t60
+b\t300\t0\t300\t-\tchr1\t600\t300\t600\t300\t300\t60
+a\t500\t400\t500\t+\tchr1\t600\t350\t450\t80\t100\t60
+c\t200\t0\t100\t+\tchr2\t1000\t350\t450\t100\t100\t0
+";
+        let records = records(paf);
+        let targets = per_target(&records);
+        assert_eq!(
+            targets,
+            vec![
+                TargetSummary {
+                    target_name: "chr1".to_string(),
+                    target_len: 600,
+                    alignments: 3,
+                    covered_bases: 600,
+                    covered_fraction: 1.0,
+                    largest_alignment: 400,
+                    mean_identity: Some(760.0 / 800.0),
+                    queries: 2,
+                },
+                TargetSummary {
+                    target_name: "chr2".to_string(),
+                    target_len: 1000,
+                    alignments: 2,
+                    covered_bases: 350,
+                    covered_fraction: 0.35,
+                    largest_alignment: 300,
+                    mean_identity: Some(370.0 / 400.0),
+                    queries: 2,
+                },
+            ]
+        );
+
+        let mut tsv = Vec::new();
+        write_target_tsv(&mut tsv, &targets).unwrap();
+        assert_eq!(
+            String::from_utf8(tsv).unwrap(),
+            "target_name\ttarget_len\talignments\tcovered_bases\tcovered_fraction\t\
+             largest_alignment\tmean_identity\tqueries\n\
+             chr1\t600\t3\t600\t1.0000\t400\t0.9500\t2\n\
+             chr2\t1000\t2\t350\t0.3500\t300\t0.9250\t2\n"
+        );
+    }
+
+    #[test]
+    fn test_gap_spectrum() {
+        // a gap of each length at the edges of the bins, and introns which
+        // are left out
+        let paf = "\
+a\t100\t0\t100\t+\tt\t100000\t0\t100\t100\t100\t60\tcg:Z:10M1I10M2I10M5I10M6I10M10I10M11I10M
+b\t100\t0\t100\t+\tt\t100000\t0\t100\t100\t100\t60\tcg:Z:10M50D10M51D10M100D10M101D10M1000N10M
+c\t100\t0\t100\t+\tt\t100000\t0\t100\t100\t100\t60\tcg:Z:10M1000D10M1001D10M10000I10M10001D10M
+d\t100\t0\t100\t+\tt\t100000\t0\t100\t100\t100\t60
+e\t100\t0\t100\t+\tt\t100000\t0\t100\t100\t100\t60\tcg:Z:10Q
+";
+        let records = records(paf);
+        let spectrum = gap_spectrum(&records);
+        let counts = |bins: &[GapBin]| bins.iter().map(|bin| bin.count).collect::<Vec<_>>();
+        assert_eq!(counts(&spectrum.insertions), vec![1, 2, 2, 1, 0, 0, 1, 0]);
+        assert_eq!(counts(&spectrum.deletions), vec![0, 0, 0, 1, 2, 2, 1, 1]);
+        assert_eq!(spectrum.records_with_cigar, 3);
+        assert_eq!(spectrum.records_without_cigar, 2);
+        assert_eq!(
+            spectrum.to_string(),
+            "\
+length        insertions   deletions
+1                      1           0
+2-5                    2           0
+6-10                   2           0
+11-50                  1           1
+51-100                 0           2
+101-1000               0           2
+1001-10000             1           1
+10001+                 0           1
+from 3 records with a cg tag, 2 without
+"
+        );
+
+        let spectrum = gap_spectrum_with_bins(&records, &[5, 100]);
+        assert_eq!(counts(&spectrum.insertions), vec![4, 1]);
+        assert_eq!(counts(&spectrum.deletions), vec![2, 5]);
+    }
+
+    #[test]
+    fn test_strand_switches() {
+        // q has an inverted segment between two forward blocks; r has a
+        // reverse block too far along the query from its forward one, and
+        // s one too far along the target
+        let paf = "\
+q\t3000\t0\t1000\t+\tchr1\t100000\t10000\t11000\t1000\t1000\t60
+r\t9000\t0\t1000\t+\tchr1\t100000\t10500\t11500\t1000\t1000\t60
+q\t3000\t1000\t2000\t-\tchr1\t100000\t11010\t12010\t1000\t1000\t60
+r\t9000\t5000\t6000\t-\tchr1\t100000\t11600\t12600\t1000\t1000\t60
+q\t3000\t2000\t3000\t+\tchr1\t100000\t12000\t13000\t1000\t1000\t60
+s\t2000\t0\t1000\t+\tchr2\t50000\t0\t1000\t1000\t1000\t60
+s\t2000\t1000\t2000\t-\tchr2\t50000\t5000\t6000\t1000\t1000\t60
+";
+        let records = records(paf);
+        let flank = |q: (u64, u64), t: (u64, u64), strand| Flank {
+            query_start: q.0,
+            query_end: q.1,
+            target_start: t.0,
+            target_end: t.1,
+            strand,
+        };
+        let targets = strand_summary_with(&records, 1000).unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].target_name, "chr1");
+        assert_eq!(targets[0].forward_bases, 3000);
+        assert_eq!(targets[0].reverse_bases, 2000);
+        assert_eq!(
+            targets[0].switches,
+            vec![
+                StrandSwitch {
+                    query_name: "q".to_string(),
+                    position: 11005,
+                    before: flank((0, 1000), (10000, 11000), '+'),
+                    after: flank((1000, 2000), (11010, 12010), '-'),
+                },
+                StrandSwitch {
+                    query_name: "q".to_string(),
+                    position: 12005,
+                    before: flank((1000, 2000), (11010, 12010), '-'),
+                    after: flank((2000, 3000), (12000, 13000), '+'),
+                },
+            ]
+        );
+        assert!(targets[1].switches.is_empty());
+
+        // with a greater distance r and s switch too
+        let targets = strand_summary(&records).unwrap();
+        assert_eq!(targets[0].switches.len(), 3);
+        assert_eq!(targets[1].switches.len(), 1);
+    }
+
+    #[test]
+    fn test_strand_summary_unsorted() {
+        let paf = "\
+q\t3000\t0\t1000\t+\tchr1\t100000\t10000\t11000\t1000\t1000\t60
+q\t3000\t0\t1000\t+\tchr2\t100000\t0\t1000\t1000\t1000\t60
+q\t3000\t1000\t2000\t-\tchr1\t100000\t9000\t10000\t1000\t1000\t60
+";
+        let records = records(paf);
+        assert!(strand_summary(&records).is_err());
+        assert!(strand_summary(&records[..2]).is_ok());
+    }
+}
@@ -0,0 +1,676 @@
+/*!
+Find structural variant candidates in the alignments of each query.
+
+```
+use paf::sv::{find_inversions, InversionOptions};
+use paf::Reader;
+
+let paf = b"q\t30000\t0\t10000\t+\tt\t50000\t0\t10000\t10000\t10000\t60\n\
+            q\t30000\t10000\t12000\t-\tt\t50000\t10000\t12000\t2000\t2000\t60\n\
+            q\t30000\t12000\t30000\t+\tt\t50000\t12000\t30000\t18000\t18000\t60\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+let inversions = find_inversions(&records, &InversionOptions::default());
+assert_eq!(inversions.len(), 1);
+assert_eq!((inversions[0].target_start, inversions[0].target_end), (10000, 12000));
+assert_eq!(inversions[0].records, [1]);
+```
+*/
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+
+use crate::{filter, AlignmentType, Error, ErrorKind, PafRecord, Result};
+
+/// What [`find_inversions`] takes to call an inversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InversionOptions {
+    /// The least target span of an alignment flanking an inversion.
+    pub min_flank_len: u64,
+    /// The least target span of an inversion.
+    pub min_inversion_len: u64,
+}
+
+impl Default for InversionOptions {
+    /// Flanks of at least 10 kb and inversions of at least 1 kb.
+    fn default() -> Self {
+        InversionOptions {
+            min_flank_len: 10_000,
+            min_inversion_len: 1000,
+        }
+    }
+}
+
+/// A part of a query aligned to a target on the opposite strand to the
+/// alignments either side of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inversion {
+    /// The query name.
+    pub query_name: String,
+    /// The target name.
+    pub target_name: String,
+    /// The start of the inverted records on the target.
+    pub target_start: u64,
+    /// The end of the inverted records on the target.
+    pub target_end: u64,
+    /// The start of the inverted records on the query.
+    pub query_start: u64,
+    /// The end of the inverted records on the query.
+    pub query_end: u64,
+    /// The span of the inversion on the target.
+    pub size: u64,
+    /// 0 for an inversion relative to its flanks, 1 for one nested in such
+    /// an inversion, inverted back, and so on.
+    pub depth: u32,
+    /// Whether minimap2 marked any of the records as an inversion, with
+    /// tp:A:I or tp:A:i.
+    pub direct: bool,
+    /// The indices among the records given of the flanking alignments
+    /// before and after the inversion on the target, or `None` for an
+    /// inversion found only from the tp tag.
+    pub flanks: Option<(usize, usize)>,
+    /// The indices among the records given of the records in the
+    /// inversion, in order along the target.
+    pub records: Vec<usize>,
+}
+
+/// An alignment of one query to one target.
+#[derive(Debug, Clone, Copy)]
+struct Aln {
+    record: usize,
+    target_start: u64,
+    target_end: u64,
+    query_start: u64,
+    query_end: u64,
+    strand: char,
+    direct: bool,
+}
+
+impl Aln {
+    fn len(&self) -> u64 {
+        self.target_end.saturating_sub(self.target_start)
+    }
+}
+
+/// Finds inversions in the alignments of each query to each target.
+///
+/// Along the target, a run of records on the opposite strand to the
+/// flanking records either side, each with a target span of at least
+/// `min_flank_len`, is an inversion. The flanks are on the strand with the
+/// most aligned bases, and inside an inversion a run back on that strand
+/// is a nested inversion, flanked by the inverted records. So a nested
+/// inversion is only seen when it is shorter than `min_flank_len`, as
+/// otherwise it flanks two inversions. Records minimap2 marked as
+/// inversions, with tp:A:I or tp:A:i, are reported even without flanks.
+///
+/// Inversions shorter on the target than `min_inversion_len` are left
+/// out, and secondary alignments other than inversions are passed over.
+/// The inversions are in order of target name and start, then depth and
+/// query name.
+pub fn find_inversions<I>(records: I, options: &InversionOptions) -> Vec<Inversion>
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    let mut groups: HashMap<(String, String), Vec<Aln>> = HashMap::new();
+    let mut direct = Vec::new();
+    for (i, record) in records.into_iter().enumerate() {
+        let record = record.borrow();
+        let tp = record.tp();
+        if tp == Some(AlignmentType::Secondary) {
+            continue;
+        }
+        let aln = Aln {
+            record: i,
+            target_start: record.target_start() as u64,
+            target_end: record.target_end() as u64,
+            query_start: record.query_start() as u64,
+            query_end: record.query_end() as u64,
+            strand: record.strand(),
+            direct: matches!(
+                tp,
+                Some(AlignmentType::Inversion | AlignmentType::SecondaryInversion)
+            ),
+        };
+        let key = (
+            record.query_name().to_string(),
+            record.target_name().to_string(),
+        );
+        if tp == Some(AlignmentType::SecondaryInversion) {
+            direct.push((key, aln));
+            continue;
+        }
+        if aln.direct {
+            direct.push((key.clone(), aln));
+        }
+        groups.entry(key).or_default().push(aln);
+    }
+
+    let mut inversions = Vec::new();
+    for ((query_name, target_name), mut alns) in groups {
+        alns.sort_unstable_by_key(|aln| (aln.target_start, aln.target_end, aln.record));
+        let mut bases = [0, 0];
+        for aln in &alns {
+            bases[(aln.strand == '-') as usize] += aln.len();
+        }
+        let strand = if bases[1] > bases[0] { '-' } else { '+' };
+        let mut runs = Vec::new();
+        scan(&alns, strand, 0, options, &mut runs);
+        for (depth, flanks, run) in runs {
+            inversions.push(inversion(
+                &query_name,
+                &target_name,
+                run,
+                depth,
+                Some(flanks),
+            ));
+        }
+    }
+
+    // inversions minimap2 found which are not already in one found here
+    for ((query_name, target_name), aln) in direct {
+        let found = inversions.iter().any(|inversion| {
+            inversion.query_name == query_name
+                && inversion.target_name == target_name
+                && inversion.records.contains(&aln.record)
+        });
+        if !found && aln.len() >= options.min_inversion_len {
+            inversions.push(inversion(&query_name, &target_name, &[aln], 0, None));
+        }
+    }
+
+    inversions.sort_by(|a, b| {
+        (&a.target_name, a.target_start, a.depth, &a.query_name).cmp(&(
+            &b.target_name,
+            b.target_start,
+            b.depth,
+            &b.query_name,
+        ))
+    });
+    inversions
+}
+
+/// Finds the runs of records between flanks on `strand`, and those nested
+/// in them, as their depth, the records of the flanks and the run.
+fn scan<'a>(
+    alns: &'a [Aln],
+    strand: char,
+    depth: u32,
+    options: &InversionOptions,
+    runs: &mut Vec<(u32, (usize, usize), &'a [Aln])>,
+) {
+    let flanks: Vec<usize> = (0..alns.len())
+        .filter(|&i| alns[i].strand == strand && alns[i].len() >= options.min_flank_len)
+        .collect();
+    for pair in flanks.windows(2) {
+        let inner = &alns[pair[0] + 1..pair[1]];
+        let Some(first) = inner.iter().position(|aln| aln.strand != strand) else {
+            continue;
+        };
+        let last = inner.iter().rposition(|aln| aln.strand != strand).unwrap();
+        let run = &inner[first..=last];
+        let start = run.iter().map(|aln| aln.target_start).min().unwrap();
+        let end = run.iter().map(|aln| aln.target_end).max().unwrap();
+        if end.saturating_sub(start) < options.min_inversion_len {
+            continue;
+        }
+        runs.push((depth, (alns[pair[0]].record, alns[pair[1]].record), run));
+        let inverted = if strand == '-' { '+' } else { '-' };
+        scan(run, inverted, depth + 1, options, runs);
+    }
+}
+
+fn inversion(
+    query_name: &str,
+    target_name: &str,
+    run: &[Aln],
+    depth: u32,
+    flanks: Option<(usize, usize)>,
+) -> Inversion {
+    let target_start = run.iter().map(|aln| aln.target_start).min().unwrap();
+    let target_end = run.iter().map(|aln| aln.target_end).max().unwrap();
+    Inversion {
+        query_name: query_name.to_string(),
+        target_name: target_name.to_string(),
+        target_start,
+        target_end,
+        query_start: run.iter().map(|aln| aln.query_start).min().unwrap(),
+        query_end: run.iter().map(|aln| aln.query_end).max().unwrap(),
+        size: target_end.saturating_sub(target_start),
+        depth,
+        direct: run.iter().any(|aln| aln.direct),
+        flanks,
+        records: run.iter().map(|aln| aln.record).collect(),
+    }
+}
+
+/// What happens to a query at a [`Breakpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakpointKind {
+    /// The query moves to another target.
+    Translocation,
+    /// The query jumps along the same target, further than an indel.
+    LargeGap,
+    /// The query changes strand on the same target.
+    Inversion,
+}
+
+/// What [`breakpoints`] takes to call a breakpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakpointOptions {
+    /// The least query span of an alignment either side of a breakpoint.
+    pub min_flank_len: u64,
+    /// The largest difference between the gaps on the target and on the
+    /// query taken as an indel rather than a breakpoint.
+    pub max_indel_len: u64,
+}
+
+impl Default for BreakpointOptions {
+    /// Flanks of at least 1 kb, and indels of up to 10 kb.
+    fn default() -> Self {
+        BreakpointOptions {
+            min_flank_len: 1000,
+            max_indel_len: 10_000,
+        }
+    }
+}
+
+/// A junction between consecutive alignments of a query, from the end of
+/// the left alignment on the query to the start of the right one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// What happens at the junction.
+    pub kind: BreakpointKind,
+    /// The query name.
+    pub query_name: String,
+    /// The end of the left alignment on the query.
+    pub left_query_end: u64,
+    /// The start of the right alignment on the query.
+    pub right_query_start: u64,
+    /// The target of the left alignment.
+    pub left_target_name: String,
+    /// Where the left alignment ends along the query on its target: its
+    /// target end on the plus strand, and its target start on the minus.
+    pub left_target_pos: u64,
+    /// The strand of the left alignment.
+    pub left_strand: char,
+    /// The target of the right alignment.
+    pub right_target_name: String,
+    /// Where the right alignment starts along the query on its target: its
+    /// target start on the plus strand, and its target end on the minus.
+    pub right_target_pos: u64,
+    /// The strand of the right alignment.
+    pub right_strand: char,
+    /// The bases of the query between the alignments, negative if they
+    /// overlap.
+    pub query_gap: i64,
+    /// The distance from the left position on the target to the right one,
+    /// in the direction of the left alignment, or `None` when the targets
+    /// differ.
+    pub target_gap: Option<i64>,
+    /// The indices among the records given of the left and right
+    /// alignments.
+    pub records: (usize, usize),
+}
+
+/// The breakpoints between the consecutive primary alignments of one
+/// query, in order along the query.
+///
+/// Alignments spanning less than `min_flank_len` of the query are passed
+/// over, as are secondary alignments. Neighbours on the same target and
+/// strand whose gaps on the query and target differ by no more than
+/// `max_indel_len` are an indel, and give no breakpoint. It is an error for
+/// the records to be of more than one query; for input grouped by query,
+/// as from minimap2, pass each group of
+/// [`select::group_by_query`](crate::select::group_by_query) in turn.
+///
+/// ```
+/// use paf::select::group_by_query;
+/// use paf::sv::{breakpoints, BreakpointKind, BreakpointOptions};
+/// use paf::Reader;
+///
+/// let paf = b"q\t10000\t0\t5000\t+\tchr1\t100000\t0\t5000\t5000\t5000\t60\n\
+///             q\t10000\t5000\t10000\t+\tchr2\t100000\t0\t5000\t5000\t5000\t60\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// for group in group_by_query(reader.records()) {
+///     let found = breakpoints(group.unwrap(), &BreakpointOptions::default()).unwrap();
+///     assert_eq!(found[0].kind, BreakpointKind::Translocation);
+/// }
+/// ```
+pub fn breakpoints<I>(
+    records_of_one_query: I,
+    options: &BreakpointOptions,
+) -> Result<Vec<Breakpoint>>
+where
+    I: IntoIterator,
+    I::Item: Borrow<PafRecord>,
+{
+    let records: Vec<I::Item> = records_of_one_query.into_iter().collect();
+    let records: Vec<&PafRecord> = records.iter().map(Borrow::borrow).collect();
+    if let Some(first) = records.first() {
+        if let Some(other) = records
+            .iter()
+            .find(|record| record.query_name() != first.query_name())
+        {
+            return Err(Error::new(ErrorKind::InvalidRecord(format!(
+                "breakpoints need records of one query, but found {} and {}",
+                first.query_name(),
+                other.query_name()
+            ))));
+        }
+    }
+
+    let primary = filter::primary_only();
+    let mut flanks: Vec<(usize, &PafRecord)> = records
+        .into_iter()
+        .enumerate()
+        .filter(|(_, record)| {
+            primary(record)
+                && record.query_end().saturating_sub(record.query_start()) as u64
+                    >= options.min_flank_len
+        })
+        .collect();
+    flanks.sort_by_key(|(i, record)| (record.query_start(), record.query_end(), *i));
+
+    Ok(flanks
+        .windows(2)
+        .filter_map(|pair| breakpoint(pair[0], pair[1], options))
+        .collect())
+}
+
+/// The breakpoint between `left` and `right`, if they are not an indel
+/// apart.
+fn breakpoint(
+    (left_index, left): (usize, &PafRecord),
+    (right_index, right): (usize, &PafRecord),
+    options: &BreakpointOptions,
+) -> Option<Breakpoint> {
+    let left_pos = match left.strand() {
+        '-' => left.target_start(),
+        _ => left.target_end(),
+    } as i64;
+    let right_pos = match right.strand() {
+        '-' => right.target_end(),
+        _ => right.target_start(),
+    } as i64;
+    let query_gap = right.query_start() as i64 - left.query_end() as i64;
+    let target_gap = (left.target_name() == right.target_name()).then(|| match left.strand() {
+        '-' => left_pos - right_pos,
+        _ => right_pos - left_pos,
+    });
+    let kind = match target_gap {
+        None => BreakpointKind::Translocation,
+        Some(_) if left.strand() != right.strand() => BreakpointKind::Inversion,
+        Some(gap) if gap.abs_diff(query_gap) > options.max_indel_len => BreakpointKind::LargeGap,
+        Some(_) => return None,
+    };
+    Some(Breakpoint {
+        kind,
+        query_name: left.query_name().to_string(),
+        left_query_end: left.query_end() as u64,
+        right_query_start: right.query_start() as u64,
+        left_target_name: left.target_name().to_string(),
+        left_target_pos: left_pos as u64,
+        left_strand: left.strand(),
+        right_target_name: right.target_name().to_string(),
+        right_target_pos: right_pos as u64,
+        right_strand: right.strand(),
+        query_gap,
+        target_gap,
+        records: (left_index, right_index),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::records;
+
+    /// Records of query `q` on target `t`, as query start and end, strand,
+    /// target start and end, and tp tag.
+    fn inversions(alignments: &[(u32, u32, char, u32, u32, char)]) -> Vec<Inversion> {
+        let paf: String = alignments
+            .iter()
+            .map(|&(qs, qe, strand, ts, te, tp)| {
+                format!(
+                    "q\t50000\t{}\t{}\t{}\tt\t50000\t{}\t{}\t{}\t{}\t60\ttp:A:{}\n",
+                    qs,
+                    qe,
+                    strand,
+                    ts,
+                    te,
+                    te - ts,
+                    te - ts,
+                    tp
+                )
+            })
+            .collect();
+        let options = InversionOptions {
+            min_flank_len: 5000,
+            min_inversion_len: 1000,
+        };
+        find_inversions(records(&paf), &options)
+    }
+
+    fn spans(inversions: &[Inversion]) -> Vec<(u64, u64, u64, u64, u32, Vec<usize>)> {
+        inversions
+            .iter()
+            .map(|inv| {
+                (
+                    inv.target_start,
+                    inv.target_end,
+                    inv.query_start,
+                    inv.query_end,
+                    inv.depth,
+                    inv.records.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_inversion() {
+        let found = inversions(&[
+            (0, 10000, '+', 0, 10000, 'P'),
+            (10000, 12000, '-', 10000, 12000, 'P'),
+            (12000, 22000, '+', 12000, 22000, 'P'),
+            // a secondary alignment is passed over
+            (10000, 12000, '+', 30000, 32000, 'S'),
+        ]);
+        assert_eq!(spans(&found), [(10000, 12000, 10000, 12000, 0, vec![1])]);
+        assert_eq!(found[0].size, 2000);
+        assert_eq!(found[0].flanks, Some((0, 2)));
+        assert!(!found[0].direct);
+
+        // the same marked by minimap2
+        let found = inversions(&[
+            (0, 10000, '+', 0, 10000, 'P'),
+            (10000, 12000, '-', 10000, 12000, 'I'),
+            (12000, 22000, '+', 12000, 22000, 'P'),
+        ]);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].direct);
+
+        // too short
+        assert!(inversions(&[
+            (0, 10000, '+', 0, 10000, 'P'),
+            (10000, 10500, '-', 10000, 10500, 'P'),
+            (10500, 22000, '+', 10500, 22000, 'P'),
+        ])
+        .is_empty());
+    }
+
+    #[test]
+    fn test_flanks_required() {
+        // a flip at the end of the query has no flank after it
+        assert!(inversions(&[
+            (0, 10000, '+', 0, 10000, 'P'),
+            (10000, 12000, '-', 10000, 12000, 'P'),
+        ])
+        .is_empty());
+        // and one with a short flank before it
+        assert!(inversions(&[
+            (0, 1000, '+', 9000, 10000, 'P'),
+            (1000, 3000, '-', 10000, 12000, 'P'),
+            (3000, 13000, '+', 12000, 22000, 'P'),
+        ])
+        .is_empty());
+        // unless minimap2 marked it
+        let found = inversions(&[
+            (0, 10000, '+', 0, 10000, 'P'),
+            (10000, 12000, '-', 10000, 12000, 'i'),
+        ]);
+        assert_eq!(spans(&found), [(10000, 12000, 10000, 12000, 0, vec![1])]);
+        assert_eq!(found[0].flanks, None);
+        assert!(found[0].direct);
+    }
+
+    #[test]
+    fn test_reversed_target() {
+        // a target interval ending before it starts spans nothing, so it
+        // neither flanks nor is an inversion
+        let paf = "\
+q\t50000\t0\t10000\t+\tt\t50000\t0\t10000\t10000\t10000\t60
+q\t50000\t10000\t12000\t-\tt\t50000\t12000\t10000\t2000\t2000\t60\ttp:A:I
+q\t50000\t12000\t22000\t+\tt\t50000\t22000\t12000\t10000\t10000\t60
+";
+        assert!(find_inversions(records(paf), &InversionOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_nested() {
+        // 10000..32000 of the target is inverted, with 20000..22000 inside
+        // it inverted back; the query runs back through the inversion
+        let found = inversions(&[
+            (0, 10000, '+', 0, 10000, 'P'),
+            (22000, 32000, '-', 10000, 20000, 'P'),
+            (20000, 22000, '+', 20000, 22000, 'P'),
+            (10000, 20000, '-', 22000, 32000, 'P'),
+            (32000, 42000, '+', 32000, 42000, 'P'),
+        ]);
+        assert_eq!(
+            spans(&found),
+            [
+                (10000, 32000, 10000, 32000, 0, vec![1, 2, 3]),
+                (20000, 22000, 20000, 22000, 1, vec![2]),
+            ]
+        );
+        assert_eq!(found[0].flanks, Some((0, 4)));
+        assert_eq!(found[1].flanks, Some((1, 3)));
+
+        // on the whole reversed, the flanks are on the minus strand
+        let found = inversions(&[
+            (40000, 50000, '-', 0, 10000, 'P'),
+            (38000, 40000, '+', 10000, 12000, 'P'),
+            (28000, 38000, '-', 12000, 22000, 'P'),
+        ]);
+        assert_eq!(spans(&found), [(10000, 12000, 38000, 40000, 0, vec![1])]);
+    }
+
+    fn breakpoints_of(paf: &str) -> Result<Vec<Breakpoint>> {
+        let records = records(paf);
+        breakpoints(&records, &BreakpointOptions::default())
+    }
+
+    fn kinds(paf: &str) -> Vec<(BreakpointKind, i64, Option<i64>)> {
+        breakpoints_of(paf)
+            .unwrap()
+            .iter()
+            .map(|bp| (bp.kind, bp.query_gap, bp.target_gap))
+            .collect()
+    }
+
+    #[test]
+    fn test_translocation() {
+        // given out of query order, with a short alignment between which
+        // is passed over
+        let paf = "\
+q\t10000\t5100\t10000\t+\tchr2\t100000\t2000\t6900\t4900\t4900\t60
+q\t10000\t4900\t5100\t+\tchr3\t100000\t0\t200\t200\t200\t60
+q\t10000\t0\t5000\t+\tchr1\t100000\t0\t5000\t5000\t5000\t60
+";
+        let found = breakpoints_of(paf).unwrap();
+        assert_eq!(found.len(), 1);
+        let bp = &found[0];
+        assert_eq!(bp.kind, BreakpointKind::Translocation);
+        assert_eq!((bp.left_query_end, bp.right_query_start), (5000, 5100));
+        assert_eq!(
+            (bp.left_target_name.as_str(), bp.left_target_pos),
+            ("chr1", 5000)
+        );
+        assert_eq!(
+            (bp.right_target_name.as_str(), bp.right_target_pos),
+            ("chr2", 2000)
+        );
+        assert_eq!((bp.query_gap, bp.target_gap), (100, None));
+        assert_eq!(bp.records, (2, 0));
+    }
+
+    #[test]
+    fn test_reversed_coordinates() {
+        // a query interval ending before it starts is too short to flank
+        let paf = "\
+q\t10000\t0\t5000\t+\tchr1\t100000\t0\t5000\t5000\t5000\t60
+q\t10000\t9000\t5100\t+\tchr2\t100000\t2000\t6900\t4900\t4900\t60
+";
+        assert!(breakpoints_of(paf).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_large_gap_and_indel() {
+        // 45 kb apart on the target, then 500 bases
+        let paf = "\
+q\t15000\t0\t5000\t+\tchr1\t100000\t0\t5000\t5000\t5000\t60
+q\t15000\t5000\t10000\t+\tchr1\t100000\t50000\t55000\t5000\t5000\t60
+q\t15000\t10000\t15000\t+\tchr1\t100000\t55500\t60500\t5000\t5000\t60
+";
+        assert_eq!(kinds(paf), [(BreakpointKind::LargeGap, 0, Some(45000))]);
+
+        // on the minus strand, a 2 kb deletion is an indel, and a jump back
+        // along the target a breakpoint
+        let paf = "\
+q\t15000\t0\t5000\t-\tchr1\t100000\t60000\t65000\t5000\t5000\t60
+q\t15000\t5000\t10000\t-\tchr1\t100000\t53000\t58000\t5000\t5000\t60
+q\t15000\t10000\t15000\t-\tchr1\t100000\t70000\t75000\t5000\t5000\t60
+";
+        assert_eq!(kinds(paf), [(BreakpointKind::LargeGap, 0, Some(-22000))]);
+    }
+
+    #[test]
+    fn test_inversion_breakpoints() {
+        let paf = "\
+q\t15000\t0\t5000\t+\tchr1\t100000\t0\t5000\t5000\t5000\t60
+q\t15000\t5000\t10000\t-\tchr1\t100000\t5000\t10000\t5000\t5000\t60\ttp:A:I
+q\t15000\t10000\t15000\t+\tchr1\t100000\t10000\t15000\t5000\t5000\t60
+q\t15000\t5000\t10000\t+\tchr2\t100000\t0\t5000\t5000\t5000\t0\ttp:A:S
+";
+        let found = breakpoints_of(paf).unwrap();
+        assert_eq!(
+            found
+                .iter()
+                .map(|bp| (
+                    bp.kind,
+                    bp.left_target_pos,
+                    bp.right_target_pos,
+                    bp.target_gap
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (BreakpointKind::Inversion, 5000, 10000, Some(5000)),
+                // the left alignment runs back along the target
+                (BreakpointKind::Inversion, 5000, 10000, Some(-5000)),
+            ]
+        );
+        assert_eq!(found[1].records, (1, 2));
+    }
+
+    #[test]
+    fn test_one_query() {
+        assert!(breakpoints_of("").unwrap().is_empty());
+        let paf = "\
+a\t10000\t0\t5000\t+\tchr1\t100000\t0\t5000\t5000\t5000\t60
+b\t10000\t5000\t10000\t+\tchr2\t100000\t0\t5000\t5000\t5000\t60
+";
+        assert!(breakpoints_of(paf).is_err());
+    }
+}
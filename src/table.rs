@@ -0,0 +1,237 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::{PafRecord, Result, Tag};
+
+/// The names of the twelve mandatory columns, as in the header row.
+const COLUMNS: [&str; 12] = [
+    "query_name",
+    "query_len",
+    "query_start",
+    "query_end",
+    "strand",
+    "target_name",
+    "target_len",
+    "target_start",
+    "target_end",
+    "residue_matches",
+    "alignment_block_len",
+    "mapping_quality",
+];
+
+/// Writes records as a table with a header row, for loading into data frame
+/// libraries.
+///
+/// There is a column for each mandatory field, then one for each of a
+/// given list of tags. Tag values are written bare, e.g. `5` for `NM:i:5`,
+/// and a tag missing from a record leaves its cell empty. Cells holding the
+/// delimiter, a quote or a line break are quoted, doubling any quotes.
+///
+/// ```
+/// use paf::{Reader, TableWriter};
+///
+/// let paf = b"q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\tNM:i:0\n";
+/// let mut reader = Reader::from_reader(&paf[..]);
+/// let mut writer = TableWriter::csv(Vec::new(), &["NM", "de"]);
+/// for record in reader.records() {
+///     writer.write_record(&record.unwrap()).unwrap();
+/// }
+/// let table = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+/// assert!(table.ends_with("\nq,100,0,10,+,t,100,0,10,10,10,60,0,\n"));
+/// ```
+#[derive(Debug)]
+pub struct TableWriter<W: Write> {
+    writer: BufWriter<W>,
+    delimiter: u8,
+    tags: Vec<String>,
+    header_written: bool,
+}
+
+impl TableWriter<File> {
+    /// Creates a table writer to a file path, tab separated unless the path
+    /// ends in `.csv`.
+    pub fn from_path<P: AsRef<Path>>(path: P, tags: &[&str]) -> Result<TableWriter<File>> {
+        let path = path.as_ref();
+        let delimiter = if path.extension().is_some_and(|ext| ext == "csv") {
+            b','
+        } else {
+            b'\t'
+        };
+        Ok(TableWriter::new(File::create(path)?, delimiter, tags))
+    }
+}
+
+impl<W: Write> TableWriter<W> {
+    /// Creates a table writer with any single byte delimiter.
+    pub fn new(writer: W, delimiter: u8, tags: &[&str]) -> Self {
+        TableWriter {
+            writer: BufWriter::new(writer),
+            delimiter,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            header_written: false,
+        }
+    }
+
+    /// Creates a tab separated table writer.
+    pub fn tsv(writer: W, tags: &[&str]) -> Self {
+        TableWriter::new(writer, b'\t', tags)
+    }
+
+    /// Creates a comma separated table writer.
+    pub fn csv(writer: W, tags: &[&str]) -> Self {
+        TableWriter::new(writer, b',', tags)
+    }
+
+    /// Writes the header row, if it has not been already.
+    fn write_header(&mut self) -> io::Result<()> {
+        if self.header_written {
+            return Ok(());
+        }
+        self.header_written = true;
+        let header: Vec<String> = COLUMNS
+            .iter()
+            .map(|column| column.to_string())
+            .chain(self.tags.iter().cloned())
+            .collect();
+        self.write_row(&header)
+    }
+
+    fn write_row(&mut self, cells: &[String]) -> io::Result<()> {
+        for (i, cell) in cells.iter().enumerate() {
+            if i > 0 {
+                self.writer.write_all(&[self.delimiter])?;
+            }
+            self.write_cell(cell)?;
+        }
+        self.writer.write_all(b"\n")
+    }
+
+    fn write_cell(&mut self, cell: &str) -> io::Result<()> {
+        let delimiter = self.delimiter as char;
+        if !cell.contains([delimiter, '"', '\n', '\r']) {
+            return self.writer.write_all(cell.as_bytes());
+        }
+        write!(self.writer, "\"{}\"", cell.replace('"', "\"\""))
+    }
+
+    /// Writes a record as a row, after the header row if this is the first.
+    pub fn write_record(&mut self, record: &PafRecord) -> Result<()> {
+        self.write_header()?;
+        let mut cells = vec![
+            record.query_name().to_string(),
+            record.query_len().to_string(),
+            record.query_start().to_string(),
+            record.query_end().to_string(),
+            record.strand().to_string(),
+            record.target_name().to_string(),
+            record.target_len().to_string(),
+            record.target_start().to_string(),
+            record.target_end().to_string(),
+            record.residue_matches().to_string(),
+            record.alignment_block_len().to_string(),
+            record.mapping_quality().to_string(),
+        ];
+        for tag in &self.tags {
+            cells.push(
+                record
+                    .optional_fields()
+                    .get(tag)
+                    .map(bare_value)
+                    .unwrap_or_default(),
+            );
+        }
+        self.write_row(&cells).map_err(Into::into)
+    }
+
+    /// Writes out the buffer, and the header row if no records were
+    /// written, and flushes the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.write_header()?;
+        self.writer.flush().map_err(Into::into)
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn into_inner(mut self) -> Result<W> {
+        self.flush()?;
+        self.writer
+            .into_inner()
+            .map_err(|err| err.into_error().into())
+    }
+}
+
+/// The value of a tag without its name and type.
+fn bare_value(tag: &Tag) -> String {
+    match tag {
+        Tag::tp(value) => value.as_char().to_string(),
+        Tag::ts(value) => value.as_char().to_string(),
+        tag => tag
+            .value()
+            .map_or_else(String::new, |value| value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Reader, ReaderBuilder};
+
+    const FIXTURE: &str = "\
+r1\t100\t0\t22\t+\tt1\t1000\t100\t123\t18\t25\t60\ttp:A:P\tNM:i:7\tde:f:0.1818\tcg:Z:10=1X2I5=3D1X3=
+r2\t100\t10\t40\t-\tt1\t1000\t500\t520\t20\t30\t0\ttp:A:S\tNM:i:10
+r3,x\t100\t0\t12\t+\tt\"2\"\t1000\t700\t712\t10\t12\t60\tzz:Z:a\tde:f:0
+";
+
+    fn table(tags: &[&str], delimiter: u8) -> String {
+        let mut reader = ReaderBuilder::new()
+            .keep_unknown_tags(true)
+            .from_reader(FIXTURE.as_bytes());
+        let mut writer = TableWriter::new(Vec::new(), delimiter, tags);
+        for record in reader.records() {
+            writer.write_record(&record.unwrap()).unwrap();
+        }
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_tsv() {
+        let expected = "\
+query_name\tquery_len\tquery_start\tquery_end\tstrand\ttarget_name\ttarget_len\ttarget_start\ttarget_end\tresidue_matches\talignment_block_len\tmapping_quality\ttp\tNM\tde\tzz
+r1\t100\t0\t22\t+\tt1\t1000\t100\t123\t18\t25\t60\tP\t7\t0.1818\t
+r2\t100\t10\t40\t-\tt1\t1000\t500\t520\t20\t30\t0\tS\t10\t\t
+r3,x\t100\t0\t12\t+\t\"t\"\"2\"\"\"\t1000\t700\t712\t10\t12\t60\t\t\t0\ta
+";
+        assert_eq!(table(&["tp", "NM", "de", "zz"], b'\t'), expected);
+    }
+
+    #[test]
+    fn test_csv() {
+        let expected = "\
+query_name,query_len,query_start,query_end,strand,target_name,target_len,target_start,target_end,residue_matches,alignment_block_len,mapping_quality,NM
+r1,100,0,22,+,t1,1000,100,123,18,25,60,7
+r2,100,10,40,-,t1,1000,500,520,20,30,0,10
+\"r3,x\",100,0,12,+,\"t\"\"2\"\"\",1000,700,712,10,12,60,
+";
+        assert_eq!(table(&["NM"], b','), expected);
+    }
+
+    #[test]
+    fn test_header_only() {
+        let writer = TableWriter::tsv(Vec::new(), &[]);
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(output, format!("{}\n", COLUMNS.join("\t")));
+    }
+
+    #[test]
+    fn test_fixture_cigar_column() {
+        let mut reader = Reader::from_reader(crate::reader::tests::PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let mut writer = TableWriter::tsv(Vec::new(), &["cg"]);
+        writer.write_record(&record).unwrap();
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        let row = output.lines().nth(1).unwrap();
+        let paf = std::str::from_utf8(crate::reader::tests::PAF_RECORD_1).unwrap();
+        let cg = paf.split("cg:Z:").nth(1).unwrap();
+        assert!(row.ends_with(&format!("	{}", cg)));
+    }
+}
@@ -0,0 +1,94 @@
+//! Names of the known tags, and marker types for typed access to them.
+//!
+//! Looking a tag up by a string literal (`optional.get("cg")`) silently
+//! returns `None` on a typo. The constants here catch typos at compile time,
+//! and the marker types also fix the Rust type of the value:
+//!
+//! ```
+//! use paf::{tags, Reader};
+//!
+//! let line = "q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\tNM:i:0\n";
+//! let mut reader = Reader::from_reader(line.as_bytes());
+//! let record = reader.read_record().unwrap().unwrap();
+//! let nm: Option<i64> = record.get::<tags::Nm>();
+//! assert_eq!(nm, Some(0));
+//! ```
+
+use crate::{AlignmentType, Tag, TranscriptStrand};
+
+/// A known tag, with the Rust type of its value.
+pub trait TypedTag {
+    /// The two letter name of the tag.
+    const NAME: &'static str;
+    /// The type of the tag's value.
+    type Value<'a>;
+    /// Get the value out of a tag, or `None` if it is a different tag or
+    /// holds a value of the wrong type.
+    fn extract(tag: &Tag) -> Option<Self::Value<'_>>;
+}
+
+macro_rules! typed_tags {
+    ($($(#[$doc:meta])* $marker:ident, $name:ident, $code:literal, $value:ty, $variant:ident($v:ident) => $get:expr;)*) => {
+        $(
+            $(#[$doc])*
+            pub const $name: &str = $code;
+        )*
+
+        $(
+            // an empty enum, so the marker only lives in the type namespace
+            // and doesn't clash with the constant of the same name
+            $(#[$doc])*
+            #[derive(Debug, Clone, Copy)]
+            pub enum $marker {}
+
+            impl TypedTag for $marker {
+                const NAME: &'static str = $name;
+                type Value<'a> = $value;
+
+                fn extract(tag: &Tag) -> Option<Self::Value<'_>> {
+                    match tag {
+                        Tag::$variant($v) => $get,
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+typed_tags! {
+    /// Type of aln: P/primary, S/secondary and I,i/inversion.
+    Tp, TP, "tp", AlignmentType, tp(v) => Some(*v);
+    /// Number of minimizers on the chain.
+    Cm, CM, "cm", i64, cm(v) => v.get_int().copied();
+    /// Chaining score.
+    S1, S1, "s1", i64, s1(v) => v.get_int().copied();
+    /// Chaining score of the best secondary chain.
+    S2, S2, "s2", i64, s2(v) => v.get_int().copied();
+    /// Total number of mismatches and gaps in the alignment.
+    Nm, NM, "NM", i64, NM(v) => v.get_int().copied();
+    /// To generate the ref sequence in the alignment.
+    Md, MD, "MD", &'a str, MD(v) => v.get_string().map(|s| s.as_str());
+    /// DP alignment score.
+    As, AS, "AS", i64, AS(v) => v.get_int().copied();
+    /// List of other supplementary alignments.
+    Sa, SA, "SA", &'a str, SA(v) => v.get_string().map(|s| s.as_str());
+    /// DP score of the max scoring segment in the alignment.
+    Ms, MS, "ms", i64, ms(v) => v.get_int().copied();
+    /// Number of ambiguous bases in the alignment.
+    Nn, NN, "nn", i64, nn(v) => v.get_int().copied();
+    /// Transcript strand (splice mode only).
+    Ts, TS, "ts", TranscriptStrand, ts(v) => Some(*v);
+    /// CIGAR string.
+    Cg, CG, "cg", &'a str, cg(v) => v.get_string().map(|s| s.as_str());
+    /// Difference string.
+    Cs, CS, "cs", &'a str, cs(v) => v.get_string().map(|s| s.as_str());
+    /// Approximate per-base sequence divergence.
+    Dv, DV, "dv", f64, dv(v) => v.get_float().copied();
+    /// Gap-compressed per-base sequence divergence.
+    De, DE, "de", f64, de(v) => v.get_float().copied();
+    /// Length of query regions harboring repetitive seeds.
+    Rl, RL, "rl", i64, rl(v) => v.get_int().copied();
+    /// ZD?
+    Zd, ZD, "zd", i64, zd(v) => v.get_int().copied();
+}
@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+
+use crate::{PafRecord, Reader};
+
+/// The records of PAF text, which must all read.
+pub(crate) fn records(paf: &str) -> Vec<PafRecord> {
+    Reader::from_reader(paf.as_bytes())
+        .records()
+        .map(Result::unwrap)
+        .collect()
+}
+
+/// The record of a line of PAF, with or without its line ending, which
+/// must read.
+pub(crate) fn record(line: &str) -> PafRecord {
+    Reader::from_reader(line.as_bytes())
+        .read_record()
+        .unwrap()
+        .unwrap()
+}
+
+/// A record of query `q` against target `t`, as length, start and end of
+/// each, matching over the whole query interval.
+pub(crate) fn hit(query: (u32, u32, u32), strand: char, target: (u32, u32, u32)) -> PafRecord {
+    let matches = query.2.saturating_sub(query.1);
+    PafRecord::new(
+        "q".to_owned(),
+        query.0,
+        query.1,
+        query.2,
+        strand,
+        "t".to_owned(),
+        target.0,
+        target.1,
+        target.2,
+        matches,
+        matches,
+        60,
+        HashMap::new(),
+    )
+}
@@ -0,0 +1,727 @@
+/*!
+Check a whole PAF file, reporting every problem rather than the first.
+
+```
+use paf::validate::{validate_file, Category, ValidationOptions};
+use paf::Reader;
+
+let paf = b"q\t100\t0\t100\t+\tt\t1000\t0\t100\t95\t100\t60\n\
+            q\t200\t0\t100\t+\tt\t1000\t0\t100\t95\t100\t60\n";
+let mut reader = Reader::from_reader(&paf[..]);
+let report = validate_file(&mut reader, &ValidationOptions::default()).unwrap();
+assert_eq!(report.count(Category::InconsistentLength), 1);
+assert_eq!(report.issues[0].line, 2);
+```
+
+[`audit_file`] checks the fields recomputed from the cs and cg tags, as
+[`PafRecord::audit`] does for one record, across a whole file.
+*/
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+use crate::{AuditField, Discrepancy, Error, ErrorKind, PafRecord, Reader, Result};
+
+/// How serious an [`Issue`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    /// The record is valid, but may not be what was meant.
+    Warning,
+    /// The record is invalid.
+    Error,
+}
+
+/// The check an [`Issue`] failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Category {
+    /// The line could not be parsed as a record.
+    Parse,
+    /// The mandatory fields disagree, as with coordinates outside the
+    /// sequence. See [`PafRecord::validate`].
+    Invariant,
+    /// A known tag holds a value of the wrong type.
+    TagType,
+    /// The cg tag does not parse or disagrees with the coordinates. See
+    /// [`PafRecord::check_cigar`].
+    Cigar,
+    /// A dv or de divergence lies outside [0, 1].
+    Divergence,
+    /// The record's mandatory fields match an earlier record's.
+    Duplicate,
+    /// A query or target has a different length than in an earlier record,
+    /// as when files aligned to different assemblies are mixed.
+    InconsistentLength,
+}
+
+impl Category {
+    /// Every category, in the order reports list them.
+    const ALL: [Category; 7] = [
+        Category::Parse,
+        Category::Invariant,
+        Category::TagType,
+        Category::Cigar,
+        Category::Divergence,
+        Category::Duplicate,
+        Category::InconsistentLength,
+    ];
+
+    fn severity(self) -> Severity {
+        match self {
+            Category::Duplicate => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Category::Parse => "parse",
+            Category::Invariant => "invariant",
+            Category::TagType => "tag type",
+            Category::Cigar => "cigar",
+            Category::Divergence => "divergence",
+            Category::Duplicate => "duplicate",
+            Category::InconsistentLength => "inconsistent length",
+        })
+    }
+}
+
+/// A problem found on one line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// The line of the file, counting from 1.
+    pub line: u64,
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// The check which failed.
+    pub category: Category,
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(
+            f,
+            "line {}: {} ({}): {}",
+            self.line, severity, self.category, self.message
+        )
+    }
+}
+
+/// Which checks [`validate_file`] runs, and how many issues it keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationOptions {
+    /// The most issues kept in the report. Later issues are still counted.
+    pub max_issues: usize,
+    /// Report records repeating an earlier record. This holds 16 bytes for
+    /// each distinct record.
+    pub duplicates: bool,
+    /// Report queries and targets with lengths which differ between
+    /// records. This holds the name of each distinct sequence.
+    pub lengths: bool,
+}
+
+impl Default for ValidationOptions {
+    /// Keep up to 1000 issues, and run every check.
+    fn default() -> Self {
+        ValidationOptions {
+            max_issues: 1000,
+            duplicates: true,
+            lengths: true,
+        }
+    }
+}
+
+/// The problems found by [`validate_file`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ValidationReport {
+    /// Number of lines parsed as records, valid or not.
+    pub records: u64,
+    /// The issues, in the order found, up to the limit set in the options.
+    pub issues: Vec<Issue>,
+    /// Number of issues found in each category, including any past the
+    /// limit.
+    counts: HashMap<Category, u64>,
+}
+
+impl ValidationReport {
+    /// The number of issues found in a category, including any past the
+    /// limit.
+    pub fn count(&self, category: Category) -> u64 {
+        self.counts.get(&category).copied().unwrap_or(0)
+    }
+
+    /// The number of issues found, including any past the limit.
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Whether issues were found past the limit and left out.
+    pub fn truncated(&self) -> bool {
+        self.total() > self.issues.len() as u64
+    }
+
+    /// Whether no errors were found, though there may be warnings.
+    pub fn is_valid(&self) -> bool {
+        Category::ALL
+            .iter()
+            .all(|&category| category.severity() == Severity::Warning || self.count(category) == 0)
+    }
+
+    fn push(&mut self, max_issues: usize, line: u64, category: Category, message: String) {
+        *self.counts.entry(category).or_insert(0) += 1;
+        if self.issues.len() < max_issues {
+            self.issues.push(Issue {
+                line,
+                severity: category.severity(),
+                category,
+                message,
+            });
+        }
+    }
+}
+
+/// Formats the issues one to a line, then the counts of each category.
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "{}", issue)?;
+        }
+        if self.truncated() {
+            writeln!(
+                f,
+                "... {} more issues not shown",
+                self.total() - self.issues.len() as u64
+            )?;
+        }
+        writeln!(f, "{} records, {} issues", self.records, self.total())?;
+        for category in Category::ALL {
+            let count = self.count(category);
+            if count > 0 {
+                writeln!(f, "{:<24}{}", category.to_string(), count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A length seen for a sequence, and the line it was first seen on.
+type SeenLength = (u32, u64);
+
+/// Read the rest of a file, checking each record and the records against
+/// each other.
+///
+/// Every check of [`PafRecord::validate`] is run, each known tag is checked
+/// for the type of its value, and the records are checked for duplicates and
+/// sequence lengths which change between records. A line which fails to
+/// parse is reported and reading carries on with the next; the reader
+/// rejects known tags with values of the wrong type, so these are reported
+/// as such rather than as failing to parse. Warnings the
+/// reader records are taken and dropped, as the checks here cover them.
+///
+/// Returns an error only if the file cannot be read.
+pub fn validate_file<R: io::Read>(
+    reader: &mut Reader<R>,
+    options: &ValidationOptions,
+) -> Result<ValidationReport> {
+    let mut report = ValidationReport::default();
+    let mut seen: HashMap<u64, u64> = HashMap::new();
+    let mut query_lens: HashMap<String, SeenLength> = HashMap::new();
+    let mut target_lens: HashMap<String, SeenLength> = HashMap::new();
+
+    loop {
+        let record = match reader.read_record() {
+            Ok(Some(record)) => record,
+            Ok(None) => break,
+            Err(err) => {
+                if let ErrorKind::Io(_) = err.kind() {
+                    return Err(err);
+                }
+                let message = message(err);
+                // known tags with values of the wrong type fail to parse
+                let category = if message.starts_with("Invalid type for PAF tag") {
+                    Category::TagType
+                } else {
+                    Category::Parse
+                };
+                report.push(options.max_issues, reader.line(), category, message);
+                continue;
+            }
+        };
+        reader.take_warnings();
+        let line = reader.line();
+        report.records += 1;
+        let mut push = |category, message| report.push(options.max_issues, line, category, message);
+
+        if let Err(err) = record.check_fields() {
+            push(Category::Invariant, message(err));
+        }
+        for err in tag_type_errors(&record) {
+            push(Category::TagType, message(err));
+        }
+        if let Err(err) = record.check_cigar() {
+            push(Category::Cigar, message(err));
+        }
+        if let Err(err) = record.check_divergence() {
+            push(Category::Divergence, message(err));
+        }
+
+        if options.duplicates {
+            if let Some(&first) = seen.get(&record.alignment_key()) {
+                push(
+                    Category::Duplicate,
+                    format!("repeats the record on line {}", first),
+                );
+            } else {
+                seen.insert(record.alignment_key(), line);
+            }
+        }
+        if options.lengths {
+            let sequences = [
+                (
+                    "query",
+                    &mut query_lens,
+                    record.query_name(),
+                    record.query_len(),
+                ),
+                (
+                    "target",
+                    &mut target_lens,
+                    record.target_name(),
+                    record.target_len(),
+                ),
+            ];
+            for (what, lens, name, len) in sequences {
+                match lens.get(name) {
+                    Some(&(first_len, first_line)) if first_len != len => push(
+                        Category::InconsistentLength,
+                        format!(
+                            "{} {} has length {}, but {} on line {}",
+                            what, name, len, first_len, first_line
+                        ),
+                    ),
+                    Some(_) => {}
+                    None => {
+                        lens.insert(name.to_string(), (len, line));
+                    }
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// How far fields may disagree with the tags before [`audit_file`] counts
+/// them, and how many of the worst records it keeps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AuditOptions {
+    /// The most residue_matches, alignment_block_len or NM may differ from
+    /// the count recomputed from the tags.
+    pub count_tolerance: u64,
+    /// The most de may differ from the divergence recomputed from the tags,
+    /// beyond the rounding minimap2 does.
+    pub de_tolerance: f64,
+    /// The most offenders kept for each field.
+    pub worst: usize,
+}
+
+impl Default for AuditOptions {
+    /// No disagreement beyond rounding, keeping the worst 10 records of each
+    /// field.
+    fn default() -> Self {
+        AuditOptions {
+            count_tolerance: 0,
+            de_tolerance: 0.0,
+            worst: 10,
+        }
+    }
+}
+
+/// A record whose field disagrees with its tags, found by [`audit_file`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Offender {
+    /// The line of the file, counting from 1.
+    pub line: u64,
+    /// The field and the two values.
+    pub discrepancy: Discrepancy,
+}
+
+impl Offender {
+    /// The observed value less the expected.
+    pub fn delta(&self) -> f64 {
+        match self.discrepancy {
+            Discrepancy::ResidueMatches { expected, observed }
+            | Discrepancy::AlignmentBlockLen { expected, observed }
+            | Discrepancy::Nm { expected, observed } => observed as f64 - expected as f64,
+            Discrepancy::De { expected, observed } => observed - expected,
+        }
+    }
+}
+
+impl fmt::Display for Offender {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.discrepancy)
+    }
+}
+
+/// The totals found by [`audit_file`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AuditSummary {
+    /// Number of lines parsed as records.
+    pub records: u64,
+    /// Number of lines which failed to parse, and were passed over.
+    pub unparsed: u64,
+    /// Number of records with a cs or cg tag which failed to parse.
+    pub tag_errors: u64,
+    /// The records disagreeing most with their tags, by field in the order
+    /// of [`AuditSummary::FIELDS`] and then the largest difference first,
+    /// up to the limit set in the options for each field.
+    pub offenders: Vec<Offender>,
+    /// Number of records checked and disagreeing, for each field.
+    counts: HashMap<AuditField, (u64, u64)>,
+}
+
+impl AuditSummary {
+    /// Every field audited, in the order summaries list them.
+    pub const FIELDS: [AuditField; 4] = [
+        AuditField::ResidueMatches,
+        AuditField::AlignmentBlockLen,
+        AuditField::Nm,
+        AuditField::De,
+    ];
+
+    /// The number of records whose tags allowed a field to be checked.
+    pub fn checked(&self, field: AuditField) -> u64 {
+        self.counts.get(&field).map_or(0, |&(checked, _)| checked)
+    }
+
+    /// The number of records whose field disagrees with the tags beyond the
+    /// tolerance.
+    pub fn disagreeing(&self, field: AuditField) -> u64 {
+        self.counts.get(&field).map_or(0, |&(_, bad)| bad)
+    }
+
+    /// The offenders for a field, the largest difference first.
+    pub fn worst(&self, field: AuditField) -> impl Iterator<Item = &Offender> {
+        self.offenders
+            .iter()
+            .filter(move |o| o.discrepancy.field() == field)
+    }
+
+    /// Whether every checked field agrees and every tag parsed.
+    pub fn is_consistent(&self) -> bool {
+        self.tag_errors == 0
+            && Self::FIELDS
+                .iter()
+                .all(|&field| self.disagreeing(field) == 0)
+    }
+
+    /// Keeps `offender` if it is among the `worst` largest differences of
+    /// its field, so far.
+    fn offend(&mut self, worst: usize, offender: Offender) {
+        let field = offender.discrepancy.field();
+        let rank = |o: &Offender| {
+            let position = Self::FIELDS
+                .iter()
+                .position(|&f| f == o.discrepancy.field());
+            (position, o.delta().abs())
+        };
+        let (position, size) = rank(&offender);
+        let at = self.offenders.partition_point(|o| {
+            let (p, s) = rank(o);
+            // earlier lines come first among equal differences
+            p.cmp(&position)
+                .then(size.partial_cmp(&s).unwrap_or(Ordering::Equal))
+                != Ordering::Greater
+        });
+        self.offenders.insert(at, offender);
+        let kept = self.worst(field).count();
+        if kept > worst {
+            let last = self
+                .offenders
+                .iter()
+                .rposition(|o| o.discrepancy.field() == field)
+                .unwrap();
+            self.offenders.remove(last);
+        }
+    }
+}
+
+/// Formats the counts of each field, then the worst records.
+impl fmt::Display for AuditSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} records, {} unparsed, {} with invalid tags",
+            self.records, self.unparsed, self.tag_errors
+        )?;
+        for field in Self::FIELDS {
+            writeln!(
+                f,
+                "{:<24}{} checked, {} disagreeing",
+                field.to_string(),
+                self.checked(field),
+                self.disagreeing(field)
+            )?;
+        }
+        for offender in &self.offenders {
+            writeln!(f, "{}", offender)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read the rest of a file, checking each record's residue_matches,
+/// alignment_block_len, NM and de against those recomputed from its cs or
+/// cg tag, as [`PafRecord::audit`].
+///
+/// A field counts as disagreeing when it differs by more than the
+/// tolerance in `options`, and the records differing most are kept with
+/// their line numbers. This is a single pass holding no more than the
+/// offenders kept, so it suits files of any size. Lines which fail to
+/// parse are counted and passed over; [`validate_file`] reports them.
+///
+/// Returns an error only if the file cannot be read.
+pub fn audit_file<R: io::Read>(
+    reader: &mut Reader<R>,
+    options: &AuditOptions,
+) -> Result<AuditSummary> {
+    let mut summary = AuditSummary::default();
+    loop {
+        let record = match reader.read_record() {
+            Ok(Some(record)) => record,
+            Ok(None) => break,
+            Err(err) => {
+                if let ErrorKind::Io(_) = err.kind() {
+                    return Err(err);
+                }
+                summary.unparsed += 1;
+                continue;
+            }
+        };
+        reader.take_warnings();
+        summary.records += 1;
+
+        let report = record.audit();
+        if !report.tag_errors.is_empty() {
+            summary.tag_errors += 1;
+        }
+        for field in report.checked {
+            summary.counts.entry(field).or_insert((0, 0)).0 += 1;
+        }
+        for discrepancy in report.discrepancies {
+            let offender = Offender {
+                line: reader.line(),
+                discrepancy,
+            };
+            let beyond = match discrepancy {
+                Discrepancy::De { .. } => offender.delta().abs() > options.de_tolerance,
+                _ => offender.delta().abs() > options.count_tolerance as f64,
+            };
+            if beyond {
+                summary
+                    .counts
+                    .entry(discrepancy.field())
+                    .or_insert((0, 0))
+                    .1 += 1;
+                summary.offend(options.worst, offender);
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// The errors from reading each known tag with the wrong type.
+fn tag_type_errors(record: &PafRecord) -> Vec<Error> {
+    [
+        record.tp_checked().err(),
+        record.cm_checked().err(),
+        record.s1_checked().err(),
+        record.s2_checked().err(),
+        record.nm_checked().err(),
+        record.md_checked().err(),
+        record.as_checked().err(),
+        record.sa_checked().err(),
+        record.ms_checked().err(),
+        record.nn_checked().err(),
+        record.ts_checked().err(),
+        record.cg_checked().err(),
+        record.cs_checked().err(),
+        record.dv_checked().err(),
+        record.de_checked().err(),
+        record.rl_checked().err(),
+        record.zd_checked().err(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// The description of an error, without the kind of error before it.
+fn message(err: Error) -> String {
+    match err.into_kind() {
+        ErrorKind::ReadRecord(msg) | ErrorKind::InvalidTag(msg) | ErrorKind::InvalidRecord(msg) => {
+            msg
+        }
+        kind => Error::new(kind).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BROKEN: &str = "\
+a\t100\t0\t100\t+\tchr1\t1000\t0\t100\t95\t100\t60\tcg:Z:100M
+b\t100\t0\t100\t+\tchr1\t1000\t900\t1001\t95\t100\t60
+c\t100\t0\t100\t+\tchr1\t1000\t0\t100\t95\t100\t60\tNM:Z:five
+not a record
+a\t100\t0\t100\t+\tchr1\t1000\t0\t100\t95\t100\t60\tcg:Z:100M
+b\t120\t0\t100\t+\tchr1\t1000\t0\t100\t95\t100\t60\tcg:Z:90M
+d\t100\t0\t100\t+\tchr1\t2000\t0\t100\t95\t100\t60\tde:f:1.5
+# a comment
+e\t100\t0\t10x\t+\tchr1\t1000\t0\t100\t95\t100\t60
+";
+
+    fn validate(paf: &str, options: &ValidationOptions) -> ValidationReport {
+        validate_file(&mut Reader::from_reader(paf.as_bytes()), options).unwrap()
+    }
+
+    #[test]
+    fn test_golden_report() {
+        let report = validate(BROKEN, &ValidationOptions::default());
+        assert_eq!(
+            report.to_string(),
+            "\
+line 2: error (invariant): target coordinates 900-1001 are not within a target of length 1000
+line 3: error (tag type): Invalid type for PAF tag NM: expected i, found Z
+line 4: error (parse): Invalid PAF at line 4: less than 12 mandatory fields
+line 5: warning (duplicate): repeats the record on line 1
+line 6: error (cigar): query bases consumed by the CIGAR (90) differs from query_end - query_start (100) by 10 bases
+line 6: error (inconsistent length): query b has length 120, but 100 on line 2
+line 7: error (divergence): de value 1.5 out of range [0, 1]
+line 7: error (inconsistent length): target chr1 has length 2000, but 1000 on line 1
+line 9: error (parse): parsing integer error - invalid digit found in string
+5 records, 9 issues
+parse                   2
+invariant               1
+tag type                1
+cigar                   1
+divergence              1
+duplicate               1
+inconsistent length     2
+"
+        );
+        assert!(!report.is_valid());
+        assert!(!report.truncated());
+    }
+
+    #[test]
+    fn test_max_issues() {
+        let options = ValidationOptions {
+            max_issues: 3,
+            ..ValidationOptions::default()
+        };
+        let report = validate(BROKEN, &options);
+        assert_eq!(report.issues.len(), 3);
+        assert_eq!(report.total(), 9);
+        assert_eq!(report.count(Category::InconsistentLength), 2);
+        assert!(report.truncated());
+        assert!(report.to_string().contains("... 6 more issues not shown\n"));
+    }
+
+    #[test]
+    fn test_optional_checks() {
+        let options = ValidationOptions {
+            duplicates: false,
+            lengths: false,
+            ..ValidationOptions::default()
+        };
+        let report = validate(BROKEN, &options);
+        assert_eq!(report.count(Category::Duplicate), 0);
+        assert_eq!(report.count(Category::InconsistentLength), 0);
+        assert_eq!(report.total(), 6);
+
+        // duplicates alone are only a warning
+        let paf = "\
+a\t100\t0\t100\t+\tchr1\t1000\t0\t100\t95\t100\t60
+a\t100\t0\t100\t+\tchr1\t1000\t0\t100\t95\t100\t60
+";
+        let report = validate(paf, &ValidationOptions::default());
+        assert_eq!(report.total(), 1);
+        assert!(report.is_valid());
+    }
+
+    /// A consistent record: 18 matches, 2 mismatches, a 2 base insertion
+    /// and a 3 base deletion, so 25 columns, NM 7 and de 4/22.
+    const CONSISTENT: &str = "q\t100\t0\t22\t+\tt\t100\t0\t23\t18\t25\t60\t\
+        NM:i:7\tde:f:0.1818\tcg:Z:10=1X2I5=3D1X3=\tcs:Z::10*ag+ac:5-ttt*ct:3\n";
+
+    fn audit(paf: &str, options: &AuditOptions) -> AuditSummary {
+        audit_file(&mut Reader::from_reader(paf.as_bytes()), options).unwrap()
+    }
+
+    #[test]
+    fn test_audit_file() {
+        // the second record has two matches too few, NM off by two and de
+        // of 0.25; the fourth has NM off by one
+        let perturbed = CONSISTENT
+            .replacen("\t18\t", "\t16\t", 1)
+            .replace("NM:i:7", "NM:i:9")
+            .replace("de:f:0.1818", "de:f:0.25");
+        let paf = [
+            CONSISTENT,
+            &perturbed,
+            "not a record\n",
+            &CONSISTENT.replace("NM:i:7", "NM:i:8"),
+            &CONSISTENT.replace("cs:Z::10", "cs:Z:!10"),
+        ]
+        .concat();
+        let summary = audit(&paf, &AuditOptions::default());
+        assert_eq!(
+            summary.to_string(),
+            "\
+4 records, 1 unparsed, 1 with invalid tags
+residue_matches         4 checked, 1 disagreeing
+alignment_block_len     4 checked, 0 disagreeing
+NM                      4 checked, 2 disagreeing
+de                      4 checked, 1 disagreeing
+line 2: residue_matches: expected 18, observed 16
+line 2: NM: expected 7, observed 9
+line 4: NM: expected 7, observed 8
+line 2: de: expected 0.1818, observed 0.25
+"
+        );
+        assert!(!summary.is_consistent());
+        assert_eq!(summary.worst(AuditField::Nm).next().unwrap().delta(), 2.0);
+        assert!(audit(CONSISTENT, &AuditOptions::default()).is_consistent());
+
+        // within a tolerance, and keeping only the worst record of each
+        let options = AuditOptions {
+            count_tolerance: 1,
+            de_tolerance: 0.1,
+            worst: 1,
+        };
+        let summary = audit(&paf, &options);
+        assert_eq!(summary.disagreeing(AuditField::ResidueMatches), 1);
+        assert_eq!(summary.disagreeing(AuditField::Nm), 1);
+        assert_eq!(summary.disagreeing(AuditField::De), 0);
+        let options = AuditOptions {
+            worst: 1,
+            ..AuditOptions::default()
+        };
+        let summary = audit(&paf, &options);
+        assert_eq!(summary.disagreeing(AuditField::Nm), 2);
+        let lines: Vec<_> = summary.worst(AuditField::Nm).map(|o| o.line).collect();
+        assert_eq!(lines, [2]);
+        assert_eq!(summary.offenders.len(), 3);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{CigarOp, CsOp, Error, ErrorKind, PafRecord, Result};
+
+/// The identity of an alignment over a window of the target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowIdentity {
+    /// Start of the window on the target, clipped to the alignment.
+    pub target_start: u64,
+    /// End of the window on the target, clipped to the alignment.
+    pub target_end: u64,
+    /// Matching bases over alignment columns: matches, mismatches, and
+    /// inserted and deleted bases.
+    pub identity: f64,
+}
+
+/// Matches and alignment columns of each window, indexed from the first
+/// window overlapping the alignment.
+struct Windows {
+    window: u64,
+    step: u64,
+    /// The target interval of the alignment.
+    start: u64,
+    end: u64,
+    /// The index of the first window, the one at `first * step`.
+    first: u64,
+    counts: Vec<(u64, u64)>,
+}
+
+impl Windows {
+    fn new(window: u64, step: u64, start: u64, end: u64) -> Self {
+        let first = (start + 1).saturating_sub(window).div_ceil(step);
+        let last = end.saturating_sub(1) / step;
+        let len = if end > start { last + 1 - first } else { 0 };
+        Windows {
+            window,
+            step,
+            start,
+            end,
+            first,
+            counts: vec![(0, 0); len as usize],
+        }
+    }
+
+    /// The indices of the windows overlapping the target interval `from..to`.
+    fn overlapping(&self, from: u64, to: u64) -> std::ops::Range<u64> {
+        let first = (from + 1).saturating_sub(self.window).div_ceil(self.step);
+        let last = (to - 1) / self.step;
+        first.max(self.first)..last + 1
+    }
+
+    /// Add a run of aligned or deleted target bases.
+    fn add_run(&mut self, from: u64, to: u64, matching: bool) {
+        for k in self.overlapping(from, to) {
+            let window_start = k * self.step;
+            let overlap = to.min(window_start + self.window) - from.max(window_start);
+            let counts = &mut self.counts[(k - self.first) as usize];
+            counts.1 += overlap;
+            if matching {
+                counts.0 += overlap;
+            }
+        }
+    }
+
+    /// Add inserted bases, to the windows holding the target base after the
+    /// insertion (or before it, at the end of the alignment). An alignment
+    /// covering no target bases has no windows to add them to.
+    fn add_insertion(&mut self, pos: u64, len: u64) {
+        if self.end <= self.start {
+            return;
+        }
+        let pos = pos.min(self.end - 1);
+        for k in self.overlapping(pos, pos + 1) {
+            self.counts[(k - self.first) as usize].1 += len;
+        }
+    }
+
+    /// The windows with any alignment columns, with their indices.
+    fn finish(self) -> Vec<(u64, WindowIdentity)> {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, columns))| *columns > 0)
+            .map(|(i, &(matches, columns))| {
+                let k = self.first + i as u64;
+                let window_start = k * self.step;
+                let identity = WindowIdentity {
+                    target_start: window_start.max(self.start),
+                    target_end: (window_start + self.window).min(self.end),
+                    identity: matches as f64 / columns as f64,
+                };
+                (k, identity)
+            })
+            .collect()
+    }
+}
+
+impl PafRecord {
+    /// The identity of the alignment in windows of `window` target bases,
+    /// starting every `step` bases from the start of the target.
+    ///
+    /// The windows are laid on the whole target, so that those of different
+    /// records line up, and clipped to the alignment where they overlap its
+    /// ends. Insertions count towards the windows holding the target base
+    /// after them. Windows with no alignment columns, such as those inside
+    /// an intron, are left out. This needs a cs tag, or a cg tag which tells
+    /// matches from mismatches with `=` and `X`, and it is an error for the
+    /// tag not to span the target interval.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` or `step` is zero.
+    pub fn windowed_identity(&self, window: u64, step: u64) -> Result<Vec<WindowIdentity>> {
+        Ok(self
+            .indexed_windowed_identity(window, step)?
+            .into_iter()
+            .map(|(_, identity)| identity)
+            .collect())
+    }
+
+    /// The windows of [`PafRecord::windowed_identity`] with their indices,
+    /// the window at `k` starting at `k * step`.
+    pub(crate) fn indexed_windowed_identity(
+        &self,
+        window: u64,
+        step: u64,
+    ) -> Result<Vec<(u64, WindowIdentity)>> {
+        assert!(window > 0 && step > 0, "window and step must be positive");
+        let span = self.coordinate_sub(self.target_end(), self.target_start())? as u64;
+        let check_span = |tag: &str, len: u64| {
+            if len == span {
+                return Ok(());
+            }
+            Err(Error::new(ErrorKind::InvalidRecord(format!(
+                "{} tag spans {} target bases, not {}",
+                tag, len, span
+            ))))
+        };
+        let mut pos = self.target_start() as u64;
+        let mut windows = Windows::new(window, step, pos, self.target_end() as u64);
+
+        if let Some(cs) = self.cs_parsed() {
+            let cs = cs?;
+            check_span("cs", cs.ops().map(|op| op.target_len() as u64).sum())?;
+            for op in &cs {
+                let len = op.target_len() as u64;
+                match op {
+                    CsOp::Identity(_) | CsOp::Match(_) => windows.add_run(pos, pos + len, true),
+                    CsOp::Mismatch { .. } | CsOp::Deletion(_) => {
+                        windows.add_run(pos, pos + len, false)
+                    }
+                    CsOp::Insertion(s) => windows.add_insertion(pos, s.len() as u64),
+                    CsOp::Splice { .. } => {}
+                }
+                pos += len;
+            }
+            return Ok(windows.finish());
+        }
+
+        let cigar = self.require_cigar("windowed identity")?;
+        check_span("cg", cigar.target_len())?;
+        for (len, op) in &cigar {
+            let len = len as u64;
+            match op {
+                CigarOp::Equal => windows.add_run(pos, pos + len, true),
+                CigarOp::Diff | CigarOp::Deletion => windows.add_run(pos, pos + len, false),
+                CigarOp::Insertion => windows.add_insertion(pos, len),
+                CigarOp::Match => {
+                    return Err(Error::new(ErrorKind::InvalidRecord(
+                        "windowed identity needs a cs tag, or =/X operations in the cg tag"
+                            .to_string(),
+                    )))
+                }
+                _ => {}
+            }
+            if op.consumes_target() {
+                pos += len;
+            }
+        }
+        Ok(windows.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    fn record(tags: &str, target: (u64, u64)) -> PafRecord {
+        test_util::record(&format!(
+            "q\t1000\t0\t100\t+\tt\t1000\t{}\t{}\t90\t100\t60\t{}\n",
+            target.0, target.1, tags
+        ))
+    }
+
+    fn spans(windows: &[WindowIdentity]) -> Vec<(u64, u64)> {
+        windows
+            .iter()
+            .map(|w| (w.target_start, w.target_end))
+            .collect()
+    }
+
+    #[test]
+    fn test_bad_patch() {
+        // 40 matches, a patch of 10 mismatches, 50 matches
+        let mut cs = String::from(":40");
+        for _ in 0..10 {
+            cs.push_str("*ag");
+        }
+        cs.push_str(":50");
+        let record = record(&format!("cs:Z:{}", cs), (100, 200));
+        let windows = record.windowed_identity(20, 20).unwrap();
+        assert_eq!(
+            spans(&windows),
+            vec![(100, 120), (120, 140), (140, 160), (160, 180), (180, 200)]
+        );
+        let identities: Vec<f64> = windows.iter().map(|w| w.identity).collect();
+        assert_eq!(identities, vec![1.0, 1.0, 0.5, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_truncated_ends() {
+        let record = record("cs:Z::100", (105, 205));
+        let windows = record.windowed_identity(20, 10).unwrap();
+        // the windows at 90 and 100 overlap the start, and those at 190 and
+        // 200 the end
+        assert_eq!(windows.first().unwrap().target_start, 105);
+        assert_eq!(windows.first().unwrap().target_end, 110);
+        assert_eq!(windows[1].target_start, 105);
+        assert_eq!(windows[1].target_end, 120);
+        assert_eq!(windows.last().unwrap().target_start, 200);
+        assert_eq!(windows.last().unwrap().target_end, 205);
+        assert_eq!(windows.len(), 12);
+        assert!(windows.iter().all(|w| w.identity == 1.0));
+    }
+
+    #[test]
+    fn test_gaps() {
+        // an insertion of 5 and a deletion of 5 in the second window
+        let cs = "cs:Z::10:5+aaaaa:2-ccccc:3";
+        let record = record(cs, (0, 25));
+        let windows = record.windowed_identity(10, 10).unwrap();
+        assert_eq!(spans(&windows), vec![(0, 10), (10, 20), (20, 25)]);
+        assert_eq!(windows[0].identity, 1.0);
+        assert_eq!(windows[1].identity, 7.0 / 15.0);
+        assert_eq!(windows[2].identity, 3.0 / 5.0);
+    }
+
+    #[test]
+    fn test_cigar_fallback() {
+        let cs = record("cs:Z::5*ag:4+aa:10", (0, 20)).windowed_identity(10, 5);
+        let cg = record("cg:Z:5=1X4=2I10=", (0, 20)).windowed_identity(10, 5);
+        assert_eq!(cs.unwrap(), cg.unwrap());
+
+        assert!(record("cg:Z:20M", (0, 20))
+            .windowed_identity(10, 5)
+            .is_err());
+        assert!(record("", (0, 20)).windowed_identity(10, 5).is_err());
+    }
+
+    #[test]
+    fn test_intron() {
+        let record = record("cs:Z::10~gt30ag:10", (0, 50));
+        let windows = record.windowed_identity(10, 10).unwrap();
+        assert_eq!(spans(&windows), vec![(0, 10), (40, 50)]);
+    }
+
+    #[test]
+    fn test_tag_spans() {
+        // tags spanning more or fewer target bases than the interval, and an
+        // interval ending before it starts
+        assert!(record("cs:Z::20", (0, 10)).windowed_identity(5, 5).is_err());
+        assert!(record("cg:Z:5=", (0, 10)).windowed_identity(5, 5).is_err());
+        assert!(record("cs:Z::10", (10, 0)).windowed_identity(5, 5).is_err());
+        // an insertion alone covers no windows
+        let windows = record("cs:Z:+aa", (0, 0)).windowed_identity(5, 5);
+        assert!(windows.unwrap().is_empty());
+    }
+}
@@ -1,32 +1,550 @@
-use std::fs::File;
-use std::io::Write;
+use std::borrow::Borrow;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
 use std::path::Path;
+use std::result::Result as StdResult;
 
-use crate::{PafRecord, Result, Tag, Type};
+#[cfg(feature = "gzip")]
+use flate2::write::GzEncoder;
+
+#[cfg(feature = "bgzf")]
+use crate::compress::BgzfEncoder;
+use crate::tags;
+use crate::{
+    Error, ErrorKind, Indel, IndelKind, PafRecord, Result, SupplementaryAlignment, Tag, Type,
+};
+
+/// The order in which a [`Writer`] emits the optional fields of a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagOrder {
+    /// The known tags in the order minimap2 writes them, then any others
+    /// sorted by name (the default).
+    #[default]
+    Minimap2,
+    /// All tags sorted by name.
+    Alphabetical,
+}
+
+/// The known tags in the order minimap2 writes them.
+const MINIMAP2_ORDER: [&str; 17] = [
+    tags::NM,
+    tags::MS,
+    tags::AS,
+    tags::NN,
+    tags::TP,
+    tags::CM,
+    tags::S1,
+    tags::S2,
+    tags::TS,
+    tags::DE,
+    tags::DV,
+    tags::ZD,
+    tags::RL,
+    tags::CG,
+    tags::CS,
+    tags::MD,
+    tags::SA,
+];
+
+impl TagOrder {
+    /// Sort optional fields into this order.
+    pub(crate) fn sort(&self, fields: &mut [(&String, &Tag)]) {
+        match self {
+            TagOrder::Minimap2 => fields.sort_by_key(|(name, _)| {
+                let rank = MINIMAP2_ORDER.iter().position(|known| known == name);
+                (rank.unwrap_or(MINIMAP2_ORDER.len()), name.as_str())
+            }),
+            TagOrder::Alphabetical => fields.sort_by_key(|(name, _)| name.as_str()),
+        }
+    }
+}
+
+/// How [`WriterBuilder::from_path`] opens a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpenMode {
+    /// Create the file, or truncate it if it exists (the default).
+    #[default]
+    Truncate,
+    /// Create the file, or add to the end of it if it exists.
+    Append,
+    /// Create the file, failing if it exists.
+    CreateNew,
+}
+
+impl OpenMode {
+    fn open(&self, path: &Path) -> io::Result<File> {
+        let mut options = OpenOptions::new();
+        options.write(true);
+        match self {
+            OpenMode::Truncate => options.create(true).truncate(true),
+            OpenMode::Append => options.create(true).append(true),
+            OpenMode::CreateNew => options.create_new(true),
+        };
+        options.open(path)
+    }
+}
+
+/// How the output of a [`Writer`] is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain text.
+    None,
+    /// gzip, at a level from 0 (none) to 9 (best).
+    #[cfg(feature = "gzip")]
+    Gzip(u32),
+    /// BGZF, blocked gzip, which any gzip reader can read and which can be
+    /// indexed.
+    #[cfg(feature = "bgzf")]
+    Bgzf,
+}
+
+/// Builds a PAF writer with various configuration knobs.
+#[derive(Debug, Clone)]
+pub struct WriterBuilder {
+    tag_order: TagOrder,
+    buffer_capacity: usize,
+    open_mode: OpenMode,
+    float_precision: Option<usize>,
+    compression: Option<Compression>,
+    validate: bool,
+    include_tags: Option<Vec<String>>,
+    exclude_tags: Vec<String>,
+    #[cfg(feature = "bgzf")]
+    compression_threads: usize,
+}
+
+impl Default for WriterBuilder {
+    fn default() -> WriterBuilder {
+        WriterBuilder {
+            tag_order: TagOrder::default(),
+            buffer_capacity: 8 * 1024,
+            open_mode: OpenMode::default(),
+            float_precision: None,
+            compression: None,
+            validate: false,
+            include_tags: None,
+            exclude_tags: Vec::new(),
+            #[cfg(feature = "bgzf")]
+            compression_threads: 1,
+        }
+    }
+}
+
+impl WriterBuilder {
+    /// Create a new builder with the default configuration.
+    pub fn new() -> WriterBuilder {
+        WriterBuilder::default()
+    }
+
+    /// The order in which to write the optional fields of each record.
+    ///
+    /// Records hold their tags in a map, so some fixed order is needed for
+    /// the same records to always be written as the same bytes.
+    pub fn tag_order(&mut self, order: TagOrder) -> &mut WriterBuilder {
+        self.tag_order = order;
+        self
+    }
+
+    /// The number of decimal places for floats, or `None` (the default) for
+    /// the shortest text which parses back to the same value.
+    ///
+    /// This only applies to floats built in code: those read from a file
+    /// keep their original text.
+    pub fn float_precision(&mut self, precision: Option<usize>) -> &mut WriterBuilder {
+        self.float_precision = precision;
+        self
+    }
+
+    /// How to compress the output.
+    ///
+    /// If this is not set, [`WriterBuilder::from_path`] compresses paths
+    /// ending in `.gz` and [`WriterBuilder::from_writer`] writes plain text.
+    pub fn compression(&mut self, compression: Compression) -> &mut WriterBuilder {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Whether to check each record with [`PafRecord::validate`], which
+    /// includes [`PafRecord::check_cigar`], before writing it. An invalid
+    /// record is not written, and the error names the broken rule and the
+    /// record's query and target. This is off by default.
+    pub fn validate(&mut self, yes: bool) -> &mut WriterBuilder {
+        self.validate = yes;
+        self
+    }
+
+    /// The number of threads to compress BGZF blocks on. Records are still
+    /// written by the calling thread, which hands each full block to a
+    /// worker, and blocks are written out in order, so the output is the
+    /// same as with one thread, the default. Gzip output is one stream and
+    /// is always compressed on the calling thread.
+    #[cfg(feature = "bgzf")]
+    pub fn compression_threads(&mut self, threads: usize) -> &mut WriterBuilder {
+        self.compression_threads = threads;
+        self
+    }
+
+    /// Write only the optional fields with these names, such as `tp`, `NM`
+    /// and `de`. Records themselves are not changed.
+    ///
+    /// This cannot be combined with [`WriterBuilder::exclude_tags`]; a
+    /// writer with both fails to build from a path, and fails every write.
+    pub fn include_tags(&mut self, tags: &[&str]) -> &mut WriterBuilder {
+        self.include_tags = Some(tags.iter().map(|tag| tag.to_string()).collect());
+        self
+    }
+
+    /// Leave out the optional fields with these names, such as `cg`, which
+    /// is often most of a line. Records themselves are not changed.
+    ///
+    /// This cannot be combined with [`WriterBuilder::include_tags`].
+    pub fn exclude_tags(&mut self, tags: &[&str]) -> &mut WriterBuilder {
+        self.exclude_tags = tags.iter().map(|tag| tag.to_string()).collect();
+        self
+    }
+
+    /// Check that the configuration is consistent.
+    fn check(&self) -> Result<()> {
+        if self.include_tags.is_some() && !self.exclude_tags.is_empty() {
+            return Err(Error::new(ErrorKind::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "include_tags and exclude_tags cannot both be set",
+            ))));
+        }
+        Ok(())
+    }
+
+    /// Whether to write the optional field with this name.
+    fn writes_tag(&self, name: &str) -> bool {
+        match &self.include_tags {
+            Some(include) => include.iter().any(|tag| tag == name),
+            None => !self.exclude_tags.iter().any(|tag| tag == name),
+        }
+    }
+
+    /// The capacity in bytes of the buffer records are written to before
+    /// they reach the underlying writer. The default is 8 KiB; zero passes
+    /// each piece of a record straight through.
+    pub fn buffer_capacity(&mut self, capacity: usize) -> &mut WriterBuilder {
+        self.buffer_capacity = capacity;
+        self
+    }
+
+    /// How to open the file given to [`WriterBuilder::from_path`]: whether
+    /// to truncate an existing file, append to it or fail.
+    pub fn open_mode(&mut self, mode: OpenMode) -> &mut WriterBuilder {
+        self.open_mode = mode;
+        self
+    }
+
+    /// Build a PAF writer to a file path, opened as set by
+    /// [`WriterBuilder::open_mode`].
+    ///
+    /// Unless [`WriterBuilder::compression`] is set, a path ending in `.gz`
+    /// is written gzip compressed, which needs the `gzip` feature, or the
+    /// `bgzf` feature for BGZF. Without either it is an error.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_path<P: AsRef<Path>>(&self, path: P) -> Result<Writer<File>> {
+        self.check()?;
+        let path = path.as_ref();
+        let compression = match self.compression {
+            Some(compression) => compression,
+            None if path.extension().is_some_and(|ext| ext == "gz") => Compression::for_gz_path()?,
+            None => Compression::None,
+        };
+        Ok(self.build(self.open_mode.open(path)?, compression))
+    }
+
+    /// Build a PAF writer to a writer.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_writer<W: Write>(&self, writer: W) -> Writer<W> {
+        self.build(writer, self.compression.unwrap_or(Compression::None))
+    }
+
+    fn build<W: Write>(&self, writer: W, compression: Compression) -> Writer<W> {
+        let sink = match compression {
+            Compression::None => {
+                Sink::Plain(BufWriter::with_capacity(self.buffer_capacity, writer))
+            }
+            #[cfg(feature = "gzip")]
+            Compression::Gzip(level) => Sink::Gzip(BufWriter::with_capacity(
+                self.buffer_capacity,
+                GzEncoder::new(writer, flate2::Compression::new(level.min(9))),
+            )),
+            #[cfg(feature = "bgzf")]
+            Compression::Bgzf => Sink::Bgzf(BufWriter::with_capacity(
+                self.buffer_capacity,
+                BgzfEncoder::with_threads(writer, self.compression_threads),
+            )),
+        };
+        Writer {
+            writer: Some(sink),
+            config: self.clone(),
+        }
+    }
+}
+
+impl Compression {
+    /// The compression for a path ending in `.gz`.
+    fn for_gz_path() -> Result<Compression> {
+        #[cfg(feature = "gzip")]
+        return Ok(Compression::Gzip(6));
+        #[cfg(all(feature = "bgzf", not(feature = "gzip")))]
+        return Ok(Compression::Bgzf);
+        #[cfg(not(any(feature = "gzip", feature = "bgzf")))]
+        Err(Error::new(ErrorKind::Io(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "writing a .gz path needs the gzip or bgzf feature",
+        ))))
+    }
+}
+
+/// The buffered, and possibly compressing, writer under a [`Writer`].
+#[derive(Debug)]
+enum Sink<W: Write> {
+    Plain(BufWriter<W>),
+    #[cfg(feature = "gzip")]
+    Gzip(BufWriter<GzEncoder<W>>),
+    #[cfg(feature = "bgzf")]
+    Bgzf(BufWriter<BgzfEncoder<W>>),
+}
+
+impl<W: Write> Sink<W> {
+    fn get_ref(&self) -> &W {
+        match self {
+            Sink::Plain(writer) => writer.get_ref(),
+            #[cfg(feature = "gzip")]
+            Sink::Gzip(writer) => writer.get_ref().get_ref(),
+            #[cfg(feature = "bgzf")]
+            Sink::Bgzf(writer) => writer.get_ref().get_ref(),
+        }
+    }
+
+    /// Write out the buffer and end any compression stream. Nothing more
+    /// can be written after this.
+    fn try_finish(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(writer) => writer.flush(),
+            #[cfg(feature = "gzip")]
+            Sink::Gzip(writer) => {
+                writer.flush()?;
+                writer.get_mut().try_finish()
+            }
+            #[cfg(feature = "bgzf")]
+            Sink::Bgzf(writer) => {
+                writer.flush()?;
+                writer.get_mut().try_finish()
+            }
+        }
+    }
+
+    /// The underlying writer, once `try_finish` has succeeded.
+    fn into_inner(self) -> W {
+        match self {
+            Sink::Plain(writer) => writer.into_parts().0,
+            #[cfg(feature = "gzip")]
+            Sink::Gzip(writer) => writer
+                .into_parts()
+                .0
+                .finish()
+                .expect("the stream is already finished"),
+            #[cfg(feature = "bgzf")]
+            Sink::Bgzf(writer) => writer
+                .into_parts()
+                .0
+                .finish()
+                .expect("the stream is already finished"),
+        }
+    }
+}
+
+impl<W: Write> Write for Sink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Plain(writer) => writer.write(buf),
+            #[cfg(feature = "gzip")]
+            Sink::Gzip(writer) => writer.write(buf),
+            #[cfg(feature = "bgzf")]
+            Sink::Bgzf(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(writer) => writer.flush(),
+            #[cfg(feature = "gzip")]
+            Sink::Gzip(writer) => writer.flush(),
+            #[cfg(feature = "bgzf")]
+            Sink::Bgzf(writer) => writer.flush(),
+        }
+    }
+}
 
 /// Struct representing a PAF file writer.
+///
+/// Output is buffered, so there is no need to wrap the underlying writer in
+/// a `BufWriter`. The buffer is flushed, and any compression stream ended,
+/// as far as possible when the `Writer` is dropped; call [`Writer::flush`]
+/// or [`Writer::into_inner`] to see any error.
+#[derive(Debug)]
 pub struct Writer<W: Write> {
-    writer: W,
+    /// Only `None` once taken by `into_inner`.
+    writer: Option<Sink<W>>,
+    config: WriterBuilder,
 }
 
 impl Writer<File> {
     /// Creates a new PAF writer from a file path.
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Writer<File>> {
-        let file = File::create(path)?;
-        Ok(Writer::new(file))
+        WriterBuilder::new().from_path(path)
+    }
+
+    /// Creates a new PAF writer which adds to the end of a file, creating
+    /// it if need be. Nothing but records is written, so the result is the
+    /// existing records followed by the new ones.
+    pub fn append_path<P: AsRef<Path>>(path: P) -> Result<Writer<File>> {
+        WriterBuilder::new()
+            .open_mode(OpenMode::Append)
+            .from_path(path)
+    }
+}
+
+impl Writer<io::StdoutLock<'static>> {
+    /// Creates a new PAF writer to standard output, holding its lock for as
+    /// long as the writer lives.
+    ///
+    /// Writes are buffered by the writer. When the reader of the output
+    /// goes away, as with `| head`, writes fail with an error for which
+    /// [`Error::is_broken_pipe`] is true.
+    pub fn to_stdout() -> Writer<io::StdoutLock<'static>> {
+        Writer::new(io::stdout().lock())
     }
 }
 
 impl<W: Write> Writer<W> {
     /// Creates a new PAF writer from a writer instance.
     pub fn new(writer: W) -> Self {
-        Writer { writer }
+        WriterBuilder::new().from_writer(writer)
+    }
+
+    /// Creates a new PAF writer which gzip compresses its output, at a
+    /// `level` from 0 (none) to 9 (best).
+    #[cfg(feature = "gzip")]
+    pub fn gzip(writer: W, level: u32) -> Self {
+        WriterBuilder::new().build(writer, Compression::Gzip(level))
+    }
+
+    /// Creates a new PAF writer which compresses its output as BGZF, which
+    /// any gzip reader can read and which can be indexed, e.g. by `bgzip`.
+    #[cfg(feature = "bgzf")]
+    pub fn bgzf(writer: W) -> Self {
+        WriterBuilder::new().build(writer, Compression::Bgzf)
+    }
+
+    /// Writes out the buffer and flushes the underlying writer. Compressed
+    /// output is flushed to a point where all written so far can be read,
+    /// but the stream is only ended by [`Writer::into_inner`] or on drop.
+    pub fn flush(&mut self) -> Result<()> {
+        self.inner().flush().map_err(Into::into)
+    }
+
+    /// Flushes, ends any compression stream and returns the underlying
+    /// writer, e.g. to get back a `Vec<u8>`.
+    ///
+    /// If this fails, the error is returned along with this writer, so that
+    /// nothing written is lost.
+    #[allow(clippy::result_large_err)]
+    pub fn into_inner(mut self) -> StdResult<W, (Self, Error)> {
+        match self.inner().try_finish() {
+            Ok(()) => Ok(self.writer.take().unwrap().into_inner()),
+            Err(err) => Err((self, err.into())),
+        }
+    }
+
+    /// A reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.writer.as_ref().unwrap().get_ref()
+    }
+
+    fn inner(&mut self) -> &mut Sink<W> {
+        self.writer.as_mut().unwrap()
+    }
+
+    /// Writes a series of records, returning how many were written.
+    ///
+    /// This stops at the first error, which is an
+    /// [`ErrorKind::WriteRecord`](ErrorKind::WriteRecord) giving the
+    /// index of the failed record.
+    pub fn write_records<I>(&mut self, records: I) -> Result<u64>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<PafRecord>,
+    {
+        self.try_write_records(records.into_iter().map(Ok))
+    }
+
+    /// Writes a series of records which may have failed to read, e.g. from
+    /// [`Reader::records`](crate::Reader::records), returning how many were
+    /// written.
+    ///
+    /// This stops at the first error, whether in getting or writing a
+    /// record, as for [`Writer::write_records`].
+    pub fn try_write_records<I, P>(&mut self, records: I) -> Result<u64>
+    where
+        I: IntoIterator<Item = Result<P>>,
+        P: Borrow<PafRecord>,
+    {
+        let mut written = 0;
+        for record in records {
+            record
+                .and_then(|record| self.write_record(record.borrow()))
+                .map_err(|err| {
+                    Error::new(ErrorKind::WriteRecord {
+                        index: written,
+                        err,
+                    })
+                })?;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Writes a comment line, adding the leading `#` if `text` lacks it.
+    ///
+    /// Comments can come anywhere among the records, and are passed over,
+    /// and kept, by the [`Reader`](crate::Reader). It is an error for
+    /// `text` to hold a line break.
+    pub fn write_comment(&mut self, text: &str) -> Result<()> {
+        if text.contains(['\n', '\r']) {
+            return Err(Error::new(ErrorKind::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a comment cannot hold a line break",
+            ))));
+        }
+        let writer = self.inner();
+        if !text.starts_with('#') {
+            writer.write_all(b"#")?;
+        }
+        writeln!(writer, "{}", text).map_err(Into::into)
     }
 
     /// Writes a single `PafRecord` to the PAF file.
     pub fn write_record(&mut self, record: &PafRecord) -> Result<()> {
+        self.config.check()?;
+        if self.config.validate {
+            record.validate().map_err(|err| match err.kind() {
+                ErrorKind::InvalidRecord(msg) => Error::new(ErrorKind::InvalidRecord(format!(
+                    "not writing {} aligned to {}: {}",
+                    record.query_name(),
+                    record.target_name(),
+                    msg
+                ))),
+                _ => err,
+            })?;
+        }
+        let writer = self.writer.as_mut().unwrap();
         write!(
-            self.writer,
+            writer,
             "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
             record.query_name(),
             record.query_len(),
@@ -42,40 +560,111 @@ impl<W: Write> Writer<W> {
             record.mapping_quality(),
         )?;
 
-        for (key, tag) in record.optional_fields() {
+        let precision = self.config.float_precision;
+        let mut fields: Vec<_> = record
+            .optional_fields()
+            .iter()
+            .filter(|(name, _)| self.config.writes_tag(name))
+            .collect();
+        self.config.tag_order.sort(&mut fields);
+        for (key, tag) in fields {
             match tag {
-                Tag::tp(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::cm(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::s1(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::s2(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::NM(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::MD(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::AS(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::SA(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::ms(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::nn(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::ts(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::cg(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::cs(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::dv(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::de(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::rl(value) => write_optional_field(&mut self.writer, key, value)?,
-                Tag::zd(value) => write_optional_field(&mut self.writer, key, value)?,
+                Tag::tp(value) => {
+                    write_optional_field(writer, key, &Type::Char(value.as_char()), precision)?
+                }
+                Tag::cm(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::s1(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::s2(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::NM(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::MD(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::AS(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::SA(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::ms(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::nn(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::ts(value) => {
+                    write_optional_field(writer, key, &Type::Char(value.as_char()), precision)?
+                }
+                Tag::cg(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::cs(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::dv(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::de(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::rl(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::zd(value) => write_optional_field(writer, key, value, precision)?,
+                Tag::Other(_, value) => write_optional_field(writer, key, value, precision)?,
             }
         }
 
-        writeln!(self.writer).map_err(Into::into)
+        writeln!(writer).map_err(Into::into)
     }
 }
 
+impl<W: Write> Drop for Writer<W> {
+    fn drop(&mut self) {
+        if let Some(writer) = self.writer.as_mut() {
+            let _ = writer.try_finish();
+        }
+    }
+}
+
+/// Write indels of a record as BED6 lines on the target.
+///
+/// The name column holds the kind and the query interval, as in
+/// `INS:read1:10-14`, and the score column the indel length. Insertions are
+/// zero-length intervals at the insertion point.
+pub fn write_indels_bed<W: Write>(
+    writer: &mut W,
+    record: &PafRecord,
+    indels: &[Indel],
+) -> Result<()> {
+    for indel in indels {
+        let kind = match indel.kind {
+            IndelKind::Insertion => "INS",
+            IndelKind::Deletion => "DEL",
+        };
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}:{}:{}-{}\t{}\t{}",
+            record.target_name(),
+            indel.target_start,
+            indel.target_end,
+            kind,
+            record.query_name(),
+            indel.query_start,
+            indel.query_end,
+            indel.len,
+            record.strand(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Build an SA tag from a list of supplementary alignments.
+pub fn supplementary_alignments_tag(alignments: &[SupplementaryAlignment]) -> Tag {
+    Tag::SA(Type::String(
+        alignments.iter().map(|sa| sa.to_string()).collect(),
+    ))
+}
+
 /// Helper function to write optional fields based on their types.
-fn write_optional_field<W: Write>(writer: &mut W, tag: &str, value: &Type) -> Result<()> {
-    match value {
-        Type::Int(v) => write!(writer, "\t{}:i:{}", tag, v).map_err(Into::into),
-        Type::Float(v) => write!(writer, "\t{}:f:{:.4}", tag, v).map_err(Into::into),
-        Type::String(v) => write!(writer, "\t{}:Z:{}", tag, v).map_err(Into::into),
-        Type::Char(v) => write!(writer, "\t{}:A:{}", tag, v).map_err(Into::into),
+///
+/// Values are formatted with the `Display` implementation of [`Type`], so
+/// formatting a value by hand always agrees with the writer.
+///
+/// Floats without original text are written with `precision` decimal
+/// places, if given.
+fn write_optional_field<W: Write>(
+    writer: &mut W,
+    tag: &str,
+    value: &Type,
+    precision: Option<usize>,
+) -> Result<()> {
+    match (value, precision) {
+        (Type::Float { value, raw: None }, Some(precision)) => {
+            write!(writer, "\t{}:f:{:.*}", tag, precision, value)
+        }
+        _ => write!(writer, "\t{}:{}:{}", tag, value.code(), value),
     }
+    .map_err(Into::into)
 }
 
 #[cfg(test)]
@@ -83,7 +672,7 @@ mod tests {
     use std::collections::HashMap;
 
     use super::*;
-    use crate::{PafRecord, Tag, Type};
+    use crate::{AlignmentType, PafRecord, Reader, ReaderBuilder, Tag, TranscriptStrand, Type};
 
     #[test]
     fn test_write_record_mandatory_fields() {
@@ -107,6 +696,7 @@ mod tests {
         );
 
         writer.write_record(&record).unwrap();
+        drop(writer);
         let output = String::from_utf8(buffer).unwrap();
 
         assert_eq!(
@@ -122,7 +712,7 @@ mod tests {
 
         let mut optional_fields = HashMap::new();
 
-        optional_fields.insert("tp".to_string(), Tag::tp(Type::Char('P')));
+        optional_fields.insert("tp".to_string(), Tag::tp(AlignmentType::Primary));
         optional_fields.insert("cm".to_string(), Tag::cm(Type::Int(42)));
         optional_fields.insert("s1".to_string(), Tag::s1(Type::Int(99)));
 
@@ -143,16 +733,792 @@ mod tests {
         );
 
         writer.write_record(&record).unwrap();
+        drop(writer);
         let output = String::from_utf8(buffer).unwrap();
 
-        // as the optional fields are a map, these are printed in an arbitrary order
-        // so we have to check the output differently
+        assert_eq!(
+            output,
+            "query2\t2000\t150\t900\t-\ttarget2\t2500\t300\t1000\t400\t800\t70\t\
+             tp:A:P\tcm:i:42\ts1:i:99\n"
+        );
+    }
+
+    #[test]
+    fn test_tag_order() {
+        let line = "q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\t\
+            zz:i:1\tcg:Z:10M\tAS:i:20\ttp:A:P\tNM:i:0\tab:Z:x\tde:f:0\n";
+        let record = ReaderBuilder::new()
+            .keep_unknown_tags(true)
+            .from_reader(line.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        Writer::new(&mut buffer).write_record(&record).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\t\
+             NM:i:0\tAS:i:20\ttp:A:P\tde:f:0\tcg:Z:10M\tab:Z:x\tzz:i:1\n"
+        );
+
+        let mut buffer = Vec::new();
+        WriterBuilder::new()
+            .tag_order(TagOrder::Alphabetical)
+            .from_writer(&mut buffer)
+            .write_record(&record)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\t\
+             AS:i:20\tNM:i:0\tab:Z:x\tcg:Z:10M\tde:f:0\ttp:A:P\tzz:i:1\n"
+        );
+    }
+
+    #[test]
+    fn test_fixture_tag_order() {
+        // minimap2's own output comes back out byte for byte
+        let mut reader = Reader::from_reader(crate::reader::tests::PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let mut buffer = Vec::new();
+        Writer::new(&mut buffer).write_record(&record).unwrap();
+        assert_eq!(
+            buffer.strip_suffix(b"\n"),
+            Some(crate::reader::tests::PAF_RECORD_1)
+        );
+    }
+
+    /// A writer which takes bytes but fails every flush.
+    struct FailingFlush(Vec<u8>);
+
+    impl Write for FailingFlush {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.write(buf)
+        }
 
+        fn flush(&mut self) -> std::io::Result<()> {
+            Err(std::io::Error::other("flush failed"))
+        }
+    }
+
+    #[test]
+    fn test_into_inner() {
+        let mut reader = Reader::from_reader(crate::reader::tests::PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let mut writer = Writer::new(Vec::new());
+        writer.write_record(&record).unwrap();
+        writer.flush().unwrap();
+        let buffer = writer.into_inner().unwrap();
+        assert!(buffer.starts_with(b"NC_041798.1\t41841605\t"));
+        assert!(buffer.ends_with(b"3D531M\n"));
+    }
+
+    #[test]
+    fn test_flush_on_drop() {
+        let path = std::env::temp_dir().join(format!("paf-drop-{}.paf", std::process::id()));
+        let mut reader = Reader::from_reader(crate::reader::tests::PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        {
+            // smaller than a record, and left to go out of scope
+            let mut writer = WriterBuilder::new()
+                .buffer_capacity(64)
+                .from_path(&path)
+                .unwrap();
+            for _ in 0..3 {
+                writer.write_record(&record).unwrap();
+            }
+        }
+        let written = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let line = [crate::reader::tests::PAF_RECORD_1, b"\n"].concat();
+        assert_eq!(written, line.repeat(3));
+    }
+
+    /// Write records without compression.
+    #[cfg(any(feature = "gzip", feature = "bgzf"))]
+    fn plain(records: &[PafRecord]) -> Vec<u8> {
+        let mut writer = Writer::new(Vec::new());
+        for record in records {
+            writer.write_record(record).unwrap();
+        }
+        writer.into_inner().unwrap()
+    }
+
+    #[cfg(any(feature = "gzip", feature = "bgzf"))]
+    fn records_from_gzip(compressed: &[u8]) -> Vec<PafRecord> {
+        Reader::from_reader(flate2::read::MultiGzDecoder::new(compressed))
+            .records()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    #[cfg(any(feature = "gzip", feature = "bgzf"))]
+    fn fixture_records() -> Vec<PafRecord> {
+        Reader::from_path("./data/5_GD_domestica.paf")
+            .unwrap()
+            .records()
+            .take(2000)
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn test_gzip_path() {
+        let records = fixture_records();
+        let path = std::env::temp_dir().join(format!("paf-gzip-{}.paf.gz", std::process::id()));
+        {
+            // dropped without a flush, which must still end the gzip member
+            let mut writer = Writer::from_path(&path).unwrap();
+            for record in &records {
+                writer.write_record(record).unwrap();
+            }
+        }
+        let compressed = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&compressed[..2], &[0x1f, 0x8b]);
+        let read_back = records_from_gzip(&compressed);
+        assert_eq!(read_back.len(), records.len());
+        assert_eq!(plain(&read_back), plain(&records));
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn test_gzip_into_inner() {
+        let records = fixture_records();
+        let mut writer = Writer::gzip(Vec::new(), 9);
+        for record in &records {
+            writer.write_record(record).unwrap();
+        }
+        let compressed = writer.into_inner().unwrap();
+        assert_eq!(plain(&records_from_gzip(&compressed)), plain(&records));
+    }
+
+    #[test]
+    #[cfg(feature = "bgzf")]
+    fn test_bgzf() {
+        let records = fixture_records();
+        let mut writer = Writer::bgzf(Vec::new());
+        for record in &records {
+            writer.write_record(record).unwrap();
+        }
+        let compressed = writer.into_inner().unwrap();
+        // the BGZF extra field, and the empty block at the end
+        assert_eq!(&compressed[12..14], b"BC");
+        assert!(compressed.ends_with(&[0x1b, 0x00, 0x03, 0x00, 0, 0, 0, 0, 0, 0, 0, 0]));
+        assert_eq!(plain(&records_from_gzip(&compressed)), plain(&records));
+    }
+
+    #[test]
+    #[cfg(feature = "bgzf")]
+    fn test_bgzf_threads() {
+        let paf: String = (0..200_000)
+            .map(|i| {
+                format!(
+                    "read{}\t{}\t0\t100\t+\tchr{}\t1000000\t{}\t{}\t95\t100\t60\tNM:i:{}\n",
+                    i,
+                    100 + i % 50,
+                    i % 20,
+                    i,
+                    i + 100,
+                    i % 7
+                )
+            })
+            .collect();
+        let records = crate::test_util::records(&paf);
+
+        let mut writer = WriterBuilder::new()
+            .compression(Compression::Bgzf)
+            .compression_threads(4)
+            .from_writer(Vec::new());
+        writer.write_records(&records).unwrap();
+        let threaded = writer.into_inner().unwrap();
+        assert_eq!(&threaded[12..14], b"BC");
+        let read_back = records_from_gzip(&threaded);
+        assert_eq!(read_back.len(), records.len());
+        assert_eq!(plain(&read_back), paf.as_bytes());
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "gzip", feature = "bgzf")))]
+    fn test_gz_path_without_feature() {
+        let path = std::env::temp_dir().join(format!("paf-nogz-{}.paf.gz", std::process::id()));
+        assert!(Writer::from_path(&path).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_open_modes() {
+        let path = std::env::temp_dir().join(format!("paf-append-{}.paf", std::process::id()));
+        let mut reader = Reader::from_reader(crate::reader::tests::PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+
+        let mut writer = Writer::from_path(&path).unwrap();
+        writer.write_record(&record).unwrap();
+        writer.write_record(&record).unwrap();
+        drop(writer);
+        let mut writer = Writer::append_path(&path).unwrap();
+        writer.write_record(&record).unwrap();
+        drop(writer);
+        let records = Reader::from_path(&path).unwrap().records().count();
+        assert_eq!(records, 3);
+
+        let create_new = WriterBuilder::new()
+            .open_mode(OpenMode::CreateNew)
+            .from_path(&path);
+        assert!(create_new.is_err());
+
+        // truncating starts again
+        let mut writer = Writer::from_path(&path).unwrap();
+        writer.write_record(&record).unwrap();
+        drop(writer);
+        let records = Reader::from_path(&path).unwrap().records().count();
+        assert_eq!(records, 1);
+
+        std::fs::remove_file(&path).unwrap();
+        let mut writer = WriterBuilder::new()
+            .open_mode(OpenMode::CreateNew)
+            .from_path(&path)
+            .unwrap();
+        writer.write_record(&record).unwrap();
+        drop(writer);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// A writer whose reader has gone away.
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn test_broken_pipe() {
+        let mut reader = Reader::from_reader(crate::reader::tests::PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let mut writer = WriterBuilder::new()
+            .buffer_capacity(0)
+            .from_writer(ClosedPipe);
+        let err = writer.write_record(&record).unwrap_err();
+        assert!(err.is_broken_pipe());
+        assert!(matches!(err.kind(), ErrorKind::Io(_)));
+
+        // buffered, the error comes with the flush
+        let mut writer = Writer::new(ClosedPipe);
+        writer.write_record(&record).unwrap();
+        assert!(writer.flush().unwrap_err().is_broken_pipe());
+        // and dropping the writer does not panic
+        drop(writer);
+
+        let err = Error::from(std::io::Error::other("disk full"));
+        assert!(!err.is_broken_pipe());
+    }
+
+    #[test]
+    fn test_comments_round_trip() {
+        let input = b"#paf 0.2\n\
+            q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\ttp:A:P\n\
+            # between records\n\
+            #\n\
+            q\t100\t0\t10\t-\tt\t100\t0\t10\t10\t10\t60\n\
+            #the end\n";
+        let mut reader = Reader::from_reader(&input[..]);
+        let mut writer = Writer::new(Vec::new());
+        while let Some(record) = reader.read_record().unwrap() {
+            for comment in reader.take_comments() {
+                writer.write_comment(&comment).unwrap();
+            }
+            writer.write_record(&record).unwrap();
+        }
+        assert_eq!(reader.comments(), ["#the end"]);
+        for comment in reader.take_comments() {
+            writer.write_comment(&comment).unwrap();
+        }
+        assert_eq!(writer.into_inner().unwrap(), input);
+    }
+
+    #[test]
+    fn test_write_comment() {
+        let mut writer = Writer::new(Vec::new());
+        writer.write_comment("tool 1.0").unwrap();
+        writer.write_comment("# date").unwrap();
+        assert!(writer.write_comment("two\nlines").is_err());
+        assert_eq!(writer.into_inner().unwrap(), b"#tool 1.0\n# date\n");
+    }
+
+    #[test]
+    fn test_write_records() {
+        let path = "./data/5_GD_domestica.paf";
+        let mut reader = Reader::from_path(path).unwrap();
+        let mut writer = Writer::new(Vec::new());
+        let written = writer.try_write_records(reader.records()).unwrap();
+        assert_eq!(written, 20495);
+        let output = writer.into_inner().unwrap();
+        assert_eq!(output, std::fs::read(path).unwrap());
+
+        // borrowed and owned records
+        let records: Vec<PafRecord> = Reader::from_reader(&output[..])
+            .records()
+            .take(3)
+            .map(|r| r.unwrap())
+            .collect();
+        let mut writer = Writer::new(Vec::new());
+        assert_eq!(writer.write_records(&records).unwrap(), 3);
+        assert_eq!(writer.write_records(records).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_write_records_errors() {
+        let good = "q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\n";
+        let input = format!("{}{}q\t100\n{}", good, good, good);
+        let mut reader = Reader::from_reader(input.as_bytes());
+        let mut writer = Writer::new(Vec::new());
+        let err = writer.try_write_records(reader.records()).unwrap_err();
+        match err.kind() {
+            ErrorKind::WriteRecord { index, err } => {
+                assert_eq!(*index, 2);
+                assert!(matches!(err.kind(), ErrorKind::ReadRecord(_)));
+            }
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        assert!(err
+            .to_string()
+            .starts_with("writing record 2 - reading record"));
+        assert_eq!(writer.into_inner().unwrap(), good.repeat(2).as_bytes());
+
+        let record = Reader::from_reader(good.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap();
+        let mut writer = WriterBuilder::new()
+            .buffer_capacity(0)
+            .from_writer(ClosedPipe);
+        let err = writer.write_records([&record, &record]).unwrap_err();
+        assert!(err.is_broken_pipe());
+        assert!(err.to_string().starts_with("writing record 0 - I/O error"));
+    }
+
+    /// The fixture record, written with the given builder.
+    fn built(builder: &WriterBuilder) -> Vec<u8> {
+        let mut reader = Reader::from_reader(crate::reader::tests::PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let mut writer = builder.from_writer(Vec::new());
+        writer.write_record(&record).unwrap();
+        writer.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_builder_defaults() {
+        let mut reader = Reader::from_reader(crate::reader::tests::PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let mut writer = Writer::new(Vec::new());
+        writer.write_record(&record).unwrap();
+        assert_eq!(built(&WriterBuilder::new()), writer.into_inner().unwrap());
+    }
+
+    #[test]
+    fn test_builder_exclude_tags() {
+        let output = built(WriterBuilder::new().exclude_tags(&["cg", "rl"]));
+        let expected = "NC_041798.1\t41841605\t28850796\t29394458\t+\tSUPER_10\t44636193\t\
+            31974877\t32470190\t495111\t515145\t60\tNM:i:48730\tms:i:488389\tAS:i:439775\t\
+            nn:i:28696\ttp:A:P\tcm:i:46495\ts1:i:466570\ts2:i:10896\tde:f:0.0003\tzd:i:3\n";
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_builder_exclude_cg() {
+        let mut reader = Reader::from_reader(crate::reader::tests::PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let full = built(&WriterBuilder::new());
+        let output = built(WriterBuilder::new().exclude_tags(&["cg"]));
+        let cg = format!("\tcg:Z:{}", record.cg().unwrap());
+        assert_eq!(output.len(), full.len() - cg.len());
+
+        let mut reader = Reader::from_reader(&output[..]);
+        let written = reader.read_record().unwrap().unwrap();
+        assert!(written.cg().is_none());
+        assert_eq!(
+            written.optional_fields().len(),
+            record.optional_fields().len() - 1
+        );
+        // the record itself keeps its cg tag
+        assert!(record.cg().is_some());
+    }
+
+    #[test]
+    fn test_builder_include_tags() {
+        let line =
+            "q\t10\t0\t10\t+\tt\t10\t0\t10\t10\t10\t60\ttp:A:P\tNM:i:0\tde:f:0\tzz:Z:x\tcg:Z:10M\n";
+        let record = ReaderBuilder::new()
+            .keep_unknown_tags(true)
+            .from_reader(line.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap();
+        let written = |builder: &WriterBuilder| {
+            let mut writer = builder.from_writer(Vec::new());
+            writer.write_record(&record).unwrap();
+            String::from_utf8(writer.into_inner().unwrap()).unwrap()
+        };
         assert!(
-            output.contains("query2\t2000\t150\t900\t-\ttarget2\t2500\t300\t1000\t400\t800\t70")
+            written(WriterBuilder::new().include_tags(&["tp", "NM", "de", "zz"]))
+                .ends_with("\t60\tNM:i:0\ttp:A:P\tde:f:0\tzz:Z:x\n")
+        );
+        assert!(written(WriterBuilder::new().exclude_tags(&["zz", "cg"]))
+            .ends_with("\t60\tNM:i:0\ttp:A:P\tde:f:0\n"));
+        assert!(written(WriterBuilder::new().include_tags(&[])).ends_with("\t60\n"));
+    }
+
+    #[test]
+    fn test_builder_include_and_exclude() {
+        let mut builder = WriterBuilder::new();
+        builder.include_tags(&["NM"]).exclude_tags(&["cg"]);
+        let mut reader = Reader::from_reader(crate::reader::tests::PAF_RECORD_1);
+        let record = reader.read_record().unwrap().unwrap();
+        let mut writer = builder.from_writer(Vec::new());
+        assert!(writer.write_record(&record).is_err());
+        assert!(writer.into_inner().unwrap().is_empty());
+
+        let path = std::env::temp_dir().join(format!("paf-tags-{}.paf", std::process::id()));
+        assert!(builder.from_path(&path).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_builder_validate() {
+        // more residue matches than columns
+        let line = "q\t100\t0\t10\t+\tt\t100\t0\t10\t11\t10\t60\n";
+        let record = Reader::from_reader(line.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap();
+
+        let mut writer = WriterBuilder::new().validate(true).from_writer(Vec::new());
+        let err = writer.write_record(&record).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidRecord(_)));
+        assert!(writer.into_inner().unwrap().is_empty());
+
+        let mut writer = WriterBuilder::new().validate(false).from_writer(Vec::new());
+        writer.write_record(&record).unwrap();
+        assert_eq!(writer.into_inner().unwrap(), line.as_bytes());
+
+        // the fixture is valid
+        assert_eq!(
+            built(WriterBuilder::new().validate(true)),
+            built(&WriterBuilder::new())
+        );
+    }
+
+    #[test]
+    fn test_builder_validate_message() {
+        let line = "read1\t100\t0\t10\t+\tchr1\t100\t20\t10\t10\t10\t60\n";
+        let record = Reader::from_reader(line.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap();
+
+        let mut writer = WriterBuilder::new().validate(true).from_writer(Vec::new());
+        let err = writer.write_record(&record).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid record - not writing read1 aligned to chr1: \
+             target coordinates 20-10 are not within a target of length 100"
+        );
+        assert!(writer.into_inner().unwrap().is_empty());
+
+        let mut writer = Writer::new(Vec::new());
+        writer.write_record(&record).unwrap();
+        assert_eq!(writer.into_inner().unwrap(), line.as_bytes());
+    }
+
+    #[test]
+    fn test_builder_compression() {
+        assert_eq!(
+            built(WriterBuilder::new().compression(Compression::None)),
+            built(&WriterBuilder::new())
+        );
+
+        // no compression overrides the .gz extension
+        let path = std::env::temp_dir().join(format!("paf-plain-{}.paf.gz", std::process::id()));
+        let mut writer = WriterBuilder::new()
+            .compression(Compression::None)
+            .from_path(&path)
+            .unwrap();
+        writer.write_records(Vec::<PafRecord>::new()).unwrap();
+        writer.write_comment("plain").unwrap();
+        drop(writer);
+        assert_eq!(std::fs::read(&path).unwrap(), b"#plain\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn test_builder_gzip() {
+        let compressed = built(WriterBuilder::new().compression(Compression::Gzip(1)));
+        // the gzip magic number and the deflate method
+        assert_eq!(&compressed[..3], &[0x1f, 0x8b, 0x08]);
+        let records = records_from_gzip(&compressed);
+        assert_eq!(plain(&records), built(&WriterBuilder::new()));
+    }
+
+    #[test]
+    fn test_failing_flush() {
+        let record = ReaderBuilder::new()
+            .from_reader(&b"q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\n"[..])
+            .read_record()
+            .unwrap()
+            .unwrap();
+        let mut writer = Writer::new(FailingFlush(Vec::new()));
+        writer.write_record(&record).unwrap();
+        assert!(writer.flush().is_err());
+
+        // the writer, and what was written, survive the failure
+        let (writer, err) = match writer.into_inner() {
+            Ok(_) => panic!("the flush should fail"),
+            Err(failed) => failed,
+        };
+        assert!(err.to_string().contains("flush failed"));
+        assert_eq!(
+            writer.get_ref().0,
+            b"q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\n"
+        );
+        // and dropping it ignores the error
+        drop(writer);
+    }
+
+    #[test]
+    fn test_write_alignment_type() {
+        let cases = [
+            (AlignmentType::Primary, "tp:A:P"),
+            (AlignmentType::Secondary, "tp:A:S"),
+            (AlignmentType::Inversion, "tp:A:I"),
+            (AlignmentType::SecondaryInversion, "tp:A:i"),
+            (AlignmentType::Other('x'), "tp:A:x"),
+        ];
+        for (tp, expected) in cases {
+            let mut buffer = Vec::new();
+            let mut writer = Writer::new(&mut buffer);
+
+            let mut optional_fields = HashMap::new();
+            optional_fields.insert("tp".to_string(), Tag::tp(tp));
+            let record = PafRecord::new(
+                "q".to_owned(),
+                100,
+                0,
+                10,
+                '+',
+                "t".to_owned(),
+                100,
+                0,
+                10,
+                10,
+                10,
+                60,
+                optional_fields,
+            );
+
+            writer.write_record(&record).unwrap();
+            drop(writer);
+            let output = String::from_utf8(buffer).unwrap();
+            assert!(output.ends_with(&format!("\t{}\n", expected)));
+        }
+    }
+
+    #[test]
+    fn test_transcript_strand_round_trip() {
+        let line = "q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\tts:A:.\n";
+        let mut reader = Reader::from_reader(line.as_bytes());
+        let record = reader.read_record().unwrap().unwrap();
+        assert_eq!(record.ts(), Some(TranscriptStrand::Unknown));
+
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new(&mut buffer);
+        writer.write_record(&record).unwrap();
+        drop(writer);
+        assert_eq!(String::from_utf8(buffer).unwrap(), line);
+    }
+
+    #[test]
+    fn test_supplementary_alignments_tag() {
+        let line = "q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\tSA:Z:chr1,100,+,50M10S,60,2;chr2,2000,-,10S50M,13,0;\n";
+        let mut reader = Reader::from_reader(line.as_bytes());
+        let record = reader.read_record().unwrap().unwrap();
+        let alignments = record.supplementary_alignments().unwrap();
+        assert_eq!(alignments.len(), 2);
+
+        let mut optional_fields = HashMap::new();
+        optional_fields.insert("SA".to_string(), supplementary_alignments_tag(&alignments));
+        let record = PafRecord::new(
+            "q".to_owned(),
+            100,
+            0,
+            10,
+            '+',
+            "t".to_owned(),
+            100,
+            0,
+            10,
+            10,
+            10,
+            60,
+            optional_fields,
+        );
+
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new(&mut buffer);
+        writer.write_record(&record).unwrap();
+        drop(writer);
+        assert_eq!(String::from_utf8(buffer).unwrap(), line);
+    }
+
+    #[test]
+    fn test_type_display_matches_writer() {
+        let values = [
+            Type::Int(-7),
+            Type::float(0.00031),
+            Type::float(2.0),
+            Type::String("770M1D".into()),
+            Type::Char('P'),
+        ];
+        for value in values {
+            let mut buffer = Vec::new();
+            write_optional_field(&mut buffer, "xx", &value, None).unwrap();
+            assert_eq!(
+                String::from_utf8(buffer).unwrap(),
+                format!("\txx:{}:{}", value.code(), value)
+            );
+        }
+    }
+
+    #[test]
+    fn test_float_round_trip() {
+        for raw in ["1e-05", "0.0003", "0"] {
+            let line = format!(
+                "q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\tdv:f:{}\n",
+                raw
+            );
+            let mut reader = Reader::from_reader(line.as_bytes());
+            let record = reader.read_record().unwrap().unwrap();
+
+            let mut buffer = Vec::new();
+            let mut writer = Writer::new(&mut buffer);
+            writer.write_record(&record).unwrap();
+            drop(writer);
+            assert_eq!(String::from_utf8(buffer).unwrap(), line);
+        }
+    }
+
+    #[test]
+    fn test_float_without_raw_text() {
+        for (value, expected) in [(1e-05, "0.00001"), (0.0003, "0.0003"), (0.0, "0")] {
+            let mut buffer = Vec::new();
+            write_optional_field(&mut buffer, "dv", &Type::float(value), None).unwrap();
+            let output = String::from_utf8(buffer).unwrap();
+            assert_eq!(output, format!("\tdv:f:{}", expected));
+            let parsed: f64 = expected.parse().unwrap();
+            assert_eq!(parsed, value);
+        }
+    }
+
+    #[test]
+    fn test_float_precision() {
+        let dv = |value: f64, precision: Option<usize>| {
+            let mut fields = HashMap::new();
+            fields.insert("dv".to_string(), Tag::dv(Type::float(value)));
+            let record = PafRecord::new(
+                "q".into(),
+                100,
+                0,
+                10,
+                '+',
+                "t".into(),
+                100,
+                0,
+                10,
+                10,
+                10,
+                60,
+                fields,
+            );
+            let mut writer = WriterBuilder::new()
+                .float_precision(precision)
+                .from_writer(Vec::new());
+            writer.write_record(&record).unwrap();
+            let output = writer.into_inner().unwrap();
+            let record = Reader::from_reader(&output[..])
+                .read_record()
+                .unwrap()
+                .unwrap();
+            let text = String::from_utf8(output).unwrap();
+            let text = text.trim_end().rsplit(':').next().unwrap().to_string();
+            (text, record.dv().unwrap())
+        };
+
+        // shortest text, which parses back to the same value
+        for value in [1e-6, 0.25, 0.0] {
+            let (_, parsed) = dv(value, None);
+            assert_eq!(parsed, value);
+        }
+        assert_eq!(dv(1e-6, None).0, "0.000001");
+        assert_eq!(dv(0.25, None).0, "0.25");
+        assert_eq!(dv(0.0, None).0, "0");
+
+        // fixed places, which may lose small values
+        assert_eq!(dv(1e-6, Some(4)), ("0.0000".to_string(), 0.0));
+        assert_eq!(dv(0.25, Some(4)), ("0.2500".to_string(), 0.25));
+        assert_eq!(dv(0.0, Some(4)), ("0.0000".to_string(), 0.0));
+        assert_eq!(dv(1e-6, Some(7)), ("0.0000010".to_string(), 1e-6));
+
+        // floats read from a file keep their text
+        let line = "q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\tdv:f:1e-06\n";
+        let record = Reader::from_reader(line.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap();
+        let mut writer = WriterBuilder::new()
+            .float_precision(Some(4))
+            .from_writer(Vec::new());
+        writer.write_record(&record).unwrap();
+        assert_eq!(writer.into_inner().unwrap(), line.as_bytes());
+    }
+
+    #[test]
+    fn test_raw_round_trip() {
+        for tag in ["cg:X:10M", "xy:X:foo:bar", "xy:i:5"] {
+            let line = format!("q\t100\t0\t10\t+\tt\t100\t0\t10\t10\t10\t60\t{}\n", tag);
+            let mut reader = ReaderBuilder::new()
+                .keep_unknown_tags(true)
+                .keep_unknown_types(true)
+                .from_reader(line.as_bytes());
+            let record = reader.read_record().unwrap().unwrap();
+
+            let mut buffer = Vec::new();
+            let mut writer = Writer::new(&mut buffer);
+            writer.write_record(&record).unwrap();
+            drop(writer);
+            assert_eq!(String::from_utf8(buffer).unwrap(), line);
+        }
+    }
+
+    #[test]
+    fn test_write_indels_bed() {
+        let line = "read1\t100\t10\t22\t-\tchr2\t200\t100\t109\t10\t14\t60\tcg:Z:3M4I2M2D1M1I1M\n";
+        let record = Reader::from_reader(line.as_bytes())
+            .read_record()
+            .unwrap()
+            .unwrap();
+        let mut buffer = Vec::new();
+        write_indels_bed(&mut buffer, &record, &record.indels(2).unwrap()).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "chr2\t103\t103\tINS:read1:15-19\t4\t-\n\
+             chr2\t105\t107\tDEL:read1:13-13\t2\t-\n"
         );
-        assert!(output.contains("\ttp:A:P"));
-        assert!(output.contains("\tcm:i:42"));
-        assert!(output.contains("\ts1:i:99"));
     }
 }